@@ -0,0 +1,128 @@
+use parking_lot::Mutex;
+
+/// Width of the soft knee around the threshold
+const KNEE_DB: f32 = 6.0;
+
+/// Soft-knee compressor settings
+#[derive(Clone, Copy, Debug, serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CompressorConfig {
+    pub enabled: bool,
+    pub threshold_db: f32, // dBFS where compression starts
+    pub ratio: f32,        // input:output above threshold, e.g. 4.0
+    pub attack_ms: f32,
+    pub release_ms: f32,
+}
+
+impl Default for CompressorConfig {
+    fn default() -> Self {
+        CompressorConfig {
+            enabled: false,
+            threshold_db: -20.0,
+            ratio: 4.0,
+            attack_ms: 5.0,
+            release_ms: 100.0,
+        }
+    }
+}
+
+lazy_static::lazy_static! {
+    static ref COMPRESSOR_CONFIG: Mutex<CompressorConfig> = Mutex::new(CompressorConfig::default());
+}
+
+/// Validate and apply compressor settings
+pub fn configure(config: CompressorConfig) -> Result<(), String> {
+    if !(-60.0..=0.0).contains(&config.threshold_db) {
+        return Err(format!("Threshold must be between -60 and 0 dBFS, got {}", config.threshold_db));
+    }
+    if !(1.0..=20.0).contains(&config.ratio) {
+        return Err(format!("Ratio must be between 1 and 20, got {}", config.ratio));
+    }
+    if !(0.1..=500.0).contains(&config.attack_ms) {
+        return Err(format!("Attack must be between 0.1 and 500ms, got {}", config.attack_ms));
+    }
+    if !(1.0..=5000.0).contains(&config.release_ms) {
+        return Err(format!("Release must be between 1 and 5000ms, got {}", config.release_ms));
+    }
+    *COMPRESSOR_CONFIG.lock() = config;
+    Ok(())
+}
+
+pub fn config() -> CompressorConfig {
+    *COMPRESSOR_CONFIG.lock()
+}
+
+/// Compress in place if enabled
+pub fn apply(samples: &mut [f32], sample_rate: u32) {
+    let config = config();
+    if config.enabled {
+        compress(samples, sample_rate, &config);
+    }
+}
+
+/// Static curve: output level for an input level, both in dB
+fn gain_computer(level_db: f32, threshold_db: f32, ratio: f32) -> f32 {
+    let over = level_db - threshold_db;
+    if 2.0 * over < -KNEE_DB {
+        level_db
+    } else if 2.0 * over > KNEE_DB {
+        threshold_db + over / ratio
+    } else {
+        // Quadratic blend across the knee
+        level_db + (1.0 / ratio - 1.0) * (over + KNEE_DB / 2.0).powi(2) / (2.0 * KNEE_DB)
+    }
+}
+
+/// Feed-forward compressor with a smoothed gain-reduction envelope
+fn compress(samples: &mut [f32], sample_rate: u32, config: &CompressorConfig) {
+    let coefficient = |ms: f32| (-1.0 / (ms / 1000.0 * sample_rate as f32)).exp();
+    let attack = coefficient(config.attack_ms);
+    let release = coefficient(config.release_ms);
+
+    let mut envelope_db = 0.0f32;
+    for sample in samples.iter_mut() {
+        let level_db = 20.0 * (sample.abs() + 1e-9).log10();
+        let reduction_db = level_db - gain_computer(level_db, config.threshold_db, config.ratio);
+        let coeff = if reduction_db > envelope_db { attack } else { release };
+        envelope_db = coeff * envelope_db + (1.0 - coeff) * reduction_db;
+        *sample *= 10f32.powf(-envelope_db / 20.0);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sine(amplitude: f32) -> Vec<f32> {
+        (0..16000)
+            .map(|i| (2.0 * std::f32::consts::PI * 220.0 * i as f32 / 16000.0).sin() * amplitude)
+            .collect()
+    }
+
+    fn peak(samples: &[f32]) -> f32 {
+        samples.iter().fold(0.0, |m, s| m.max(s.abs()))
+    }
+
+    #[test]
+    fn test_gain_reduction_above_threshold() {
+        let config = CompressorConfig { enabled: true, ..CompressorConfig::default() };
+
+        // 0.9 peak is ~-1 dBFS, well above -20: expect heavy reduction once settled
+        let mut loud = sine(0.9);
+        compress(&mut loud, 16000, &config);
+        assert!(peak(&loud[8000..]) < 0.5);
+
+        // 0.01 peak is -40 dBFS, below the knee: untouched
+        let mut quiet = sine(0.01);
+        compress(&mut quiet, 16000, &config);
+        assert!((peak(&quiet[8000..]) - 0.01).abs() < 1e-4);
+    }
+
+    #[test]
+    fn test_soft_knee_is_continuous() {
+        let below = gain_computer(-23.0, -20.0, 4.0);
+        let above = gain_computer(-17.0, -20.0, 4.0);
+        assert!((below - -23.0).abs() < 1e-4);
+        assert!((above - (-20.0 + 3.0 / 4.0)).abs() < 1e-4);
+    }
+}
@@ -0,0 +1,270 @@
+use parking_lot::Mutex;
+use std::collections::BTreeMap;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::Instant;
+use tauri::{AppHandle, Emitter, EventTarget};
+
+// Accumulate transcripts into speaker turns
+static ENABLED: AtomicBool = AtomicBool::new(false);
+
+/// Label for segments from providers without diarization
+const UNKNOWN_SPEAKER: &str = "Speaker";
+
+/// A labeled stretch of speech, timed in seconds from the start of the conversation
+#[derive(Clone, Debug, PartialEq, serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Turn {
+    pub speaker: String,
+    pub text: String,
+    pub start: f64,
+    pub end: f64,
+}
+
+/// A segment's place in the conversation, reserved when it's captured so its turns land in
+/// capture order however long its transcription takes
+#[derive(Clone, Copy, Debug)]
+pub struct Slot {
+    order: u64,
+    /// Seconds into the conversation the segment starts
+    start: f64,
+}
+
+struct Conversation {
+    started_at: Instant,
+    turns: Vec<Turn>,
+    /// Order of the next slot handed out
+    reserved: u64,
+    /// Order of the next slot to be appended
+    next: u64,
+    /// Slots that finished ahead of `next`, with their segments
+    finished: BTreeMap<u64, Vec<Turn>>,
+}
+
+impl Conversation {
+    fn new() -> Self {
+        Conversation { started_at: Instant::now(), turns: Vec::new(), reserved: 0, next: 0, finished: BTreeMap::new() }
+    }
+
+    /// Start afresh; slots reserved before now are ignored when they finish
+    fn restart(&mut self) {
+        self.started_at = Instant::now();
+        self.turns.clear();
+        self.finished.clear();
+        self.next = self.reserved;
+    }
+
+    /// Record slot `order`'s segments (none if it produced nothing), then append every slot
+    /// that's now due in order; returns the indexes of the turns that changed
+    fn finish(&mut self, order: u64, segments: Vec<Turn>) -> Vec<usize> {
+        if order < self.next || self.finished.contains_key(&order) {
+            return Vec::new();
+        }
+        self.finished.insert(order, segments);
+        let mut changed = Vec::new();
+        while let Some(segments) = self.finished.remove(&self.next) {
+            self.next += 1;
+            for segment in segments {
+                let index = self.add(segment);
+                if changed.last() != Some(&index) {
+                    changed.push(index);
+                }
+            }
+        }
+        changed
+    }
+
+    /// Add a segment, merging it into the last turn if the speaker hasn't changed;
+    /// returns the index of the turn it landed in
+    fn add(&mut self, segment: Turn) -> usize {
+        if let Some(last) = self.turns.last_mut() {
+            if last.speaker == segment.speaker {
+                last.text.push(' ');
+                last.text.push_str(&segment.text);
+                last.end = last.end.max(segment.end);
+                return self.turns.len() - 1;
+            }
+        }
+        self.turns.push(segment);
+        self.turns.len() - 1
+    }
+}
+
+lazy_static::lazy_static! {
+    static ref CONVERSATION: Mutex<Conversation> = Mutex::new(Conversation::new());
+}
+
+/// Turn conversation mode on or off; enabling starts a fresh conversation
+pub fn set_enabled(enabled: bool) {
+    if enabled && !ENABLED.load(Ordering::SeqCst) {
+        CONVERSATION.lock().restart();
+    }
+    ENABLED.store(enabled, Ordering::SeqCst);
+}
+
+pub fn is_enabled() -> bool {
+    ENABLED.load(Ordering::SeqCst)
+}
+
+/// Reserve the next place in the conversation for a segment of `duration` seconds that just
+/// finished capturing; None when conversation mode is off. Every slot must be passed to
+/// `add_transcript` or `skip`, or the turns after it are held back
+pub fn reserve(duration: f64) -> Option<Slot> {
+    if !is_enabled() {
+        return None;
+    }
+    let mut conversation = CONVERSATION.lock();
+    let order = conversation.reserved;
+    conversation.reserved += 1;
+    let start = (conversation.started_at.elapsed().as_secs_f64() - duration).max(0.0);
+    Some(Slot { order, start })
+}
+
+/// Speaker utterances from an AssemblyAI result, timed in seconds from the start of the audio
+pub fn utterances(result: &serde_json::Value) -> Vec<Turn> {
+    result["utterances"]
+        .as_array()
+        .map(|utterances| {
+            utterances
+                .iter()
+                .filter_map(|u| {
+                    Some(Turn {
+                        speaker: format!("Speaker {}", u["speaker"].as_str()?),
+                        text: u["text"].as_str()?.trim().to_string(),
+                        start: u["start"].as_f64()? / 1000.0,
+                        end: u["end"].as_f64()? / 1000.0,
+                    })
+                })
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+/// Add a chunk's transcript in its slot, split into `utterances` when the provider diarized
+/// it, and emit the turns that changed
+pub fn add_transcript(app: &AppHandle, slot: Slot, text: &str, utterances: Vec<Turn>, duration: f64) {
+    let mut segments = utterances;
+    if segments.is_empty() {
+        segments.push(Turn {
+            speaker: UNKNOWN_SPEAKER.to_string(),
+            text: text.trim().to_string(),
+            start: 0.0,
+            end: duration,
+        });
+    }
+
+    for segment in &mut segments {
+        segment.start += slot.start;
+        segment.end += slot.start;
+    }
+    finish(app, slot, segments);
+}
+
+/// Give up a slot whose segment produced no transcript, releasing the turns after it.
+/// Does nothing if the slot was already filled
+pub fn skip(app: &AppHandle, slot: Slot) {
+    finish(app, slot, Vec::new());
+}
+
+fn finish(app: &AppHandle, slot: Slot, segments: Vec<Turn>) {
+    let mut conversation = CONVERSATION.lock();
+    for index in conversation.finish(slot.order, segments) {
+        let turn = &conversation.turns[index];
+        app.emit_to(
+            EventTarget::Any,
+            "voice:conversation",
+            serde_json::json!({
+                "index": index,
+                "speaker": turn.speaker,
+                "text": turn.text,
+                "start": turn.start,
+                "end": turn.end
+            }),
+        )
+        .ok();
+    }
+}
+
+/// Export the conversation as "markdown" or "json"
+pub fn export(format: &str) -> Result<String, String> {
+    let conversation = CONVERSATION.lock();
+    match format.to_ascii_lowercase().as_str() {
+        "markdown" | "md" => Ok(to_markdown(&conversation.turns)),
+        "json" => serde_json::to_string_pretty(&conversation.turns).map_err(|e| e.to_string()),
+        other => Err(format!("Unknown conversation format '{}' (expected markdown or json)", other)),
+    }
+}
+
+fn to_markdown(turns: &[Turn]) -> String {
+    turns
+        .iter()
+        .map(|turn| {
+            let secs = turn.start.max(0.0) as u64;
+            format!(
+                "**{}** ({:02}:{:02}:{:02})\n{}\n",
+                turn.speaker,
+                secs / 3600,
+                secs / 60 % 60,
+                secs % 60,
+                turn.text
+            )
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn turn(speaker: &str, text: &str, start: f64, end: f64) -> Turn {
+        Turn { speaker: speaker.to_string(), text: text.to_string(), start, end }
+    }
+
+    #[test]
+    fn test_merges_consecutive_speaker_segments() {
+        let mut conversation = Conversation::new();
+        assert_eq!(conversation.add(turn("Speaker A", "Hi.", 0.0, 1.0)), 0);
+        assert_eq!(conversation.add(turn("Speaker A", "How are you?", 1.2, 2.0)), 0);
+        assert_eq!(conversation.add(turn("Speaker B", "Fine.", 2.5, 3.0)), 1);
+        assert_eq!(conversation.turns[0], turn("Speaker A", "Hi. How are you?", 0.0, 2.0));
+
+        assert_eq!(
+            to_markdown(&conversation.turns),
+            "**Speaker A** (00:00:00)\nHi. How are you?\n\n**Speaker B** (00:00:02)\nFine.\n"
+        );
+    }
+
+    #[test]
+    fn test_utterances() {
+        let utterances = utterances(&serde_json::json!({
+            "utterances": [{"speaker": "B", "text": " Yes. ", "start": 1500, "end": 2000}]
+        }));
+        assert_eq!(utterances, vec![turn("Speaker B", "Yes.", 1.5, 2.0)]);
+    }
+
+    #[test]
+    fn test_appends_slots_in_capture_order() {
+        let mut conversation = Conversation::new();
+        conversation.reserved = 3;
+
+        // The second segment's transcription finished first; it waits for the first
+        assert!(conversation.finish(1, vec![turn("Speaker B", "Second.", 1.0, 2.0)]).is_empty());
+        assert_eq!(conversation.finish(0, vec![turn("Speaker A", "First.", 0.0, 1.0)]), vec![0, 1]);
+        assert_eq!(conversation.turns[0].text, "First.");
+        assert_eq!(conversation.turns[1].text, "Second.");
+
+        // A skipped slot releases nothing itself, and finishing it again is ignored
+        assert!(conversation.finish(2, Vec::new()).is_empty());
+        assert!(conversation.finish(2, vec![turn("Speaker A", "Late.", 2.0, 3.0)]).is_empty());
+        assert_eq!(conversation.turns.len(), 2);
+    }
+
+    #[test]
+    fn test_restart_ignores_earlier_slots() {
+        let mut conversation = Conversation::new();
+        conversation.reserved = 2;
+        conversation.restart();
+        assert!(conversation.finish(1, vec![turn("Speaker A", "Old.", 0.0, 1.0)]).is_empty());
+        assert!(conversation.turns.is_empty());
+    }
+}
@@ -0,0 +1,140 @@
+/// What went wrong, by kind, so callers (and the frontend) can react to each differently
+#[derive(Debug, Clone, PartialEq, serde::Serialize)]
+#[serde(tag = "kind", content = "message", rename_all = "camelCase")]
+pub enum KoeError {
+    NoInputDevice,
+    AudioDevice(String),   // opening or running the capture stream
+    ApiAuth(String),       // the provider rejected the key; retrying won't help
+    ApiRateLimit(String),  // 429 after retries
+    Network(String),       // timeouts, connection failures, unreadable responses
+    Transcription(String), // the provider or local model failed to produce a transcript
+    TalonNotInstalled,
+    TalonTimeout(String), // the REPL stalled and was killed; carries any stdout it produced
+    Other(String),
+}
+
+/// Payload of `voice:error`: a stable code for the UI to branch on, plus a readable fallback
+#[derive(Debug, Clone, PartialEq, serde::Serialize)]
+pub struct ErrorEvent {
+    pub code: &'static str,
+    pub message: String,
+    pub recoverable: bool, // the app is retrying or reconnecting on its own
+}
+
+impl KoeError {
+    /// Stable identifier matching the serialized `kind`
+    pub fn code(&self) -> &'static str {
+        match self {
+            KoeError::NoInputDevice => "noInputDevice",
+            KoeError::AudioDevice(_) => "audioDevice",
+            KoeError::ApiAuth(_) => "apiAuth",
+            KoeError::ApiRateLimit(_) => "apiRateLimit",
+            KoeError::Network(_) => "network",
+            KoeError::Transcription(_) => "transcription",
+            KoeError::TalonNotInstalled => "talonNotInstalled",
+            KoeError::TalonTimeout(_) => "talonTimeout",
+            KoeError::Other(_) => "other",
+        }
+    }
+
+    /// Whether this kind of failure can clear up without the user changing anything
+    pub fn recoverable(&self) -> bool {
+        matches!(
+            self,
+            KoeError::AudioDevice(_)
+                | KoeError::ApiRateLimit(_)
+                | KoeError::Network(_)
+                | KoeError::Transcription(_)
+                | KoeError::TalonTimeout(_)
+        )
+    }
+
+    pub fn event(&self) -> ErrorEvent {
+        ErrorEvent { code: self.code(), message: self.to_string(), recoverable: self.recoverable() }
+    }
+}
+
+impl std::fmt::Display for KoeError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            KoeError::NoInputDevice => f.write_str("No input device available"),
+            KoeError::TalonNotInstalled => {
+                f.write_str("Talon Voice is not installed. Install from https://talonvoice.com")
+            }
+            KoeError::TalonTimeout(partial) if partial.is_empty() => f.write_str("Talon did not respond in time"),
+            KoeError::TalonTimeout(partial) => write!(f, "Talon did not respond in time (partial output: {})", partial),
+            KoeError::AudioDevice(message)
+            | KoeError::ApiAuth(message)
+            | KoeError::ApiRateLimit(message)
+            | KoeError::Network(message)
+            | KoeError::Transcription(message)
+            | KoeError::Other(message) => f.write_str(message),
+        }
+    }
+}
+
+impl std::error::Error for KoeError {}
+
+impl From<String> for KoeError {
+    fn from(message: String) -> Self {
+        KoeError::Other(message)
+    }
+}
+
+impl From<&str> for KoeError {
+    fn from(message: &str) -> Self {
+        KoeError::Other(message.to_string())
+    }
+}
+
+// cpal failures while finding, opening or starting the input device
+impl From<cpal::DevicesError> for KoeError {
+    fn from(e: cpal::DevicesError) -> Self {
+        KoeError::AudioDevice(e.to_string())
+    }
+}
+
+impl From<cpal::SupportedStreamConfigsError> for KoeError {
+    fn from(e: cpal::SupportedStreamConfigsError) -> Self {
+        KoeError::AudioDevice(e.to_string())
+    }
+}
+
+impl From<cpal::DefaultStreamConfigError> for KoeError {
+    fn from(e: cpal::DefaultStreamConfigError) -> Self {
+        KoeError::AudioDevice(e.to_string())
+    }
+}
+
+impl From<cpal::BuildStreamError> for KoeError {
+    fn from(e: cpal::BuildStreamError) -> Self {
+        KoeError::AudioDevice(e.to_string())
+    }
+}
+
+impl From<cpal::PlayStreamError> for KoeError {
+    fn from(e: cpal::PlayStreamError) -> Self {
+        KoeError::AudioDevice(e.to_string())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_messages_pass_through() {
+        assert_eq!(KoeError::ApiAuth("OpenAI rejected the API key (401)".to_string()).to_string(), "OpenAI rejected the API key (401)");
+        assert_eq!(KoeError::from("boom"), KoeError::Other("boom".to_string()));
+        assert!(KoeError::TalonNotInstalled.to_string().contains("talonvoice.com"));
+    }
+
+    #[test]
+    fn test_error_event_shape() {
+        let event = KoeError::ApiAuth("Groq rejected the API key (401)".to_string()).event();
+        assert_eq!(event.code, "apiAuth");
+        assert_eq!(event.message, "Groq rejected the API key (401)");
+        assert!(!event.recoverable);
+        assert!(KoeError::Network("timed out".to_string()).event().recoverable);
+    }
+}
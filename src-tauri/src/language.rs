@@ -0,0 +1,178 @@
+use parking_lot::Mutex;
+
+/// Whisper language names (as returned in verbose_json) to ISO-639-1 request codes
+const LANGUAGE_CODES: &[(&str, &str)] = &[
+    ("english", "en"),
+    ("chinese", "zh"),
+    ("german", "de"),
+    ("spanish", "es"),
+    ("russian", "ru"),
+    ("korean", "ko"),
+    ("french", "fr"),
+    ("japanese", "ja"),
+    ("portuguese", "pt"),
+    ("turkish", "tr"),
+    ("polish", "pl"),
+    ("catalan", "ca"),
+    ("dutch", "nl"),
+    ("arabic", "ar"),
+    ("swedish", "sv"),
+    ("italian", "it"),
+    ("indonesian", "id"),
+    ("hindi", "hi"),
+    ("finnish", "fi"),
+    ("vietnamese", "vi"),
+    ("hebrew", "he"),
+    ("ukrainian", "uk"),
+    ("greek", "el"),
+    ("czech", "cs"),
+    ("romanian", "ro"),
+    ("danish", "da"),
+    ("hungarian", "hu"),
+    ("norwegian", "no"),
+    ("thai", "th"),
+];
+
+/// ISO code for a language name or code, if known
+pub fn language_code(name: &str) -> Option<&'static str> {
+    let name = name.trim().to_ascii_lowercase();
+    LANGUAGE_CODES
+        .iter()
+        .find(|(full, code)| *full == name || *code == name)
+        .map(|(_, code)| *code)
+}
+
+/// Only switches language on confident, repeated detections
+pub struct LanguageGate {
+    pub threshold: f32,   // minimum detection confidence, 0-1
+    pub agreement: usize, // consecutive confident detections needed to switch
+    current: Option<String>,
+    candidate: Option<(String, usize)>,
+}
+
+impl LanguageGate {
+    pub fn new(threshold: f32, agreement: usize) -> Self {
+        LanguageGate {
+            threshold,
+            agreement: agreement.max(1),
+            current: None,
+            candidate: None,
+        }
+    }
+
+    /// Feed a detection; returns the language to use for this chunk
+    pub fn observe(&mut self, code: &str, confidence: f32) -> Option<String> {
+        if confidence < self.threshold {
+            return self.current.clone();
+        }
+        match &self.current {
+            None => {
+                self.current = Some(code.to_string());
+            }
+            Some(current) if current == code => {
+                self.candidate = None;
+            }
+            Some(_) => {
+                let count = match &self.candidate {
+                    Some((candidate, count)) if candidate == code => count + 1,
+                    _ => 1,
+                };
+                if count >= self.agreement {
+                    println!("Language switched to {}", code);
+                    self.current = Some(code.to_string());
+                    self.candidate = None;
+                } else {
+                    self.candidate = Some((code.to_string(), count));
+                }
+            }
+        }
+        self.current.clone()
+    }
+
+    pub fn reset(&mut self) {
+        self.current = None;
+        self.candidate = None;
+    }
+}
+
+lazy_static::lazy_static! {
+    static ref GATE: Mutex<LanguageGate> = Mutex::new(LanguageGate::new(0.6, 2));
+}
+
+/// Validate and apply gate settings; clears the learned language
+pub fn configure(threshold: f32, agreement: usize) -> Result<(), String> {
+    if !(0.0..=1.0).contains(&threshold) {
+        return Err(format!("Confidence threshold must be between 0 and 1, got {}", threshold));
+    }
+    if !(1..=10).contains(&agreement) {
+        return Err(format!("Agreement must be between 1 and 10 chunks, got {}", agreement));
+    }
+    *GATE.lock() = LanguageGate::new(threshold, agreement);
+    Ok(())
+}
+
+pub fn reset() {
+    GATE.lock().reset();
+}
+
+/// Detected language and a confidence proxy from an OpenAI-style verbose_json response.
+/// There's no explicit detection score, so confidence is the mean segment token
+/// probability (exp of avg_logprob), discounted by the no-speech probability.
+pub fn detection(result: &serde_json::Value) -> Option<(&'static str, f32)> {
+    let code = language_code(result["language"].as_str()?)?;
+    let segments = result["segments"].as_array().filter(|s| !s.is_empty())?;
+    let confidence = segments
+        .iter()
+        .map(|segment| {
+            let token_prob = segment["avg_logprob"].as_f64().unwrap_or(f64::NEG_INFINITY).exp();
+            let speech_prob = 1.0 - segment["no_speech_prob"].as_f64().unwrap_or(0.0);
+            token_prob * speech_prob
+        })
+        .sum::<f64>()
+        / segments.len() as f64;
+    Some((code, confidence as f32))
+}
+
+/// Gate a verbose_json detection: returns the language to re-transcribe in when rejected
+pub fn gate(result: &serde_json::Value) -> Option<String> {
+    let (code, confidence) = detection(result)?;
+    let accepted = GATE.lock().observe(code, confidence)?;
+    if accepted != code {
+        println!(
+            "Ignoring detected language {} ({:.2} confidence), keeping {}",
+            code, confidence, accepted
+        );
+        Some(accepted)
+    } else {
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_gate_requires_confident_agreement() {
+        let mut gate = LanguageGate::new(0.6, 2);
+        assert_eq!(gate.observe("en", 0.9).as_deref(), Some("en"));
+        // Low confidence and one-off detections don't switch
+        assert_eq!(gate.observe("de", 0.3).as_deref(), Some("en"));
+        assert_eq!(gate.observe("de", 0.8).as_deref(), Some("en"));
+        // A second confident detection in a row does
+        assert_eq!(gate.observe("de", 0.8).as_deref(), Some("de"));
+    }
+
+    #[test]
+    fn test_detection_confidence() {
+        let result = serde_json::json!({
+            "language": "english",
+            "segments": [
+                {"avg_logprob": 0.0, "no_speech_prob": 0.0},
+                {"avg_logprob": 0.0, "no_speech_prob": 0.5}
+            ]
+        });
+        assert_eq!(detection(&result), Some(("en", 0.75)));
+        assert_eq!(language_code("German"), Some("de"));
+    }
+}
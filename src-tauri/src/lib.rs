@@ -1,5 +1,29 @@
 mod voice;
+mod compressor;
+mod conversation;
+mod error;
+mod highpass;
+mod language;
+mod meter;
+#[cfg(feature = "whisper-local")]
+mod models;
+mod monitor;
+mod normalize;
+mod pcm;
 mod talon;
+mod tts;
+mod realtime;
+mod recording;
+mod resampler;
+mod self_check;
+mod subtitles;
+mod typing;
+mod vad;
+mod voice_commands;
+#[cfg(feature = "punctuation-restore")]
+mod punctuation;
+
+pub use voice::{set_transcript_sink, EventSink, TranscriptEvent, TranscriptSink};
 
 #[tauri::command]
 fn start_voice_capture(app: tauri::AppHandle) -> Result<(), String> {
@@ -11,41 +35,526 @@ fn stop_voice_capture() -> Result<(), String> {
     voice::stop_capture().map_err(|e| e.to_string())
 }
 
+/// Run a WAV file through live capture and transcription instead of the microphone, at
+/// `speed` times real time (default 1; 0 is as fast as possible)
 #[tauri::command]
-fn speak_text(text: String) -> Result<(), String> {
-    // Use macOS 'say' command for TTS
-    #[cfg(target_os = "macos")]
-    {
-        std::process::Command::new("say")
-            .arg(&text)
-            .spawn()
-            .map_err(|e| e.to_string())?;
+fn start_capture_from_file(app: tauri::AppHandle, path: String, speed: Option<f32>) -> Result<(), String> {
+    voice::start_capture_from_file(app, std::path::Path::new(&path), speed.unwrap_or(1.0)).map_err(|e| e.to_string())
+}
+
+/// Capture with live transcription while saving the session to a WAV file;
+/// `subtitles` ("srt" or "vtt") writes a time-aligned transcript next to it on stop
+#[tauri::command]
+fn start_capture_to_file(app: tauri::AppHandle, path: String, subtitles: Option<String>) -> Result<(), String> {
+    let subtitles = subtitles
+        .map(|format| recording::SubtitleFormat::parse(&format))
+        .transpose()?;
+    voice::start_capture_to_file(app, path.into(), subtitles)
+}
+
+/// Transcribe the audio buffered so far without waiting for the chunk boundary
+#[tauri::command]
+fn flush_now(app: tauri::AppHandle) {
+    voice::flush_now(app);
+}
+
+/// Configure failure tolerance and connectivity probing for long sessions
+#[tauri::command]
+fn configure_recovery(max_consecutive_failures: u32, probe_interval_ms: u64) {
+    voice::configure_recovery(max_consecutive_failures, probe_interval_ms);
+}
+
+/// Set VAD hangover, pre-roll and minimum speech durations (milliseconds)
+#[tauri::command]
+fn set_vad_timing(hangover_ms: u32, preroll_ms: u32, min_speech_ms: u32) -> Result<(), String> {
+    vad::set_timing(hangover_ms, preroll_ms, min_speech_ms)
+}
+
+#[tauri::command]
+fn get_vad_timing() -> vad::VadTiming {
+    vad::timing()
+}
+
+/// Set the VAD energy threshold as linear RMS (0.000001-1, e.g. 0.01); `voice:level` reports the live RMS for calibration
+#[tauri::command]
+fn configure_vad(threshold: f32) -> Result<(), String> {
+    vad::set_threshold(threshold)
+}
+
+/// Set the VAD energy threshold in dBFS (e.g. -40)
+#[tauri::command]
+fn set_vad_threshold_dbfs(dbfs: f32) -> Result<(), String> {
+    vad::set_threshold_dbfs(dbfs)
+}
+
+#[tauri::command]
+fn get_vad_threshold_dbfs() -> f32 {
+    vad::to_dbfs(vad::threshold())
+}
+
+/// Report `voice:level` in "dbfs" or "linear" units
+#[tauri::command]
+fn set_level_units(units: String) -> Result<(), String> {
+    match units.as_str() {
+        "dbfs" => voice::set_level_units_dbfs(true),
+        "linear" => voice::set_level_units_dbfs(false),
+        other => return Err(format!("Unknown level units '{}' (expected dbfs or linear)", other)),
     }
     Ok(())
 }
 
+/// Seconds of audio per transcription chunk (clamped to 0.25-10); returns the value applied.
+/// Small values cut latency but increase API call volume.
 #[tauri::command]
-fn stop_speaking() -> Result<(), String> {
-    #[cfg(target_os = "macos")]
+fn set_chunk_duration(seconds: f32) -> Result<f32, String> {
+    vad::set_chunk_duration(seconds)
+}
+
+/// Skip transcribing flushed segments shorter than `seconds` (default 0.3, pre-roll included),
+/// so coughs and clicks don't cost an API call; 0 sends everything
+#[tauri::command]
+fn set_min_segment_duration(seconds: f32) -> Result<(), String> {
+    vad::set_min_segment_duration(seconds)
+}
+
+/// Chunk on a fixed interval ("fixed_interval") or at the end of each utterance ("silence_gated",
+/// after `silence_ms` of trailing audio below `silence_dbfs`; 600ms and -45 dBFS by default)
+#[tauri::command]
+fn set_segmentation_mode(mode: String, silence_ms: Option<u32>, silence_dbfs: Option<f32>) -> Result<(), String> {
+    vad::set_segmentation(vad::SegmentationMode::parse(&mode, silence_ms, silence_dbfs)?);
+    Ok(())
+}
+
+/// Adapt chunk length and hangover to the speaker's recent words-per-second
+#[tauri::command]
+fn set_adaptive_chunking(enabled: bool) {
+    vad::set_adaptive(enabled);
+}
+
+/// Clear the transcript carried into the next chunk's prompt (e.g. on topic change)
+#[tauri::command]
+fn reset_context() {
+    voice::reset_context();
+}
+
+/// Auto-reset prompt context after this much silence, in milliseconds (None disables)
+#[tauri::command]
+fn set_context_reset_gap(gap_ms: Option<u64>) {
+    voice::set_context_reset_gap(gap_ms);
+}
+
+/// Configure spacing and capitalization of the `insertText` transcript field
+#[tauri::command]
+fn configure_typing_formatter(auto_space: bool, capitalize_sentences: bool) {
+    typing::configure(auto_space, capitalize_sentences);
+}
+
+/// Accumulate transcripts into speaker turns, emitted as `voice:conversation`; enabling starts afresh
+#[tauri::command]
+fn set_conversation_mode(enabled: bool) {
+    conversation::set_enabled(enabled);
+}
+
+/// Export the conversation so far as "markdown" or "json"
+#[tauri::command]
+fn export_conversation(format: String) -> Result<String, String> {
+    conversation::export(&format)
+}
+
+/// Capture audio into a named buffer to transcribe later
+#[tauri::command]
+fn record_to_buffer(app: tauri::AppHandle, name: String) -> Result<(), String> {
+    voice::record_to_buffer(app, name)
+}
+
+#[tauri::command]
+fn stop_recording_to_buffer(name: String) -> Result<(), String> {
+    voice::stop_recording_to_buffer(&name)
+}
+
+/// Transcribe a named buffer off the main thread
+#[tauri::command]
+async fn transcribe_buffer(name: String) -> Result<String, String> {
+    tauri::async_runtime::spawn_blocking(move || voice::transcribe_buffer(&name).map_err(|e| e.to_string()))
+        .await
+        .map_err(|e| e.to_string())?
+}
+
+/// Transcribe a WAV file with timestamps and return it as SRT subtitles
+#[tauri::command]
+async fn transcribe_to_srt(path: String) -> Result<String, String> {
+    tauri::async_runtime::spawn_blocking(move || voice::transcribe_to_srt(&path).map_err(|e| e.to_string()))
+        .await
+        .map_err(|e| e.to_string())?
+}
+
+#[tauri::command]
+fn list_buffers() -> Vec<voice::BufferInfo> {
+    voice::list_buffers()
+}
+
+#[tauri::command]
+fn delete_buffer(name: String) -> Result<(), String> {
+    voice::delete_buffer(&name)
+}
+
+/// Configure the soft-knee compressor applied before transcription
+#[tauri::command]
+fn configure_compressor(
+    enabled: bool,
+    threshold_db: f32,
+    ratio: f32,
+    attack_ms: f32,
+    release_ms: f32,
+) -> Result<(), String> {
+    compressor::configure(compressor::CompressorConfig {
+        enabled,
+        threshold_db,
+        ratio,
+        attack_ms,
+        release_ms,
+    })
+}
+
+#[tauri::command]
+fn get_compressor_config() -> compressor::CompressorConfig {
+    compressor::config()
+}
+
+/// Filter out DC offset and rumble below `cutoff_hz` from captured audio; applies from the next capture
+#[tauri::command]
+fn configure_high_pass(enabled: bool, cutoff_hz: f32) -> Result<(), String> {
+    highpass::configure(highpass::HighPassConfig { enabled, cutoff_hz })
+}
+
+#[tauri::command]
+fn get_high_pass_config() -> highpass::HighPassConfig {
+    highpass::config()
+}
+
+/// Emit captured audio as 16kHz mono f32 frames of `frame_size` samples on `voice:pcm`
+/// (base64, little-endian): "tap" alongside transcription, "exclusive" instead of it, or "off".
+/// Applies from the next capture
+#[tauri::command]
+fn subscribe_raw_audio(mode: String, frame_size: Option<usize>) -> Result<(), String> {
+    let frame_size = frame_size.unwrap_or(pcm::PcmConfig::default().frame_size);
+    pcm::configure(pcm::PcmConfig { mode: pcm::PcmMode::parse(&mode)?, frame_size })
+}
+
+#[tauri::command]
+fn get_raw_audio_config() -> pcm::PcmConfig {
+    pcm::config()
+}
+
+/// Raise each segment's peak to `target_db` dBFS before transcription, by at most `max_gain_db`
+#[tauri::command]
+fn configure_auto_gain(auto_gain: bool, target_db: f32, max_gain_db: f32) -> Result<(), String> {
+    normalize::configure(normalize::NormalizeConfig {
+        auto_gain,
+        target_db,
+        max_gain_db,
+    })
+}
+
+#[tauri::command]
+fn get_auto_gain_config() -> normalize::NormalizeConfig {
+    normalize::config()
+}
+
+/// Download a ggml Whisper model ("tiny", "base", "small" or "medium") for local transcription,
+/// resuming a partial download; emits `model:download_progress` and returns the model's path
+#[tauri::command]
+async fn download_whisper_model(app: tauri::AppHandle, size: String) -> Result<String, String> {
+    #[cfg(feature = "whisper-local")]
+    return tauri::async_runtime::spawn_blocking(move || models::download_model(&app, &size))
+        .await
+        .map_err(|e| e.to_string())?
+        .map(|path| path.display().to_string());
+    #[cfg(not(feature = "whisper-local"))]
     {
-        std::process::Command::new("killall")
-            .arg("say")
-            .spawn()
-            .ok();
+        let _ = (app, size);
+        Err("Local Whisper isn't available in this build".to_string())
     }
+}
+
+/// Set the transcription language: an ISO-639-1 code such as "en", or "auto"
+#[tauri::command]
+fn set_language(language: String) -> Result<(), String> {
+    voice::set_language(&language)
+}
+
+/// Gate auto-detected language switches on confidence (0-1) and consecutive agreement
+#[tauri::command]
+fn configure_language_gate(threshold: f32, agreement: usize) -> Result<(), String> {
+    language::configure(threshold, agreement)
+}
+
+/// Set transcription response parsing ("strict" or "lenient") and an optional JSON path to the text
+#[tauri::command]
+fn set_response_parsing(mode: String, text_path: Option<String>) -> Result<(), String> {
+    voice::set_response_parsing(&mode, text_path)
+}
+
+/// Trade CPU for fidelity when resampling: "fast" (alias "linear"), "balanced", "high" (alias "sinc"),
+/// or "rubato" (needs the `high-quality-resample` feature, otherwise same as "high")
+#[tauri::command]
+fn set_resampler_quality(quality: String) -> Result<(), String> {
+    resampler::set_quality(resampler::ResamplerQuality::parse(&quality)?);
     Ok(())
 }
 
 #[tauri::command]
-fn configure_whisper(
-    api_key: Option<String>,
-    use_local: bool,
-    model_path: Option<String>,
-    provider: Option<String>,
+fn get_resampler_quality() -> String {
+    resampler::quality().name().to_string()
+}
+
+/// Why the most recent chunk was or wasn't transcribed (RMS, threshold, duration, decision)
+#[tauri::command]
+fn get_last_chunk_decision() -> Option<voice::ChunkDecision> {
+    voice::get_last_chunk_decision()
+}
+
+/// Device, sample rate and channels capture negotiated, with the current chunking settings
+#[tauri::command]
+fn get_capture_status() -> voice::CaptureStatus {
+    voice::get_capture_status()
+}
+
+/// Number of audio dropouts (xruns) detected since capture started
+#[tauri::command]
+fn get_dropout_count() -> u64 {
+    voice::get_dropout_count()
+}
+
+/// Speak text, optionally with a voice, rate (words per minute) and volume (0-1)
+#[tauri::command]
+fn speak_text(app: tauri::AppHandle, text: String, options: Option<tts::SpeechOptions>) -> Result<(), String> {
+    tts::speak(app, text, options.unwrap_or_default())
+}
+
+/// Whether speech started by speak_text is still playing
+#[tauri::command]
+fn is_speaking() -> bool {
+    tts::is_speaking()
+}
+
+/// Speak with the system voices ("system") or OpenAI's speech API ("openai"), using
+/// the OpenAI transcription key; model and voice default to tts-1 and alloy
+#[tauri::command]
+fn set_tts_backend(backend: String, model: Option<String>, voice: Option<String>) -> Result<(), String> {
+    tts::set_engine(&backend, model, voice)
+}
+
+/// Write speech to a WAV (or, with the OpenAI backend, MP3) file instead of playing it
+#[tauri::command]
+fn synthesize_to_file(
+    text: String,
+    path: String,
+    options: Option<tts::SpeechOptions>,
+) -> Result<tts::SynthesizedFile, String> {
+    tts::synthesize_to_file(&text, &path, &options.unwrap_or_default())
+}
+
+/// Voices offered by the system speech backend
+#[tauri::command]
+fn list_tts_voices() -> Result<Vec<tts::VoiceInfo>, String> {
+    tts::list_voices()
+}
+
+/// Stop the current utterance; `flush_queue` also drops everything queued after it
+#[tauri::command]
+fn stop_speaking(flush_queue: Option<bool>) -> Result<(), String> {
+    tts::stop(flush_queue.unwrap_or(false));
+    Ok(())
+}
+
+/// Drop queued utterances without interrupting the one playing
+#[tauri::command]
+fn clear_tts_queue() {
+    tts::clear_queue();
+}
+
+/// Play the microphone back through the output device (sidetone) at `gain` (0-1)
+#[tauri::command]
+fn set_monitoring(enabled: bool, gain: f32) -> Result<(), String> {
+    monitor::set_monitoring(enabled, gain)
+}
+
+/// Synthesize speech to a file and play it through cpal instead of letting the backend play it
+#[tauri::command]
+fn set_say_playback_through_cpal(enabled: bool) {
+    tts::set_play_through_cpal(enabled);
+}
+
+/// Update transcription settings; omitted (null) fields keep their values and an empty string
+/// clears a key, model path or prompt
+#[tauri::command]
+fn configure_whisper(settings: voice::WhisperSettings) -> Result<(), String> {
+    voice::configure_whisper(settings)
+}
+
+/// Remove one provider's API key ("openai", "groq", "assemblyai", "deepgram" or "azure"); passing
+/// null to `configure_whisper` leaves keys unchanged
+#[tauri::command]
+fn clear_api_key(provider: String) -> Result<(), String> {
+    voice::clear_api_key(&provider)
+}
+
+/// Providers whose API key is configured (the keys themselves stay in the backend)
+#[tauri::command]
+fn get_configured_api_keys() -> Vec<String> {
+    voice::configured_api_keys()
+}
+
+/// Remove saved API keys from the OS keychain; configured keys last until restart
+#[tauri::command]
+fn clear_stored_keys() -> Result<(), String> {
+    voice::clear_stored_keys()
+}
+
+/// Also emit `voice:transcript_detailed` with per-word start/end times for each transcript
+#[tauri::command]
+fn set_word_timestamps(enabled: bool) {
+    voice::set_word_timestamps(enabled);
+}
+
+/// Abandon an API transcription request after `seconds` (default 30); rate limits and server
+/// errors are retried up to 3 times with backoff, each retry raising a `voice:warning`
+#[tauri::command]
+fn set_request_timeout(seconds: u32) -> Result<(), String> {
+    voice::set_request_timeout(seconds)
+}
+
+/// Point the OpenAI provider at an OpenAI-compatible server, e.g. "http://localhost:8080/v1"
+/// (an API key becomes optional); null restores api.openai.com
+#[tauri::command]
+fn set_openai_base_url(url: Option<String>) -> Result<(), String> {
+    voice::set_openai_base_url(url)
+}
+
+/// "transcribe" as spoken (default) or "translate" to English; translation needs OpenAI or
+/// Groq (whose turbo model can't translate)
+#[tauri::command]
+fn set_transcription_task(task: String) -> Result<(), String> {
+    voice::set_transcription_task(voice::TranscriptionTask::parse(&task)?);
+    Ok(())
+}
+
+/// Change the active provider ("openai", "groq", "assemblyai", "deepgram", "azure" or "local") using already-configured keys
+#[tauri::command]
+fn switch_provider(name: String) -> Result<(), String> {
+    voice::switch_provider(&name)
+}
+
+/// Store the model, language and prompt a provider uses whenever it's active
+#[tauri::command]
+fn configure_provider_defaults(
+    provider: String,
     model: Option<String>,
-    groq_api_key: Option<String>,
-) {
-    voice::configure_whisper(api_key, use_local, model_path, provider, model, groq_api_key);
+    language: Option<String>,
+    prompt: Option<String>,
+) -> Result<(), String> {
+    voice::configure_provider_defaults(&provider, model, language, prompt)
+}
+
+#[tauri::command]
+fn get_provider_defaults(provider: String) -> Option<voice::ProviderDefaults> {
+    voice::get_provider_defaults(&provider)
+}
+
+/// Stream audio to a realtime WebSocket provider instead of chunked uploads
+#[tauri::command]
+fn configure_realtime(
+    enabled: bool,
+    provider: Option<String>,
+    api_key: Option<String>,
+) -> Result<(), String> {
+    realtime::configure_realtime(enabled, provider, api_key)
+}
+
+/// Report the provider/model the next transcription will actually use
+#[tauri::command]
+fn get_effective_config() -> voice::EffectiveConfig {
+    voice::get_effective_config()
+}
+
+/// Known-good transcription models for a provider, without a network request; for a
+/// dropdown, with `fetch_provider_models` for the live list
+#[tauri::command]
+fn list_models(provider: String) -> Result<Vec<String>, String> {
+    voice::static_models(&provider)
+}
+
+/// Transcription models offered by a provider, fetched from its API when possible
+#[tauri::command]
+async fn fetch_provider_models(provider: String) -> Result<Vec<String>, String> {
+    tauri::async_runtime::spawn_blocking(move || voice::fetch_provider_models(&provider))
+        .await
+        .map_err(|e| e.to_string())?
+}
+
+/// Check device, stream, provider key, local model and Talon, reporting each step
+#[tauri::command]
+async fn self_check() -> Result<self_check::SelfCheckReport, String> {
+    tauri::async_runtime::spawn_blocking(self_check::run)
+        .await
+        .map_err(|e| e.to_string())
+}
+
+/// Enable local punctuation restoration (requires the `punctuation-restore` feature)
+#[tauri::command]
+fn set_punctuation_restoration(enabled: bool) {
+    voice::set_punctuation_restoration(enabled);
+}
+
+/// Drop transcript segments Whisper scored as unlikely speech; `None` disables a threshold
+#[tauri::command]
+fn set_confidence_filter(min_confidence: Option<f64>, max_no_speech_prob: Option<f64>) -> Result<(), String> {
+    voice::set_confidence_filter(min_confidence, max_no_speech_prob)
+}
+
+/// Whisper sampling temperature for OpenAI and Groq (0–1, clamped); `None` uses the server default
+#[tauri::command]
+fn set_transcription_temperature(temperature: Option<f32>) {
+    voice::set_temperature(temperature);
+}
+
+/// Stream live OpenAI transcriptions, emitting non-final transcripts as text arrives
+#[tauri::command]
+fn set_streaming_transcription(enabled: bool) {
+    voice::set_streaming_transcription(enabled);
+}
+
+/// Emit `voice:level` from the selected input without starting capture, for a mic check
+#[tauri::command]
+fn start_level_monitoring(app: tauri::AppHandle) -> Result<(), String> {
+    meter::start(app).map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+fn stop_level_monitoring() {
+    meter::stop();
+}
+
+/// Whether an input device is connected, so the UI can disable the mic button without one;
+/// `voice:input_available` reports later changes while capturing or a device picker is open
+#[tauri::command]
+fn has_input_device() -> bool {
+    voice::has_input_device()
+}
+
+/// Tell the backend a device picker is showing (true) or closed (false), so it watches for
+/// devices being plugged in while idle
+#[tauri::command]
+fn set_device_picker_open(open: bool) {
+    voice::set_device_picker_open(open);
+}
+
+/// Average transcription latency per provider and model for live segments since launch;
+/// each segment's timings are also emitted as `voice:metrics`
+#[tauri::command]
+fn get_transcription_stats() -> Vec<voice::ModelStats> {
+    voice::get_transcription_stats()
 }
 
 #[tauri::command]
@@ -53,6 +562,28 @@ fn list_audio_devices() -> Result<Vec<String>, String> {
     voice::list_input_devices().map_err(|e| e.to_string())
 }
 
+/// Sample rates, channel counts and formats an input device supports; errors if it isn't connected
+#[tauri::command]
+fn get_device_capabilities(name: String) -> Result<voice::DeviceCapabilities, String> {
+    voice::get_device_capabilities(&name).map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+fn list_output_devices() -> Result<Vec<String>, String> {
+    tts::list_output_devices()
+}
+
+#[tauri::command]
+fn get_selected_output_device() -> Option<String> {
+    tts::get_output_device()
+}
+
+/// Play speech on the named output device (cpal playback only); `None` uses the default
+#[tauri::command]
+fn set_output_device(device_name: Option<String>) {
+    voice::set_output_device(device_name);
+}
+
 #[tauri::command]
 fn get_selected_audio_device() -> Option<String> {
     voice::get_selected_device()
@@ -63,6 +594,70 @@ fn set_audio_device(device_name: Option<String>) {
     voice::set_input_device(device_name);
 }
 
+/// Select a single zero-based input channel to capture, or None to downmix
+#[tauri::command]
+fn set_input_channel(channel: Option<u16>) {
+    voice::set_input_channel(channel);
+}
+
+#[tauri::command]
+fn get_input_channel() -> Option<u16> {
+    voice::get_input_channel()
+}
+
+/// How multi-channel input becomes mono: "first", "average" (default), or "channel" with `channel`
+#[tauri::command]
+fn set_downmix(mode: String, channel: Option<u16>) -> Result<(), String> {
+    voice::set_downmix(voice::Downmix::parse(&mode, channel)?);
+    Ok(())
+}
+
+/// Highest capture rate to negotiate when the device doesn't support 16kHz
+#[tauri::command]
+fn set_capture_rate_ceiling(hz: u32) -> Result<(), String> {
+    voice::set_capture_rate_ceiling(hz)
+}
+
+/// Also emit non-final transcripts of speech still being buffered (doubles API calls on
+/// non-streaming providers)
+#[tauri::command]
+fn set_interim_results(enabled: bool) {
+    voice::set_interim_results(enabled);
+}
+
+/// Save each live segment as a WAV in the log dir (newest 50 kept), emitting `voice:segment_saved`
+#[tauri::command]
+fn set_save_segments(enabled: bool) {
+    voice::set_save_segments(enabled);
+}
+
+/// Skip live transcripts that repeat the previous one within a few seconds
+#[tauri::command]
+fn set_suppress_repeats(enabled: bool) {
+    voice::set_suppress_repeats(enabled);
+}
+
+/// "continuous" chunks speech as it pauses; "pushToTalk" holds audio until capture stops and
+/// transcribes it as one segment
+#[tauri::command]
+fn set_capture_mode(mode: String) -> Result<(), String> {
+    voice::set_capture_mode(voice::CaptureMode::parse(&mode)?);
+    Ok(())
+}
+
+/// Drop the oldest live audio once more than `seconds` has built up without a flush (default 60,
+/// and never under the 30s a chunk can take)
+#[tauri::command]
+fn set_max_buffer_duration(seconds: u32) -> Result<(), String> {
+    voice::set_max_buffer_duration(seconds)
+}
+
+/// Capture in the device's native format and do all conversion off the audio thread
+#[tauri::command]
+fn set_deferred_conversion(enabled: bool) {
+    voice::set_deferred_conversion(enabled);
+}
+
 /// Simulate a voice transcript event for testing (no actual audio needed)
 #[tauri::command]
 fn test_emit_transcript(app: tauri::AppHandle, text: String) -> Result<(), String> {
@@ -73,13 +668,20 @@ fn test_emit_transcript(app: tauri::AppHandle, text: String) -> Result<(), Strin
         "voice:transcript",
         serde_json::json!({
             "text": text,
-            "isFinal": true
+            "isFinal": true,
+            "seq": voice::transcript_seq(true)
         }),
     ).map_err(|e| e.to_string())?;
     println!("[TEST] Test transcript emitted successfully");
     Ok(())
 }
 
+/// WAV bytes of a sine test tone, for testing uploads without a microphone
+#[tauri::command]
+fn generate_test_tone(freq: f32, secs: f32, sample_rate: u32) -> Result<Vec<u8>, String> {
+    voice::generate_test_tone(freq, secs, sample_rate)
+}
+
 /// Log from frontend to Rust stdout (visible in terminal)
 #[tauri::command]
 fn frontend_log(level: String, message: String) {
@@ -97,10 +699,70 @@ fn is_talon_available() -> bool {
     talon::is_talon_installed()
 }
 
-/// Execute a Talon command via the REPL
+/// Replace the voice command rules: final transcripts matching a phrase (whole transcript,
+/// case-insensitive) or regex run its Talon code instead of being emitted as dictation
 #[tauri::command]
-fn run_talon(code: String) -> Result<String, String> {
-    talon::execute_talon(&code)
+fn register_voice_commands(rules: Vec<voice_commands::VoiceCommandRule>) -> Result<(), String> {
+    voice_commands::register(rules)
+}
+
+#[tauri::command]
+fn get_voice_commands() -> Vec<voice_commands::VoiceCommandRule> {
+    voice_commands::rules()
+}
+
+/// Emit transcripts that match no voice command as dictation (default), or drop them
+#[tauri::command]
+fn set_dictation_passthrough(enabled: bool) {
+    voice_commands::set_dictation_passthrough(enabled);
+}
+
+/// Use a Talon install outside the default location; `None` goes back to auto-detection
+#[tauri::command]
+fn set_talon_path(path: Option<String>) -> Result<(), String> {
+    voice::set_talon_path(path).map_err(|e| e.to_string())
+}
+
+/// Report whether Talon is installed and running, so the UI can offer "Install" vs "Start"
+#[tauri::command]
+async fn is_talon_running() -> Result<talon::TalonStatus, String> {
+    tauri::async_runtime::spawn_blocking(talon::talon_status)
+        .await
+        .map_err(|e| e.to_string())
+}
+
+/// Execute a Talon command via the REPL off the main thread; `timeout_ms` defaults to 5s
+#[tauri::command]
+async fn run_talon(code: String, timeout_ms: Option<u64>) -> Result<String, String> {
+    let timeout = timeout_ms.map(std::time::Duration::from_millis).unwrap_or(talon::DEFAULT_TIMEOUT);
+    tauri::async_runtime::spawn_blocking(move || talon::execute_talon(&code, timeout).map_err(|e| e.to_string()))
+        .await
+        .map_err(|e| e.to_string())?
+}
+
+/// Execute Talon commands in order, returning each one's result; `stop_on_error` ends the batch at the first failure
+#[tauri::command]
+async fn run_talon_batch(
+    commands: Vec<String>,
+    stop_on_error: Option<bool>,
+    timeout_ms: Option<u64>,
+) -> Result<Vec<Result<String, String>>, String> {
+    let timeout = timeout_ms.map(std::time::Duration::from_millis).unwrap_or(talon::DEFAULT_TIMEOUT);
+    let results = tauri::async_runtime::spawn_blocking(move || {
+        talon::execute_talon_batch(&commands, stop_on_error.unwrap_or(true), timeout)
+    })
+    .await
+    .map_err(|e| e.to_string())?
+    .map_err(|e| e.to_string())?;
+    Ok(results.into_iter().map(|result| result.map_err(|e| e.to_string())).collect())
+}
+
+/// Restart the shared Talon REPL, e.g. after it crashed or Talon was restarted
+#[tauri::command]
+async fn restart_talon_session() -> Result<(), String> {
+    tauri::async_runtime::spawn_blocking(|| talon::restart_session().map_err(|e| e.to_string()))
+        .await
+        .map_err(|e| e.to_string())?
 }
 
 #[tauri::command]
@@ -136,21 +798,127 @@ pub fn run() {
         .invoke_handler(tauri::generate_handler![
             start_voice_capture,
             stop_voice_capture,
+            start_capture_from_file,
+            start_capture_to_file,
+            flush_now,
+            get_dropout_count,
+            get_capture_status,
+            get_last_chunk_decision,
             speak_text,
+            list_tts_voices,
+            set_tts_backend,
+            synthesize_to_file,
             stop_speaking,
+            clear_tts_queue,
+            is_speaking,
+            set_say_playback_through_cpal,
+            set_monitoring,
             configure_whisper,
+            clear_api_key,
+            clear_stored_keys,
+            get_configured_api_keys,
+            configure_provider_defaults,
+            get_provider_defaults,
+            get_effective_config,
+            switch_provider,
+            set_transcription_task,
+            set_openai_base_url,
+            set_request_timeout,
+            set_word_timestamps,
+            list_models,
+            fetch_provider_models,
+            self_check,
+            configure_realtime,
+            configure_recovery,
+            set_vad_timing,
+            get_vad_timing,
+            configure_vad,
+            set_vad_threshold_dbfs,
+            get_vad_threshold_dbfs,
+            set_level_units,
+            set_chunk_duration,
+            set_min_segment_duration,
+            set_segmentation_mode,
+            set_adaptive_chunking,
+            reset_context,
+            set_context_reset_gap,
+            configure_typing_formatter,
+            set_conversation_mode,
+            export_conversation,
+            record_to_buffer,
+            stop_recording_to_buffer,
+            transcribe_buffer,
+            transcribe_to_srt,
+            list_buffers,
+            delete_buffer,
+            configure_compressor,
+            get_compressor_config,
+            configure_high_pass,
+            get_high_pass_config,
+            subscribe_raw_audio,
+            get_raw_audio_config,
+            configure_auto_gain,
+            get_auto_gain_config,
+            download_whisper_model,
+            set_response_parsing,
+            set_language,
+            configure_language_gate,
+            set_resampler_quality,
+            get_resampler_quality,
+            set_punctuation_restoration,
+            set_streaming_transcription,
+            set_transcription_temperature,
+            set_confidence_filter,
+            start_level_monitoring,
+            stop_level_monitoring,
+            has_input_device,
+            set_device_picker_open,
+            get_transcription_stats,
             list_audio_devices,
+            get_device_capabilities,
             get_selected_audio_device,
             set_audio_device,
+            list_output_devices,
+            get_selected_output_device,
+            set_output_device,
+            set_input_channel,
+            get_input_channel,
+            set_downmix,
+            set_capture_rate_ceiling,
+            set_deferred_conversion,
+            set_max_buffer_duration,
+            set_interim_results,
+            set_suppress_repeats,
+            set_save_segments,
+            set_capture_mode,
             open_external_url,
             frontend_log,
             test_emit_transcript,
+            generate_test_tone,
             is_talon_available,
+            is_talon_running,
+            set_talon_path,
+            register_voice_commands,
+            get_voice_commands,
+            set_dictation_passthrough,
             run_talon,
+            run_talon_batch,
+            restart_talon_session,
         ])
-        .setup(|_app| {
+        .setup(|app| {
             // Initialize voice capture system
-            voice::init()?;
+            voice::init(app.handle().clone())?;
+
+            // Optional startup self-check, off the main thread since it hits the network
+            if std::env::var_os("KOE_SELF_CHECK").is_some() {
+                use tauri::{Emitter, EventTarget};
+                let handle = app.handle().clone();
+                std::thread::spawn(move || {
+                    let report = self_check::run();
+                    println!("Self-check:\n{}", self_check::describe(&report));
+                    handle.emit_to(EventTarget::Any, "app:self-check", report).ok();
+                });
+            }
 
             // Log that we're ready
             println!("Koe initialized");
@@ -0,0 +1,167 @@
+use cpal::traits::{DeviceTrait, StreamTrait};
+use parking_lot::Mutex;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{self, Sender};
+use tauri::AppHandle;
+
+use crate::error::KoeError;
+use crate::voice;
+
+// Whether the standalone level meter is running or starting
+static RUNNING: AtomicBool = AtomicBool::new(false);
+// Dropping this tells the meter thread to close its stream
+static STOP: Mutex<Option<Sender<()>>> = parking_lot::const_mutex(None);
+
+/// `voice:level` updates per second while metering
+const UPDATES_PER_SEC: u32 = 30;
+
+/// RMS and peak over fixed-length windows of mono samples
+struct Meter {
+    interval: usize, // samples per reading
+    sum_squares: f32,
+    peak: f32,
+    count: usize,
+}
+
+impl Meter {
+    fn new(sample_rate: u32) -> Self {
+        Meter {
+            interval: (sample_rate / UPDATES_PER_SEC).max(1) as usize,
+            sum_squares: 0.0,
+            peak: 0.0,
+            count: 0,
+        }
+    }
+
+    /// Add samples, returning `(rms, peak)` each time a window fills
+    fn push(&mut self, samples: &[f32]) -> Option<(f32, f32)> {
+        let mut reading = None;
+        for &sample in samples {
+            self.sum_squares += sample * sample;
+            self.peak = self.peak.max(sample.abs());
+            self.count += 1;
+            if self.count >= self.interval {
+                reading = Some(((self.sum_squares / self.count as f32).sqrt(), self.peak));
+                self.sum_squares = 0.0;
+                self.peak = 0.0;
+                self.count = 0;
+            }
+        }
+        reading
+    }
+}
+
+/// Emit `voice:level` from the selected input without capturing for transcription,
+/// e.g. for a mic-check screen
+pub fn start(app: AppHandle) -> Result<(), KoeError> {
+    if RUNNING.compare_exchange(false, true, Ordering::SeqCst, Ordering::SeqCst).is_err() {
+        return Ok(());
+    }
+    let result = spawn_meter(app);
+    match result {
+        Ok(sender) => {
+            // A stop that landed while the stream was opening wins; dropping the sender closes it
+            let mut stop = STOP.lock();
+            if RUNNING.load(Ordering::SeqCst) {
+                *stop = Some(sender);
+            }
+            Ok(())
+        }
+        Err(e) => {
+            RUNNING.store(false, Ordering::SeqCst);
+            Err(e)
+        }
+    }
+}
+
+pub fn stop() {
+    let mut stop = STOP.lock();
+    RUNNING.store(false, Ordering::SeqCst);
+    stop.take();
+}
+
+/// Open the meter's stream on its own thread (cpal streams aren't Send), returning the
+/// sender whose drop closes it
+fn spawn_meter(app: AppHandle) -> Result<Sender<()>, KoeError> {
+    let device = voice::get_input_device()?;
+    let supported = device.default_input_config()?;
+    let (stop, stopped) = mpsc::channel::<()>();
+    let (ready, opened) = mpsc::channel();
+    std::thread::spawn(move || match open(app, device, supported) {
+        Ok(_stream) => {
+            ready.send(Ok(())).ok();
+            // Keeps the stream open until the sender is dropped
+            stopped.recv().ok();
+        }
+        Err(e) => {
+            ready.send(Err(e)).ok();
+        }
+    });
+    opened
+        .recv()
+        .map_err(|_| KoeError::AudioDevice("Level meter thread exited".to_string()))??;
+    Ok(stop)
+}
+
+/// Open and play a metering stream on `device`
+fn open(
+    app: AppHandle,
+    device: cpal::Device,
+    supported: cpal::SupportedStreamConfig,
+) -> Result<cpal::Stream, KoeError> {
+    let sample_format = supported.sample_format();
+    let config: cpal::StreamConfig = supported.into();
+    let stream = match sample_format {
+        cpal::SampleFormat::F32 => build::<f32>(&device, &config, app)?,
+        cpal::SampleFormat::I16 => build::<i16>(&device, &config, app)?,
+        cpal::SampleFormat::I32 => build::<i32>(&device, &config, app)?,
+        cpal::SampleFormat::U16 => build::<u16>(&device, &config, app)?,
+        other => return Err(KoeError::AudioDevice(format!("Unsupported sample format {}", other))),
+    };
+    stream.play()?;
+    Ok(stream)
+}
+
+fn build<T>(device: &cpal::Device, config: &cpal::StreamConfig, app: AppHandle) -> Result<cpal::Stream, KoeError>
+where
+    T: cpal::SizedSample + Send + 'static,
+    f32: cpal::FromSample<T>,
+{
+    let channels = config.channels as usize;
+    let mut meter = Meter::new(config.sample_rate.0);
+    let mut mono = Vec::new();
+    let stream = device.build_input_stream(
+        config,
+        move |data: &[T], _: &cpal::InputCallbackInfo| {
+            // The loudest channel in each frame, so a signal on any input shows up
+            mono.clear();
+            mono.extend(data.chunks(channels).map(|frame| {
+                frame.iter().map(|s| s.to_sample::<f32>()).fold(0.0, |loudest: f32, s| {
+                    if s.abs() > loudest.abs() { s } else { loudest }
+                })
+            }));
+            if let Some((rms, peak)) = meter.push(&mono) {
+                voice::emit_level(&app, rms, peak);
+            }
+        },
+        |err| eprintln!("Level meter stream error: {}", err),
+        None,
+    )?;
+    Ok(stream)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_meter_reads_once_per_window() {
+        let mut meter = Meter::new(300); // 10 samples per reading
+        assert_eq!(meter.push(&[0.5; 9]), None);
+        let (rms, peak) = meter.push(&[-0.5, 0.0]).unwrap();
+        assert!((rms - 0.5).abs() < 1e-6);
+        assert_eq!(peak, 0.5);
+        // The extra sample starts the next window
+        assert_eq!(meter.count, 1);
+    }
+}
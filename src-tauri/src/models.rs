@@ -0,0 +1,167 @@
+use sha1::{Digest, Sha1};
+use std::fs::{File, OpenOptions};
+use std::io::{Read, Write};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::{Duration, Instant};
+use tauri::{AppHandle, Emitter, EventTarget, Manager};
+
+use crate::voice;
+
+// A model download is in progress; one at a time
+static DOWNLOADING: AtomicBool = AtomicBool::new(false);
+
+/// whisper.cpp's ggml models on Hugging Face
+const MODEL_BASE_URL: &str = "https://huggingface.co/ggerganov/whisper.cpp/resolve/main";
+
+/// Minimum time between `model:download_progress` events
+const PROGRESS_INTERVAL: Duration = Duration::from_millis(250);
+
+/// A downloadable ggml model and its published SHA-1
+struct ModelFile {
+    size: &'static str,
+    file: &'static str,
+    sha1: &'static str,
+}
+
+const MODELS: &[ModelFile] = &[
+    ModelFile { size: "tiny", file: "ggml-tiny.bin", sha1: "bd577a113a864445d4c299885e0cb97d4ba92b5f" },
+    ModelFile { size: "base", file: "ggml-base.bin", sha1: "465707469ff3a37a2b9b8d8f89f2f99de7299dac" },
+    ModelFile { size: "small", file: "ggml-small.bin", sha1: "55356645c2b361a969dfd0ef2c5a50d530afd8d5" },
+    ModelFile { size: "medium", file: "ggml-medium.bin", sha1: "fd9727b6e1217c2f614f9b698455c4ffd82463b4" },
+];
+
+fn find_model(size: &str) -> Result<&'static ModelFile, String> {
+    let size = size.trim().to_ascii_lowercase();
+    MODELS.iter().find(|m| m.size == size).ok_or_else(|| {
+        let sizes: Vec<&str> = MODELS.iter().map(|m| m.size).collect();
+        format!("Unknown model size '{}' (expected {})", size, sizes.join(", "))
+    })
+}
+
+/// Download a ggml Whisper model into the app data dir, resuming a partial download, and switch
+/// to transcribing locally with it. Emits `model:download_progress`; returns the model's path
+pub fn download_model(app: &AppHandle, size: &str) -> Result<PathBuf, String> {
+    let model = find_model(size)?;
+    if DOWNLOADING.swap(true, Ordering::SeqCst) {
+        return Err("A model download is already running".to_string());
+    }
+    let result = fetch(app, model);
+    DOWNLOADING.store(false, Ordering::SeqCst);
+
+    let path = result?;
+    voice::use_local_model(path.display().to_string());
+    Ok(path)
+}
+
+fn fetch(app: &AppHandle, model: &ModelFile) -> Result<PathBuf, String> {
+    let dir = app.path().app_data_dir().map_err(|e| e.to_string())?.join("models");
+    std::fs::create_dir_all(&dir).map_err(|e| format!("Failed to create {}: {}", dir.display(), e))?;
+    let path = dir.join(model.file);
+    if path.exists() {
+        return Ok(path);
+    }
+
+    let partial = dir.join(format!("{}.part", model.file));
+    let mut offset = std::fs::metadata(&partial).map(|m| m.len()).unwrap_or(0);
+    // Large files: only the connection gets a deadline, not the whole transfer
+    let client = reqwest::blocking::Client::builder()
+        .connect_timeout(Duration::from_secs(15))
+        .timeout(None)
+        .build()
+        .map_err(|e| e.to_string())?;
+    let mut response = client
+        .get(format!("{}/{}", MODEL_BASE_URL, model.file))
+        .header("Range", format!("bytes={}-", offset))
+        .send()
+        .map_err(|e| format!("Model download failed: {}", e))?;
+
+    let status = response.status().as_u16();
+    let mut file = match status {
+        206 => OpenOptions::new().append(true).open(&partial),
+        // The server ignored the range: start over
+        200 => {
+            offset = 0;
+            File::create(&partial)
+        }
+        // Nothing past `offset`: the previous attempt got the whole file
+        416 => return finish(model, &partial, &path),
+        other => return Err(format!("Model download failed with HTTP {}", other)),
+    }
+    .map_err(|e| format!("Failed to open {}: {}", partial.display(), e))?;
+
+    let total = response.content_length().map(|len| len + offset);
+    let mut downloaded = offset;
+    let mut last_progress = Instant::now() - PROGRESS_INTERVAL;
+    let mut buf = vec![0u8; 64 * 1024];
+    loop {
+        let n = response.read(&mut buf).map_err(|e| format!("Model download interrupted: {}", e))?;
+        if n == 0 {
+            break;
+        }
+        file.write_all(&buf[..n]).map_err(|e| format!("Failed to write {}: {}", partial.display(), e))?;
+        downloaded += n as u64;
+        if last_progress.elapsed() >= PROGRESS_INTERVAL {
+            emit_progress(app, model, downloaded, total);
+            last_progress = Instant::now();
+        }
+    }
+    emit_progress(app, model, downloaded, total);
+    drop(file);
+
+    if total.is_some_and(|total| downloaded < total) {
+        return Err("Model download ended early; try again to resume".to_string());
+    }
+    finish(model, &partial, &path)
+}
+
+/// Check the completed download against the published SHA-1 and move it into place
+fn finish(model: &ModelFile, partial: &Path, path: &Path) -> Result<PathBuf, String> {
+    let digest = sha1_hex(partial)?;
+    if digest != model.sha1 {
+        // A corrupt partial would otherwise be resumed forever
+        std::fs::remove_file(partial).ok();
+        return Err(format!("Downloaded {} failed verification (SHA-1 {}); try again", model.file, digest));
+    }
+    std::fs::rename(partial, path).map_err(|e| format!("Failed to move {} into place: {}", path.display(), e))?;
+    Ok(path.to_path_buf())
+}
+
+fn sha1_hex(path: &Path) -> Result<String, String> {
+    let mut file = File::open(path).map_err(|e| format!("Failed to open {}: {}", path.display(), e))?;
+    let mut hasher = Sha1::new();
+    let mut buf = vec![0u8; 64 * 1024];
+    loop {
+        let n = file.read(&mut buf).map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+        if n == 0 {
+            break;
+        }
+        hasher.update(&buf[..n]);
+    }
+    Ok(format!("{:x}", hasher.finalize()))
+}
+
+fn emit_progress(app: &AppHandle, model: &ModelFile, downloaded: u64, total: Option<u64>) {
+    app.emit_to(
+        EventTarget::Any,
+        "model:download_progress",
+        serde_json::json!({
+            "size": model.size,
+            "downloaded": downloaded,
+            "total": total
+        }),
+    )
+    .ok();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_find_model() {
+        assert_eq!(find_model(" Base ").unwrap().file, "ggml-base.bin");
+        let error = find_model("huge").err().unwrap();
+        assert!(error.contains("tiny, base, small, medium"));
+    }
+}
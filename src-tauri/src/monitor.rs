@@ -0,0 +1,190 @@
+use cpal::traits::{DeviceTrait, StreamTrait};
+use parking_lot::Mutex;
+use std::collections::VecDeque;
+use std::sync::atomic::{AtomicBool, AtomicU32, AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+// Whether captured audio is being routed to the output device
+static ENABLED: AtomicBool = AtomicBool::new(false);
+// Monitor gain, stored as f32 bits
+static GAIN: AtomicU32 = AtomicU32::new(0);
+// Bumped on disable so the output thread knows to close its stream
+static GENERATION: AtomicU64 = AtomicU64::new(0);
+
+/// Most audio queued between input and output before the oldest is dropped to catch up
+const MAX_LATENCY_MS: u32 = 80;
+
+/// Output device names that suggest speakers, where monitoring would feed back into the mic
+const SPEAKER_HINTS: &[&str] = &["speaker", "built-in output", "display audio"];
+
+/// Mono input samples waiting for the output callback
+struct Ring {
+    samples: VecDeque<f32>,
+    input_rate: u32,
+    phase: f64, // fractional read position between samples[0] and samples[1]
+}
+
+impl Ring {
+    fn new() -> Self {
+        Ring {
+            samples: VecDeque::new(),
+            input_rate: 16000,
+            phase: 0.0,
+        }
+    }
+
+    fn push(&mut self, samples: &[f32], input_rate: u32) {
+        if input_rate != self.input_rate {
+            self.samples.clear();
+            self.phase = 0.0;
+            self.input_rate = input_rate;
+        }
+        self.samples.extend(samples);
+        let max_len = (input_rate * MAX_LATENCY_MS / 1000) as usize;
+        if self.samples.len() > max_len {
+            let excess = self.samples.len() - max_len;
+            self.samples.drain(..excess);
+        }
+    }
+
+    /// Next output sample at `output_rate`, linearly interpolated; silence on underrun
+    fn next(&mut self, output_rate: u32) -> f32 {
+        if self.samples.len() < 2 {
+            return 0.0;
+        }
+        let sample = self.samples[0] + (self.samples[1] - self.samples[0]) * self.phase as f32;
+        self.phase += self.input_rate as f64 / output_rate as f64;
+        while self.phase >= 1.0 && !self.samples.is_empty() {
+            self.samples.pop_front();
+            self.phase -= 1.0;
+        }
+        sample
+    }
+}
+
+lazy_static::lazy_static! {
+    static ref RING: Arc<Mutex<Ring>> = Arc::new(Mutex::new(Ring::new()));
+}
+
+/// Route captured audio to the selected output device at `gain` (0-1), or stop doing so
+pub fn set_monitoring(enabled: bool, gain: f32) -> Result<(), String> {
+    if !(0.0..=1.0).contains(&gain) {
+        return Err(format!("Monitor gain must be between 0 and 1, got {}", gain));
+    }
+    GAIN.store(gain.to_bits(), Ordering::SeqCst);
+
+    if !enabled {
+        if ENABLED.swap(false, Ordering::SeqCst) {
+            GENERATION.fetch_add(1, Ordering::SeqCst);
+            println!("Input monitoring stopped");
+        }
+        return Ok(());
+    }
+    if ENABLED.load(Ordering::SeqCst) {
+        return Ok(());
+    }
+
+    let device = crate::tts::output_device()?;
+    let device_name = device.name().unwrap_or_default();
+    if looks_like_speakers(&device_name) {
+        return Err(format!(
+            "'{}' looks like speakers; monitoring would feed back into the microphone. Use headphones.",
+            device_name
+        ));
+    }
+
+    RING.lock().samples.clear();
+    ENABLED.store(true, Ordering::SeqCst);
+    let generation = GENERATION.load(Ordering::SeqCst);
+    // cpal streams aren't Send, so the output stream lives on its own thread
+    std::thread::spawn(move || {
+        if let Err(e) = run_output(device, generation) {
+            eprintln!("Input monitoring failed: {}", e);
+            ENABLED.store(false, Ordering::SeqCst);
+        }
+    });
+    println!("Input monitoring through '{}'", device_name);
+    Ok(())
+}
+
+fn looks_like_speakers(device_name: &str) -> bool {
+    let name = device_name.to_ascii_lowercase();
+    SPEAKER_HINTS.iter().any(|hint| name.contains(hint))
+}
+
+/// Queue captured mono samples for playback, if monitoring
+pub fn push(samples: &[f32], input_rate: u32) {
+    if ENABLED.load(Ordering::Relaxed) {
+        RING.lock().push(samples, input_rate);
+    }
+}
+
+/// Play the ring on `device` until monitoring is disabled
+fn run_output(device: cpal::Device, generation: u64) -> Result<(), String> {
+    let supported = device.default_output_config().map_err(|e| e.to_string())?;
+    let sample_format = supported.sample_format();
+    let config: cpal::StreamConfig = supported.into();
+
+    let stream = match sample_format {
+        cpal::SampleFormat::F32 => build_stream::<f32>(&device, &config),
+        cpal::SampleFormat::I16 => build_stream::<i16>(&device, &config),
+        cpal::SampleFormat::U16 => build_stream::<u16>(&device, &config),
+        other => return Err(format!("Unsupported output sample format {}", other)),
+    }
+    .map_err(|e| e.to_string())?;
+    stream.play().map_err(|e| e.to_string())?;
+
+    while GENERATION.load(Ordering::SeqCst) == generation {
+        std::thread::sleep(Duration::from_millis(50));
+    }
+    Ok(())
+}
+
+/// Play the ring on every channel, converted to the device's sample format
+fn build_stream<T>(device: &cpal::Device, config: &cpal::StreamConfig) -> Result<cpal::Stream, cpal::BuildStreamError>
+where
+    T: cpal::SizedSample + cpal::FromSample<f32>,
+{
+    let channels = config.channels as usize;
+    let output_rate = config.sample_rate.0;
+    let ring = RING.clone();
+    device.build_output_stream(
+        config,
+        move |data: &mut [T], _: &cpal::OutputCallbackInfo| {
+            let gain = f32::from_bits(GAIN.load(Ordering::Relaxed));
+            let mut ring = ring.lock();
+            for frame in data.chunks_mut(channels) {
+                frame.fill(T::from_sample((ring.next(output_rate) * gain).clamp(-1.0, 1.0)));
+            }
+        },
+        |err| eprintln!("Monitor stream error: {}", err),
+        None,
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_ring_converts_rate_and_caps_latency() {
+        let mut ring = Ring::new();
+        ring.push(&[0.0, 0.2, 0.4, 0.6, 0.8], 16000);
+        // Output at 32kHz interpolates halfway between input samples
+        let out: Vec<f32> = (0..4).map(|_| ring.next(32000)).collect();
+        for (got, want) in out.iter().zip([0.0, 0.1, 0.2, 0.3]) {
+            assert!((got - want).abs() < 1e-6);
+        }
+
+        // 200ms of input keeps only the newest 80ms
+        ring.push(&vec![0.5; 3200], 16000);
+        assert_eq!(ring.samples.len(), 1280);
+    }
+
+    #[test]
+    fn test_speaker_guard() {
+        assert!(looks_like_speakers("MacBook Pro Speakers"));
+        assert!(!looks_like_speakers("AirPods Pro"));
+    }
+}
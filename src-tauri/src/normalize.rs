@@ -0,0 +1,92 @@
+use parking_lot::Mutex;
+
+/// Peak normalization settings
+#[derive(Clone, Copy, Debug, serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct NormalizeConfig {
+    pub auto_gain: bool,
+    pub target_db: f32,   // dBFS the segment's peak is raised to
+    pub max_gain_db: f32, // cap, so near-silence isn't boosted into loud noise
+}
+
+impl Default for NormalizeConfig {
+    fn default() -> Self {
+        NormalizeConfig {
+            auto_gain: false,
+            target_db: -3.0,
+            max_gain_db: 20.0,
+        }
+    }
+}
+
+lazy_static::lazy_static! {
+    static ref NORMALIZE_CONFIG: Mutex<NormalizeConfig> = Mutex::new(NormalizeConfig::default());
+}
+
+/// Validate and apply normalization settings
+pub fn configure(config: NormalizeConfig) -> Result<(), String> {
+    if !(-30.0..=0.0).contains(&config.target_db) {
+        return Err(format!("Target must be between -30 and 0 dBFS, got {}", config.target_db));
+    }
+    if !(0.0..=40.0).contains(&config.max_gain_db) {
+        return Err(format!("Max gain must be between 0 and 40 dB, got {}", config.max_gain_db));
+    }
+    *NORMALIZE_CONFIG.lock() = config;
+    Ok(())
+}
+
+pub fn config() -> NormalizeConfig {
+    *NORMALIZE_CONFIG.lock()
+}
+
+/// Normalize in place if auto gain is on
+pub fn apply(samples: &mut [f32]) {
+    let config = config();
+    if config.auto_gain {
+        normalize(samples, &config);
+    }
+}
+
+/// Scale so the peak reaches the target, never attenuating and never above the gain cap
+fn normalize(samples: &mut [f32], config: &NormalizeConfig) {
+    let peak = samples.iter().fold(0.0f32, |m, s| m.max(s.abs()));
+    if peak == 0.0 {
+        return;
+    }
+    let target = 10f32.powf(config.target_db / 20.0);
+    let gain = (target / peak).min(10f32.powf(config.max_gain_db / 20.0));
+    if gain <= 1.0 {
+        return;
+    }
+    for sample in samples.iter_mut() {
+        *sample *= gain;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn peak(samples: &[f32]) -> f32 {
+        samples.iter().fold(0.0, |m, s| m.max(s.abs()))
+    }
+
+    #[test]
+    fn test_quiet_signal_reaches_target() {
+        let config = NormalizeConfig { auto_gain: true, ..NormalizeConfig::default() };
+
+        // 0.1 peak is -20 dBFS; +17 dB lands on -3 dBFS (~0.708)
+        let mut quiet: Vec<f32> = (0..1600).map(|i| (i as f32 * 0.05).sin() * 0.1).collect();
+        normalize(&mut quiet, &config);
+        assert!((peak(&quiet) - 0.708).abs() < 0.01);
+
+        let mut silent = vec![0.0; 1600];
+        normalize(&mut silent, &config);
+        assert!(silent.iter().all(|&s| s == 0.0));
+
+        // A -60 dBFS noise floor only gets the 20 dB cap
+        let mut floor = vec![0.001, -0.001];
+        normalize(&mut floor, &config);
+        assert!((peak(&floor) - 0.01).abs() < 1e-5);
+    }
+}
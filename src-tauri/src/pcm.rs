@@ -0,0 +1,169 @@
+use base64::Engine;
+use parking_lot::Mutex;
+use tauri::{AppHandle, Emitter, EventTarget};
+
+/// Rate of the frames on `voice:pcm`, matching what transcription uses
+pub const PCM_RATE: u32 = 16000;
+/// Frame size range, in samples (10ms to 1s at 16kHz)
+const MIN_FRAME_SIZE: usize = 160;
+const MAX_FRAME_SIZE: usize = 16000;
+
+/// Whether captured audio is also (or only) sent to the frontend as raw PCM
+#[derive(Clone, Copy, Debug, Default, PartialEq, serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum PcmMode {
+    #[default]
+    Off,
+    Tap,       // alongside the built-in transcription
+    Exclusive, // instead of it
+}
+
+impl PcmMode {
+    pub fn parse(mode: &str) -> Result<Self, String> {
+        match mode.trim().to_ascii_lowercase().as_str() {
+            "off" => Ok(PcmMode::Off),
+            "tap" => Ok(PcmMode::Tap),
+            "exclusive" => Ok(PcmMode::Exclusive),
+            other => Err(format!("Unknown raw audio mode '{}' (expected off, tap or exclusive)", other)),
+        }
+    }
+}
+
+/// Raw audio subscription settings
+#[derive(Clone, Copy, Debug, serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PcmConfig {
+    pub mode: PcmMode,
+    pub frame_size: usize, // samples per `voice:pcm` event
+}
+
+impl Default for PcmConfig {
+    fn default() -> Self {
+        PcmConfig {
+            mode: PcmMode::Off,
+            frame_size: 320, // 20ms
+        }
+    }
+}
+
+lazy_static::lazy_static! {
+    static ref PCM_CONFIG: Mutex<PcmConfig> = Mutex::new(PcmConfig::default());
+}
+
+/// Validate and apply raw audio settings; they take effect on the next capture stream
+pub fn configure(config: PcmConfig) -> Result<(), String> {
+    if !(MIN_FRAME_SIZE..=MAX_FRAME_SIZE).contains(&config.frame_size) {
+        return Err(format!(
+            "Frame size must be between {} and {} samples, got {}",
+            MIN_FRAME_SIZE, MAX_FRAME_SIZE, config.frame_size
+        ));
+    }
+    *PCM_CONFIG.lock() = config;
+    Ok(())
+}
+
+pub fn config() -> PcmConfig {
+    *PCM_CONFIG.lock()
+}
+
+/// Turns one stream's mono capture into fixed-size 16kHz frames on `voice:pcm`
+pub struct PcmTap {
+    pub exclusive: bool,
+    resampler: LinearResampler,
+    pending: Vec<f32>,
+    frame_size: usize,
+}
+
+impl PcmTap {
+    /// A tap for a new capture stream, if raw audio is subscribed
+    pub fn for_stream(sample_rate: u32) -> Option<Self> {
+        let config = config();
+        (config.mode != PcmMode::Off).then(|| PcmTap {
+            exclusive: config.mode == PcmMode::Exclusive,
+            resampler: LinearResampler::new(sample_rate, PCM_RATE),
+            pending: Vec::new(),
+            frame_size: config.frame_size,
+        })
+    }
+
+    /// Queue captured mono samples, emitting each completed frame as base64 little-endian f32
+    pub fn push(&mut self, app: &AppHandle, mono: &[f32]) {
+        self.resampler.push(mono, &mut self.pending);
+        let whole = self.pending.len() - self.pending.len() % self.frame_size;
+        for frame in self.pending[..whole].chunks(self.frame_size) {
+            let bytes: Vec<u8> = frame.iter().flat_map(|s| s.to_le_bytes()).collect();
+            app.emit_to(
+                EventTarget::Any,
+                "voice:pcm",
+                serde_json::json!({
+                    "data": base64::engine::general_purpose::STANDARD.encode(bytes),
+                    "sampleRate": PCM_RATE
+                }),
+            )
+            .ok();
+        }
+        self.pending.drain(..whole);
+    }
+}
+
+/// Linear interpolation that carries its position across blocks, so a stream can be
+/// resampled a callback at a time without seams
+struct LinearResampler {
+    step: f64, // input samples per output sample
+    pos: f64,  // next output's position, relative to the current block; -1 is `prev`
+    prev: f32,
+}
+
+impl LinearResampler {
+    fn new(from_rate: u32, to_rate: u32) -> Self {
+        LinearResampler {
+            step: from_rate as f64 / to_rate as f64,
+            pos: 0.0,
+            prev: 0.0,
+        }
+    }
+
+    fn push(&mut self, input: &[f32], out: &mut Vec<f32>) {
+        let Some(&last) = input.last() else {
+            return;
+        };
+        let end = (input.len() - 1) as f64;
+        while self.pos < end {
+            let index = self.pos.floor();
+            let frac = (self.pos - index) as f32;
+            let a = if index < 0.0 { self.prev } else { input[index as usize] };
+            let b = input[(index + 1.0) as usize];
+            out.push(a + (b - a) * frac);
+            self.pos += self.step;
+        }
+        self.pos -= input.len() as f64;
+        self.prev = last;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_linear_resampler_is_seamless() {
+        let ramp: Vec<f32> = (0..480).map(|i| i as f32).collect();
+        let mut whole = Vec::new();
+        LinearResampler::new(48000, 16000).push(&ramp, &mut whole);
+
+        let mut resampler = LinearResampler::new(48000, 16000);
+        let mut blocked = Vec::new();
+        for block in ramp.chunks(7) {
+            resampler.push(block, &mut blocked);
+        }
+        assert_eq!(blocked, whole);
+        assert_eq!(&whole[..3], &[0.0, 3.0, 6.0]);
+    }
+
+    #[test]
+    fn test_configure_rejects_frame_size() {
+        let config = PcmConfig { mode: PcmMode::Tap, frame_size: 10 };
+        assert!(configure(config).is_err());
+        assert_eq!(PcmMode::parse(" Exclusive "), Ok(PcmMode::Exclusive));
+    }
+}
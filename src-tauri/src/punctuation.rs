@@ -0,0 +1,73 @@
+//! Rule-based punctuation restoration for providers that return bare text
+//! (some Whisper configurations, Vosk). Deliberately conservative: text that
+//! already carries punctuation is passed through untouched.
+
+/// Leading words that turn an utterance into a question
+const QUESTION_WORDS: &[&str] = &[
+    "what", "why", "how", "who", "whom", "whose", "when", "where", "which",
+    "is", "are", "am", "was", "were", "can", "could", "would", "should",
+    "will", "shall", "do", "does", "did", "have", "has", "may", "might",
+];
+
+/// Check whether the provider already punctuated the text
+pub fn has_punctuation(text: &str) -> bool {
+    text.chars()
+        .any(|c| matches!(c, '.' | '?' | '!' | ',' | ';' | ':'))
+}
+
+/// Restore sentence casing and terminal punctuation on unpunctuated text
+pub fn restore(text: &str) -> String {
+    let trimmed = text.trim();
+    if trimmed.is_empty() || has_punctuation(trimmed) {
+        return trimmed.to_string();
+    }
+
+    // Fix the standalone pronoun "i" and its contractions (i'm, i've, ...)
+    let words: Vec<String> = trimmed
+        .split_whitespace()
+        .map(|w| {
+            if w == "i" || w.starts_with("i'") {
+                format!("I{}", &w[1..])
+            } else {
+                w.to_string()
+            }
+        })
+        .collect();
+
+    let first_word = words[0].to_lowercase();
+    let terminal = if QUESTION_WORDS.contains(&first_word.as_str()) {
+        '?'
+    } else {
+        '.'
+    };
+
+    let joined = words.join(" ");
+    let mut chars = joined.chars();
+    let mut restored = match chars.next() {
+        Some(c) => c.to_uppercase().collect::<String>() + chars.as_str(),
+        None => String::new(),
+    };
+    restored.push(terminal);
+    restored
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_restore_statement() {
+        assert_eq!(restore("i think i'm ready"), "I think I'm ready.");
+    }
+
+    #[test]
+    fn test_restore_question() {
+        assert_eq!(restore("what time is it"), "What time is it?");
+    }
+
+    #[test]
+    fn test_restore_leaves_punctuated_text() {
+        assert_eq!(restore(" Hello, world. "), "Hello, world.");
+        assert_eq!(restore(""), "");
+    }
+}
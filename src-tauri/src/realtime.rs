@@ -0,0 +1,405 @@
+//! Real-time streaming transcription over provider WebSockets (Deepgram,
+//! AssemblyAI). Captured audio is forwarded frame-by-frame and the provider
+//! does all segmentation, returning interim and final results as they land.
+
+use parking_lot::Mutex;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::mpsc;
+use std::sync::Arc;
+use tauri::AppHandle;
+
+type FrameSender = mpsc::SyncSender<Vec<f32>>;
+
+// Frames the audio callback dropped because the socket fell behind, reported when the
+// session stops so the callback itself never logs
+static DROPPED_FRAMES: AtomicU64 = AtomicU64::new(0);
+
+struct RealtimeConfig {
+    enabled: bool,
+    provider: String, // "deepgram" or "assemblyai"
+    api_key: Option<String>,
+}
+
+lazy_static::lazy_static! {
+    static ref REALTIME_CONFIG: Arc<Mutex<RealtimeConfig>> = Arc::new(Mutex::new(RealtimeConfig {
+        enabled: false,
+        provider: "deepgram".to_string(),
+        api_key: None,
+    }));
+    // Feeds captured audio to the socket thread; dropping it (and the sink's copy) ends the session
+    static ref FRAME_SENDER: Arc<Mutex<Option<FrameSender>>> = Arc::new(Mutex::new(None));
+    // Where start and stop hand the current session's sender to the capture callback's sink
+    static ref SINK_UPDATES: Mutex<Option<mpsc::Sender<Option<FrameSender>>>> = Mutex::new(None);
+}
+
+/// Configure the realtime provider used instead of chunked transcription
+pub fn configure_realtime(
+    enabled: bool,
+    provider: Option<String>,
+    api_key: Option<String>,
+) -> Result<(), String> {
+    if enabled && !cfg!(feature = "realtime") {
+        return Err("Realtime transcription requires the `realtime` feature".to_string());
+    }
+    if let Some(ref p) = provider {
+        if !matches!(p.as_str(), "deepgram" | "assemblyai") {
+            return Err(format!("Unsupported realtime provider: {}", p));
+        }
+    }
+
+    let mut config = REALTIME_CONFIG.lock();
+    config.enabled = enabled;
+    if let Some(p) = provider {
+        config.provider = p;
+    }
+    // None keeps the stored key; an empty string clears it
+    crate::voice::patch(&mut config.api_key, api_key);
+    Ok(())
+}
+
+/// Provider and key to stream with, if realtime transcription is enabled
+pub fn session() -> Option<(String, String)> {
+    let config = REALTIME_CONFIG.lock();
+    if !config.enabled {
+        return None;
+    }
+    Some((config.provider.clone(), config.api_key.clone()?))
+}
+
+/// Open the provider socket on a background thread for a capture session
+#[cfg(feature = "realtime")]
+pub fn start(app: AppHandle, provider: &str, api_key: &str, sample_rate: u32) -> Result<(), String> {
+    let (provider, api_key) = (provider.to_string(), api_key.to_string());
+    let (tx, rx) = mpsc::sync_channel(socket::FRAME_QUEUE_CAPACITY);
+    *FRAME_SENDER.lock() = Some(tx.clone());
+    update_sink(Some(tx));
+
+    std::thread::Builder::new()
+        .name("koe-realtime".to_string())
+        .spawn(move || socket::run(app, &provider, &api_key, sample_rate, rx))
+        .map_err(|e| e.to_string())?;
+
+    println!("Realtime transcription started");
+    Ok(())
+}
+
+#[cfg(not(feature = "realtime"))]
+pub fn start(_app: AppHandle, _provider: &str, _api_key: &str, _sample_rate: u32) -> Result<(), String> {
+    Err("Realtime transcription requires the `realtime` feature".to_string())
+}
+
+/// The capture callback's end of the frame queue. It learns of sessions starting and stopping
+/// through a channel, so pushing a frame takes no lock and never blocks
+pub struct FrameSink {
+    updates: mpsc::Receiver<Option<FrameSender>>,
+    sender: Option<FrameSender>,
+}
+
+impl FrameSink {
+    /// A sink for a new capture stream, picking up any session already running; replaces the
+    /// previous stream's sink
+    pub fn new() -> Self {
+        let (updates_tx, updates) = mpsc::channel();
+        let sender = FRAME_SENDER.lock().clone();
+        *SINK_UPDATES.lock() = Some(updates_tx);
+        FrameSink { updates, sender }
+    }
+
+    /// Queue a mono frame for streaming (called from the audio callback)
+    pub fn push(&mut self, frame: Vec<f32>) {
+        while let Ok(sender) = self.updates.try_recv() {
+            self.sender = sender;
+        }
+        if let Some(ref tx) = self.sender {
+            // Never block the audio thread; drop audio if the socket is backed up
+            if let Err(mpsc::TrySendError::Full(_)) = tx.try_send(frame) {
+                DROPPED_FRAMES.fetch_add(1, Ordering::Relaxed);
+            }
+        }
+    }
+}
+
+impl Default for FrameSink {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Point the current sink at `sender`; the sink drops its old sender on its next frame
+fn update_sink(sender: Option<FrameSender>) {
+    if let Some(updates) = SINK_UPDATES.lock().as_ref() {
+        updates.send(sender).ok();
+    }
+}
+
+/// End the session; the socket thread flushes pending results and closes
+pub fn stop() {
+    if FRAME_SENDER.lock().take().is_some() {
+        update_sink(None);
+    }
+    let dropped = DROPPED_FRAMES.swap(0, Ordering::Relaxed);
+    if dropped > 0 {
+        eprintln!("Realtime frame queue fell behind; dropped {} frames", dropped);
+    }
+}
+
+#[cfg(feature = "realtime")]
+mod socket {
+    use std::net::TcpStream;
+    use std::sync::mpsc::{Receiver, TryRecvError};
+    use std::time::{Duration, Instant};
+    use crate::error::{ErrorEvent, KoeError};
+    use tauri::{AppHandle, Emitter, EventTarget};
+    use tungstenite::client::IntoClientRequest;
+    use tungstenite::http::header::HeaderValue;
+    use tungstenite::stream::MaybeTlsStream;
+    use tungstenite::{Message, WebSocket};
+
+    type Socket = WebSocket<MaybeTlsStream<TcpStream>>;
+
+    /// Frames buffered for the socket thread before new audio is dropped
+    pub const FRAME_QUEUE_CAPACITY: usize = 256;
+    const MAX_RECONNECT_ATTEMPTS: u32 = 5;
+    /// How long a read waits before we go back to forwarding audio
+    const POLL_INTERVAL: Duration = Duration::from_millis(20);
+    /// How long to wait for trailing finals after asking the server to close
+    const CLOSE_TIMEOUT: Duration = Duration::from_secs(3);
+
+    /// Extract `(text, is_final)` from a provider message, ignoring metadata
+    pub fn parse_message(provider: &str, message: &str) -> Option<(String, bool)> {
+        let value: serde_json::Value = serde_json::from_str(message).ok()?;
+
+        let (text, is_final) = match provider {
+            "assemblyai" => {
+                if value["type"] != "Turn" {
+                    return None;
+                }
+                // With format_turns the end of a turn arrives twice; only the
+                // formatted copy is final
+                let is_final = value["end_of_turn"].as_bool().unwrap_or(false)
+                    && value["turn_is_formatted"].as_bool().unwrap_or(false);
+                (value["transcript"].as_str()?, is_final)
+            }
+            _ => {
+                if value["type"] != "Results" {
+                    return None;
+                }
+                (
+                    value["channel"]["alternatives"][0]["transcript"].as_str()?,
+                    value["is_final"].as_bool().unwrap_or(false),
+                )
+            }
+        };
+
+        if text.trim().is_empty() {
+            None
+        } else {
+            Some((text.to_string(), is_final))
+        }
+    }
+
+    /// Convert f32 samples to little-endian 16-bit PCM
+    pub fn pcm16_bytes(samples: &[f32]) -> Vec<u8> {
+        samples
+            .iter()
+            .flat_map(|s| ((s * 32767.0).clamp(-32768.0, 32767.0) as i16).to_le_bytes())
+            .collect()
+    }
+
+    /// Session loop: stream until capture stops, reconnecting on drops
+    pub fn run(app: AppHandle, provider: &str, api_key: &str, sample_rate: u32, frames: Receiver<Vec<f32>>) {
+        let mut attempts = 0;
+        loop {
+            match stream(&app, provider, api_key, sample_rate, &frames, &mut attempts) {
+                Ok(()) => break,
+                Err(e) => {
+                    attempts += 1;
+                    if attempts > MAX_RECONNECT_ATTEMPTS {
+                        eprintln!("Realtime transcription failed: {}", e);
+                        let event = ErrorEvent { recoverable: false, ..KoeError::Network(e).event() };
+                        let _ = app.emit_to(EventTarget::Any, "voice:error", event);
+                        break;
+                    }
+                    eprintln!("Realtime connection lost ({}), reconnecting (attempt {})", e, attempts);
+                    app.emit_to(EventTarget::Any, "voice:state", "reconnecting").ok();
+                    std::thread::sleep(Duration::from_millis(500 * 2u64.pow(attempts - 1)));
+
+                    // Audio captured while disconnected is stale; start fresh
+                    loop {
+                        match frames.try_recv() {
+                            Ok(_) => {}
+                            Err(TryRecvError::Empty) => break,
+                            Err(TryRecvError::Disconnected) => return,
+                        }
+                    }
+                }
+            }
+        }
+        println!("Realtime transcription stopped");
+    }
+
+    /// One connection: returns Ok when capture stopped, Err when the link dropped
+    fn stream(
+        app: &AppHandle,
+        provider: &str,
+        api_key: &str,
+        sample_rate: u32,
+        frames: &Receiver<Vec<f32>>,
+        attempts: &mut u32,
+    ) -> Result<(), String> {
+        let mut socket = connect(provider, api_key, sample_rate)?;
+        if *attempts > 0 {
+            app.emit_to(EventTarget::Any, "voice:state", "listening").ok();
+        }
+        *attempts = 0;
+
+        loop {
+            // Forward everything captured since the last pass
+            loop {
+                match frames.try_recv() {
+                    Ok(frame) => socket
+                        .send(Message::Binary(pcm16_bytes(&frame)))
+                        .map_err(|e| format!("Send failed: {}", e))?,
+                    Err(TryRecvError::Empty) => break,
+                    Err(TryRecvError::Disconnected) => {
+                        close(app, provider, &mut socket);
+                        return Ok(());
+                    }
+                }
+            }
+
+            if !read_results(app, provider, &mut socket)? {
+                return Err("Connection closed by server".to_string());
+            }
+        }
+    }
+
+    /// Emit any results the server has sent; false once the socket is closed
+    fn read_results(app: &AppHandle, provider: &str, socket: &mut Socket) -> Result<bool, String> {
+        match socket.read() {
+            Ok(Message::Text(text)) => {
+                if let Some((transcript, is_final)) = parse_message(provider, &text) {
+                    crate::voice::emit_transcript(app, &transcript, is_final, crate::voice::transcript_seq(is_final));
+                }
+                Ok(true)
+            }
+            Ok(Message::Close(_)) => Ok(false),
+            Ok(_) => Ok(true),
+            Err(tungstenite::Error::Io(ref e))
+                if matches!(e.kind(), std::io::ErrorKind::WouldBlock | std::io::ErrorKind::TimedOut) =>
+            {
+                Ok(true)
+            }
+            Err(tungstenite::Error::ConnectionClosed) | Err(tungstenite::Error::AlreadyClosed) => Ok(false),
+            Err(e) => Err(format!("Read failed: {}", e)),
+        }
+    }
+
+    /// Ask the server to finish, draining trailing finals until it closes
+    fn close(app: &AppHandle, provider: &str, socket: &mut Socket) {
+        let terminate = match provider {
+            "assemblyai" => r#"{"type":"Terminate"}"#,
+            _ => r#"{"type":"CloseStream"}"#,
+        };
+        if socket.send(Message::Text(terminate.to_string())).is_err() {
+            return;
+        }
+
+        let deadline = Instant::now() + CLOSE_TIMEOUT;
+        while Instant::now() < deadline {
+            match read_results(app, provider, socket) {
+                Ok(true) => {}
+                _ => break,
+            }
+        }
+        let _ = socket.close(None);
+    }
+
+    fn connect(provider: &str, api_key: &str, sample_rate: u32) -> Result<Socket, String> {
+        let (url, auth) = match provider {
+            "assemblyai" => (
+                format!(
+                    "wss://streaming.assemblyai.com/v3/ws?sample_rate={}&encoding=pcm_s16le&format_turns=true",
+                    sample_rate
+                ),
+                api_key.to_string(),
+            ),
+            _ => (
+                format!(
+                    "wss://api.deepgram.com/v1/listen?encoding=linear16&sample_rate={}&channels=1&interim_results=true&punctuate=true",
+                    sample_rate
+                ),
+                format!("Token {}", api_key),
+            ),
+        };
+
+        let mut request = url.into_client_request().map_err(|e| e.to_string())?;
+        request.headers_mut().insert(
+            "Authorization",
+            HeaderValue::from_str(&auth).map_err(|e| e.to_string())?,
+        );
+
+        let (socket, _) = tungstenite::connect(request)
+            .map_err(|e| format!("Realtime connection to {} failed: {}", provider, e))?;
+
+        // Short read timeout so reads interleave with sending audio
+        let timeout = Some(POLL_INTERVAL);
+        match socket.get_ref() {
+            MaybeTlsStream::Plain(s) => s.set_read_timeout(timeout),
+            MaybeTlsStream::NativeTls(s) => s.get_ref().set_read_timeout(timeout),
+            _ => Ok(()),
+        }
+        .map_err(|e| e.to_string())?;
+
+        println!("Connected to {} realtime transcription", provider);
+        Ok(socket)
+    }
+}
+
+#[cfg(all(test, feature = "realtime"))]
+mod tests {
+    use super::socket::*;
+    use super::{configure_realtime, REALTIME_CONFIG};
+
+    #[test]
+    fn test_configure_keeps_key_unless_given() {
+        configure_realtime(false, None, Some("dg-key".to_string())).unwrap();
+        configure_realtime(false, Some("assemblyai".to_string()), None).unwrap();
+        assert_eq!(REALTIME_CONFIG.lock().api_key.as_deref(), Some("dg-key"));
+        configure_realtime(false, None, Some(String::new())).unwrap();
+        assert_eq!(REALTIME_CONFIG.lock().api_key, None);
+    }
+
+    #[test]
+    fn test_parse_deepgram_results() {
+        let msg = r#"{"type":"Results","is_final":true,"channel":{"alternatives":[{"transcript":"hello world","confidence":0.98}]}}"#;
+        assert_eq!(
+            parse_message("deepgram", msg),
+            Some(("hello world".to_string(), true))
+        );
+
+        let empty = r#"{"type":"Results","is_final":false,"channel":{"alternatives":[{"transcript":""}]}}"#;
+        assert_eq!(parse_message("deepgram", empty), None);
+        assert_eq!(parse_message("deepgram", r#"{"type":"Metadata"}"#), None);
+    }
+
+    #[test]
+    fn test_parse_assemblyai_turns() {
+        let partial = r#"{"type":"Turn","transcript":"hello wor","end_of_turn":false,"turn_is_formatted":false}"#;
+        assert_eq!(
+            parse_message("assemblyai", partial),
+            Some(("hello wor".to_string(), false))
+        );
+
+        let formatted = r#"{"type":"Turn","transcript":"Hello world.","end_of_turn":true,"turn_is_formatted":true}"#;
+        assert_eq!(
+            parse_message("assemblyai", formatted),
+            Some(("Hello world.".to_string(), true))
+        );
+    }
+
+    #[test]
+    fn test_pcm16_bytes() {
+        assert_eq!(pcm16_bytes(&[0.0, 1.0, -1.0]), vec![0, 0, 0xff, 0x7f, 0x01, 0x80]);
+    }
+}
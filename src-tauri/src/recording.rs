@@ -0,0 +1,307 @@
+use parking_lot::Mutex;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::mpsc;
+use std::thread::JoinHandle;
+
+/// Blocks of audio queued for the writer thread before the callback starts dropping
+const WRITE_QUEUE_CAPACITY: usize = 512;
+
+// Samples the audio callback dropped because the writer fell behind, reported when the
+// recording finishes so the callback itself never logs
+static DROPPED_SAMPLES: AtomicU64 = AtomicU64::new(0);
+
+/// Subtitle file written next to the recording on stop
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum SubtitleFormat {
+    Srt,
+    Vtt,
+}
+
+impl SubtitleFormat {
+    pub fn parse(name: &str) -> Result<Self, String> {
+        match name.to_ascii_lowercase().as_str() {
+            "srt" => Ok(SubtitleFormat::Srt),
+            "vtt" => Ok(SubtitleFormat::Vtt),
+            other => Err(format!("Unknown subtitle format '{}' (expected srt or vtt)", other)),
+        }
+    }
+
+    fn extension(&self) -> &'static str {
+        match self {
+            SubtitleFormat::Srt => "srt",
+            SubtitleFormat::Vtt => "vtt",
+        }
+    }
+}
+
+/// A transcript segment, timed relative to the start of the audio
+#[derive(Clone, Debug, PartialEq)]
+pub struct Cue {
+    pub start: f64,
+    pub end: f64,
+    pub text: String,
+}
+
+/// Audio being written to disk while live transcription runs
+struct FileRecording {
+    path: PathBuf,
+    sample_rate: u32,
+    subtitles: Option<SubtitleFormat>,
+    sender: mpsc::SyncSender<Vec<f32>>,
+    writer: JoinHandle<Result<(), String>>,
+    samples_written: u64,
+    cues: Vec<Cue>,
+}
+
+lazy_static::lazy_static! {
+    static ref RECORDING: Mutex<Option<FileRecording>> = Mutex::new(None);
+}
+
+/// Start writing mono 16-bit audio to `path`
+pub fn start(path: PathBuf, sample_rate: u32, subtitles: Option<SubtitleFormat>) -> Result<(), String> {
+    let mut recording = RECORDING.lock();
+    if recording.is_some() {
+        return Err("Already recording to a file".to_string());
+    }
+
+    let spec = hound::WavSpec {
+        channels: 1,
+        sample_rate,
+        bits_per_sample: 16,
+        sample_format: hound::SampleFormat::Int,
+    };
+    let mut wav = hound::WavWriter::create(&path, spec)
+        .map_err(|e| format!("Failed to create {}: {}", path.display(), e))?;
+
+    // Disk writes happen off the audio thread
+    let (sender, receiver) = mpsc::sync_channel::<Vec<f32>>(WRITE_QUEUE_CAPACITY);
+    let writer = std::thread::spawn(move || {
+        for block in receiver {
+            for sample in block {
+                let sample_i16 = (sample * 32767.0).clamp(-32768.0, 32767.0) as i16;
+                wav.write_sample(sample_i16).map_err(|e| e.to_string())?;
+            }
+        }
+        wav.finalize().map_err(|e| e.to_string())
+    });
+
+    DROPPED_SAMPLES.store(0, Ordering::Relaxed);
+    println!("Recording audio to {}", path.display());
+    *recording = Some(FileRecording {
+        path,
+        sample_rate,
+        subtitles,
+        sender,
+        writer,
+        samples_written: 0,
+        cues: Vec::new(),
+    });
+    Ok(())
+}
+
+/// Queue captured samples for the file, if recording. Called from the capture callback, so it
+/// never waits: samples arriving while the recording is locked are dropped like a full queue's
+pub fn write(samples: &[f32]) {
+    if samples.is_empty() {
+        return;
+    }
+    let Some(mut recording) = RECORDING.try_lock() else {
+        DROPPED_SAMPLES.fetch_add(samples.len() as u64, Ordering::Relaxed);
+        return;
+    };
+    if let Some(recording) = recording.as_mut() {
+        recording.samples_written += queue(&recording.sender, samples);
+    }
+}
+
+/// Hand samples to the writer without blocking; returns how many made it into the file's
+/// queue, so offsets match the file even when the writer falls behind
+fn queue(sender: &mpsc::SyncSender<Vec<f32>>, samples: &[f32]) -> u64 {
+    if sender.try_send(samples.to_vec()).is_err() {
+        DROPPED_SAMPLES.fetch_add(samples.len() as u64, Ordering::Relaxed);
+        return 0;
+    }
+    samples.len() as u64
+}
+
+/// File offset (seconds) of a chunk whose last `buffered` samples were just written
+pub fn chunk_offset(buffered: usize) -> Option<f64> {
+    RECORDING.lock().as_ref().map(|recording| {
+        let start = recording.samples_written.saturating_sub(buffered as u64);
+        start as f64 / recording.sample_rate as f64
+    })
+}
+
+/// Record a transcript cue for the paired subtitle file
+pub fn add_cue(start: f64, duration: f64, text: &str) {
+    if let Some(recording) = RECORDING.lock().as_mut() {
+        recording.cues.push(Cue {
+            start,
+            end: start + duration,
+            text: text.trim().to_string(),
+        });
+    }
+}
+
+/// Finalize the WAV and write the subtitle file, if any; returns the audio path
+pub fn finish() -> Result<Option<PathBuf>, String> {
+    let Some(recording) = RECORDING.lock().take() else {
+        return Ok(None);
+    };
+
+    drop(recording.sender);
+    recording
+        .writer
+        .join()
+        .map_err(|_| "Recording writer panicked".to_string())??;
+    let dropped = DROPPED_SAMPLES.swap(0, Ordering::Relaxed);
+    if dropped > 0 {
+        eprintln!("Recording writer fell behind; dropped {} samples", dropped);
+    }
+
+    if let Some(format) = recording.subtitles {
+        let mut cues = recording.cues;
+        // Chunks can finish transcribing out of order
+        cues.sort_by(|a, b| a.start.total_cmp(&b.start));
+        let contents = match format {
+            SubtitleFormat::Srt => format_srt(&cues),
+            SubtitleFormat::Vtt => format_vtt(&cues),
+        };
+        let subtitle_path = recording.path.with_extension(format.extension());
+        std::fs::write(&subtitle_path, contents)
+            .map_err(|e| format!("Failed to write {}: {}", subtitle_path.display(), e))?;
+        println!("Wrote subtitles to {}", subtitle_path.display());
+    }
+
+    println!("Finished recording {}", recording.path.display());
+    Ok(Some(recording.path))
+}
+
+/// Decode a WAV file to mono f32 samples
+pub fn decode_wav(path: &Path) -> Result<(Vec<f32>, u32), String> {
+    let mut reader = hound::WavReader::open(path).map_err(|e| e.to_string())?;
+    let spec = reader.spec();
+    let channels = spec.channels.max(1) as usize;
+
+    let interleaved: Vec<f32> = match spec.sample_format {
+        hound::SampleFormat::Float => reader
+            .samples::<f32>()
+            .collect::<Result<_, _>>()
+            .map_err(|e| e.to_string())?,
+        hound::SampleFormat::Int => {
+            let scale = (1i64 << (spec.bits_per_sample - 1)) as f32;
+            reader
+                .samples::<i32>()
+                .map(|s| s.map(|v| v as f32 / scale))
+                .collect::<Result<_, _>>()
+                .map_err(|e| e.to_string())?
+        }
+    };
+
+    let mono = interleaved
+        .chunks(channels)
+        .map(|frame| frame.iter().sum::<f32>() / frame.len() as f32)
+        .collect();
+    Ok((mono, spec.sample_rate))
+}
+
+/// `HH:MM:SS` plus milliseconds after `separator`
+fn timestamp(secs: f64, separator: char) -> String {
+    let millis = (secs.max(0.0) * 1000.0).round() as u64;
+    format!(
+        "{:02}:{:02}:{:02}{}{:03}",
+        millis / 3_600_000,
+        millis / 60_000 % 60,
+        millis / 1000 % 60,
+        separator,
+        millis % 1000
+    )
+}
+
+pub fn format_srt(cues: &[Cue]) -> String {
+    cues.iter()
+        .enumerate()
+        .map(|(i, cue)| {
+            format!(
+                "{}\n{} --> {}\n{}\n",
+                i + 1,
+                timestamp(cue.start, ','),
+                timestamp(cue.end, ','),
+                cue.text
+            )
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+pub fn format_vtt(cues: &[Cue]) -> String {
+    let mut out = String::from("WEBVTT\n");
+    for cue in cues {
+        out.push_str(&format!(
+            "\n{} --> {}\n{}\n",
+            timestamp(cue.start, '.'),
+            timestamp(cue.end, '.'),
+            cue.text
+        ));
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn cues() -> Vec<Cue> {
+        vec![
+            Cue { start: 0.0, end: 1.5, text: "Hello there.".to_string() },
+            Cue { start: 3661.25, end: 3662.0, text: "Much later.".to_string() },
+        ]
+    }
+
+    #[test]
+    fn test_dropped_samples_are_not_counted() {
+        let (sender, _receiver) = mpsc::sync_channel(1);
+        assert_eq!(queue(&sender, &[0.0; 160]), 160);
+        // The queue is full, so this block is dropped
+        assert_eq!(queue(&sender, &[0.0; 160]), 0);
+        assert!(DROPPED_SAMPLES.load(Ordering::Relaxed) >= 160);
+    }
+
+    #[test]
+    fn test_format_srt() {
+        assert_eq!(
+            format_srt(&cues()),
+            "1\n00:00:00,000 --> 00:00:01,500\nHello there.\n\n2\n01:01:01,250 --> 01:01:02,000\nMuch later.\n"
+        );
+    }
+
+    #[test]
+    fn test_format_vtt() {
+        assert_eq!(
+            format_vtt(&cues()),
+            "WEBVTT\n\n00:00:00.000 --> 00:00:01.500\nHello there.\n\n01:01:01.250 --> 01:01:02.000\nMuch later.\n"
+        );
+    }
+
+    #[test]
+    fn test_decode_wav_downmixes_stereo() {
+        let path = std::env::temp_dir().join(format!("koe-decode-test-{}.wav", std::process::id()));
+        let spec = hound::WavSpec {
+            channels: 2,
+            sample_rate: 22050,
+            bits_per_sample: 16,
+            sample_format: hound::SampleFormat::Int,
+        };
+        let mut writer = hound::WavWriter::create(&path, spec).unwrap();
+        for sample in [16384i16, 0, -16384, -16384] {
+            writer.write_sample(sample).unwrap();
+        }
+        writer.finalize().unwrap();
+
+        let (samples, rate) = decode_wav(&path).unwrap();
+        std::fs::remove_file(&path).ok();
+        assert_eq!(rate, 22050);
+        assert_eq!(samples, vec![0.25, -0.5]);
+    }
+}
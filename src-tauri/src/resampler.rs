@@ -0,0 +1,491 @@
+use parking_lot::Mutex;
+
+/// Resampler CPU/fidelity tradeoff
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ResamplerQuality {
+    Fast,     // linear interpolation, no anti-aliasing
+    Balanced, // short windowed-sinc
+    High,     // long windowed-sinc
+    Rubato,   // rubato's SincFixedIn (`high-quality-resample` feature), else High
+}
+
+impl ResamplerQuality {
+    pub fn parse(name: &str) -> Result<Self, String> {
+        match name.to_ascii_lowercase().as_str() {
+            "fast" | "linear" => Ok(ResamplerQuality::Fast),
+            "balanced" => Ok(ResamplerQuality::Balanced),
+            "high" | "sinc" => Ok(ResamplerQuality::High),
+            "rubato" => Ok(ResamplerQuality::Rubato),
+            other => Err(format!("Unknown resampler quality '{}' (expected fast, balanced, high or rubato)", other)),
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            ResamplerQuality::Fast => "fast",
+            ResamplerQuality::Balanced => "balanced",
+            ResamplerQuality::High => "high",
+            ResamplerQuality::Rubato => "rubato",
+        }
+    }
+
+    /// Sinc zero crossings on each side of the kernel
+    fn half_taps(&self) -> usize {
+        match self {
+            ResamplerQuality::Fast => 0,
+            ResamplerQuality::Balanced => 8,
+            ResamplerQuality::High | ResamplerQuality::Rubato => 32,
+        }
+    }
+
+    /// Passband as a fraction of the lower Nyquist frequency; longer kernels
+    /// have a narrower transition band, so they can pass closer to Nyquist
+    fn passband(&self) -> f64 {
+        match self {
+            ResamplerQuality::Fast => 1.0,
+            ResamplerQuality::Balanced => 0.8,
+            ResamplerQuality::High | ResamplerQuality::Rubato => 0.95,
+        }
+    }
+}
+
+/// Input frames rubato filters per call
+#[cfg(feature = "high-quality-resample")]
+const RUBATO_CHUNK: usize = 1024;
+
+lazy_static::lazy_static! {
+    static ref QUALITY: Mutex<ResamplerQuality> = Mutex::new(ResamplerQuality::Balanced);
+}
+
+// Rubato resampler for whole buffers, kept across calls since building its sinc table is
+// the expensive part; rebuilt only when the rates change
+#[cfg(feature = "high-quality-resample")]
+lazy_static::lazy_static! {
+    static ref RUBATO: Mutex<Option<RubatoStream>> = Mutex::new(None);
+}
+
+pub fn set_quality(quality: ResamplerQuality) {
+    *QUALITY.lock() = quality;
+}
+
+pub fn quality() -> ResamplerQuality {
+    *QUALITY.lock()
+}
+
+/// Resample with the configured quality
+pub fn resample(samples: &[f32], from_rate: u32, to_rate: u32) -> Vec<f32> {
+    resample_with(samples, from_rate, to_rate, quality())
+}
+
+pub fn resample_with(samples: &[f32], from_rate: u32, to_rate: u32, quality: ResamplerQuality) -> Vec<f32> {
+    if from_rate == to_rate || samples.is_empty() {
+        return samples.to_vec();
+    }
+    match quality {
+        ResamplerQuality::Fast => linear(samples, from_rate, to_rate),
+        #[cfg(feature = "high-quality-resample")]
+        ResamplerQuality::Rubato => rubato_sinc(samples, from_rate, to_rate).unwrap_or_else(|e| {
+            eprintln!("rubato resampling failed, using built-in sinc: {}", e);
+            windowed_sinc(samples, from_rate, to_rate, quality.half_taps(), quality.passband())
+        }),
+        _ => windowed_sinc(samples, from_rate, to_rate, quality.half_taps(), quality.passband()),
+    }
+}
+
+/// Resamples one continuous stream that arrives in pieces. Filter state carries across pieces,
+/// so chunk boundaries don't click and the output length doesn't drift the way resampling
+/// each piece on its own does. Output trails input by half the kernel until `flush`.
+pub struct StreamResampler {
+    from_rate: u32,
+    to_rate: u32,
+    quality: ResamplerQuality,
+    input: Vec<f32>, // unread input, plus the history the kernel still reaches back into
+    dropped: u64,    // input samples already discarded from the front of `input`
+    produced: u64,   // output samples so far
+    #[cfg(feature = "high-quality-resample")]
+    rubato: Option<RubatoStream>, // one rubato filter for the whole stream, at Rubato quality
+}
+
+impl StreamResampler {
+    /// A resampler with the configured quality
+    pub fn new(from_rate: u32, to_rate: u32) -> Self {
+        Self::with_quality(from_rate, to_rate, quality())
+    }
+
+    pub fn with_quality(from_rate: u32, to_rate: u32, quality: ResamplerQuality) -> Self {
+        #[cfg(feature = "high-quality-resample")]
+        let rubato = (quality == ResamplerQuality::Rubato && from_rate != to_rate)
+            .then(|| RubatoStream::new(from_rate, to_rate))
+            .and_then(|built| built.map_err(|e| eprintln!("rubato unavailable, using built-in sinc: {}", e)).ok());
+        StreamResampler {
+            from_rate,
+            to_rate,
+            quality,
+            input: Vec::new(),
+            dropped: 0,
+            produced: 0,
+            #[cfg(feature = "high-quality-resample")]
+            rubato,
+        }
+    }
+
+    /// Resample the next piece of the stream
+    pub fn process(&mut self, samples: &[f32]) -> Vec<f32> {
+        if self.from_rate == self.to_rate {
+            return samples.to_vec();
+        }
+        #[cfg(feature = "high-quality-resample")]
+        if let Some(rubato) = &mut self.rubato {
+            match rubato.process(samples) {
+                Ok(out) => return out,
+                Err(e) => {
+                    eprintln!("rubato resampling failed, using built-in sinc: {}", e);
+                    self.rubato = None;
+                }
+            }
+        }
+        self.input.extend_from_slice(samples);
+
+        let ratio = self.from_rate as f64 / self.to_rate as f64;
+        let (half_width, cutoff) = match self.quality {
+            ResamplerQuality::Fast => (1.0, 1.0),
+            quality => {
+                let cutoff = (1.0 / ratio).min(1.0) * quality.passband();
+                (quality.half_taps() as f64 / cutoff, cutoff)
+            }
+        };
+        let mut out = Vec::new();
+        loop {
+            // Positions come from the output count, not a running sum, so they can't drift
+            let center = self.produced as f64 * ratio - self.dropped as f64;
+            if center + half_width >= self.input.len() as f64 {
+                break;
+            }
+            out.push(match self.quality {
+                ResamplerQuality::Fast => linear_at(&self.input, center),
+                _ => sinc_at(&self.input, center, half_width, cutoff),
+            });
+            self.produced += 1;
+        }
+
+        // Discard input the kernel can no longer reach
+        let center = self.produced as f64 * ratio - self.dropped as f64;
+        let unreachable = ((center - half_width).floor().max(0.0) as usize).min(self.input.len());
+        self.input.drain(..unreachable);
+        self.dropped += unreachable as u64;
+        out
+    }
+
+    /// End the stream, returning the output still held back for lookahead
+    pub fn flush(&mut self) -> Vec<f32> {
+        if self.from_rate == self.to_rate {
+            return Vec::new();
+        }
+        #[cfg(feature = "high-quality-resample")]
+        if let Some(mut rubato) = self.rubato.take() {
+            return rubato.flush().unwrap_or_else(|e| {
+                eprintln!("rubato flush failed: {}", e);
+                Vec::new()
+            });
+        }
+        let total = self.dropped + self.input.len() as u64;
+        let expected = (total as f64 * self.to_rate as f64 / self.from_rate as f64) as u64;
+        let remaining = expected.saturating_sub(self.produced) as usize;
+        // Enough trailing silence to cover the widest kernel
+        let lookahead = (self.quality.half_taps().max(1) * 2) as u32 * self.from_rate.div_ceil(self.to_rate);
+        let mut out = self.process(&vec![0.0; lookahead as usize]);
+        out.truncate(remaining);
+        out
+    }
+}
+
+/// Resample the whole buffer with rubato, reusing the cached filter when the rates match
+#[cfg(feature = "high-quality-resample")]
+fn rubato_sinc(samples: &[f32], from_rate: u32, to_rate: u32) -> Result<Vec<f32>, String> {
+    let mut cached = RUBATO.lock();
+    let rubato = match cached.take() {
+        Some(mut rubato) if (rubato.from_rate, rubato.to_rate) == (from_rate, to_rate) => {
+            rubato.reset();
+            rubato
+        }
+        _ => RubatoStream::new(from_rate, to_rate)?,
+    };
+    let rubato = cached.insert(rubato);
+    let mut out = rubato.process(samples)?;
+    out.extend(rubato.flush()?);
+    Ok(out)
+}
+
+/// Rubato's SincFixedIn fed in fixed-size chunks, trimming its filter delay so output lines
+/// up with input
+#[cfg(feature = "high-quality-resample")]
+struct RubatoStream {
+    resampler: rubato::SincFixedIn<f32>,
+    from_rate: u32,
+    to_rate: u32,
+    pending: Vec<f32>, // input short of a whole chunk
+    consumed: u64,     // input samples handed to the filter
+    delay: usize,      // leading output samples still to discard
+    produced: u64,     // output samples returned
+}
+
+#[cfg(feature = "high-quality-resample")]
+impl RubatoStream {
+    fn new(from_rate: u32, to_rate: u32) -> Result<Self, String> {
+        use rubato::{Resampler, SincFixedIn, SincInterpolationParameters, SincInterpolationType, WindowFunction};
+
+        let params = SincInterpolationParameters {
+            sinc_len: 256,
+            f_cutoff: 0.95,
+            interpolation: SincInterpolationType::Linear,
+            oversampling_factor: 256,
+            window: WindowFunction::BlackmanHarris2,
+        };
+        let ratio = to_rate as f64 / from_rate as f64;
+        let resampler = SincFixedIn::<f32>::new(ratio, 1.0, params, RUBATO_CHUNK, 1).map_err(|e| e.to_string())?;
+        let delay = resampler.output_delay();
+        Ok(RubatoStream { resampler, from_rate, to_rate, pending: Vec::new(), consumed: 0, delay, produced: 0 })
+    }
+
+    /// Start a new, unrelated stream with the same filter
+    fn reset(&mut self) {
+        use rubato::Resampler;
+
+        self.resampler.reset();
+        self.pending.clear();
+        self.consumed = 0;
+        self.delay = self.resampler.output_delay();
+        self.produced = 0;
+    }
+
+    fn process(&mut self, samples: &[f32]) -> Result<Vec<f32>, String> {
+        use rubato::Resampler;
+
+        self.pending.extend_from_slice(samples);
+        let mut out = Vec::new();
+        while self.pending.len() >= self.resampler.input_frames_next() {
+            let chunk: Vec<f32> = self.pending.drain(..self.resampler.input_frames_next()).collect();
+            self.consumed += chunk.len() as u64;
+            out.extend(self.resampler.process(&[chunk], None).map_err(|e| e.to_string())?.remove(0));
+        }
+        let out = self.skip_delay(out);
+        self.produced += out.len() as u64;
+        Ok(out)
+    }
+
+    /// Filter what's left and drain the delay line, ending at the length the ratio implies
+    fn flush(&mut self) -> Result<Vec<f32>, String> {
+        use rubato::Resampler;
+
+        let total = self.consumed + self.pending.len() as u64;
+        let expected = (total as f64 * self.to_rate as f64 / self.from_rate as f64) as u64;
+        let remaining = expected.saturating_sub(self.produced) as usize;
+        let mut out = Vec::new();
+        if !self.pending.is_empty() {
+            let chunk = std::mem::take(&mut self.pending);
+            let tail = self.resampler.process_partial(Some(&[chunk]), None).map_err(|e| e.to_string())?.remove(0);
+            out.extend(self.skip_delay(tail));
+        }
+        while out.len() < remaining {
+            let tail = self.resampler.process_partial::<&[f32]>(None, None).map_err(|e| e.to_string())?.remove(0);
+            if tail.is_empty() {
+                break;
+            }
+            out.extend(self.skip_delay(tail));
+        }
+        out.truncate(remaining);
+        self.produced += out.len() as u64;
+        Ok(out)
+    }
+
+    fn skip_delay(&mut self, mut out: Vec<f32>) -> Vec<f32> {
+        let skipped = self.delay.min(out.len());
+        out.drain(..skipped);
+        self.delay -= skipped;
+        out
+    }
+}
+
+/// Resample audio to target sample rate using linear interpolation
+fn linear(samples: &[f32], from_rate: u32, to_rate: u32) -> Vec<f32> {
+    let ratio = from_rate as f64 / to_rate as f64;
+    let new_len = (samples.len() as f64 / ratio) as usize;
+    (0..new_len).map(|i| linear_at(samples, i as f64 * ratio)).collect()
+}
+
+/// The input interpolated at fractional position `src_idx`
+fn linear_at(samples: &[f32], src_idx: f64) -> f32 {
+    let idx_floor = src_idx.floor() as usize;
+    let idx_ceil = (idx_floor + 1).min(samples.len() - 1);
+    let frac = src_idx - idx_floor as f64;
+
+    let sample = samples[idx_floor] as f64 * (1.0 - frac) + samples[idx_ceil] as f64 * frac;
+    sample as f32
+}
+
+/// Band-limited resampling with a Blackman-windowed sinc kernel
+fn windowed_sinc(samples: &[f32], from_rate: u32, to_rate: u32, half_taps: usize, passband: f64) -> Vec<f32> {
+    let ratio = from_rate as f64 / to_rate as f64;
+    let new_len = (samples.len() as f64 / ratio) as usize;
+    // Cutoff relative to the input Nyquist; downsampling must filter below the output Nyquist
+    let cutoff = (1.0 / ratio).min(1.0) * passband;
+    let half_width = half_taps as f64 / cutoff;
+    (0..new_len).map(|i| sinc_at(samples, i as f64 * ratio, half_width, cutoff)).collect()
+}
+
+/// The filtered input at fractional position `center`
+fn sinc_at(samples: &[f32], center: f64, half_width: f64, cutoff: f64) -> f32 {
+    let start = (center - half_width).ceil().max(0.0) as usize;
+    let end = ((center + half_width).floor() as usize).min(samples.len() - 1);
+
+    let mut acc = 0.0;
+    let mut weight = 0.0;
+    for (j, sample) in samples.iter().enumerate().take(end + 1).skip(start) {
+        let x = j as f64 - center;
+        let t = std::f64::consts::PI * x * cutoff;
+        let sinc = if t.abs() < 1e-9 { 1.0 } else { t.sin() / t };
+        let u = std::f64::consts::PI * x / half_width;
+        let window = 0.42 + 0.5 * u.cos() + 0.08 * (2.0 * u).cos();
+        let k = sinc * window;
+        acc += *sample as f64 * k;
+        weight += k;
+    }
+    // Normalizing by the kernel sum keeps unity gain, including at the edges
+    if weight.abs() > 1e-12 {
+        (acc / weight) as f32
+    } else {
+        0.0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tone(freq: f32, rate: u32, secs: f32) -> Vec<f32> {
+        (0..(rate as f32 * secs) as usize)
+            .map(|i| (2.0 * std::f32::consts::PI * freq * i as f32 / rate as f32).sin() * 0.5)
+            .collect()
+    }
+
+    fn rms(samples: &[f32]) -> f32 {
+        (samples.iter().map(|s| s * s).sum::<f32>() / samples.len() as f32).sqrt()
+    }
+
+    /// Error against the ideal tone at the output rate, in dB (higher is better)
+    fn snr_db(freq: f32, from_rate: u32, to_rate: u32, quality: ResamplerQuality) -> f32 {
+        let out = resample_with(&tone(freq, from_rate, 0.5), from_rate, to_rate, quality);
+        let ideal = tone(freq, to_rate, 0.5);
+        // Skip the edges, where the kernel is truncated
+        let range = 200..out.len().min(ideal.len()) - 200;
+        let error: Vec<f32> = range.clone().map(|i| out[i] - ideal[i]).collect();
+        20.0 * (rms(&ideal[range]) / rms(&error)).log10()
+    }
+
+    #[test]
+    fn test_sinc_rejects_aliases() {
+        // 12kHz is above the 8kHz output Nyquist and would alias to 4kHz
+        let input = tone(12000.0, 48000, 0.5);
+        let fast = resample_with(&input, 48000, 16000, ResamplerQuality::Fast);
+        let high = resample_with(&input, 48000, 16000, ResamplerQuality::High);
+        assert!(rms(&fast[200..7800]) > 0.1);
+        assert!(rms(&high[200..7800]) < 0.01);
+    }
+
+    #[test]
+    fn test_output_length_matches_ratio() {
+        for (from_rate, expected) in [(48000, 16000), (44100, 16000)] {
+            let input = tone(440.0, from_rate, 1.0);
+            for quality in [
+                ResamplerQuality::Fast,
+                ResamplerQuality::Balanced,
+                ResamplerQuality::High,
+                ResamplerQuality::Rubato,
+            ] {
+                let out = resample_with(&input, from_rate, 16000, quality);
+                assert_eq!(out.len(), expected, "{} from {}Hz", quality.name(), from_rate);
+            }
+        }
+    }
+
+    #[test]
+    fn test_sinc_keeps_7khz_and_filters_18khz() {
+        let kept = resample_with(&tone(7000.0, 48000, 0.5), 48000, 16000, ResamplerQuality::parse("sinc").unwrap());
+        assert!((rms(&kept[200..7800]) - rms(&tone(7000.0, 16000, 0.5))).abs() < 0.02);
+
+        // 18kHz would fold down to 2kHz without the low-pass
+        let input = tone(18000.0, 48000, 0.5);
+        let linear = resample_with(&input, 48000, 16000, ResamplerQuality::parse("linear").unwrap());
+        let sinc = resample_with(&input, 48000, 16000, ResamplerQuality::High);
+        assert!(rms(&linear[200..7800]) > 0.1);
+        assert!(rms(&sinc[200..7800]) < 0.01);
+    }
+
+    #[test]
+    fn test_sinc_preserves_passband() {
+        assert!(snr_db(1000.0, 48000, 16000, ResamplerQuality::Balanced) > 30.0);
+        assert!(snr_db(1000.0, 44100, 16000, ResamplerQuality::High) > 40.0);
+        assert_eq!(ResamplerQuality::parse("High"), Ok(ResamplerQuality::High));
+    }
+
+    #[cfg(feature = "high-quality-resample")]
+    #[test]
+    fn test_rubato_stream_and_cached_filter() {
+        let input = tone(440.0, 44100, 0.5);
+        let whole = resample_with(&input, 44100, 16000, ResamplerQuality::Rubato);
+        // The cached filter is reset between buffers, so the same input gives the same output
+        assert_eq!(resample_with(&input, 44100, 16000, ResamplerQuality::Rubato), whole);
+
+        let mut stream = StreamResampler::with_quality(44100, 16000, ResamplerQuality::Rubato);
+        let mut pieced: Vec<f32> = input.chunks(700).flat_map(|piece| stream.process(piece)).collect();
+        pieced.extend(stream.flush());
+        assert_eq!(pieced.len(), whole.len());
+    }
+
+    #[test]
+    fn test_stream_matches_whole_buffer() {
+        // TTS audio arrives at 24kHz in ~8KB pieces; any seam would differ from the batch result
+        let input = tone(440.0, 24000, 0.5);
+        for quality in [ResamplerQuality::Fast, ResamplerQuality::Balanced] {
+            let whole = resample_with(&input, 24000, 48000, quality);
+            let mut stream = StreamResampler::with_quality(24000, 48000, quality);
+            let mut pieced: Vec<f32> = input.chunks(2048).flat_map(|piece| stream.process(piece)).collect();
+            pieced.extend(stream.flush());
+
+            assert_eq!(pieced.len(), whole.len(), "{}", quality.name());
+            // The batch result truncates its kernel at the end, so compare up to there
+            let end = whole.len() - 100;
+            let worst = (0..end).map(|i| (pieced[i] - whole[i]).abs()).fold(0.0, f32::max);
+            assert!(worst < 1e-4, "{} differs by {}", quality.name(), worst);
+        }
+    }
+
+    /// Benchmark: latency and accuracy per mode (`cargo test --release -- --ignored --nocapture`)
+    #[test]
+    #[ignore]
+    fn bench_resampler_quality() {
+        let input = tone(1000.0, 44100, 10.0);
+        let alias_input = tone(12000.0, 44100, 1.0);
+        for quality in [
+            ResamplerQuality::Fast,
+            ResamplerQuality::Balanced,
+            ResamplerQuality::High,
+            ResamplerQuality::Rubato,
+        ] {
+            let start = std::time::Instant::now();
+            let out = resample_with(&input, 44100, 16000, quality);
+            let elapsed = start.elapsed();
+            let alias = resample_with(&alias_input, 44100, 16000, quality);
+            let alias_db = 20.0 * (rms(&alias[200..15800]) / rms(&alias_input)).log10();
+            println!(
+                "{:>8}: {:>8.2?} for 10s of audio ({} samples), 3.4kHz SNR {:.1} dB, 7kHz SNR {:.1} dB, 12kHz alias {:.1} dB",
+                quality.name(),
+                elapsed,
+                out.len(),
+                snr_db(3400.0, 44100, 16000, quality),
+                snr_db(7000.0, 44100, 16000, quality),
+                alias_db,
+            );
+        }
+    }
+}
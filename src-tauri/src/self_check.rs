@@ -0,0 +1,117 @@
+use crate::{talon, voice};
+
+/// Outcome of one self-check step
+#[derive(Clone, Copy, Debug, PartialEq, serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum CheckStatus {
+    Pass,
+    Fail,
+    Skip, // not applicable to the current configuration
+}
+
+#[derive(Clone, Debug, serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CheckStep {
+    pub name: String,
+    pub status: CheckStatus,
+    pub message: String,
+}
+
+impl CheckStep {
+    fn new(name: &str, status: CheckStatus, message: String) -> Self {
+        CheckStep {
+            name: name.to_string(),
+            status,
+            message,
+        }
+    }
+
+    fn from_result(name: &str, result: Result<String, String>) -> Self {
+        match result {
+            Ok(message) => CheckStep::new(name, CheckStatus::Pass, message),
+            Err(message) => CheckStep::new(name, CheckStatus::Fail, message),
+        }
+    }
+}
+
+/// Result of every step; `passed` is false if any step failed
+#[derive(Clone, Debug, serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SelfCheckReport {
+    pub passed: bool,
+    pub steps: Vec<CheckStep>,
+}
+
+impl SelfCheckReport {
+    fn new(steps: Vec<CheckStep>) -> Self {
+        let passed = steps.iter().all(|step| step.status != CheckStatus::Fail);
+        SelfCheckReport { passed, steps }
+    }
+}
+
+/// Walk the pipeline from microphone to transcript and report each step
+pub fn run() -> SelfCheckReport {
+    let mut steps = Vec::new();
+
+    let device = voice::check_input_device();
+    let device_ok = device.is_ok();
+    steps.push(CheckStep::from_result("inputDevice", device));
+
+    if device_ok {
+        steps.push(CheckStep::from_result("inputStream", voice::check_input_stream()));
+    } else {
+        steps.push(CheckStep::new(
+            "inputStream",
+            CheckStatus::Skip,
+            "No input device to open".to_string(),
+        ));
+    }
+
+    steps.push(CheckStep::from_result("provider", voice::check_provider()));
+
+    steps.push(match voice::check_local_model() {
+        Some(result) => CheckStep::from_result("localModel", result),
+        None => CheckStep::new("localModel", CheckStatus::Skip, "Local Whisper is not enabled".to_string()),
+    });
+
+    // Talon is optional, so a missing install isn't a failure
+    steps.push(if talon::is_talon_installed() {
+        CheckStep::new("talon", CheckStatus::Pass, "Talon is installed".to_string())
+    } else {
+        CheckStep::new(
+            "talon",
+            CheckStatus::Skip,
+            "Talon not found; install it from talonvoice.com to use voice commands".to_string(),
+        )
+    });
+
+    SelfCheckReport::new(steps)
+}
+
+/// Human-readable summary, for logging at startup
+pub fn describe(report: &SelfCheckReport) -> String {
+    report
+        .steps
+        .iter()
+        .map(|step| format!("{:?} {}: {}", step.status, step.name, step.message))
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_report_fails_only_on_failed_steps() {
+        let report = SelfCheckReport::new(vec![
+            CheckStep::new("inputDevice", CheckStatus::Pass, "Mic".to_string()),
+            CheckStep::new("talon", CheckStatus::Skip, "Not installed".to_string()),
+        ]);
+        assert!(report.passed);
+
+        let report = SelfCheckReport::new(vec![CheckStep::from_result("provider", Err("No key".to_string()))]);
+        assert!(!report.passed);
+        assert_eq!(describe(&report), "Fail provider: No key");
+    }
+}
@@ -0,0 +1,171 @@
+use crate::recording::Cue;
+
+/// Subtitle line length and lines per cue, per common captioning guidelines
+const MAX_LINE_CHARS: usize = 42;
+const MAX_LINES: usize = 2;
+
+/// A word with its timing, in seconds
+#[derive(Clone, Debug, PartialEq, serde::Serialize)]
+pub struct Word {
+    pub start: f64,
+    pub end: f64,
+    pub text: String,
+}
+
+/// Segments and words from an OpenAI-style verbose_json response, shifted by `offset` seconds
+pub fn parse_verbose(result: &serde_json::Value, offset: f64) -> (Vec<Cue>, Vec<Word>) {
+    let timed = |item: &serde_json::Value, key: &str| -> Option<(f64, f64, String)> {
+        Some((
+            item["start"].as_f64()? + offset,
+            item["end"].as_f64()? + offset,
+            item[key].as_str()?.trim().to_string(),
+        ))
+    };
+
+    let segments = result["segments"]
+        .as_array()
+        .map(|segments| {
+            segments
+                .iter()
+                .filter_map(|s| timed(s, "text"))
+                .filter(|(_, _, text)| !text.is_empty())
+                .map(|(start, end, text)| Cue { start, end, text })
+                .collect()
+        })
+        .unwrap_or_default();
+    let words = result["words"]
+        .as_array()
+        .map(|words| {
+            words
+                .iter()
+                .filter_map(|w| timed(w, "word"))
+                .map(|(start, end, text)| Word { start, end, text })
+                .collect()
+        })
+        .unwrap_or_default();
+    (segments, words)
+}
+
+/// Split segments into cues that fit the line limits, wrapping each cue's text.
+/// Splits are timed from word timestamps when they line up with the segment's
+/// words, otherwise interpolated by character position.
+pub fn layout(segments: &[Cue], words: &[Word]) -> Vec<Cue> {
+    let mut cues = Vec::new();
+    for segment in segments {
+        let timed = time_tokens(segment, words);
+        let mut current: Vec<&Word> = Vec::new();
+        for token in &timed {
+            let candidate = current
+                .iter()
+                .chain(std::iter::once(&token))
+                .map(|w| w.text.as_str())
+                .collect::<Vec<_>>()
+                .join(" ");
+            if !current.is_empty() && wrap(&candidate).lines().count() > MAX_LINES {
+                cues.push(cue_from(&current));
+                current.clear();
+            }
+            current.push(token);
+        }
+        if !current.is_empty() {
+            cues.push(cue_from(&current));
+        }
+    }
+    cues
+}
+
+/// The segment's words with timings; its text keeps the punctuation word lists drop
+fn time_tokens(segment: &Cue, words: &[Word]) -> Vec<Word> {
+    let tokens: Vec<&str> = segment.text.split_whitespace().collect();
+    let matched: Vec<&Word> = words
+        .iter()
+        .filter(|w| {
+            let mid = (w.start + w.end) / 2.0;
+            mid >= segment.start && mid <= segment.end
+        })
+        .collect();
+
+    if matched.len() == tokens.len() {
+        return tokens
+            .iter()
+            .zip(matched)
+            .map(|(token, word)| Word { start: word.start, end: word.end, text: token.to_string() })
+            .collect();
+    }
+
+    let total_chars = tokens.iter().map(|t| t.len() + 1).sum::<usize>().max(1) as f64;
+    let duration = segment.end - segment.start;
+    let mut chars_before = 0usize;
+    tokens
+        .iter()
+        .map(|token| {
+            let start = segment.start + duration * chars_before as f64 / total_chars;
+            chars_before += token.len() + 1;
+            let end = segment.start + duration * chars_before as f64 / total_chars;
+            Word { start, end, text: token.to_string() }
+        })
+        .collect()
+}
+
+fn cue_from(words: &[&Word]) -> Cue {
+    let text = words.iter().map(|w| w.text.as_str()).collect::<Vec<_>>().join(" ");
+    Cue {
+        start: words[0].start,
+        end: words[words.len() - 1].end,
+        text: wrap(&text),
+    }
+}
+
+/// Greedy word wrap at MAX_LINE_CHARS
+fn wrap(text: &str) -> String {
+    let mut lines: Vec<String> = Vec::new();
+    for word in text.split_whitespace() {
+        match lines.last_mut() {
+            Some(line) if line.chars().count() + 1 + word.chars().count() <= MAX_LINE_CHARS => {
+                line.push(' ');
+                line.push_str(word);
+            }
+            _ => lines.push(word.to_string()),
+        }
+    }
+    lines.join("\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_verbose_offsets_and_layout() {
+        let result = serde_json::json!({
+            "segments": [{"start": 0.0, "end": 2.0, "text": " Hello, world."}],
+            "words": [
+                {"word": "Hello", "start": 0.0, "end": 0.5},
+                {"word": "world", "start": 1.0, "end": 1.8}
+            ]
+        });
+        let (segments, words) = parse_verbose(&result, 600.0);
+        assert_eq!(segments[0].start, 600.0);
+        assert_eq!(words[1].end, 601.8);
+
+        // Word timings carry over, with the segment's punctuation
+        let cues = layout(&segments, &words);
+        assert_eq!(cues, vec![Cue { start: 600.0, end: 601.8, text: "Hello, world.".to_string() }]);
+    }
+
+    #[test]
+    fn test_layout_splits_long_segments() {
+        let text = "word ".repeat(40);
+        let segment = Cue { start: 0.0, end: 20.0, text: text.trim().to_string() };
+        let cues = layout(&[segment], &[]);
+        assert!(cues.len() > 1);
+        for cue in &cues {
+            assert!(cue.text.lines().count() <= MAX_LINES);
+            assert!(cue.text.lines().all(|line| line.len() <= MAX_LINE_CHARS));
+        }
+        // Interpolated timings tile the segment
+        assert_eq!(cues[0].start, 0.0);
+        assert!((cues[cues.len() - 1].end - 20.0).abs() < 1e-9);
+        assert!(cues.windows(2).all(|pair| (pair[0].end - pair[1].start).abs() < 1e-9));
+    }
+}
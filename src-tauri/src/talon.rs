@@ -1,22 +1,138 @@
-use std::path::PathBuf;
-use std::process::Command;
+use parking_lot::Mutex;
+use std::io::{BufRead, BufReader, Read, Write};
+use std::path::{Path, PathBuf};
+use std::process::{Child, ChildStdin, Command, Stdio};
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError};
+use std::time::{Duration, Instant};
 
-/// Get the path to Talon's REPL executable
+use crate::error::KoeError;
+
+/// Places Talon's REPL may live, most likely first
+fn repl_candidates() -> Vec<PathBuf> {
+    let mut candidates = Vec::new();
+    if cfg!(windows) {
+        // Talon installs under %APPDATA%\talon on Windows
+        if let Some(appdata) = std::env::var_os("APPDATA") {
+            candidates.push(PathBuf::from(appdata).join("talon").join("bin").join("repl.bat"));
+        }
+        if let Some(home) = dirs::home_dir() {
+            candidates.push(home.join("AppData").join("Roaming").join("talon").join("bin").join("repl.bat"));
+            candidates.push(home.join(".talon").join("bin").join("repl.bat"));
+        }
+    } else if let Some(home) = dirs::home_dir() {
+        candidates.push(home.join(".talon/bin/repl"));
+    }
+    candidates
+}
+
+/// Get the path to Talon's REPL executable, preferring a path the user set
 pub fn get_talon_repl_path() -> Option<PathBuf> {
-    let home = dirs::home_dir()?;
-    let repl_path = home.join(".talon/bin/repl");
-    if repl_path.exists() {
-        Some(repl_path)
+    REPL_OVERRIDE
+        .lock()
+        .clone()
+        .filter(|path| path.exists())
+        .or_else(|| repl_candidates().into_iter().find(|path| path.exists()))
+}
+
+/// The user-chosen REPL location, if any
+pub fn repl_override() -> Option<PathBuf> {
+    REPL_OVERRIDE.lock().clone()
+}
+
+/// Point at a REPL outside the default locations; `path` may be the REPL itself or a Talon
+/// home directory. None goes back to detection
+pub fn set_repl_override(path: Option<PathBuf>) -> Result<(), KoeError> {
+    let path = path.map(|path| resolve_repl(&path)).transpose()?;
+    replace_override(path);
+    Ok(())
+}
+
+/// Reinstate a saved override without validating it, so a briefly missing install isn't forgotten
+pub fn restore_repl_override(path: PathBuf) {
+    replace_override(Some(path));
+}
+
+fn replace_override(path: Option<PathBuf>) {
+    *REPL_OVERRIDE.lock() = path;
+    // The running REPL and cached status may belong to the old location
+    *SESSION.lock() = None;
+    *STATUS_CACHE.lock() = None;
+}
+
+/// The REPL executable for a user-supplied path
+fn resolve_repl(path: &Path) -> Result<PathBuf, KoeError> {
+    let repl = if path.is_dir() {
+        path.join("bin").join(if cfg!(windows) { "repl.bat" } else { "repl" })
     } else {
-        None
+        path.to_path_buf()
+    };
+    if !repl.is_file() {
+        return Err(KoeError::Other(format!("No Talon REPL found at {}", repl.display())));
+    }
+    if !is_executable(&repl) {
+        return Err(KoeError::Other(format!("{} is not executable", repl.display())));
     }
+    Ok(repl)
+}
+
+#[cfg(unix)]
+fn is_executable(path: &Path) -> bool {
+    use std::os::unix::fs::PermissionsExt;
+    std::fs::metadata(path).is_ok_and(|meta| meta.permissions().mode() & 0o111 != 0)
+}
+
+#[cfg(not(unix))]
+fn is_executable(path: &Path) -> bool {
+    path.extension()
+        .and_then(|ext| ext.to_str())
+        .is_some_and(|ext| ["bat", "cmd", "exe"].contains(&ext.to_ascii_lowercase().as_str()))
 }
 
+/// How long a REPL call may run before it is killed
+pub const DEFAULT_TIMEOUT: Duration = Duration::from_secs(5);
+
 /// Check if Talon Voice is installed
 pub fn is_talon_installed() -> bool {
     get_talon_repl_path().is_some()
 }
 
+/// Whether Talon is installed, and whether its REPL is answering
+#[derive(Debug, Clone, Copy, PartialEq, serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TalonStatus {
+    pub installed: bool,
+    pub running: bool,
+}
+
+/// How long a status check is reused, so polling doesn't hit the REPL every time
+const STATUS_TTL: Duration = Duration::from_secs(2);
+/// How long to wait for an exited REPL's last stderr lines
+const STDERR_GRACE: Duration = Duration::from_secs(1);
+
+/// Check whether Talon is installed and responding, using a cached answer for a couple of seconds
+pub fn talon_status() -> TalonStatus {
+    if let Some((checked_at, status)) = *STATUS_CACHE.lock() {
+        if checked_at.elapsed() < STATUS_TTL {
+            return status;
+        }
+    }
+
+    let status = match get_talon_repl_path() {
+        None => TalonStatus { installed: false, running: false },
+        Some(repl_path) => {
+            let running = match SESSION.try_lock() {
+                // An empty command round-trips the marker only if Talon is up
+                Some(mut session) => run_in_session(&mut session, &repl_path, "", Duration::from_secs(1)).is_ok(),
+                // A command is in flight, so the REPL is in use
+                None => true,
+            };
+            TalonStatus { installed: true, running }
+        }
+    };
+    *STATUS_CACHE.lock() = Some((Instant::now(), status));
+    status
+}
+
 /// Execute a Talon command via the REPL
 ///
 /// # Arguments
@@ -24,33 +140,204 @@ pub fn is_talon_installed() -> bool {
 ///
 /// # Examples
 /// ```ignore
-/// execute_talon("actions.key('cmd-s')"); // Send Cmd+S
-/// execute_talon("actions.insert('hello')"); // Type 'hello'
-/// execute_talon("actions.mimic('focus chrome')"); // Run voice command
+/// execute_talon("actions.key('cmd-s')", DEFAULT_TIMEOUT); // Send Cmd+S
+/// execute_talon("actions.insert('hello')", DEFAULT_TIMEOUT); // Type 'hello'
+/// execute_talon("actions.mimic('focus chrome')", DEFAULT_TIMEOUT); // Run voice command
 /// ```
-pub fn execute_talon(code: &str) -> Result<String, String> {
-    let repl_path = get_talon_repl_path()
-        .ok_or_else(|| "Talon Voice is not installed. Install from https://talonvoice.com".to_string())?;
+pub fn execute_talon(code: &str, timeout: Duration) -> Result<String, KoeError> {
+    let repl_path = get_talon_repl_path().ok_or(KoeError::TalonNotInstalled)?;
+    run_in_session(&mut SESSION.lock(), &repl_path, code, timeout)
+}
 
-    // Escape single quotes in the code
-    let escaped_code = code.replace('\'', "'\\''");
+/// Execute several Talon commands in order over the shared REPL; each gets its own `timeout`.
+/// With `stop_on_error` the batch ends at the first failure, so the results may be shorter than `commands`
+pub fn execute_talon_batch(
+    commands: &[String],
+    stop_on_error: bool,
+    timeout: Duration,
+) -> Result<Vec<Result<String, KoeError>>, KoeError> {
+    let repl_path = get_talon_repl_path().ok_or(KoeError::TalonNotInstalled)?;
+    // Hold the session for the whole batch so other callers can't interleave
+    Ok(run_batch(&mut SESSION.lock(), &repl_path, commands, stop_on_error, timeout))
+}
 
-    let output = Command::new("sh")
-        .arg("-c")
-        .arg(format!("echo '{}' | '{}'", escaped_code, repl_path.display()))
-        .output()
-        .map_err(|e| format!("Failed to execute Talon command: {}", e))?;
+fn run_batch(
+    slot: &mut Option<Session>,
+    repl_path: &Path,
+    commands: &[String],
+    stop_on_error: bool,
+    timeout: Duration,
+) -> Vec<Result<String, KoeError>> {
+    let mut results = Vec::with_capacity(commands.len());
+    for code in commands {
+        let result = run_in_session(slot, repl_path, code, timeout);
+        let failed = result.is_err();
+        results.push(result);
+        if failed && stop_on_error {
+            break;
+        }
+    }
+    results
+}
 
-    if output.status.success() {
-        Ok(String::from_utf8_lossy(&output.stdout).trim().to_string())
-    } else {
-        let stderr = String::from_utf8_lossy(&output.stderr);
-        if stderr.is_empty() {
-            Err("Talon command failed with no error message".to_string())
+/// Drop the current REPL and start a fresh one, e.g. after Talon was restarted
+pub fn restart_session() -> Result<(), KoeError> {
+    let repl_path = get_talon_repl_path().ok_or(KoeError::TalonNotInstalled)?;
+    let mut session = SESSION.lock();
+    *session = None;
+    *session = Some(Session::spawn(&repl_path)?);
+    Ok(())
+}
+
+lazy_static::lazy_static! {
+    // One long-lived REPL, so repeated commands skip Talon's Python startup
+    static ref SESSION: Mutex<Option<Session>> = Mutex::new(None);
+    static ref STATUS_CACHE: Mutex<Option<(Instant, TalonStatus)>> = Mutex::new(None);
+    static ref REPL_OVERRIDE: Mutex<Option<PathBuf>> = Mutex::new(None);
+}
+
+/// Run `code` in the session held in `slot`, spawning it if missing or dead
+fn run_in_session(slot: &mut Option<Session>, repl_path: &Path, code: &str, timeout: Duration) -> Result<String, KoeError> {
+    if !slot.as_mut().is_some_and(Session::is_alive) {
+        *slot = Some(Session::spawn(repl_path)?);
+    }
+    let session = slot.as_mut().expect("session was just spawned");
+    let result = session.run(code, timeout);
+    // A stalled or broken REPL is out of sync with our markers; the next call starts over
+    if matches!(result, Err(KoeError::TalonTimeout(_))) || !session.is_alive() {
+        *slot = None;
+    }
+    result
+}
+
+/// Defined once per session: runs a command and prints `marker ok`, or the traceback and then
+/// `marker error`. Compiling as "single" echoes an expression's value like the REPL itself would;
+/// multi-statement code falls back to "exec"
+const RUNNER: &str = "def _koe_run(_src, _marker):
+    import traceback
+    try:
+        try:
+            _code = compile(_src, '<koe>', 'single')
+        except SyntaxError:
+            _code = compile(_src, '<koe>', 'exec')
+        exec(_code, globals())
+    except BaseException:
+        traceback.print_exc(file=__import__('sys').stdout)
+        print(_marker, 'error')
+    else:
+        print(_marker, 'ok')
+
+";
+
+/// A running REPL child with its output forwarded line by line
+struct Session {
+    child: Child,
+    stdin: ChildStdin,
+    stdout: Receiver<String>,
+    stderr: Receiver<String>,
+    commands: u64,
+}
+
+impl Session {
+    fn spawn(repl_path: &Path) -> Result<Session, KoeError> {
+        // No shell and no interpolation, so quotes, newlines and `$()` reach Talon literally
+        let mut child = Command::new(repl_path)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()
+            .map_err(|e| KoeError::Other(format!("Failed to start the Talon REPL: {}", e)))?;
+        let stdin = child.stdin.take().ok_or("Talon REPL has no stdin")?;
+        let stdout = read_lines(child.stdout.take().ok_or("Talon REPL has no stdout")?);
+        let stderr = read_lines(child.stderr.take().ok_or("Talon REPL has no stderr")?);
+        let mut session = Session { child, stdin, stdout, stderr, commands: 0 };
+        session.send(RUNNER)?;
+        Ok(session)
+    }
+
+    fn send(&mut self, text: &str) -> Result<(), KoeError> {
+        self.stdin
+            .write_all(text.as_bytes())
+            .and_then(|_| self.stdin.flush())
+            .map_err(|e| KoeError::Other(format!("Failed to send Talon command: {}", e)))
+    }
+
+    fn is_alive(&mut self) -> bool {
+        matches!(self.child.try_wait(), Ok(None))
+    }
+
+    /// Run `code` through the session's runner, and collect stdout until its status marker
+    /// says whether the code succeeded
+    fn run(&mut self, code: &str, timeout: Duration) -> Result<String, KoeError> {
+        self.commands += 1;
+        let marker = format!("__koe_status_{}__", self.commands);
+        // The code goes over as a string literal (JSON's escapes are valid Python), so no
+        // quoting or indentation in it can break out of the runner call
+        let literal = serde_json::to_string(code).map_err(|e| KoeError::Other(e.to_string()))?;
+        // Stderr from earlier commands (prompts included) is stale, and would otherwise pile up
+        // for the life of the session
+        self.stderr.try_iter().for_each(drop);
+        self.send(&format!("_koe_run({}, \"{}\")\n", literal, marker))?;
+
+        let deadline = Instant::now() + timeout;
+        let mut output = Vec::new();
+        let succeeded = loop {
+            match self.stdout.recv_timeout(deadline.saturating_duration_since(Instant::now())) {
+                Ok(line) if line.starts_with(&marker) => break line[marker.len()..].trim() == "ok",
+                Ok(line) => output.push(line.trim_start_matches(">>> ").trim_start_matches("... ").to_string()),
+                Err(RecvTimeoutError::Timeout) => {
+                    return Err(KoeError::TalonTimeout(output.join("\n").trim().to_string()));
+                }
+                Err(RecvTimeoutError::Disconnected) => {
+                    let message = self.final_stderr();
+                    return Err(if message.is_empty() { "Talon REPL exited unexpectedly".into() } else { message.into() });
+                }
+            }
+        };
+
+        // On failure the runner printed the traceback ahead of the marker
+        let output = output.join("\n").trim().to_string();
+        if succeeded {
+            Ok(output)
         } else {
-            Err(stderr.trim().to_string())
+            Err(output.into())
         }
     }
+
+    /// What the exited REPL wrote to stderr, read to the end of the pipe so the reader thread
+    /// can't still be holding the last lines
+    fn final_stderr(&mut self) -> String {
+        let deadline = Instant::now() + STDERR_GRACE;
+        let mut lines = Vec::new();
+        while let Ok(line) = self.stderr.recv_timeout(deadline.saturating_duration_since(Instant::now())) {
+            let line = line.trim_start_matches(">>> ").trim_start_matches("... ");
+            if !line.trim().is_empty() {
+                lines.push(line.to_string());
+            }
+        }
+        lines.join("\n").trim().to_string()
+    }
+}
+
+impl Drop for Session {
+    fn drop(&mut self) {
+        self.child.kill().ok();
+        self.child.wait().ok();
+    }
+}
+
+/// Forward a pipe's lines from a reader thread, so a stalled REPL can't block the caller
+fn read_lines(pipe: impl Read + Send + 'static) -> Receiver<String> {
+    let (tx, rx) = mpsc::channel();
+    std::thread::spawn(move || {
+        for line in BufReader::new(pipe).lines() {
+            let Ok(line) = line else { break };
+            if tx.send(line).is_err() {
+                break;
+            }
+        }
+    });
+    rx
 }
 
 #[cfg(test)]
@@ -62,5 +349,119 @@ mod tests {
         // This test will pass if Talon is installed, skip gracefully if not
         let installed = is_talon_installed();
         println!("Talon installed: {}", installed);
+        assert_eq!(talon_status().installed, installed);
+    }
+
+    /// An interactive Python standing in for Talon's REPL; None where python3 isn't installed.
+    /// Written once per test run, so no test execs the script while another rewrites it
+    #[cfg(unix)]
+    fn python_repl() -> Option<PathBuf> {
+        static REPL: std::sync::OnceLock<Option<PathBuf>> = std::sync::OnceLock::new();
+        REPL.get_or_init(|| {
+            use std::os::unix::fs::PermissionsExt;
+            Command::new("python3").arg("--version").output().ok().filter(|o| o.status.success())?;
+            let repl = std::env::temp_dir().join(format!("koe-python-repl-{}", std::process::id()));
+            std::fs::write(&repl, "#!/bin/sh\nexec python3 -i -q -u\n").ok()?;
+            std::fs::set_permissions(&repl, std::fs::Permissions::from_mode(0o755)).ok()?;
+            Some(repl)
+        })
+        .clone()
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_code_reaches_repl_literally() {
+        let Some(repl) = python_repl() else { return };
+        let mut session = Session::spawn(&repl).unwrap();
+        let text = "it's \"$(rm -rf ~)\" `id` \\n";
+        let code = format!("print({})", serde_json::to_string(text).unwrap());
+        assert_eq!(session.run(&code, DEFAULT_TIMEOUT).unwrap(), text);
+        assert_eq!(session.run("print(\"a\")\nprint(\"b\")", DEFAULT_TIMEOUT).unwrap(), "a\nb");
+        assert_eq!(session.run("1 + 1", DEFAULT_TIMEOUT).unwrap(), "2");
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_exit_reports_its_own_stderr() {
+        let Some(repl) = python_repl() else { return };
+        let mut session = Session::spawn(&repl).unwrap();
+        let code = "import sys, os\nsys.stderr.write('fatal: bad config\\n')\nsys.stderr.flush()\nos._exit(3)";
+        let err = session.run(code, DEFAULT_TIMEOUT).unwrap_err().to_string();
+        assert!(err.contains("fatal: bad config"), "{}", err);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_errors_reported_by_status() {
+        let Some(repl) = python_repl() else { return };
+        let mut session = Session::spawn(&repl).unwrap();
+        // A SyntaxError has no traceback frames, and still fails
+        let err = session.run("print(", DEFAULT_TIMEOUT).unwrap_err();
+        assert!(err.to_string().contains("SyntaxError"), "{}", err);
+        let err = session.run("1 / 0", DEFAULT_TIMEOUT).unwrap_err();
+        assert!(err.to_string().contains("ZeroDivisionError"), "{}", err);
+        assert_eq!(session.run("print('still fine')", DEFAULT_TIMEOUT).unwrap(), "still fine");
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_stalled_repl_times_out_with_partial_output() {
+        let Some(repl) = python_repl() else { return };
+        let started = Instant::now();
+        let mut slot = None;
+        let code = "print('partial'); import time; time.sleep(10)";
+        let result = run_in_session(&mut slot, &repl, code, Duration::from_millis(500));
+        assert_eq!(result, Err(KoeError::TalonTimeout("partial".to_string())));
+        assert!(slot.is_none());
+        assert!(started.elapsed() < Duration::from_secs(5));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_dead_session_respawns() {
+        let Some(repl) = python_repl() else { return };
+        let mut slot = None;
+        assert_eq!(run_in_session(&mut slot, &repl, "print('first')", DEFAULT_TIMEOUT).unwrap(), "first");
+        let pid = slot.as_ref().unwrap().child.id();
+        slot.as_mut().unwrap().child.kill().unwrap();
+        slot.as_mut().unwrap().child.wait().unwrap();
+        assert_eq!(run_in_session(&mut slot, &repl, "print('second')", DEFAULT_TIMEOUT).unwrap(), "second");
+        assert_ne!(slot.as_ref().unwrap().child.id(), pid);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_batch_stop_or_continue_on_error() {
+        let Some(repl) = python_repl() else { return };
+        let commands = vec!["1 / 0".to_string(), "print('two')".to_string()];
+        assert_eq!(run_batch(&mut None, &repl, &commands, true, DEFAULT_TIMEOUT).len(), 1);
+        let results = run_batch(&mut None, &repl, &commands, false, DEFAULT_TIMEOUT);
+        assert!(results[0].is_err());
+        assert_eq!(results[1], Ok("two".to_string()));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_resolve_repl_from_home_dir() {
+        use std::os::unix::fs::PermissionsExt;
+        let home = std::env::temp_dir().join(format!("koe-talon-{}", std::process::id()));
+        std::fs::create_dir_all(home.join("bin")).unwrap();
+        let repl = home.join("bin/repl");
+        std::fs::write(&repl, "#!/bin/sh\n").unwrap();
+
+        std::fs::set_permissions(&repl, std::fs::Permissions::from_mode(0o644)).unwrap();
+        assert!(resolve_repl(&home).is_err());
+        std::fs::set_permissions(&repl, std::fs::Permissions::from_mode(0o755)).unwrap();
+        assert_eq!(resolve_repl(&home), Ok(repl.clone()));
+        assert_eq!(resolve_repl(&repl), Ok(repl));
+        assert!(resolve_repl(&home.join("missing")).is_err());
+        std::fs::remove_dir_all(&home).ok();
+    }
+
+    #[test]
+    fn test_repl_candidates_match_platform() {
+        let candidates = repl_candidates();
+        let expected = if cfg!(windows) { "repl.bat" } else { "repl" };
+        assert!(candidates.iter().all(|path| path.file_name().unwrap() == expected));
     }
 }
@@ -0,0 +1,889 @@
+use cpal::traits::{DeviceTrait, HostTrait, StreamTrait};
+use parking_lot::Mutex;
+use std::collections::VecDeque;
+use std::io::{Read, Write};
+use std::path::{Path, PathBuf};
+use std::process::{Child, Command, ExitStatus, Stdio};
+use std::sync::atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering};
+use std::sync::mpsc::{self, Sender};
+use std::sync::Arc;
+use std::time::Duration;
+use tauri::{AppHandle, Emitter, EventTarget};
+
+use crate::recording;
+use crate::resampler;
+use crate::voice;
+
+// Route speech through a file and cpal playback instead of letting the backend play it
+static PLAY_THROUGH_CPAL: AtomicBool = AtomicBool::new(false);
+// Bumped by stop() so in-flight synthesis/playback knows to bail out
+static PLAYBACK_GENERATION: AtomicU64 = AtomicU64::new(0);
+// Distinguishes concurrent temp files
+static SYNTH_COUNTER: AtomicUsize = AtomicUsize::new(0);
+// An utterance is playing through cpal
+static CPAL_PLAYING: AtomicBool = AtomicBool::new(false);
+// Bumped by clear_queue() so the worker skips utterances queued before it
+static QUEUE_GENERATION: AtomicU64 = AtomicU64::new(0);
+// Utterances queued or playing
+static QUEUED: AtomicUsize = AtomicUsize::new(0);
+
+/// How often waiter threads check on tracked speech processes
+const CHILD_POLL_INTERVAL: Duration = Duration::from_millis(50);
+
+/// Sample format requested from `say -o`
+const SAY_DATA_FORMAT: &str = "LEI16@22050";
+
+/// Speaking rate the platform backends use by default, in words per minute
+const DEFAULT_RATE_WPM: u32 = 180;
+
+/// OpenAI TTS defaults
+const OPENAI_TTS_MODEL: &str = "tts-1";
+const OPENAI_TTS_VOICE: &str = "alloy";
+/// Sample rate of OpenAI's `pcm` response format
+const OPENAI_PCM_RATE: u32 = 24000;
+
+/// Windows speech via SAPI, reading the text from stdin and the voice from the
+/// environment so neither needs quoting
+const SAPI_SCRIPT: &str = "Add-Type -AssemblyName System.Speech; \
+    $s = New-Object System.Speech.Synthesis.SpeechSynthesizer; \
+    if ($env:KOE_TTS_VOICE) { $s.SelectVoice($env:KOE_TTS_VOICE) }; \
+    if ($env:KOE_TTS_RATE) { $s.Rate = [int]$env:KOE_TTS_RATE }; \
+    if ($env:KOE_TTS_VOLUME) { $s.Volume = [int]$env:KOE_TTS_VOLUME }; \
+    if ($env:KOE_TTS_OUTPUT) { $s.SetOutputToWaveFile($env:KOE_TTS_OUTPUT) }; \
+    $s.Speak([Console]::In.ReadToEnd())";
+
+/// Lists installed SAPI voices as `name<TAB>culture` lines
+const SAPI_VOICES_SCRIPT: &str = "Add-Type -AssemblyName System.Speech; \
+    (New-Object System.Speech.Synthesis.SpeechSynthesizer).GetInstalledVoices() | \
+    ForEach-Object { $_.VoiceInfo.Name + \"`t\" + $_.VoiceInfo.Culture.Name }";
+
+/// Voice, speaking rate and volume for speech; unset fields use the system default
+#[derive(Clone, Debug, Default, serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SpeechOptions {
+    pub voice: Option<String>,
+    pub rate_wpm: Option<u32>,
+    pub volume: Option<f32>, // 0-1
+}
+
+/// A voice the speech backend can use
+#[derive(Clone, Debug, PartialEq, serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct VoiceInfo {
+    pub name: String,
+    pub language: String,
+}
+
+/// Where synthesize_to_file wrote speech, and how long it runs (unknown for MP3)
+#[derive(Clone, Debug, serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SynthesizedFile {
+    pub path: String,
+    pub duration_secs: Option<f64>,
+}
+
+/// A process invocation that speaks (or lists voices)
+struct SpeechCommand {
+    program: &'static str,
+    args: Vec<String>,
+    input: Option<String>,
+    env: Vec<(&'static str, String)>,
+}
+
+impl SpeechCommand {
+    fn new(program: &'static str) -> Self {
+        SpeechCommand {
+            program,
+            args: Vec::new(),
+            input: None,
+            env: Vec::new(),
+        }
+    }
+
+    fn arg(mut self, arg: impl Into<String>) -> Self {
+        self.args.push(arg.into());
+        self
+    }
+}
+
+lazy_static::lazy_static! {
+    // Speech processes we started, so stop() kills only ours
+    static ref SPEAKING: Mutex<Vec<Child>> = Mutex::new(Vec::new());
+    // Feeds the queue worker, started with the first utterance
+    static ref QUEUE: Mutex<Option<Sender<Utterance>>> = Mutex::new(None);
+    static ref ENGINE: Mutex<Engine> = Mutex::new(Engine::System);
+    static ref OUTPUT_CONFIG: Mutex<OutputConfig> = Mutex::new(OutputConfig::default());
+}
+
+/// Where cpal playback goes
+#[derive(Debug, Default, Clone)]
+struct OutputConfig {
+    selected_device: Option<String>, // None is the system default
+}
+
+/// Names of the available output devices
+pub fn list_output_devices() -> Result<Vec<String>, String> {
+    let host = cpal::default_host();
+    let devices: Vec<String> = host
+        .output_devices()
+        .map_err(|e| e.to_string())?
+        .filter_map(|d| d.name().ok())
+        .collect();
+    Ok(devices)
+}
+
+/// The output device speech plays on, if one was chosen
+pub fn get_output_device() -> Option<String> {
+    OUTPUT_CONFIG.lock().selected_device.clone()
+}
+
+/// Play speech on the output device with this name; None goes back to the default.
+/// Applies from the next utterance
+pub fn set_output_device(device_name: Option<String>) {
+    OUTPUT_CONFIG.lock().selected_device = device_name;
+}
+
+/// Get the selected output device by name, or the default output device
+pub(crate) fn output_device() -> Result<cpal::Device, String> {
+    let host = cpal::default_host();
+    // Copy the name out so the lock isn't held while enumerating devices
+    let selected_device = OUTPUT_CONFIG.lock().selected_device.clone();
+
+    if let Some(ref name) = selected_device {
+        for device in host.output_devices().map_err(|e| e.to_string())? {
+            if device.name().ok().as_ref() == Some(name) {
+                return Ok(device);
+            }
+        }
+        // Fall through to default if not found
+        eprintln!("Selected output device '{}' not found, using default", name);
+    }
+
+    host.default_output_device().ok_or_else(|| "No output device available".to_string())
+}
+
+/// What turns text into speech
+#[derive(Clone, Debug, PartialEq)]
+enum Engine {
+    System, // say, SAPI, espeak-ng or spd-say
+    OpenAi { model: String, voice: String },
+}
+
+/// Text waiting its turn in the speech queue
+struct Utterance {
+    app: AppHandle,
+    text: String,
+    options: SpeechOptions,
+    queue_generation: u64,
+}
+
+/// A system speech command
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Backend {
+    Say,        // macOS
+    Sapi,       // Windows, through PowerShell
+    EspeakNg,   // Linux
+    SpdSay,     // Linux, speech-dispatcher
+}
+
+impl Backend {
+    /// Backends to try on this platform, in order of preference
+    fn for_platform() -> &'static [Backend] {
+        if cfg!(target_os = "macos") {
+            &[Backend::Say]
+        } else if cfg!(target_os = "windows") {
+            &[Backend::Sapi]
+        } else {
+            &[Backend::EspeakNg, Backend::SpdSay]
+        }
+    }
+
+    fn program(&self) -> &'static str {
+        match self {
+            Backend::Say => "say",
+            Backend::Sapi => "powershell",
+            Backend::EspeakNg => "espeak-ng",
+            Backend::SpdSay => "spd-say",
+        }
+    }
+
+    /// The first of this platform's backends that is installed
+    fn available() -> Option<Backend> {
+        let paths = std::env::var_os("PATH")?;
+        Backend::for_platform().iter().copied().find(|backend| {
+            let file = format!("{}{}", backend.program(), std::env::consts::EXE_SUFFIX);
+            std::env::split_paths(&paths).any(|dir| dir.join(&file).is_file())
+        })
+    }
+
+    /// Command that speaks `text` with the given options
+    fn command(&self, text: &str, options: &SpeechOptions) -> SpeechCommand {
+        let volume = options.volume.map(|v| v.clamp(0.0, 1.0));
+        match self {
+            Backend::Say => {
+                // With no message argument, say reads the text from stdin
+                let mut command = SpeechCommand::new("say");
+                command.args = say_args(options);
+                command.input = Some(say_text(text, volume));
+                command
+            }
+            Backend::Sapi => {
+                let mut command = SpeechCommand::new("powershell")
+                    .arg("-NoProfile")
+                    .arg("-Command")
+                    .arg(SAPI_SCRIPT);
+                command.input = Some(text.to_string());
+                if let Some(voice) = &options.voice {
+                    command.env.push(("KOE_TTS_VOICE", voice.clone()));
+                }
+                if let Some(wpm) = options.rate_wpm {
+                    // SAPI rate runs -10..10 around the default
+                    command.env.push(("KOE_TTS_RATE", relative_rate(wpm, 10).to_string()));
+                }
+                if let Some(volume) = volume {
+                    command.env.push(("KOE_TTS_VOLUME", ((volume * 100.0).round() as i32).to_string()));
+                }
+                command
+            }
+            Backend::EspeakNg => {
+                let mut command = SpeechCommand::new("espeak-ng").arg("--stdin");
+                if let Some(voice) = &options.voice {
+                    command = command.arg("-v").arg(voice);
+                }
+                if let Some(wpm) = options.rate_wpm {
+                    command = command.arg("-s").arg(wpm.to_string());
+                }
+                if let Some(volume) = volume {
+                    // Amplitude runs 0-200, 100 being the default
+                    command = command.arg("-a").arg(((volume * 100.0).round() as i32).to_string());
+                }
+                command.input = Some(text.to_string());
+                command
+            }
+            Backend::SpdSay => {
+                // --wait keeps the process alive while speaking, so stop() can tell it's busy
+                let mut command = SpeechCommand::new("spd-say").arg("--wait");
+                if let Some(voice) = &options.voice {
+                    command = command.arg("-y").arg(voice);
+                }
+                if let Some(wpm) = options.rate_wpm {
+                    command = command.arg("-r").arg(relative_rate(wpm, 100).to_string());
+                }
+                if let Some(volume) = volume {
+                    // -100..100, 0 being the default
+                    command = command.arg("-i").arg(((volume * 200.0 - 100.0).round() as i32).to_string());
+                }
+                command.arg("--").arg(text)
+            }
+        }
+    }
+
+    /// Command that lists the installed voices
+    fn voices_command(&self) -> SpeechCommand {
+        match self {
+            Backend::Say => SpeechCommand::new("say").arg("-v").arg("?"),
+            Backend::Sapi => SpeechCommand::new("powershell")
+                .arg("-NoProfile")
+                .arg("-Command")
+                .arg(SAPI_VOICES_SCRIPT),
+            Backend::EspeakNg => SpeechCommand::new("espeak-ng").arg("--voices"),
+            Backend::SpdSay => SpeechCommand::new("spd-say").arg("-L"),
+        }
+    }
+
+    fn parse_voices(&self, output: &str) -> Vec<VoiceInfo> {
+        match self {
+            Backend::Say => parse_say_voices(output),
+            Backend::Sapi => output
+                .lines()
+                .filter_map(|line| line.trim().split_once('\t'))
+                .map(|(name, language)| VoiceInfo {
+                    name: name.to_string(),
+                    language: language.to_string(),
+                })
+                .collect(),
+            // Pty Language Age/Gender VoiceName File Other-Languages
+            Backend::EspeakNg => output
+                .lines()
+                .skip(1)
+                .filter_map(|line| {
+                    let fields: Vec<&str> = line.split_whitespace().collect();
+                    Some(VoiceInfo {
+                        name: fields.get(3)?.to_string(),
+                        language: fields.get(1)?.to_string(),
+                    })
+                })
+                .collect(),
+            // NAME LANGUAGE VARIANT
+            Backend::SpdSay => output
+                .lines()
+                .skip(1)
+                .filter_map(|line| {
+                    let fields: Vec<&str> = line.split_whitespace().collect();
+                    let split = fields.len().checked_sub(2).filter(|&n| n > 0)?;
+                    Some(VoiceInfo {
+                        name: fields[..split].join(" "),
+                        language: fields[split].to_string(),
+                    })
+                })
+                .collect(),
+        }
+    }
+
+    /// Command that renders `text` to a WAV file instead of playing it, if the backend can
+    fn file_command(&self, text: &str, options: &SpeechOptions, path: &Path) -> Option<SpeechCommand> {
+        let path = path.to_string_lossy().into_owned();
+        let mut command = self.command(text, options);
+        match self {
+            Backend::Say => {
+                let output = ["-o".to_string(), path, "--file-format=WAVE".to_string(), format!("--data-format={}", SAY_DATA_FORMAT)];
+                command.args.splice(0..0, output);
+            }
+            Backend::Sapi => command.env.push(("KOE_TTS_OUTPUT", path)),
+            Backend::EspeakNg => command = command.arg("-w").arg(path),
+            Backend::SpdSay => return None,
+        }
+        Some(command)
+    }
+}
+
+fn spawn(speech: SpeechCommand) -> std::io::Result<Child> {
+    let mut command = Command::new(speech.program);
+    command.args(&speech.args).envs(speech.env);
+    if speech.input.is_some() {
+        command.stdin(Stdio::piped());
+    }
+    let mut child = command.spawn()?;
+    if let (Some(input), Some(mut stdin)) = (speech.input, child.stdin.take()) {
+        // Dropping stdin closes it so the backend starts speaking
+        stdin.write_all(input.as_bytes())?;
+    }
+    Ok(child)
+}
+
+/// `say` flags for voice and rate
+fn say_args(options: &SpeechOptions) -> Vec<String> {
+    let mut args = Vec::new();
+    if let Some(voice) = &options.voice {
+        args.extend(["-v".to_string(), voice.clone()]);
+    }
+    if let Some(wpm) = options.rate_wpm {
+        args.extend(["-r".to_string(), wpm.to_string()]);
+    }
+    args
+}
+
+/// `say` has no volume flag, but honours an embedded `[[volm]]` command
+fn say_text(text: &str, volume: Option<f32>) -> String {
+    match volume {
+        Some(volume) => format!("[[volm {:.2}]] {}", volume, text),
+        None => text.to_string(),
+    }
+}
+
+/// Map words per minute onto a backend's relative rate scale of -`range`..`range`
+fn relative_rate(wpm: u32, range: i32) -> i32 {
+    let scaled = (wpm as f32 / DEFAULT_RATE_WPM as f32 - 1.0) * range as f32;
+    (scaled.round() as i32).clamp(-range, range)
+}
+
+/// Parse `say -v '?'` lines like `Bad News    en_US    # The light you see...`
+fn parse_say_voices(output: &str) -> Vec<VoiceInfo> {
+    output
+        .lines()
+        .filter_map(|line| {
+            let spec = line.split('#').next()?.trim();
+            let (name, language) = spec.rsplit_once(char::is_whitespace)?;
+            Some(VoiceInfo {
+                name: name.trim().to_string(),
+                language: language.to_string(),
+            })
+        })
+        .collect()
+}
+
+/// Voices available from the first installed speech backend
+pub fn list_voices() -> Result<Vec<VoiceInfo>, String> {
+    for backend in Backend::for_platform() {
+        let speech = backend.voices_command();
+        let output = match Command::new(speech.program).args(&speech.args).output() {
+            Ok(output) => output,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => continue,
+            Err(e) => return Err(format!("Listing voices ({:?}) failed: {}", backend, e)),
+        };
+        if !output.status.success() {
+            return Err(format!("Listing voices ({:?}) exited with {}", backend, output.status));
+        }
+        return Ok(backend.parse_voices(&String::from_utf8_lossy(&output.stdout)));
+    }
+    Err(no_backend_error())
+}
+
+/// Speak through the system backend ("system") or OpenAI's speech API ("openai"), which
+/// reuses the transcription API key. `voice` is the default when speak_text doesn't pick one.
+pub fn set_engine(name: &str, model: Option<String>, voice: Option<String>) -> Result<(), String> {
+    let engine = match name {
+        "system" => Engine::System,
+        "openai" => Engine::OpenAi {
+            model: model.unwrap_or_else(|| OPENAI_TTS_MODEL.to_string()),
+            voice: voice.unwrap_or_else(|| OPENAI_TTS_VOICE.to_string()),
+        },
+        other => return Err(format!("Unknown TTS backend '{}' (expected system or openai)", other)),
+    };
+    *ENGINE.lock() = engine;
+    Ok(())
+}
+
+/// Choose between direct backend playback and synthesizing to a file played via cpal
+pub fn set_play_through_cpal(enabled: bool) {
+    PLAY_THROUGH_CPAL.store(enabled, Ordering::SeqCst);
+}
+
+/// Queue text to speak after anything already queued; `tts:done` is emitted as it
+/// finishes or is stopped, and `tts:queue_empty` once nothing is left
+pub fn speak(app: AppHandle, text: String, options: SpeechOptions) -> Result<(), String> {
+    if *ENGINE.lock() == Engine::System && Backend::available().is_none() {
+        return Err(no_backend_error());
+    }
+    let utterance = Utterance {
+        app,
+        text,
+        options,
+        queue_generation: QUEUE_GENERATION.load(Ordering::SeqCst),
+    };
+    let mut queue = QUEUE.lock();
+    let sender = queue.get_or_insert_with(spawn_queue_worker);
+    QUEUED.fetch_add(1, Ordering::SeqCst);
+    sender.send(utterance).map_err(|_| {
+        QUEUED.fetch_sub(1, Ordering::SeqCst);
+        "Speech queue worker exited".to_string()
+    })
+}
+
+/// Drop queued utterances that haven't started yet
+pub fn clear_queue() {
+    QUEUE_GENERATION.fetch_add(1, Ordering::SeqCst);
+}
+
+/// Speak queued utterances one at a time
+fn spawn_queue_worker() -> Sender<Utterance> {
+    let (sender, receiver) = mpsc::channel::<Utterance>();
+    std::thread::spawn(move || {
+        for utterance in receiver {
+            if utterance.queue_generation == QUEUE_GENERATION.load(Ordering::SeqCst) {
+                let engine = ENGINE.lock().clone();
+                let interrupted = match engine {
+                    Engine::System => speak_now(&utterance.text, &utterance.options),
+                    Engine::OpenAi { model, voice } => {
+                        speak_openai(&utterance, &model, &voice).unwrap_or_else(|e| {
+                            eprintln!("{}", e);
+                            utterance.app.emit_to(EventTarget::Any, "tts:error", e).ok();
+                            false
+                        })
+                    }
+                };
+                emit_done(&utterance.app, interrupted);
+            }
+            if QUEUED.fetch_sub(1, Ordering::SeqCst) == 1 {
+                utterance.app.emit_to(EventTarget::Any, "tts:queue_empty", ()).ok();
+            }
+        }
+    });
+    sender
+}
+
+/// Speak one utterance to completion, returning whether stop() cut it short
+fn speak_now(text: &str, options: &SpeechOptions) -> bool {
+    let generation = PLAYBACK_GENERATION.load(Ordering::SeqCst);
+    let stopped = || PLAYBACK_GENERATION.load(Ordering::SeqCst) != generation;
+
+    if PLAY_THROUGH_CPAL.load(Ordering::SeqCst) {
+        match synthesize(text, options) {
+            Ok((samples, sample_rate)) => {
+                if !stopped() {
+                    CPAL_PLAYING.store(true, Ordering::SeqCst);
+                    if let Err(e) = play_samples(samples, sample_rate, generation) {
+                        eprintln!("TTS playback failed: {}", e);
+                    }
+                    CPAL_PLAYING.store(false, Ordering::SeqCst);
+                }
+                return stopped();
+            }
+            Err(_) if stopped() => return true,
+            Err(e) => eprintln!("Speech file synthesis failed, playing directly: {}", e),
+        }
+    }
+
+    match speak_direct(text, options) {
+        Ok(status) => status.is_none(),
+        Err(e) => {
+            eprintln!("{}", e);
+            false
+        }
+    }
+}
+
+/// Stream speech from OpenAI into the output device as it arrives, returning whether
+/// stop() cut it short
+fn speak_openai(utterance: &Utterance, model: &str, default_voice: &str) -> Result<bool, String> {
+    let generation = PLAYBACK_GENERATION.load(Ordering::SeqCst);
+    let stopped = || PLAYBACK_GENERATION.load(Ordering::SeqCst) != generation;
+    let options = &utterance.options;
+    let voice = options.voice.as_deref().unwrap_or(default_voice);
+    let volume = options.volume.map_or(1.0, |v| v.clamp(0.0, 1.0));
+
+    let mut response = voice::request_openai_speech(&utterance.text, model, voice, openai_speed(options), "pcm")?;
+    if stopped() {
+        return Ok(true);
+    }
+
+    let mut playback = Playback::open(OPENAI_PCM_RATE)?;
+    CPAL_PLAYING.store(true, Ordering::SeqCst);
+    let mut buf = [0u8; 8192];
+    let mut pending: Vec<u8> = Vec::new(); // a sample split across reads
+    let result = loop {
+        if stopped() {
+            break Ok(());
+        }
+        let n = match response.read(&mut buf) {
+            Ok(0) => break Ok(()),
+            Ok(n) => n,
+            Err(e) => break Err(format!("OpenAI TTS stream failed: {}", e)),
+        };
+        pending.extend_from_slice(&buf[..n]);
+        playback.push(&take_pcm16(&mut pending, volume));
+    };
+    if result.is_ok() {
+        playback.drain(generation);
+    }
+    CPAL_PLAYING.store(false, Ordering::SeqCst);
+    result.map(|_| stopped())
+}
+
+/// OpenAI's speed multiplier for a words-per-minute rate
+fn openai_speed(options: &SpeechOptions) -> Option<f32> {
+    options.rate_wpm.map(|wpm| (wpm as f32 / DEFAULT_RATE_WPM as f32).clamp(0.25, 4.0))
+}
+
+/// Decode the whole 16-bit little-endian samples in `pending`, leaving any odd byte behind
+fn take_pcm16(pending: &mut Vec<u8>, volume: f32) -> Vec<f32> {
+    let whole = pending.len() / 2 * 2;
+    let samples = pending[..whole]
+        .chunks_exact(2)
+        .map(|b| i16::from_le_bytes([b[0], b[1]]) as f32 / i16::MAX as f32 * volume)
+        .collect();
+    pending.drain(..whole);
+    samples
+}
+
+/// Whether any of our speech is playing right now
+pub fn is_speaking() -> bool {
+    let mut speaking = SPEAKING.lock();
+    speaking.retain_mut(|child| matches!(child.try_wait(), Ok(None)));
+    !speaking.is_empty() || CPAL_PLAYING.load(Ordering::SeqCst)
+}
+
+fn emit_done(app: &AppHandle, interrupted: bool) {
+    app.emit_to(EventTarget::Any, "tts:done", serde_json::json!({ "interrupted": interrupted }))
+        .ok();
+}
+
+/// Keep a speech process so stop() can kill it, returning its pid
+fn track(child: Child) -> u32 {
+    let pid = child.id();
+    SPEAKING.lock().push(child);
+    pid
+}
+
+/// Wait for a tracked process to exit; None if stop() killed it first
+fn wait_tracked(pid: u32) -> Option<ExitStatus> {
+    loop {
+        {
+            let mut speaking = SPEAKING.lock();
+            let index = speaking.iter().position(|child| child.id() == pid)?;
+            if let Ok(Some(status)) = speaking[index].try_wait() {
+                speaking.retain(|child| child.id() != pid);
+                return Some(status);
+            }
+        }
+        std::thread::sleep(CHILD_POLL_INTERVAL);
+    }
+}
+
+/// Stop the utterance playing now, and with `flush` everything queued behind it
+pub fn stop(flush: bool) {
+    if flush {
+        clear_queue();
+    }
+    PLAYBACK_GENERATION.fetch_add(1, Ordering::SeqCst);
+
+    // Only Koe's own speech processes; other apps' speech is left alone
+    for mut child in SPEAKING.lock().drain(..) {
+        child.kill().ok();
+        child.wait().ok();
+    }
+}
+
+/// Speak through the system backend, which plays it itself, until it exits or is stopped
+fn speak_direct(text: &str, options: &SpeechOptions) -> Result<Option<ExitStatus>, String> {
+    let backend = Backend::available().ok_or_else(no_backend_error)?;
+    let child = spawn(backend.command(text, options))
+        .map_err(|e| format!("Text-to-speech ({:?}) failed: {}", backend, e))?;
+    Ok(wait_tracked(track(child)))
+}
+
+fn no_backend_error() -> String {
+    match Backend::for_platform() {
+        [Backend::EspeakNg, ..] => "No text-to-speech backend found; install espeak-ng or speech-dispatcher".to_string(),
+        backends => format!("No text-to-speech backend found (tried {:?})", backends),
+    }
+}
+
+/// Render speech to a WAV file with the system backend
+fn render_to_wav(text: &str, options: &SpeechOptions, path: &Path) -> Result<(), String> {
+    let backend = Backend::available().ok_or_else(no_backend_error)?;
+    let speech = backend
+        .file_command(text, options, path)
+        .ok_or_else(|| format!("{:?} can't write speech to a file", backend))?;
+    let child = spawn(speech).map_err(|e| format!("Failed to run {}: {}", backend.program(), e))?;
+    let status = wait_tracked(track(child)).ok_or("Speech stopped")?;
+    if !status.success() {
+        return Err(format!("{} exited with {}", backend.program(), status));
+    }
+    Ok(())
+}
+
+/// Render speech to a temporary WAV and decode it
+fn synthesize(text: &str, options: &SpeechOptions) -> Result<(Vec<f32>, u32), String> {
+    let id = SYNTH_COUNTER.fetch_add(1, Ordering::SeqCst);
+    let path = std::env::temp_dir().join(format!("koe-tts-{}-{}.wav", std::process::id(), id));
+
+    let decoded = render_to_wav(text, options, &path).and_then(|_| recording::decode_wav(&path));
+    std::fs::remove_file(&path).ok();
+    decoded
+}
+
+/// Write speech to `path` instead of playing it, as WAV or (OpenAI backend only) MP3
+/// by extension; a path without one gets `.wav`
+pub fn synthesize_to_file(text: &str, path: &str, options: &SpeechOptions) -> Result<SynthesizedFile, String> {
+    let mut path = PathBuf::from(path);
+    if path.extension().is_none() {
+        path.set_extension("wav");
+    }
+    let is_mp3 = path.extension().is_some_and(|ext| ext.eq_ignore_ascii_case("mp3"));
+
+    let engine = ENGINE.lock().clone();
+    match engine {
+        Engine::System if is_mp3 => return Err("System voices can only write WAV files".to_string()),
+        Engine::System => render_to_wav(text, options, &path)?,
+        Engine::OpenAi { model, voice } => {
+            let voice = options.voice.as_deref().unwrap_or(&voice);
+            let format = if is_mp3 { "mp3" } else { "pcm" };
+            let mut response = voice::request_openai_speech(text, &model, voice, openai_speed(options), format)?;
+            if is_mp3 {
+                let mut file = std::fs::File::create(&path)
+                    .map_err(|e| format!("Failed to create {}: {}", path.display(), e))?;
+                std::io::copy(&mut response, &mut file).map_err(|e| e.to_string())?;
+            } else {
+                let mut pcm = Vec::new();
+                response.read_to_end(&mut pcm).map_err(|e| e.to_string())?;
+                let volume = options.volume.map_or(1.0, |v| v.clamp(0.0, 1.0));
+                write_wav(&path, &take_pcm16(&mut pcm, volume), OPENAI_PCM_RATE)?;
+            }
+        }
+    }
+
+    let duration_secs = if is_mp3 {
+        None
+    } else {
+        let (samples, sample_rate) = recording::decode_wav(&path)?;
+        Some(samples.len() as f64 / sample_rate as f64)
+    };
+    Ok(SynthesizedFile {
+        path: path.to_string_lossy().into_owned(),
+        duration_secs,
+    })
+}
+
+fn write_wav(path: &Path, samples: &[f32], sample_rate: u32) -> Result<(), String> {
+    let spec = hound::WavSpec {
+        channels: 1,
+        sample_rate,
+        bits_per_sample: 16,
+        sample_format: hound::SampleFormat::Int,
+    };
+    let mut wav = hound::WavWriter::create(path, spec)
+        .map_err(|e| format!("Failed to create {}: {}", path.display(), e))?;
+    for sample in samples {
+        let sample_i16 = (sample * 32767.0).clamp(-32768.0, 32767.0) as i16;
+        wav.write_sample(sample_i16).map_err(|e| e.to_string())?;
+    }
+    wav.finalize().map_err(|e| e.to_string())
+}
+
+/// Play mono samples on the selected output device, blocking until done or stopped
+fn play_samples(samples: Vec<f32>, sample_rate: u32, generation: u64) -> Result<(), String> {
+    let mut playback = Playback::open(sample_rate)?;
+    playback.push(&samples);
+    playback.drain(generation);
+    Ok(())
+}
+
+/// The selected output device, playing whatever is pushed and silence when it runs dry
+struct Playback {
+    _stream: cpal::Stream,
+    queue: Arc<Mutex<VecDeque<f32>>>,
+    resampler: resampler::StreamResampler, // source rate to device rate, for the whole utterance
+}
+
+impl Playback {
+    /// Open the output for mono audio at `sample_rate`
+    fn open(sample_rate: u32) -> Result<Self, String> {
+        let device = output_device()?;
+        let supported = device.default_output_config().map_err(|e| e.to_string())?;
+        let sample_format = supported.sample_format();
+        let device_rate = supported.sample_rate().0;
+        let config: cpal::StreamConfig = supported.into();
+
+        let queue = Arc::new(Mutex::new(VecDeque::new()));
+        let stream = match sample_format {
+            cpal::SampleFormat::F32 => build_playback_stream::<f32>(&device, &config, queue.clone()),
+            cpal::SampleFormat::I16 => build_playback_stream::<i16>(&device, &config, queue.clone()),
+            cpal::SampleFormat::U16 => build_playback_stream::<u16>(&device, &config, queue.clone()),
+            other => return Err(format!("Unsupported output sample format {}", other)),
+        }
+        .map_err(|e| e.to_string())?;
+        stream.play().map_err(|e| e.to_string())?;
+
+        Ok(Playback {
+            _stream: stream,
+            queue,
+            resampler: resampler::StreamResampler::new(sample_rate, device_rate),
+        })
+    }
+
+    /// Queue the next mono samples of the utterance, resampled to the device rate
+    fn push(&mut self, samples: &[f32]) {
+        let samples = self.resampler.process(samples);
+        self.queue.lock().extend(samples);
+    }
+
+    /// Block until everything queued has played, or stop() is called
+    fn drain(&mut self, generation: u64) {
+        let tail = self.resampler.flush();
+        self.queue.lock().extend(tail);
+        while !self.queue.lock().is_empty() {
+            if PLAYBACK_GENERATION.load(Ordering::SeqCst) != generation {
+                break;
+            }
+            std::thread::sleep(Duration::from_millis(20));
+        }
+    }
+}
+
+/// Play queued mono samples on every channel, converted to the device's sample format
+fn build_playback_stream<T>(
+    device: &cpal::Device,
+    config: &cpal::StreamConfig,
+    queue: Arc<Mutex<VecDeque<f32>>>,
+) -> Result<cpal::Stream, cpal::BuildStreamError>
+where
+    T: cpal::SizedSample + cpal::FromSample<f32>,
+{
+    let channels = config.channels as usize;
+    device.build_output_stream(
+        config,
+        move |data: &mut [T], _: &cpal::OutputCallbackInfo| {
+            let mut queue = queue.lock();
+            for frame in data.chunks_mut(channels) {
+                frame.fill(T::from_sample(queue.pop_front().unwrap_or(0.0)));
+            }
+        },
+        |err| eprintln!("Playback stream error: {}", err),
+        None,
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_backend_commands() {
+        // Text goes through stdin or after `--`, never where it could be read as an option
+        let defaults = SpeechOptions::default();
+        let command = Backend::EspeakNg.command("-v hello", &defaults);
+        assert_eq!((command.program, command.args), ("espeak-ng", vec!["--stdin".to_string()]));
+        assert_eq!(command.input.as_deref(), Some("-v hello"));
+
+        let command = Backend::SpdSay.command("-v hello", &defaults);
+        assert_eq!(command.args[command.args.len() - 2..], ["--".to_string(), "-v hello".to_string()]);
+        assert_eq!(command.input, None);
+
+        let command = Backend::Say.command("-v hello", &defaults);
+        assert!(command.args.is_empty());
+        assert_eq!(command.input.as_deref(), Some("-v hello"));
+
+        let command = Backend::Sapi.command("it's \"quoted\"", &defaults);
+        assert_eq!(command.program, "powershell");
+        assert!(command.args.iter().all(|a| !a.contains("quoted")));
+        assert_eq!(command.input.as_deref(), Some("it's \"quoted\""));
+    }
+
+    #[test]
+    fn test_speech_options_and_voices() {
+        let options = SpeechOptions {
+            voice: Some("Samantha".to_string()),
+            rate_wpm: Some(360),
+            volume: Some(0.5),
+        };
+        let command = Backend::Say.command("hi", &options);
+        assert_eq!(command.args, ["-v", "Samantha", "-r", "360"]);
+        assert_eq!(command.input.as_deref(), Some("[[volm 0.50]] hi"));
+        let command = Backend::Sapi.command("hi", &options);
+        assert_eq!(command.env, [("KOE_TTS_VOICE", "Samantha".to_string()), ("KOE_TTS_RATE", "10".to_string()), ("KOE_TTS_VOLUME", "50".to_string())]);
+        assert_eq!(relative_rate(90, 100), -50);
+
+        let voices = parse_say_voices("Alex                en_US    # Most people recognize me by my voice.\nBad News            en_US    # The light you see at the end of the tunnel...\n");
+        assert_eq!(voices[1], VoiceInfo { name: "Bad News".to_string(), language: "en_US".to_string() });
+        assert_eq!(voices.len(), 2);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_stop_kills_only_tracked_speech() {
+        let ours = track(Command::new("sleep").arg("5").spawn().unwrap());
+        let mut theirs = Command::new("sleep").arg("5").spawn().unwrap();
+        assert!(is_speaking());
+
+        let waiter = std::thread::spawn(move || wait_tracked(ours));
+        stop(false);
+        assert_eq!(waiter.join().unwrap(), None);
+        assert!(!is_speaking());
+        assert!(matches!(theirs.try_wait(), Ok(None)));
+        theirs.kill().ok();
+        theirs.wait().ok();
+    }
+
+    #[test]
+    fn test_pcm_split_across_reads() {
+        let mut pending = vec![0xff, 0x7f, 0x00];
+        assert_eq!(take_pcm16(&mut pending, 0.5), vec![0.5]);
+        assert_eq!(pending, vec![0x00]);
+        pending.push(0x80);
+        assert_eq!(take_pcm16(&mut pending, 1.0), vec![i16::MIN as f32 / i16::MAX as f32]);
+        assert!(pending.is_empty());
+    }
+
+    #[test]
+    fn test_file_commands() {
+        let path = Path::new("/tmp/note.wav");
+        let command = Backend::Say.file_command("hi", &SpeechOptions::default(), path).unwrap();
+        assert_eq!(command.args[..2], ["-o".to_string(), "/tmp/note.wav".to_string()]);
+        assert_eq!(command.input.as_deref(), Some("hi"));
+
+        let command = Backend::EspeakNg.file_command("hi", &SpeechOptions::default(), path).unwrap();
+        assert!(command.args.ends_with(&["-w".to_string(), "/tmp/note.wav".to_string()]));
+        assert!(Backend::SpdSay.file_command("hi", &SpeechOptions::default(), path).is_none());
+    }
+}
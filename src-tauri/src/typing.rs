@@ -0,0 +1,113 @@
+use parking_lot::Mutex;
+
+/// Punctuation that attaches to the previous word without a space
+const ATTACHING_PUNCTUATION: &[char] = &['.', ',', ';', ':', '!', '?', ')', ']', '}', '%', '\'', '…'];
+
+/// Formats consecutive transcripts as they'd appear typed into an editor
+pub struct TypingFormatter {
+    pub auto_space: bool,
+    pub capitalize_sentences: bool,
+    last_char: Option<char>, // end of the previous inserted transcript
+}
+
+impl TypingFormatter {
+    pub fn new() -> Self {
+        TypingFormatter {
+            auto_space: true,
+            capitalize_sentences: true,
+            last_char: None,
+        }
+    }
+
+    /// Text to insert after what has been typed so far, without advancing state
+    pub fn preview(&self, text: &str) -> String {
+        let text = text.trim();
+        let Some(first) = text.chars().next() else {
+            return String::new();
+        };
+
+        let mut out = String::with_capacity(text.len() + 1);
+        if self.auto_space {
+            if let Some(last) = self.last_char {
+                let opens = matches!(last, '(' | '[' | '{' | '"' | '\'') || last.is_whitespace();
+                if !opens && !ATTACHING_PUNCTUATION.contains(&first) {
+                    out.push(' ');
+                }
+            }
+        }
+
+        let sentence_start = matches!(self.last_char, None | Some('.' | '!' | '?'));
+        if self.capitalize_sentences && sentence_start {
+            out.extend(first.to_uppercase());
+            out.push_str(&text[first.len_utf8()..]);
+        } else {
+            out.push_str(text);
+        }
+        out
+    }
+
+    /// Text to insert, remembering how it ends for the next transcript
+    pub fn format(&mut self, text: &str) -> String {
+        let out = self.preview(text);
+        if let Some(last) = out.chars().last() {
+            self.last_char = Some(last);
+        }
+        out
+    }
+
+    /// Start a fresh document: no leading space, capitalize the next transcript
+    pub fn reset(&mut self) {
+        self.last_char = None;
+    }
+}
+
+lazy_static::lazy_static! {
+    static ref FORMATTER: Mutex<TypingFormatter> = Mutex::new(TypingFormatter::new());
+}
+
+/// Choose which typing rules apply to inserted text
+pub fn configure(auto_space: bool, capitalize_sentences: bool) {
+    let mut formatter = FORMATTER.lock();
+    formatter.auto_space = auto_space;
+    formatter.capitalize_sentences = capitalize_sentences;
+}
+
+/// Insert text for a transcript; final transcripts advance the typed state
+pub fn insert_text(text: &str, is_final: bool) -> String {
+    let mut formatter = FORMATTER.lock();
+    if is_final {
+        formatter.format(text)
+    } else {
+        formatter.preview(text)
+    }
+}
+
+pub fn reset() {
+    FORMATTER.lock().reset();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_spacing_and_capitalization() {
+        let mut formatter = TypingFormatter::new();
+        assert_eq!(formatter.format("hello there"), "Hello there");
+        assert_eq!(formatter.format(", friend."), ", friend.");
+        assert_eq!(formatter.format("how are you"), " How are you");
+        assert_eq!(formatter.format("doing"), " doing");
+        // Previewing partials doesn't advance state
+        assert_eq!(formatter.preview("today?"), " today?");
+        assert_eq!(formatter.format("?"), "?");
+    }
+
+    #[test]
+    fn test_disabled_rules() {
+        let mut formatter = TypingFormatter::new();
+        formatter.auto_space = false;
+        formatter.capitalize_sentences = false;
+        assert_eq!(formatter.format("one."), "one.");
+        assert_eq!(formatter.format("two"), "two");
+    }
+}
@@ -0,0 +1,585 @@
+//! Energy-based voice activity detection for live capture. Decides when the buffered audio
+//! becomes a chunk for transcription (on a fixed interval, or at the silence after an
+//! utterance), and drives the speaking indicator from each callback's level.
+
+use parking_lot::Mutex;
+
+/// Analysis window for per-frame voice activity
+const WINDOW_MS: u32 = 10;
+/// Hangover never holds a chunk past Whisper's 30s context
+pub const MAX_CHUNK_SECS: usize = 30;
+/// Base chunk duration range and default, in seconds
+const MIN_CHUNK_DURATION: f32 = 0.25;
+const MAX_CHUNK_DURATION: f32 = 10.0;
+const DEFAULT_CHUNK_DURATION: f32 = 1.0;
+/// Trailing silence that ends an utterance in silence-gated mode, unless configured
+const DEFAULT_SILENCE_MS: u32 = 600;
+/// Level below which audio counts as that silence, unless configured. The energy gate is far
+/// lower (-80 dBFS) and room noise alone sits above it, so utterances would never end on it
+const DEFAULT_SILENCE_DBFS: f32 = -45.0;
+
+/// Flushed segments shorter than this (a cough, a click) aren't sent, unless configured
+const DEFAULT_MIN_SEGMENT_SECS: f32 = 0.3;
+const MAX_MIN_SEGMENT_SECS: f32 = 5.0;
+
+/// Valid ranges, in milliseconds
+const MAX_HANGOVER_MS: u32 = 3000;
+const MAX_PREROLL_MS: u32 = 1000;
+const MAX_MIN_SPEECH_MS: u32 = 2000;
+/// Lead-in kept from discarded audio so the start of a phrase isn't clipped
+const DEFAULT_PREROLL_MS: u32 = 300;
+
+/// Speech must last this long to start, and silence this long to end it
+const SPEECH_START_MS: f32 = 30.0;
+const SPEECH_END_MS: f32 = 300.0;
+/// Energy must fall below this fraction of the threshold to count as silence, so a level
+/// hovering at the threshold doesn't flap
+const SPEECH_END_RATIO: f32 = 0.5;
+
+/// Floor for dBFS values, so silence doesn't report -inf
+pub const DBFS_FLOOR: f32 = -120.0;
+/// Energy gate for sending chunks; very low by default (-80 dBFS) to let Whisper filter silence
+const DEFAULT_THRESHOLD: f32 = 0.0001;
+
+/// Typical dictation pace (~150 wpm); adaptive chunking scales relative to this
+const BASELINE_WORDS_PER_SEC: f32 = 2.5;
+/// Weight of the newest chunk in the smoothed speech rate
+const RATE_SMOOTHING: f32 = 0.3;
+/// Adaptive scaling never more than halves or doubles chunk timing
+const MIN_RATE_SCALE: f32 = 0.5;
+const MAX_RATE_SCALE: f32 = 2.0;
+
+/// Segmentation timing, applied on top of the base chunk duration.
+///
+/// - `hangover_ms`: once a chunk is due, keep accumulating while speech ended less
+///   than this long ago, so trailing words aren't split across chunks.
+/// - `preroll_ms`: audio kept from a discarded chunk and prepended to the next, so
+///   speech onsets aren't clipped. Pre-roll doesn't count toward `min_speech_ms`.
+/// - `min_speech_ms`: chunks with less voiced audio than this are discarded as blips.
+///   A long hangover gives short utterances more chances to reach it.
+///
+/// The default keeps 300ms of pre-roll and otherwise chunks at fixed size; all zero
+/// gives plain fixed-size chunking.
+#[derive(Clone, Copy, Debug, PartialEq, serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct VadTiming {
+    pub hangover_ms: u32,
+    pub preroll_ms: u32,
+    pub min_speech_ms: u32,
+}
+
+impl Default for VadTiming {
+    fn default() -> Self {
+        VadTiming {
+            hangover_ms: 0,
+            preroll_ms: DEFAULT_PREROLL_MS,
+            min_speech_ms: 0,
+        }
+    }
+}
+
+/// How chunk boundaries are chosen
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum SegmentationMode {
+    /// Consider a chunk every chunk duration (hangover permitting)
+    FixedInterval,
+    /// Gather speech until `silence_ms` of trailing audio below `silence_dbfs`, up to the 30s cap
+    SilenceGated { silence_ms: u32, silence_dbfs: f32 },
+}
+
+impl SegmentationMode {
+    pub fn parse(name: &str, silence_ms: Option<u32>, silence_dbfs: Option<f32>) -> Result<Self, String> {
+        match name.to_ascii_lowercase().replace('_', "").as_str() {
+            "fixed" | "fixedinterval" => Ok(SegmentationMode::FixedInterval),
+            "silence" | "silencegated" => {
+                let silence_ms = silence_ms.unwrap_or(DEFAULT_SILENCE_MS);
+                if !(WINDOW_MS..=MAX_HANGOVER_MS).contains(&silence_ms) {
+                    return Err(format!(
+                        "Silence must be between {} and {}ms, got {}",
+                        WINDOW_MS, MAX_HANGOVER_MS, silence_ms
+                    ));
+                }
+                let silence_dbfs = silence_dbfs.unwrap_or(DEFAULT_SILENCE_DBFS);
+                if !(DBFS_FLOOR..=0.0).contains(&silence_dbfs) {
+                    return Err(format!(
+                        "Silence level must be between {} and 0 dBFS, got {}",
+                        DBFS_FLOOR, silence_dbfs
+                    ));
+                }
+                Ok(SegmentationMode::SilenceGated { silence_ms, silence_dbfs })
+            }
+            _ => Err(format!("Unknown segmentation mode '{}' (expected fixed_interval or silence_gated)", name)),
+        }
+    }
+}
+
+/// What to do with a chunk that reached the minimum size
+#[derive(Debug, PartialEq)]
+pub enum ChunkAction {
+    /// Keep accumulating (speech still in its hangover)
+    Wait,
+    /// Send the chunk for transcription
+    Flush,
+    /// Drop the chunk, keeping the trailing `preroll` samples
+    Discard { preroll: usize },
+}
+
+/// Recent speech rate, for adapting chunk timing to the speaker
+struct SpeechRate {
+    adaptive: bool,
+    words_per_sec: Option<f32>,
+}
+
+lazy_static::lazy_static! {
+    static ref VAD_TIMING: Mutex<VadTiming> = Mutex::new(VadTiming::default());
+    static ref CHUNK_DURATION: Mutex<f32> = Mutex::new(DEFAULT_CHUNK_DURATION);
+    static ref SEGMENTATION: Mutex<SegmentationMode> = Mutex::new(SegmentationMode::FixedInterval);
+    static ref THRESHOLD: Mutex<f32> = Mutex::new(DEFAULT_THRESHOLD);
+    static ref MIN_SEGMENT_SECS: Mutex<f32> = Mutex::new(DEFAULT_MIN_SEGMENT_SECS);
+    static ref SPEECH_RATE: Mutex<SpeechRate> = Mutex::new(SpeechRate {
+        adaptive: false,
+        words_per_sec: None,
+    });
+}
+
+/// Validate and apply new timing
+pub fn set_timing(hangover_ms: u32, preroll_ms: u32, min_speech_ms: u32) -> Result<(), String> {
+    if hangover_ms > MAX_HANGOVER_MS {
+        return Err(format!("Hangover must be at most {}ms, got {}", MAX_HANGOVER_MS, hangover_ms));
+    }
+    if preroll_ms > MAX_PREROLL_MS {
+        return Err(format!("Pre-roll must be at most {}ms, got {}", MAX_PREROLL_MS, preroll_ms));
+    }
+    if min_speech_ms > MAX_MIN_SPEECH_MS {
+        return Err(format!("Minimum speech must be at most {}ms, got {}", MAX_MIN_SPEECH_MS, min_speech_ms));
+    }
+    *VAD_TIMING.lock() = VadTiming { hangover_ms, preroll_ms, min_speech_ms };
+    Ok(())
+}
+
+pub fn timing() -> VadTiming {
+    *VAD_TIMING.lock()
+}
+
+/// Linear RMS to dBFS, clamped at the floor
+pub fn to_dbfs(rms: f32) -> f32 {
+    if rms <= 0.0 {
+        return DBFS_FLOOR;
+    }
+    (20.0 * rms.log10()).max(DBFS_FLOOR)
+}
+
+pub fn from_dbfs(dbfs: f32) -> f32 {
+    10f32.powf(dbfs / 20.0)
+}
+
+/// Set the energy gate in dBFS (e.g. -40)
+pub fn set_threshold_dbfs(dbfs: f32) -> Result<(), String> {
+    if !(DBFS_FLOOR..=0.0).contains(&dbfs) {
+        return Err(format!("Threshold must be between {} and 0 dBFS, got {}", DBFS_FLOOR, dbfs));
+    }
+    set_threshold(from_dbfs(dbfs))
+}
+
+/// Set the energy gate as linear RMS (e.g. 0.01), over the same -120 to 0 dBFS span as
+/// `set_threshold_dbfs`
+pub fn set_threshold(rms: f32) -> Result<(), String> {
+    let min = from_dbfs(DBFS_FLOOR);
+    if !(min..=1.0).contains(&rms) {
+        return Err(format!("Threshold must be an RMS level between {} and 1, got {}", min, rms));
+    }
+    *THRESHOLD.lock() = rms;
+    Ok(())
+}
+
+/// Energy gate as linear RMS
+pub fn threshold() -> f32 {
+    *THRESHOLD.lock()
+}
+
+/// Set how much audio is gathered before a chunk is considered, clamped to 0.25-10s.
+/// Short chunks lower latency but multiply API calls; returns the duration applied.
+pub fn set_chunk_duration(secs: f32) -> Result<f32, String> {
+    if !secs.is_finite() {
+        return Err(format!("Chunk duration must be a number of seconds, got {}", secs));
+    }
+    let secs = secs.clamp(MIN_CHUNK_DURATION, MAX_CHUNK_DURATION);
+    *CHUNK_DURATION.lock() = secs;
+    Ok(secs)
+}
+
+pub fn chunk_duration() -> f32 {
+    *CHUNK_DURATION.lock()
+}
+
+/// Set the shortest flushed segment worth transcribing (0-5s; 0 sends everything)
+pub fn set_min_segment_duration(secs: f32) -> Result<(), String> {
+    if !(0.0..=MAX_MIN_SEGMENT_SECS).contains(&secs) {
+        return Err(format!(
+            "Minimum segment duration must be between 0 and {} seconds, got {}",
+            MAX_MIN_SEGMENT_SECS, secs
+        ));
+    }
+    *MIN_SEGMENT_SECS.lock() = secs;
+    Ok(())
+}
+
+pub fn min_segment_duration() -> f32 {
+    *MIN_SEGMENT_SECS.lock()
+}
+
+/// Whether a segment of `len` samples lasts at least `min_secs`
+pub fn long_enough(len: usize, sample_rate: u32, min_secs: f32) -> bool {
+    len as f32 >= min_secs * sample_rate as f32
+}
+
+pub fn set_segmentation(mode: SegmentationMode) {
+    *SEGMENTATION.lock() = mode;
+}
+
+/// Samples to gather before deciding a chunk. Fixed chunks apply adaptive scaling;
+/// silence-gated ones are checked as soon as they could hold the ending silence.
+pub fn chunk_samples(sample_rate: u32) -> usize {
+    match *SEGMENTATION.lock() {
+        SegmentationMode::FixedInterval => (sample_rate as f32 * chunk_duration() * rate_scale()) as usize,
+        SegmentationMode::SilenceGated { silence_ms, .. } => (sample_rate as u64 * silence_ms as u64 / 1000) as usize,
+    }
+}
+
+/// Enable or disable speech-rate-adaptive chunking (resets the measured rate)
+pub fn set_adaptive(enabled: bool) {
+    let mut rate = SPEECH_RATE.lock();
+    rate.adaptive = enabled;
+    rate.words_per_sec = None;
+}
+
+/// Fold a transcribed chunk into the smoothed speech rate
+pub fn record_speech_rate(words: usize, secs: f32) {
+    if secs <= 0.0 {
+        return;
+    }
+    let mut rate = SPEECH_RATE.lock();
+    if !rate.adaptive {
+        return;
+    }
+    let sample = words as f32 / secs;
+    rate.words_per_sec = Some(match rate.words_per_sec {
+        Some(prev) => prev + RATE_SMOOTHING * (sample - prev),
+        None => sample,
+    });
+}
+
+/// Multiplier for chunk length and hangover: >1 for fast speakers, <1 for slow
+pub fn rate_scale() -> f32 {
+    let rate = SPEECH_RATE.lock();
+    match (rate.adaptive, rate.words_per_sec) {
+        (true, Some(wps)) => scale_for_rate(wps),
+        _ => 1.0,
+    }
+}
+
+fn scale_for_rate(words_per_sec: f32) -> f32 {
+    (words_per_sec / BASELINE_WORDS_PER_SEC).clamp(MIN_RATE_SCALE, MAX_RATE_SCALE)
+}
+
+/// Configured timing with the speech-rate adjustment applied to hangover
+pub fn effective_timing() -> VadTiming {
+    let timing = timing();
+    let hangover_ms = (timing.hangover_ms as f32 * rate_scale()) as u32;
+    let timing = VadTiming { hangover_ms: hangover_ms.min(MAX_HANGOVER_MS), ..timing };
+    with_segmentation(timing, *SEGMENTATION.lock())
+}
+
+/// In silence-gated mode, the ending silence acts as the hangover
+fn with_segmentation(timing: VadTiming, mode: SegmentationMode) -> VadTiming {
+    match mode {
+        SegmentationMode::FixedInterval => timing,
+        SegmentationMode::SilenceGated { silence_ms, .. } => VadTiming { hangover_ms: silence_ms, ..timing },
+    }
+}
+
+/// Level below which trailing audio counts as silence when deciding a chunk: the energy gate
+/// itself, except in silence-gated mode, which ends utterances at its own silence level
+pub fn silence_threshold(threshold: f32) -> f32 {
+    match *SEGMENTATION.lock() {
+        SegmentationMode::FixedInterval => threshold,
+        SegmentationMode::SilenceGated { silence_dbfs, .. } => from_dbfs(silence_dbfs).max(threshold),
+    }
+}
+
+/// Per-window energy of the chunk being gathered, kept up to date as samples are appended so
+/// deciding a chunk never rescans it. Windows are judged against the thresholds in force
+/// when they complete.
+#[derive(Debug, Default)]
+pub struct ChunkEnergy {
+    window: usize,
+    len: usize,
+    sum_squares: f32,
+    window_sum: f32,
+    window_len: usize,
+    voiced_windows: u32,
+    trailing_quiet_windows: u32,
+}
+
+impl ChunkEnergy {
+    pub fn new(sample_rate: u32) -> Self {
+        ChunkEnergy { window: (sample_rate * WINDOW_MS / 1000).max(1) as usize, ..Default::default() }
+    }
+
+    /// Count appended samples: speech against `threshold`, trailing silence against
+    /// `silence_threshold`
+    pub fn push(&mut self, samples: &[f32], threshold: f32, silence_threshold: f32) {
+        for s in samples {
+            let square = s * s;
+            self.sum_squares += square;
+            self.window_sum += square;
+            self.window_len += 1;
+            if self.window_len == self.window {
+                let rms = (self.window_sum / self.window_len as f32).sqrt();
+                if rms > threshold {
+                    self.voiced_windows += 1;
+                }
+                if rms > silence_threshold {
+                    self.trailing_quiet_windows = 0;
+                } else {
+                    self.trailing_quiet_windows += 1;
+                }
+                self.window_sum = 0.0;
+                self.window_len = 0;
+            }
+        }
+        self.len += samples.len();
+    }
+
+    /// Forget everything, for a chunk that starts empty
+    pub fn reset(&mut self) {
+        *self = ChunkEnergy { window: self.window, ..Default::default() };
+    }
+
+    /// Samples counted so far
+    pub fn counted(&self) -> usize {
+        self.len
+    }
+
+    /// RMS over every sample counted
+    pub fn rms(&self) -> f32 {
+        if self.len == 0 {
+            return 0.0;
+        }
+        (self.sum_squares / self.len as f32).sqrt()
+    }
+
+    /// Milliseconds of windows above the speech threshold
+    pub fn voiced_ms(&self) -> u32 {
+        self.voiced_windows * WINDOW_MS
+    }
+
+    fn trailing_silence_ms(&self) -> u32 {
+        self.trailing_quiet_windows * WINDOW_MS
+    }
+}
+
+/// Decide a due chunk's fate from its per-window energy: speech measured against the speech
+/// threshold, and the hangover against trailing audio below the silence threshold
+pub fn decide(energy: &ChunkEnergy, sample_rate: u32, timing: &VadTiming) -> ChunkAction {
+    let preroll = (sample_rate as usize * timing.preroll_ms as usize / 1000).min(energy.counted());
+
+    if energy.voiced_ms() == 0 {
+        return ChunkAction::Discard { preroll };
+    }
+    if energy.trailing_silence_ms() < timing.hangover_ms && energy.counted() < sample_rate as usize * MAX_CHUNK_SECS {
+        return ChunkAction::Wait;
+    }
+    if energy.voiced_ms() < timing.min_speech_ms {
+        return ChunkAction::Discard { preroll };
+    }
+    ChunkAction::Flush
+}
+
+/// A change in whether the user is speaking
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum SpeechEdge {
+    Started,
+    Ended,
+}
+
+/// Tracks speaking vs silent from block energy, with a level gap and debounce in each direction
+#[derive(Debug, Default)]
+pub struct SpeechDetector {
+    speaking: bool,
+    pending_ms: f32, // how long the opposite state has held
+}
+
+impl SpeechDetector {
+    /// Feed one block's RMS and length; returns an edge when speech starts or ends
+    pub fn update(&mut self, rms: f32, threshold: f32, block_ms: f32) -> Option<SpeechEdge> {
+        let flipping = if self.speaking {
+            rms < threshold * SPEECH_END_RATIO
+        } else {
+            rms > threshold
+        };
+        if !flipping {
+            self.pending_ms = 0.0;
+            return None;
+        }
+        self.pending_ms += block_ms;
+        let needed = if self.speaking { SPEECH_END_MS } else { SPEECH_START_MS };
+        if self.pending_ms < needed {
+            return None;
+        }
+        self.speaking = !self.speaking;
+        self.pending_ms = 0.0;
+        Some(if self.speaking { SpeechEdge::Started } else { SpeechEdge::Ended })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_min_segment_duration() {
+        assert!(!long_enough(1600, 16000, DEFAULT_MIN_SEGMENT_SECS));
+        assert!(long_enough(8000, 16000, DEFAULT_MIN_SEGMENT_SECS));
+        assert!(long_enough(1, 16000, 0.0));
+        assert!(set_min_segment_duration(-0.1).is_err());
+    }
+
+    #[test]
+    fn test_speech_detector_debounces() {
+        let mut detector = SpeechDetector::default();
+        let mut feed = |rms: f32, blocks: usize| {
+            (0..blocks).filter_map(|_| detector.update(rms, 0.1, 10.0)).collect::<Vec<_>>()
+        };
+        // A 20ms click is too short to count
+        assert!(feed(0.5, 2).is_empty());
+        assert!(feed(0.0, 1).is_empty());
+        assert_eq!(feed(0.5, 3), vec![SpeechEdge::Started]);
+        // Dips that stay above half the threshold, or don't last, keep speech going
+        assert!(feed(0.07, 50).is_empty());
+        assert!(feed(0.0, 20).is_empty());
+        assert!(feed(0.5, 1).is_empty());
+        assert_eq!(feed(0.0, 30), vec![SpeechEdge::Ended]);
+    }
+
+    /// `decide` for a whole chunk at once
+    fn decide(samples: &[f32], sample_rate: u32, threshold: f32, silence_threshold: f32, timing: &VadTiming) -> ChunkAction {
+        let mut energy = ChunkEnergy::new(sample_rate);
+        energy.push(samples, threshold, silence_threshold);
+        super::decide(&energy, sample_rate, timing)
+    }
+
+    /// 100ms of tone followed by `silence_ms` of silence, at 16kHz
+    fn blip(silence_ms: usize) -> Vec<f32> {
+        let mut samples = vec![0.5; 1600];
+        samples.extend(vec![0.0; silence_ms * 16]);
+        samples
+    }
+
+    #[test]
+    fn test_decide_hangover_and_min_speech() {
+        let timing = VadTiming { hangover_ms: 500, preroll_ms: 200, min_speech_ms: 250 };
+        // Speech ended 300ms ago: still within hangover
+        assert_eq!(decide(&blip(300), 16000, 0.0001, 0.0001, &timing), ChunkAction::Wait);
+        // Hangover elapsed, but 100ms of speech is a blip
+        assert_eq!(decide(&blip(900), 16000, 0.0001, 0.0001, &timing), ChunkAction::Discard { preroll: 3200 });
+
+        let timing = VadTiming { min_speech_ms: 50, ..timing };
+        assert_eq!(decide(&blip(900), 16000, 0.0001, 0.0001, &timing), ChunkAction::Flush);
+        assert_eq!(decide(&blip(300), 16000, 0.0001, 0.0001, &VadTiming::default()), ChunkAction::Flush);
+    }
+
+    #[test]
+    fn test_chunk_energy_counts_across_blocks() {
+        let samples = blip(450);
+        let mut whole = ChunkEnergy::new(16000);
+        whole.push(&samples, 0.0001, 0.0001);
+        // Blocks that split analysis windows
+        let mut blocks = ChunkEnergy::new(16000);
+        for block in samples.chunks(333) {
+            blocks.push(block, 0.0001, 0.0001);
+        }
+        assert_eq!((blocks.voiced_ms(), blocks.trailing_silence_ms()), (100, 450));
+        assert_eq!((blocks.voiced_ms(), blocks.trailing_silence_ms()), (whole.voiced_ms(), whole.trailing_silence_ms()));
+        assert_eq!(blocks.counted(), samples.len());
+        assert!((blocks.rms() - whole.rms()).abs() < 1e-6);
+
+        blocks.reset();
+        assert_eq!((blocks.counted(), blocks.voiced_ms(), blocks.rms()), (0, 0, 0.0));
+    }
+
+    #[test]
+    fn test_quiet_utterance_flushes_below_chunk_rms() {
+        // 300ms just over the threshold, then a second of hangover silence
+        let mut samples = vec![0.002; 300 * 16];
+        samples.extend(vec![0.0; 1000 * 16]);
+        let mut energy = ChunkEnergy::new(16000);
+        energy.push(&samples, 0.0015, 0.0015);
+        assert!(energy.rms() < 0.0015);
+        assert_eq!(super::decide(&energy, 16000, &VadTiming::default()), ChunkAction::Flush);
+    }
+
+    #[test]
+    fn test_default_preroll_survives_discard() {
+        let silence = vec![0.0; 16000];
+        assert_eq!(
+            decide(&silence, 16000, 0.0001, 0.0001, &VadTiming::default()),
+            ChunkAction::Discard { preroll: 4800 }
+        );
+    }
+
+    #[test]
+    fn test_silence_gated_waits_for_silence() {
+        let mode = SegmentationMode::parse("silence_gated", Some(600), None).unwrap();
+        let timing = with_segmentation(VadTiming::default(), mode);
+        // Still inside the utterance's ending silence
+        assert_eq!(decide(&blip(400), 16000, 0.0001, 0.0001, &timing), ChunkAction::Wait);
+        assert_eq!(decide(&blip(700), 16000, 0.0001, 0.0001, &timing), ChunkAction::Flush);
+        assert!(SegmentationMode::parse("silence", Some(5000), None).is_err());
+        assert!(SegmentationMode::parse("silence", None, Some(10.0)).is_err());
+        assert_eq!(SegmentationMode::parse("FixedInterval", None, None), Ok(SegmentationMode::FixedInterval));
+    }
+
+    #[test]
+    fn test_silence_gated_ends_over_room_noise() {
+        // -60 dBFS of room noise after the speech: above the -80 dBFS gate, below -45 dBFS
+        let mut samples = vec![0.5; 1600];
+        samples.extend(vec![0.001; 700 * 16]);
+        let timing = VadTiming { hangover_ms: 600, ..VadTiming::default() };
+        assert_eq!(decide(&samples, 16000, 0.0001, 0.0001, &timing), ChunkAction::Wait);
+        assert_eq!(decide(&samples, 16000, 0.0001, from_dbfs(DEFAULT_SILENCE_DBFS), &timing), ChunkAction::Flush);
+    }
+
+    #[test]
+    fn test_dbfs_conversion() {
+        assert_eq!(to_dbfs(1.0), 0.0);
+        assert!((to_dbfs(0.01) - -40.0).abs() < 1e-4);
+        assert_eq!(to_dbfs(0.0), DBFS_FLOOR);
+        assert_eq!(to_dbfs(1e-9), DBFS_FLOOR);
+        assert!((from_dbfs(-80.0) - DEFAULT_THRESHOLD).abs() < 1e-9);
+        assert!(set_threshold(0.0).is_err());
+        assert!(set_chunk_duration(f32::NAN).is_err());
+        assert!(set_threshold(f32::NAN).is_err());
+        assert!(set_threshold(1e-7).is_err());
+        assert!(set_threshold(1.5).is_err());
+        // Anything the dBFS setter takes is also valid as linear RMS, the default included
+        assert!(set_threshold(from_dbfs(DBFS_FLOOR)).is_ok());
+        assert!(set_threshold(DEFAULT_THRESHOLD).is_ok());
+    }
+
+    #[test]
+    fn test_scale_for_rate() {
+        assert_eq!(scale_for_rate(BASELINE_WORDS_PER_SEC), 1.0);
+        assert_eq!(scale_for_rate(3.75), 1.5);
+        // Clamped at both ends
+        assert_eq!(scale_for_rate(0.1), MIN_RATE_SCALE);
+        assert_eq!(scale_for_rate(20.0), MAX_RATE_SCALE);
+    }
+
+    #[test]
+    fn test_set_timing_validation() {
+        assert!(set_timing(5000, 0, 0).is_err());
+        assert!(set_timing(0, 1500, 0).is_err());
+        assert!(set_timing(0, 0, 3000).is_err());
+    }
+}
@@ -1,14 +1,120 @@
 use cpal::traits::{DeviceTrait, HostTrait, StreamTrait};
 use parking_lot::Mutex;
-use std::sync::atomic::{AtomicBool, Ordering};
-use std::sync::Arc;
-use tauri::{AppHandle, Emitter, EventTarget};
+use std::collections::{HashMap, VecDeque};
+use std::sync::atomic::{AtomicBool, AtomicU32, AtomicU64, Ordering};
+use std::path::{Path, PathBuf};
+use std::sync::{mpsc, Arc};
+use std::time::{Duration, Instant};
+use tauri::{AppHandle, Emitter, EventTarget, Manager};
+
+use crate::compressor;
+use crate::conversation;
+use crate::error::{ErrorEvent, KoeError};
+use crate::highpass::HighPass;
+use crate::language;
+use crate::monitor;
+use crate::normalize;
+use crate::pcm::PcmTap;
+use crate::realtime;
+use crate::recording;
+use crate::resampler;
+use crate::subtitles;
+use crate::talon;
+use crate::tts;
+use crate::typing;
+use crate::vad;
+use crate::voice_commands;
 
 // Voice capture state
 static CAPTURING: AtomicBool = AtomicBool::new(false);
+// Audio dropouts detected since capture started
+static DROPOUT_COUNT: AtomicU64 = AtomicU64::new(0);
+// Report `voice:level` in dBFS rather than linear RMS
+static LEVEL_IN_DBFS: AtomicBool = AtomicBool::new(false);
+// API transcription failures since the last success
+static CONSECUTIVE_FAILURES: AtomicU32 = AtomicU32::new(0);
+// Whether a connectivity probe thread is running
+static PROBING: AtomicBool = AtomicBool::new(false);
+// Chunks decided since startup, for matching transcription results to decisions
+static CHUNK_DECISIONS: AtomicU64 = AtomicU64::new(0);
+// Capture natively and convert off the audio thread
+static DEFERRED_CONVERSION: AtomicBool = AtomicBool::new(false);
+// Request word timestamps and emit `voice:transcript_detailed` alongside each transcript
+static WORD_TIMESTAMPS: AtomicBool = AtomicBool::new(false);
+// Seconds before an API transcription request is abandoned
+static REQUEST_TIMEOUT_SECS: AtomicU32 = AtomicU32::new(30);
+// The capture device disappeared and needs reopening
+static DEVICE_LOST: AtomicBool = AtomicBool::new(false);
+// Capture session a reconnect thread is watching, 0 for none
+static RECONNECTING: AtomicU64 = AtomicU64::new(0);
+// Bumped whenever capture is released, so work tied to a capture session can tell it ended
+static CAPTURE_SESSION: AtomicU64 = AtomicU64::new(1);
+// Transcribe the growing buffer for `isFinal: false` previews before each chunk is flushed
+static INTERIM_RESULTS: AtomicBool = AtomicBool::new(false);
+// An interim transcription is running; at most one at a time
+static INTERIM_IN_FLIGHT: AtomicBool = AtomicBool::new(false);
+// Bumped whenever the live buffer is flushed or discarded, so stale interims are dropped
+static UTTERANCE_GENERATION: AtomicU64 = AtomicU64::new(0);
+// Most audio the live buffer may hold before the oldest is dropped, in seconds
+static MAX_BUFFER_SECS: AtomicU32 = AtomicU32::new(DEFAULT_MAX_BUFFER_SECS);
+// CaptureMode::PushToTalk: buffer until stop_capture instead of chunking
+static PUSH_TO_TALK: AtomicBool = AtomicBool::new(false);
+// Skip a live transcript that repeats the previous one within REPEAT_WINDOW
+static SUPPRESS_REPEATS: AtomicBool = AtomicBool::new(false);
+// Write each live segment to a WAV in the log dir before transcribing it
+static SAVE_SEGMENTS: AtomicBool = AtomicBool::new(false);
+// A device picker is showing, so device changes are watched even while idle
+static DEVICE_PICKER_OPEN: AtomicBool = AtomicBool::new(false);
+// The transcription workers have been started
+static WORKERS_STARTED: AtomicBool = AtomicBool::new(false);
+// Sequence number for the next flushed chunk, so the UI can order transcripts and drop stale ones
+static NEXT_SEQ: AtomicU64 = AtomicU64::new(0);
+
+/// How soon an identical transcript counts as a hallucinated repeat (e.g. "you" from silent chunks)
+const REPEAT_WINDOW: Duration = Duration::from_secs(5);
+
+/// Saved segment WAVs kept before the oldest are deleted
+const MAX_SAVED_SEGMENTS: usize = 50;
+
+/// Captured blocks queued for the deferred-conversion worker before the callback starts dropping
+const DEFERRED_QUEUE_CAPACITY: usize = 256;
+
+/// Tries per API transcription request when rate limited or the server errors
+const MAX_REQUEST_ATTEMPTS: u32 = 3;
+
+/// Reopen attempts after the input device disappears before capture stops
+const MAX_RECONNECT_ATTEMPTS: u32 = 5;
+
+/// Default cap on buffered live audio (~3.8MB at 16kHz)
+const DEFAULT_MAX_BUFFER_SECS: u32 = 60;
+
+/// Least a push-to-talk hold keeps, since it's only sent on release (~19MB at 16kHz)
+const PUSH_TO_TALK_MAX_BUFFER_SECS: u32 = 300;
+
+/// Audio per Azure short-audio request, under its 60s limit
+const AZURE_MAX_REQUEST_SECS: usize = 55;
+
+/// Audio per request when transcribing a file with timestamps (~19MB as 16kHz WAV)
+const TIMESTAMP_CHUNK_SECS: usize = 600;
+
+/// Saved transcription settings in the app config dir; API keys go to the OS keychain
+const CONFIG_FILE: &str = "whisper-config.json";
+
+/// Keychain service, with one account per provider key
+#[cfg(not(test))]
+const KEYCHAIN_SERVICE: &str = "koe";
+const KEYCHAIN_ACCOUNTS: [&str; 5] = ["openai", "groq", "assemblyai", "deepgram", "azure"];
+
+/// How long a core lock may stay held before the watchdog reports it
+const STUCK_LOCK_TIMEOUT: Duration = Duration::from_secs(2);
+const WATCHDOG_INTERVAL: Duration = Duration::from_secs(5);
+/// Shorter keys are mid-edit fragments; self-hosted servers accepting any key still need this many
+const MIN_API_KEY_LEN: usize = 8;
+/// How often to re-check whether any input device is connected
+const DEVICE_POLL_INTERVAL: Duration = Duration::from_secs(2);
 
 struct AudioBuffer {
-    samples: Vec<f32>,
+    samples: VecDeque<f32>, // dropping the oldest at the cap doesn't shift the rest
     sample_rate: u32,
 }
 
@@ -16,41 +122,348 @@ struct WhisperConfig {
     api_key: Option<String>,
     use_local: bool,
     model_path: Option<String>,
-    provider: String,       // "openai" or "groq"
+    provider: String,       // "openai", "groq", "assemblyai", "deepgram" or "azure"
     model: String,          // e.g. "whisper-1", "whisper-large-v3-turbo"
     groq_api_key: Option<String>,
+    assemblyai_api_key: Option<String>,
+    deepgram_api_key: Option<String>,
+    azure_api_key: Option<String>,
+    azure_region: Option<String>, // Speech resource region, e.g. "westus"
+    restore_punctuation: bool, // only honored with the "punctuation-restore" feature
+    streaming: bool,           // stream OpenAI transcripts, emitting partials as they arrive
+    confidence: ConfidenceLimits,
+    temperature: Option<f32>, // sampling temperature for OpenAI and Groq; None uses the server's
+    parse_mode: ParseMode,
+    text_path: Option<String>, // dotted path to the transcript, e.g. "result.text"
+    language: String,          // ISO-639-1 code, or "auto" to detect per chunk
+    task: TranscriptionTask,
+    prompt: Option<String>, // vocabulary hint for every provider without its own prompt
+    base_url: Option<String>, // OpenAI-compatible server replacing OPENAI_BASE_URL
+    provider_defaults: HashMap<String, ProviderDefaults>, // keyed by provider name
+}
+
+/// Per-provider preferences, applied whenever that provider handles a chunk
+#[derive(Clone, Debug, Default, PartialEq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ProviderDefaults {
+    pub model: Option<String>,    // OpenAI and Groq only
+    pub language: Option<String>, // overrides the global language
+    pub prompt: Option<String>,   // prepended to the carried-over context
+}
+
+/// Thresholds for dropping likely-hallucinated verbose_json segments; None disables a check
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+struct ConfidenceLimits {
+    min_confidence: Option<f64>,     // mean token probability, exp(avg_logprob)
+    max_no_speech_prob: Option<f64>, // Whisper's own estimate that the segment is silence
+}
+
+impl ConfidenceLimits {
+    fn keeps(&self, confidence: f64, no_speech_prob: f64) -> bool {
+        self.min_confidence.is_none_or(|min| confidence >= min)
+            && self.max_no_speech_prob.is_none_or(|max| no_speech_prob <= max)
+    }
+}
+
+/// How strictly OpenAI-compatible transcription responses are parsed
+#[derive(Clone, Copy, PartialEq, Debug)]
+enum ParseMode {
+    Strict,  // exactly OpenAI's schema
+    Lenient, // fall back to searching for a text-like field
+}
+
+impl ParseMode {
+    fn parse(name: &str) -> Result<Self, String> {
+        match name {
+            "strict" => Ok(ParseMode::Strict),
+            "lenient" => Ok(ParseMode::Lenient),
+            other => Err(format!("Unknown parsing mode '{}' (expected strict or lenient)", other)),
+        }
+    }
+
+    fn name(&self) -> &'static str {
+        match self {
+            ParseMode::Strict => "strict",
+            ParseMode::Lenient => "lenient",
+        }
+    }
+}
+
+/// Whether speech is transcribed as spoken or translated into English
+#[derive(Clone, Copy, PartialEq, Debug, Default)]
+pub enum TranscriptionTask {
+    #[default]
+    Transcribe,
+    Translate, // OpenAI and Groq only; other providers transcribe
+}
+
+impl TranscriptionTask {
+    pub fn parse(name: &str) -> Result<Self, String> {
+        match name.to_ascii_lowercase().as_str() {
+            "transcribe" => Ok(TranscriptionTask::Transcribe),
+            "translate" => Ok(TranscriptionTask::Translate),
+            other => Err(format!("Unknown transcription task '{}' (expected transcribe or translate)", other)),
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            TranscriptionTask::Transcribe => "transcribe",
+            TranscriptionTask::Translate => "translate",
+        }
+    }
+
+    /// OpenAI-compatible endpoint under /audio
+    fn endpoint(&self) -> &'static str {
+        match self {
+            TranscriptionTask::Transcribe => "transcriptions",
+            TranscriptionTask::Translate => "translations",
+        }
+    }
+}
+
+/// When live capture hands audio to transcription
+#[derive(Clone, Copy, PartialEq, Debug, Default)]
+pub enum CaptureMode {
+    #[default]
+    Continuous, // chunked by the VAD as speech pauses
+    PushToTalk, // one segment per capture, sent when it stops
+}
+
+impl CaptureMode {
+    pub fn parse(name: &str) -> Result<Self, String> {
+        match name.to_ascii_lowercase().as_str() {
+            "continuous" => Ok(CaptureMode::Continuous),
+            "pushtotalk" | "push_to_talk" | "push-to-talk" => Ok(CaptureMode::PushToTalk),
+            other => Err(format!("Unknown capture mode '{}' (expected continuous or pushToTalk)", other)),
+        }
+    }
+}
+
+/// How multi-channel input becomes the mono signal that is transcribed
+#[derive(Clone, Copy, PartialEq, Debug, Default)]
+pub enum Downmix {
+    First, // channel 0 only
+    #[default]
+    Average,
+    Channel(u16), // one zero-based channel, e.g. an interface's mic input
+}
+
+impl Downmix {
+    /// "first", "average", or "channel" with `channel` set
+    pub fn parse(mode: &str, channel: Option<u16>) -> Result<Self, String> {
+        match (mode.to_ascii_lowercase().as_str(), channel) {
+            ("first", _) => Ok(Downmix::First),
+            ("average", _) => Ok(Downmix::Average),
+            ("channel", Some(channel)) => Ok(Downmix::Channel(channel)),
+            ("channel", None) => Err("Downmix mode 'channel' needs a channel index".to_string()),
+            (other, _) => Err(format!("Unknown downmix mode '{}' (expected first, average or channel)", other)),
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Downmix::First => "first",
+            Downmix::Average => "average",
+            Downmix::Channel(_) => "channel",
+        }
+    }
+
+    /// The single channel captured, if any
+    pub fn channel(self) -> Option<u16> {
+        match self {
+            Downmix::Channel(channel) => Some(channel),
+            _ => None,
+        }
+    }
+}
+
+/// Field names a lenient parse accepts as the transcript, in priority order
+const TEXT_LIKE_KEYS: &[&str] = &["text", "transcript", "transcription"];
+
+impl Default for WhisperConfig {
+    fn default() -> Self {
+        WhisperConfig {
+            api_key: None,
+            use_local: false,
+            model_path: None,
+            provider: "openai".to_string(),
+            model: "whisper-1".to_string(),
+            groq_api_key: None,
+            assemblyai_api_key: None,
+            deepgram_api_key: None,
+            azure_api_key: None,
+            azure_region: None,
+            restore_punctuation: false,
+            streaming: false,
+            confidence: ConfidenceLimits::default(),
+            temperature: None,
+            parse_mode: ParseMode::Strict,
+            text_path: None,
+            language: "en".to_string(),
+            task: TranscriptionTask::Transcribe,
+            prompt: None,
+            base_url: None,
+            provider_defaults: HashMap::new(),
+        }
+    }
+}
+
+/// Where the next transcription will actually be sent, after fallbacks
+enum Route {
+    #[cfg(feature = "whisper-local")]
+    Local { model_path: String },
+    OpenAi { api_key: String, model: String, base_url: String },
+    Groq { api_key: String, model: String },
+    AssemblyAi { api_key: String },
+    #[cfg(feature = "deepgram")]
+    Deepgram { api_key: String, model: String }, // live capture streams instead
+    Azure { api_key: String, region: String },
+    Unconfigured,
+}
+
+impl Route {
+    /// Provider name, as used for per-provider defaults
+    fn provider(&self) -> &'static str {
+        match self {
+            #[cfg(feature = "whisper-local")]
+            Route::Local { .. } => "local",
+            Route::OpenAi { .. } => "openai",
+            Route::Groq { .. } => "groq",
+            Route::AssemblyAi { .. } => "assemblyai",
+            #[cfg(feature = "deepgram")]
+            Route::Deepgram { .. } => "deepgram",
+            Route::Azure { .. } => "azure",
+            Route::Unconfigured => "none",
+        }
+    }
+
+    /// Model ID, or model path for local
+    fn model(&self) -> Option<&str> {
+        match self {
+            #[cfg(feature = "whisper-local")]
+            Route::Local { model_path } => Some(model_path),
+            Route::OpenAi { model, .. } | Route::Groq { model, .. } => Some(model),
+            #[cfg(feature = "deepgram")]
+            Route::Deepgram { model, .. } => Some(model),
+            Route::AssemblyAi { .. } | Route::Azure { .. } | Route::Unconfigured => None,
+        }
+    }
+}
+
+/// Resolved transcription behavior, as reported to the frontend
+#[derive(Clone, serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct EffectiveConfig {
+    pub provider: String,           // "local", "openai", "groq", "assemblyai", "deepgram", "azure" or "none"
+    pub model: Option<String>,      // model ID, or model path for local
+    pub use_local: bool,
+    pub key_source: Option<String>, // config field supplying the API key
+    pub fell_back: bool,            // configured provider couldn't be used
+}
+
+/// Audio recorded under a name for later transcription
+struct NamedBuffers {
+    buffers: HashMap<String, AudioBuffer>,
+    recording: Option<String>, // buffer currently receiving capture audio
+    owns_capture: bool,        // capture was started for the recording
+}
+
+/// Summary of a named buffer, as reported to the frontend
+#[derive(Clone, serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BufferInfo {
+    pub name: String,
+    pub duration_secs: f32,
+    pub sample_rate: u32,
+    pub recording: bool,
+}
+
+/// How long fetched model lists are reused
+const MODEL_CACHE_TTL: Duration = Duration::from_secs(3600);
+
+/// Longest transcript tail carried into the next chunk's prompt
+const CONTEXT_TAIL_CHARS: usize = 200;
+
+/// Deepgram model for uploaded chunks unless the provider defaults name another
+#[cfg(feature = "deepgram")]
+const DEEPGRAM_DEFAULT_MODEL: &str = "nova-2";
+
+/// OpenAI API root; `set_openai_base_url` points the OpenAI provider at a compatible server instead
+const OPENAI_BASE_URL: &str = "https://api.openai.com/v1";
+
+/// Longest prompt sent (~224 tokens, all Whisper reads; longer prompts can be rejected)
+const MAX_PROMPT_CHARS: usize = 800;
+
+/// Prompt continuity between chunks
+struct TranscriptContext {
+    last_transcript_tail: Option<String>,
+    updated_at: Option<Instant>,
+    reset_after: Option<Duration>, // drop the tail after this much silence
+}
+
+/// Long-session recovery policy for API transcription
+struct RecoveryConfig {
+    max_consecutive_failures: u32,
+    probe_interval: Duration,
 }
 
 struct DeviceConfig {
     selected_device: Option<String>,
+    downmix: Downmix,
+    max_capture_rate: u32,      // ceiling when 16kHz isn't directly supported
 }
 
 lazy_static::lazy_static! {
     static ref AUDIO_BUFFER: Arc<Mutex<AudioBuffer>> = Arc::new(Mutex::new(AudioBuffer {
-        samples: Vec::new(),
+        samples: VecDeque::new(),
         sample_rate: 16000,
     }));
-    static ref WHISPER_CONFIG: Arc<Mutex<WhisperConfig>> = Arc::new(Mutex::new(WhisperConfig {
-        api_key: None,
-        use_local: false,
-        model_path: None,
-        provider: "openai".to_string(),
-        model: "whisper-1".to_string(),
-        groq_api_key: None,
+    static ref WHISPER_CONFIG: Arc<Mutex<WhisperConfig>> = Arc::new(Mutex::new(WhisperConfig::default()));
+    static ref RECOVERY_CONFIG: Arc<Mutex<RecoveryConfig>> = Arc::new(Mutex::new(RecoveryConfig {
+        max_consecutive_failures: 5,
+        probe_interval: Duration::from_secs(5),
     }));
+    static ref NAMED_BUFFERS: Mutex<NamedBuffers> = Mutex::new(NamedBuffers {
+        buffers: HashMap::new(),
+        recording: None,
+        owns_capture: false,
+    });
+    // For events raised outside a capture session's own handle, e.g. request retries
+    static ref EVENT_APP: Mutex<Option<AppHandle>> = Mutex::new(None);
+    // Where save_config writes; None until init finds the app config dir
+    static ref CONFIG_DIR: Mutex<Option<PathBuf>> = Mutex::new(None);
+    static ref CAPTURE_STATUS: Mutex<CaptureStatus> = Mutex::new(CaptureStatus::default());
+    static ref MODEL_CACHE: Mutex<HashMap<String, (Instant, Vec<String>)>> = Mutex::new(HashMap::new());
+    static ref TRANSCRIPT_CONTEXT: Mutex<TranscriptContext> = Mutex::new(TranscriptContext {
+        last_transcript_tail: None,
+        updated_at: None,
+        reset_after: None,
+    });
+    static ref LAST_DECISION: Mutex<Option<ChunkDecision>> = Mutex::new(None);
+    // The last live transcript and when it arrived, for SUPPRESS_REPEATS
+    static ref LAST_TRANSCRIPT: Mutex<Option<(String, Instant)>> = Mutex::new(None);
+    static ref TRANSCRIPT_SINK: Mutex<Option<Arc<dyn TranscriptSink>>> = Mutex::new(None);
     static ref DEVICE_CONFIG: Arc<Mutex<DeviceConfig>> = Arc::new(Mutex::new(DeviceConfig {
         selected_device: None,
+        downmix: Downmix::default(),
+        max_capture_rate: 48000,
     }));
+    // The deferred-conversion worker for the current stream, joined on stop so queued
+    // blocks reach the buffer before it's flushed
+    static ref DEFERRED_WORKER: Mutex<Option<std::thread::JoinHandle<()>>> = Mutex::new(None);
 }
 
-// Stream handle stored separately because cpal::Stream is not Send+Sync
-// We use thread_local for the stream, but control it via atomic flag
-use std::cell::RefCell;
-thread_local! {
-    static STREAM_HANDLE: RefCell<Option<cpal::Stream>> = RefCell::new(None);
-}
+pub fn init(app: AppHandle) -> Result<(), Box<dyn std::error::Error>> {
+    match app.path().app_config_dir() {
+        Ok(dir) => {
+            load_config(&dir);
+            *CONFIG_DIR.lock() = Some(dir);
+        }
+        Err(e) => eprintln!("No app config dir, transcription settings won't be saved: {}", e),
+    }
+    *EVENT_APP.lock() = Some(app);
 
-pub fn init() -> Result<(), Box<dyn std::error::Error>> {
     // Check for available audio input
     let host = cpal::default_host();
     match host.default_input_device() {
@@ -61,504 +474,5342 @@ pub fn init() -> Result<(), Box<dyn std::error::Error>> {
             println!("Warning: No audio input device found");
         }
     }
+    spawn_lock_watchdog();
+    spawn_device_watch();
     println!("Voice system initialized");
     Ok(())
 }
 
-/// Configure transcription settings
-pub fn configure_whisper(
-    api_key: Option<String>,
-    use_local: bool,
-    model_path: Option<String>,
-    provider: Option<String>,
-    model: Option<String>,
-    groq_api_key: Option<String>,
-) {
+/// Periodically check that the core locks can be acquired, logging any that stay held.
+/// parking_lot locks don't poison (a panicking holder releases them on unwind), so what
+/// this catches is a deadlock or a holder stalled inside its critical section.
+fn spawn_lock_watchdog() {
+    std::thread::spawn(|| {
+        let mut stuck = [false; 3];
+        loop {
+            std::thread::sleep(WATCHDOG_INTERVAL);
+            let checks = [
+                ("AUDIO_BUFFER", lock_is_stuck(&AUDIO_BUFFER, STUCK_LOCK_TIMEOUT)),
+                ("WHISPER_CONFIG", lock_is_stuck(&WHISPER_CONFIG, STUCK_LOCK_TIMEOUT)),
+                ("DEVICE_CONFIG", lock_is_stuck(&DEVICE_CONFIG, STUCK_LOCK_TIMEOUT)),
+            ];
+            for ((name, is_stuck), was_stuck) in checks.into_iter().zip(stuck.iter_mut()) {
+                if is_stuck && !*was_stuck {
+                    eprintln!("Watchdog: {} has been held for over {:?}; voice capture may be stalled", name, STUCK_LOCK_TIMEOUT);
+                } else if !is_stuck && *was_stuck {
+                    eprintln!("Watchdog: {} released", name);
+                }
+                *was_stuck = is_stuck;
+            }
+        }
+    });
+}
+
+/// Emit `voice:input_available` whenever an input device appears or the last one goes away.
+/// Devices are only enumerated while capturing or while a device picker is open
+fn spawn_device_watch() {
+    std::thread::spawn(|| {
+        let mut available = has_input_device();
+        loop {
+            std::thread::sleep(DEVICE_POLL_INTERVAL);
+            if !CAPTURING.load(Ordering::SeqCst) && !DEVICE_PICKER_OPEN.load(Ordering::SeqCst) {
+                continue;
+            }
+            let now = has_input_device();
+            if now != available {
+                println!("Audio input {}", if now { "available" } else { "unavailable" });
+                if let Some(app) = EVENT_APP.lock().as_ref() {
+                    app.emit_to(EventTarget::Any, "voice:input_available", now).ok();
+                }
+                available = now;
+            }
+        }
+    });
+}
+
+/// Watch for devices coming and going while a device picker is open, not just while capturing
+pub fn set_device_picker_open(open: bool) {
+    DEVICE_PICKER_OPEN.store(open, Ordering::SeqCst);
+}
+
+fn lock_is_stuck<T>(lock: &Mutex<T>, timeout: Duration) -> bool {
+    lock.try_lock_for(timeout).is_none()
+}
+
+/// A patch to the transcription settings. Each field patches one setting: None (or a field
+/// left out of the JSON) leaves it as is, and an empty string clears it
+#[derive(Debug, Default, serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct WhisperSettings {
+    pub api_key: Option<String>,
+    pub use_local: Option<bool>,
+    pub model_path: Option<String>,
+    pub provider: Option<String>,
+    pub model: Option<String>,
+    pub groq_api_key: Option<String>,
+    pub assemblyai_api_key: Option<String>,
+    pub deepgram_api_key: Option<String>,
+    pub azure_api_key: Option<String>,
+    pub azure_region: Option<String>,
+    pub language: Option<String>,
+    pub prompt: Option<String>,
+}
+
+/// Update transcription settings from a patch
+pub fn configure_whisper(settings: WhisperSettings) -> Result<(), String> {
+    let WhisperSettings {
+        api_key,
+        use_local,
+        model_path,
+        provider,
+        model,
+        groq_api_key,
+        assemblyai_api_key,
+        deepgram_api_key,
+        azure_api_key,
+        azure_region,
+        language,
+        prompt,
+    } = settings;
+    // Validate before touching the config so a bad code or key changes nothing
+    let language = language.map(|l| normalize_language(&l)).transpose()?;
+    let api_key = validate_key("OpenAI", api_key)?;
+    let groq_api_key = validate_key("Groq", groq_api_key)?;
+    let assemblyai_api_key = validate_key("AssemblyAI", assemblyai_api_key)?;
+    let deepgram_api_key = validate_key("Deepgram", deepgram_api_key)?;
+    let azure_api_key = validate_key("Azure", azure_api_key)?;
+    let azure_region = azure_region.map(|r| validate_azure_region(&r)).transpose()?;
+    // Same order as KEYCHAIN_ACCOUNTS
+    let key_patched = [
+        api_key.is_some(),
+        groq_api_key.is_some(),
+        assemblyai_api_key.is_some(),
+        deepgram_api_key.is_some(),
+        azure_api_key.is_some(),
+    ];
     let mut config = WHISPER_CONFIG.lock();
-    config.api_key = api_key;
-    config.use_local = use_local;
-    config.model_path = model_path;
+    // Permissive: a model newer than our list may well work, so only warn
+    if let Some(ref model) = model {
+        let provider = provider.as_deref().unwrap_or(&config.provider);
+        if config.base_url.is_none() && !is_known_model(provider, model) {
+            eprintln!("Model '{}' isn't a known {} transcription model", model, provider);
+            emit_warning(serde_json::json!({ "kind": "unknownModel", "provider": provider, "model": model }));
+        }
+    }
+    patch(&mut config.api_key, api_key);
+    if let Some(use_local) = use_local {
+        config.use_local = use_local;
+    }
+    patch(&mut config.model_path, model_path);
     if let Some(p) = provider {
         config.provider = p;
     }
     if let Some(m) = model {
         config.model = m;
     }
-    config.groq_api_key = groq_api_key;
+    patch(&mut config.groq_api_key, groq_api_key);
+    patch(&mut config.assemblyai_api_key, assemblyai_api_key);
+    patch(&mut config.deepgram_api_key, deepgram_api_key);
+    patch(&mut config.azure_api_key, azure_api_key);
+    patch(&mut config.azure_region, azure_region);
+    if let Some(l) = language {
+        config.language = l;
+        language::reset();
+    }
+    patch(&mut config.prompt, prompt);
+    let changed_keys: Vec<(&str, Option<String>)> = KEYCHAIN_ACCOUNTS
+        .into_iter()
+        .zip(key_patched)
+        .filter(|(_, patched)| *patched)
+        .map(|(account, _)| (account, key_slot(&mut config, account).clone()))
+        .collect();
+    drop(config);
+
+    // The in-memory config still applies if it can't be saved
+    if let Err(e) = store_keys(&changed_keys) {
+        eprintln!("{}; API keys will only be kept until the app quits", e);
+    }
+    persist();
+    Ok(())
 }
 
-/// List available audio input devices
-pub fn list_input_devices() -> Result<Vec<String>, Box<dyn std::error::Error>> {
-    let host = cpal::default_host();
-    let devices: Vec<String> = host
-        .input_devices()?
-        .filter_map(|d| d.name().ok())
-        .collect();
-    Ok(devices)
+/// Set an optional setting from a patch: None leaves it, an empty string clears it
+pub(crate) fn patch(field: &mut Option<String>, value: Option<String>) {
+    if let Some(value) = value {
+        *field = Some(value.trim().to_string()).filter(|v| !v.is_empty());
+    }
 }
 
-/// Get the currently selected device name (or default)
-pub fn get_selected_device() -> Option<String> {
-    let config = DEVICE_CONFIG.lock();
-    config.selected_device.clone()
+/// Lowercase an Azure region name, which becomes part of the endpoint's host; empty passes
+/// through to clear it
+fn validate_azure_region(region: &str) -> Result<String, String> {
+    let region = region.trim().to_ascii_lowercase();
+    if !region.chars().all(|c| c.is_ascii_alphanumeric()) {
+        return Err(format!("'{}' isn't an Azure region name like \"westus\"", region));
+    }
+    Ok(region)
 }
 
-/// Set the audio input device by name
-pub fn set_input_device(device_name: Option<String>) {
-    let mut config = DEVICE_CONFIG.lock();
-    config.selected_device = device_name;
+/// Reject a key that can't be one, like a fragment sent while it's still being typed;
+/// empty passes through to clear the key
+fn validate_key(provider: &str, key: Option<String>) -> Result<Option<String>, String> {
+    let Some(key) = key.map(|k| k.trim().to_string()) else {
+        return Ok(None);
+    };
+    if !key.is_empty() && key.len() < MIN_API_KEY_LEN {
+        return Err(format!("{} API key is too short to be valid", provider));
+    }
+    if key.chars().any(char::is_whitespace) {
+        return Err(format!("{} API key can't contain spaces", provider));
+    }
+    Ok(Some(key))
 }
 
-/// Get a device by name, or the default input device
-fn get_input_device() -> Result<cpal::Device, Box<dyn std::error::Error>> {
-    let host = cpal::default_host();
-    let config = DEVICE_CONFIG.lock();
+/// Non-secret transcription and device settings saved across restarts; missing fields
+/// keep defaults
+#[derive(Debug, Default, PartialEq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct SavedConfig {
+    provider: Option<String>,
+    model: Option<String>,
+    use_local: Option<bool>,
+    model_path: Option<String>,
+    language: Option<String>,
+    task: Option<String>,
+    prompt: Option<String>,
+    base_url: Option<String>,
+    azure_region: Option<String>,
+    selected_device: Option<String>, // None is the system default
+    talon_path: Option<String>,      // None auto-detects the REPL
+    output_device: Option<String>,   // None is the system default
+    parse_mode: Option<String>,
+    text_path: Option<String>,
+    provider_defaults: Option<HashMap<String, ProviderDefaults>>,
+    downmix: Option<String>,
+    input_channel: Option<u16>, // the channel a "channel" downmix captures
+    max_capture_rate: Option<u32>,
+    restore_punctuation: Option<bool>,
+    temperature: Option<f32>, // None leaves it to the server
+    streaming: Option<bool>,
+    min_confidence: Option<f64>,     // None disables the check
+    max_no_speech_prob: Option<f64>, // None disables the check
+}
 
-    if let Some(ref name) = config.selected_device {
-        // Try to find the device by name
-        for device in host.input_devices()? {
-            if device.name().ok().as_ref() == Some(name) {
-                return Ok(device);
-            }
+impl SavedConfig {
+    fn from_config(config: &WhisperConfig, device: &DeviceConfig) -> Self {
+        SavedConfig {
+            provider: Some(config.provider.clone()),
+            model: Some(config.model.clone()),
+            use_local: Some(config.use_local),
+            model_path: config.model_path.clone(),
+            language: Some(config.language.clone()),
+            task: Some(config.task.name().to_string()),
+            prompt: config.prompt.clone(),
+            base_url: config.base_url.clone(),
+            azure_region: config.azure_region.clone(),
+            selected_device: device.selected_device.clone(),
+            talon_path: talon::repl_override().map(|path| path.display().to_string()),
+            output_device: tts::get_output_device(),
+            parse_mode: Some(config.parse_mode.name().to_string()),
+            text_path: config.text_path.clone(),
+            provider_defaults: Some(config.provider_defaults.clone()),
+            downmix: Some(device.downmix.name().to_string()),
+            input_channel: device.downmix.channel(),
+            max_capture_rate: Some(device.max_capture_rate),
+            restore_punctuation: Some(config.restore_punctuation),
+            temperature: config.temperature,
+            streaming: Some(config.streaming),
+            min_confidence: config.confidence.min_confidence,
+            max_no_speech_prob: config.confidence.max_no_speech_prob,
         }
-        // Fall through to default if not found
-        eprintln!("Selected device '{}' not found, using default", name);
     }
 
-    host.default_input_device()
-        .ok_or_else(|| "No input device available".into())
+    /// Apply over `config`, skipping values that no longer validate. A device that has
+    /// since been unplugged is still selected; capture falls back and warns.
+    fn apply(self, config: &mut WhisperConfig, device: &mut DeviceConfig) {
+        if let Some(provider) = self.provider {
+            config.provider = provider;
+        }
+        if let Some(model) = self.model {
+            config.model = model;
+        }
+        if let Some(use_local) = self.use_local {
+            config.use_local = use_local;
+        }
+        config.model_path = self.model_path.or(config.model_path.take());
+        if let Some(language) = self.language.and_then(|l| normalize_language(&l).ok()) {
+            config.language = language;
+        }
+        if let Some(task) = self.task.and_then(|t| TranscriptionTask::parse(&t).ok()) {
+            config.task = task;
+        }
+        config.prompt = self.prompt.or(config.prompt.take());
+        if let Some(base_url) = self.base_url.and_then(|u| normalize_base_url(&u).ok()) {
+            config.base_url = Some(base_url);
+        }
+        if let Some(region) = self.azure_region.and_then(|r| validate_azure_region(&r).ok()) {
+            config.azure_region = Some(region).filter(|r| !r.is_empty());
+        }
+        device.selected_device = self.selected_device.or(device.selected_device.take());
+        // A Talon path that has since gone away just falls back to detection
+        if let Some(path) = self.talon_path {
+            talon::restore_repl_override(PathBuf::from(path));
+        }
+        if self.output_device.is_some() {
+            tts::set_output_device(self.output_device);
+        }
+        if let Some(mode) = self.parse_mode.and_then(|m| ParseMode::parse(&m).ok()) {
+            config.parse_mode = mode;
+        }
+        config.text_path = self.text_path.or(config.text_path.take());
+        if let Some(defaults) = self.provider_defaults {
+            config.provider_defaults = defaults;
+        }
+        if let Some(downmix) = self.downmix.and_then(|m| Downmix::parse(&m, self.input_channel).ok()) {
+            device.downmix = downmix;
+        }
+        if let Some(rate) = self.max_capture_rate.filter(|&hz| hz >= 16000) {
+            device.max_capture_rate = rate;
+        }
+        if let Some(restore_punctuation) = self.restore_punctuation {
+            config.restore_punctuation = restore_punctuation;
+        }
+        config.temperature = self.temperature.map(clamp_temperature).or(config.temperature);
+        if let Some(streaming) = self.streaming {
+            config.streaming = streaming;
+        }
+        let in_range = |value: &f64| (0.0..=1.0).contains(value);
+        if let Some(min_confidence) = self.min_confidence.filter(in_range) {
+            config.confidence.min_confidence = Some(min_confidence);
+        }
+        if let Some(max_no_speech_prob) = self.max_no_speech_prob.filter(in_range) {
+            config.confidence.max_no_speech_prob = Some(max_no_speech_prob);
+        }
+    }
 }
 
-pub fn start_capture(app: AppHandle) -> Result<(), Box<dyn std::error::Error>> {
-    if CAPTURING.load(Ordering::SeqCst) {
-        return Ok(());
+/// Load saved settings from `dir` and keys from the keychain over the current config;
+/// missing files and entries change nothing
+fn load_config(dir: &Path) {
+    let mut config = WHISPER_CONFIG.lock();
+    let path = dir.join(CONFIG_FILE);
+    if let Some(text) = read_saved(&path) {
+        match serde_json::from_str::<SavedConfig>(&text) {
+            Ok(saved) => saved.apply(&mut config, &mut DEVICE_CONFIG.lock()),
+            Err(e) => eprintln!("Ignoring unreadable {}: {}", path.display(), e),
+        }
     }
+    load_keys(&mut config);
+}
 
-    let device = get_input_device()?;
-    let device_name = device.name().unwrap_or_else(|_| "Unknown".to_string());
-    println!("Using audio device: {}", device_name);
+/// The config field holding the key for a keychain account
+fn key_slot<'a>(config: &'a mut WhisperConfig, account: &str) -> &'a mut Option<String> {
+    match account {
+        "groq" => &mut config.groq_api_key,
+        "assemblyai" => &mut config.assemblyai_api_key,
+        "deepgram" => &mut config.deepgram_api_key,
+        "azure" => &mut config.azure_api_key,
+        _ => &mut config.api_key,
+    }
+}
 
-    // Get supported config - prefer mono at any sample rate
-    let supported_config = device
-        .supported_input_configs()?
-        .filter(|c| c.channels() == 1)
-        .max_by_key(|c| c.max_sample_rate().0)
-        .or_else(|| {
-            // Fall back to any config if no mono available
-            device.supported_input_configs().ok()?.next()
-        })
-        .ok_or("No supported audio configuration found")?;
+#[cfg(not(test))]
+fn keychain_entry(account: &str) -> Result<keyring::Entry, String> {
+    keyring::Entry::new(KEYCHAIN_SERVICE, account).map_err(|e| format!("Keychain unavailable: {}", e))
+}
 
-    // Use a reasonable sample rate within the supported range
-    let min_rate = supported_config.min_sample_rate().0;
-    let max_rate = supported_config.max_sample_rate().0;
+/// Read one account's key from the keychain; Ok(None) when nothing is saved
+#[cfg(not(test))]
+fn keychain_get(account: &str) -> Result<Option<String>, String> {
+    match keychain_entry(account)?.get_password() {
+        Ok(key) => Ok(Some(key)),
+        Err(keyring::Error::NoEntry) => Ok(None),
+        Err(e) => Err(format!("Couldn't read API keys from the keychain: {}", e)),
+    }
+}
 
-    // Prefer 16kHz (Whisper native), then 44.1kHz, then 48kHz, then max available
-    let target_rate = if min_rate <= 16000 && 16000 <= max_rate {
-        16000
-    } else if min_rate <= 44100 && 44100 <= max_rate {
-        44100
-    } else if min_rate <= 48000 && 48000 <= max_rate {
-        48000
-    } else {
-        max_rate
+/// Write one account's key to the keychain, removing it when cleared
+#[cfg(not(test))]
+fn keychain_set(account: &str, key: Option<&str>) -> Result<(), String> {
+    let entry = keychain_entry(account)?;
+    let result = match key {
+        Some(key) => entry.set_password(key),
+        None => match entry.delete_password() {
+            Err(keyring::Error::NoEntry) => Ok(()),
+            other => other,
+        },
     };
+    result.map_err(|e| format!("Keychain error for {}: {}", account, e))
+}
 
-    let config: cpal::StreamConfig = supported_config
-        .with_sample_rate(cpal::SampleRate(target_rate))
-        .into();
-
-    let actual_channels = config.channels;
-    let actual_sample_rate = config.sample_rate.0;
-    println!("Audio config: {}Hz, {} channel(s)", actual_sample_rate, actual_channels);
+// Stands in for the OS keychain under test, so tests never read or overwrite saved keys;
+// None acts like a machine without a keychain
+#[cfg(test)]
+static TEST_KEYCHAIN: Mutex<Option<HashMap<String, String>>> = Mutex::new(None);
 
-    // Update buffer sample rate
-    {
-        let mut buffer = AUDIO_BUFFER.lock();
-        buffer.sample_rate = actual_sample_rate;
-        buffer.samples.clear();
+#[cfg(test)]
+fn keychain_get(account: &str) -> Result<Option<String>, String> {
+    match &*TEST_KEYCHAIN.lock() {
+        Some(keys) => Ok(keys.get(account).cloned()),
+        None => Err("Keychain unavailable: no test keychain".to_string()),
     }
+}
 
-    let app_handle = app.clone();
-    let err_app = app.clone();
-    let channels = actual_channels;
+#[cfg(test)]
+fn keychain_set(account: &str, key: Option<&str>) -> Result<(), String> {
+    let mut keychain = TEST_KEYCHAIN.lock();
+    let keys = keychain.as_mut().ok_or("Keychain unavailable: no test keychain")?;
+    match key {
+        Some(key) => keys.insert(account.to_string(), key.to_string()),
+        None => keys.remove(account),
+    };
+    Ok(())
+}
 
-    // Build input stream
-    let stream = device.build_input_stream(
-        &config,
-        move |data: &[f32], _: &cpal::InputCallbackInfo| {
-            if !CAPTURING.load(Ordering::SeqCst) {
+/// Fill in keys from the keychain; without one, keys only live in memory
+fn load_keys(config: &mut WhisperConfig) {
+    for account in KEYCHAIN_ACCOUNTS {
+        match keychain_get(account) {
+            Ok(Some(key)) => *key_slot(config, account) = Some(key),
+            Ok(None) => {}
+            Err(e) => {
+                eprintln!("{}; API keys won't be remembered", e);
                 return;
             }
+        }
+    }
+}
 
-            let mut buffer = AUDIO_BUFFER.lock();
+/// Store changed keys, trying every entry even if one fails. Keychain calls can block on
+/// a prompt, so callers must not hold WHISPER_CONFIG.
+fn store_keys(changed: &[(&str, Option<String>)]) -> Result<(), String> {
+    let errors: Vec<String> = changed
+        .iter()
+        .filter_map(|(account, key)| keychain_set(account, key.as_deref()).err())
+        .collect();
+    if errors.is_empty() {
+        Ok(())
+    } else {
+        Err(errors.join("; "))
+    }
+}
 
-            // Convert to mono if stereo
-            if channels == 2 {
-                for chunk in data.chunks(2) {
-                    if chunk.len() == 2 {
-                        buffer.samples.push((chunk[0] + chunk[1]) / 2.0);
-                    }
-                }
-            } else if channels == 1 {
-                buffer.samples.extend_from_slice(data);
-            } else {
-                // Multi-channel: take first channel only
-                for chunk in data.chunks(channels as usize) {
-                    if !chunk.is_empty() {
-                        buffer.samples.push(chunk[0]);
-                    }
-                }
-            }
+/// Forget one provider's API key ("openai", "groq", "assemblyai", "deepgram" or "azure"). The
+/// in-memory key is always cleared; a keychain that can't be updated is only logged.
+pub fn clear_api_key(provider: &str) -> Result<(), String> {
+    let account = KEYCHAIN_ACCOUNTS
+        .into_iter()
+        .find(|account| *account == provider.trim().to_ascii_lowercase())
+        .ok_or_else(|| format!("Unknown provider '{}'", provider))?;
+    *key_slot(&mut WHISPER_CONFIG.lock(), account) = None;
+    if let Err(e) = keychain_set(account, None) {
+        eprintln!("{}; the key is only cleared until the app quits", e);
+    }
+    Ok(())
+}
 
-            // Simple VAD: check if we have enough audio and energy
-            // Process every ~1 second of audio (reduced from 2s for lower latency)
-            let samples_per_chunk = buffer.sample_rate as usize * 1;
-            if buffer.samples.len() >= samples_per_chunk {
-                // Calculate RMS energy
-                let rms: f32 = (buffer.samples.iter().map(|s| s * s).sum::<f32>()
-                    / buffer.samples.len() as f32)
-                    .sqrt();
-
-                // Send audio for transcription if there's any meaningful signal
-                // Threshold very low (0.0001) - let Whisper filter silence
-                if rms > 0.0001 {
-                    // Clone samples for processing
-                    let audio_data = buffer.samples.clone();
-                    let sample_rate = buffer.sample_rate;
-                    let app = app_handle.clone();
-
-                    // Process using Tauri's async runtime (required for events to reach frontend)
-                    tauri::async_runtime::spawn_blocking(move || {
-                        match transcribe_audio(&audio_data, sample_rate) {
-                            Ok(Some(transcript)) if !transcript.trim().is_empty() => {
-                                println!("Transcript: {}", transcript);
-                                if let Err(e) = app.emit_to(
-                                    EventTarget::Any,
-                                    "voice:transcript",
-                                    serde_json::json!({
-                                        "text": transcript,
-                                        "isFinal": true
-                                    }),
-                                ) {
-                                    eprintln!("Failed to emit transcript: {}", e);
-                                }
-                            }
-                            Ok(Some(_)) | Ok(None) => {
-                                // Empty or no transcript - ignore
-                            }
-                            Err(e) => {
-                                eprintln!("Transcription error: {}", e);
-                                let _ = app.emit_to(EventTarget::Any, "voice:error", e.to_string());
-                            }
-                        }
-                    });
-                }
+/// Providers with an API key configured, so the frontend needn't hold the keys itself
+pub fn configured_api_keys() -> Vec<String> {
+    let mut config = WHISPER_CONFIG.lock();
+    KEYCHAIN_ACCOUNTS
+        .into_iter()
+        .filter(|account| key_slot(&mut config, account).is_some())
+        .map(str::to_string)
+        .collect()
+}
+
+/// Remove every saved API key from the keychain. Keys already configured stay in
+/// memory until the app restarts or they're reconfigured.
+pub fn clear_stored_keys() -> Result<(), String> {
+    for account in KEYCHAIN_ACCOUNTS {
+        keychain_set(account, None)?;
+    }
+    Ok(())
+}
+
+/// Save the current settings to the app config dir, if init found one
+fn save_config() -> Result<(), String> {
+    let Some(dir) = CONFIG_DIR.lock().clone() else {
+        return Ok(());
+    };
+    let saved = SavedConfig::from_config(&WHISPER_CONFIG.lock(), &DEVICE_CONFIG.lock());
+    std::fs::create_dir_all(&dir).map_err(|e| format!("Failed to create {}: {}", dir.display(), e))?;
+    write_json(&dir.join(CONFIG_FILE), &saved)
+}
+
+/// Save the settings a setter just changed; the change still applies if it can't be saved
+fn persist() {
+    save_config().unwrap_or_else(|e| eprintln!("Failed to save transcription settings: {}", e));
+}
+
+/// A saved settings file's contents; None if it was never written
+fn read_saved(path: &Path) -> Option<String> {
+    match std::fs::read_to_string(path) {
+        Ok(text) => Some(text),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => None,
+        Err(e) => {
+            eprintln!("Failed to read {}: {}", path.display(), e);
+            None
+        }
+    }
+}
+
+fn write_json<T: serde::Serialize>(path: &Path, value: &T) -> Result<(), String> {
+    let json = serde_json::to_string_pretty(value).map_err(|e| e.to_string())?;
+    std::fs::write(path, json).map_err(|e| format!("Failed to write {}: {}", path.display(), e))
+}
+
+/// Set response parsing: "strict" or "lenient", with an optional dotted path to the text
+pub fn set_response_parsing(mode: &str, text_path: Option<String>) -> Result<(), String> {
+    let parse_mode = ParseMode::parse(mode)?;
+    let mut config = WHISPER_CONFIG.lock();
+    config.parse_mode = parse_mode;
+    config.text_path = text_path.filter(|p| !p.trim().is_empty());
+    drop(config);
+    persist();
+    Ok(())
+}
+
+/// Set the transcription language: an ISO-639-1 code, or "auto" to detect (gated) per chunk
+pub fn set_language(code: &str) -> Result<(), String> {
+    WHISPER_CONFIG.lock().language = normalize_language(code)?;
+    language::reset();
+    persist();
+    Ok(())
+}
+
+/// Transcribe locally with a ggml model file, e.g. one `download_whisper_model` fetched
+#[cfg(feature = "whisper-local")]
+pub fn use_local_model(path: String) {
+    let mut config = WHISPER_CONFIG.lock();
+    config.model_path = Some(path);
+    config.use_local = true;
+    drop(config);
+    persist();
+}
+
+/// Use the Talon REPL at `path` (or a Talon home containing it) instead of the detected one;
+/// None goes back to detection
+pub fn set_talon_path(path: Option<String>) -> Result<(), KoeError> {
+    talon::set_repl_override(path.map(PathBuf::from))?;
+    persist();
+    Ok(())
+}
+
+/// ISO code for a language name or code, passing "auto" through
+fn normalize_language(code: &str) -> Result<String, String> {
+    match code.trim().to_ascii_lowercase().as_str() {
+        "auto" => Ok("auto".to_string()),
+        other => language::language_code(other)
+            .map(str::to_string)
+            .ok_or_else(|| format!("Unsupported language '{}'", other)),
+    }
+}
+
+/// Transcribe speech as spoken, or translate it into English (OpenAI and Groq)
+pub fn set_transcription_task(task: TranscriptionTask) {
+    WHISPER_CONFIG.lock().task = task;
+    persist();
+}
+
+/// Store the model, language and prompt to use whenever `provider` is active
+pub fn configure_provider_defaults(
+    provider: &str,
+    model: Option<String>,
+    language: Option<String>,
+    prompt: Option<String>,
+) -> Result<(), String> {
+    if !["openai", "groq", "assemblyai", "deepgram", "azure", "local"].contains(&provider) {
+        return Err(format!("Unknown provider '{}'", provider));
+    }
+    let defaults = ProviderDefaults {
+        model: model.filter(|m| !m.trim().is_empty()),
+        language: language.map(|l| normalize_language(&l)).transpose()?,
+        prompt: prompt.filter(|p| !p.trim().is_empty()),
+    };
+    WHISPER_CONFIG.lock().provider_defaults.insert(provider.to_string(), defaults);
+    language::reset();
+    persist();
+    Ok(())
+}
+
+/// Stored defaults for a provider, if any
+pub fn get_provider_defaults(provider: &str) -> Option<ProviderDefaults> {
+    WHISPER_CONFIG.lock().provider_defaults.get(provider).cloned()
+}
+
+/// Join a fixed prompt with the carried-over context, keeping the last MAX_PROMPT_CHARS
+/// (Whisper itself only reads the end of a long prompt)
+fn combine_prompts(fixed: Option<&str>, context: Option<&str>) -> Option<String> {
+    let prompt = match (fixed, context) {
+        (Some(fixed), Some(context)) => format!("{} {}", fixed, context),
+        (Some(text), None) | (None, Some(text)) => text.to_string(),
+        (None, None) => return None,
+    };
+    Some(transcript_tail(&prompt, MAX_PROMPT_CHARS))
+}
+
+/// Configure how many consecutive failures are tolerated and how often connectivity is probed
+pub fn configure_recovery(max_consecutive_failures: u32, probe_interval_ms: u64) {
+    let mut config = RECOVERY_CONFIG.lock();
+    config.max_consecutive_failures = max_consecutive_failures.max(1);
+    config.probe_interval = Duration::from_millis(probe_interval_ms.max(100));
+}
+
+/// Forget the carried-over transcript so the next chunk starts without prompt bias
+pub fn reset_context() {
+    let mut context = TRANSCRIPT_CONTEXT.lock();
+    context.last_transcript_tail = None;
+    context.updated_at = None;
+}
+
+/// Automatically reset context after this many milliseconds without a transcript (None disables)
+pub fn set_context_reset_gap(gap_ms: Option<u64>) {
+    TRANSCRIPT_CONTEXT.lock().reset_after = gap_ms.map(Duration::from_millis);
+}
+
+/// Prompt for the next chunk, dropping it if the silence gap has elapsed
+fn context_prompt() -> Option<String> {
+    let mut context = TRANSCRIPT_CONTEXT.lock();
+    if let (Some(gap), Some(updated_at)) = (context.reset_after, context.updated_at) {
+        if updated_at.elapsed() > gap {
+            context.last_transcript_tail = None;
+            context.updated_at = None;
+        }
+    }
+    context.last_transcript_tail.clone()
+}
+
+fn remember_transcript(text: &str) {
+    let mut context = TRANSCRIPT_CONTEXT.lock();
+    context.last_transcript_tail = Some(transcript_tail(text, CONTEXT_TAIL_CHARS));
+    context.updated_at = Some(Instant::now());
+}
+
+/// Last whole words of `text` fitting in `max_chars`
+fn transcript_tail(text: &str, max_chars: usize) -> String {
+    let text = text.trim();
+    if text.len() <= max_chars {
+        return text.to_string();
+    }
+    let mut start = text.len() - max_chars;
+    while !text.is_char_boundary(start) {
+        start += 1;
+    }
+    let tail = &text[start..];
+    // Skip a partial leading word
+    match tail.find(char::is_whitespace) {
+        Some(i) if !text[..start].ends_with(char::is_whitespace) => tail[i..].trim_start().to_string(),
+        _ => tail.to_string(),
+    }
+}
+
+/// Make `name` the active provider, refusing if its API key (or local model) isn't configured
+pub fn switch_provider(name: &str) -> Result<(), String> {
+    apply_provider_switch(&mut WHISPER_CONFIG.lock(), name)?;
+    println!("Switched transcription provider to {}", name);
+    persist();
+    Ok(())
+}
+
+fn apply_provider_switch(config: &mut WhisperConfig, name: &str) -> Result<(), String> {
+    let missing = match name {
+        "openai" => openai_key(config).is_none().then_some("OpenAI API key (api_key)"),
+        "groq" => config.groq_api_key.is_none().then_some("Groq API key (groq_api_key)"),
+        "assemblyai" => config.assemblyai_api_key.is_none().then_some("AssemblyAI API key (assemblyai_api_key)"),
+        "deepgram" => {
+            if !cfg!(feature = "deepgram") {
+                return Err("Deepgram isn't available in this build".to_string());
+            }
+            config.deepgram_api_key.is_none().then_some("Deepgram API key (deepgram_api_key)")
+        }
+        "azure" => match (&config.azure_api_key, &config.azure_region) {
+            (None, _) => Some("Azure API key (azure_api_key)"),
+            (Some(_), None) => Some("Azure region (azure_region)"),
+            _ => None,
+        },
+        "local" => {
+            if !cfg!(feature = "whisper-local") {
+                return Err("Local Whisper isn't available in this build".to_string());
+            }
+            config.model_path.is_none().then_some("local Whisper model path (model_path)")
+        }
+        other => return Err(format!("Unknown provider '{}'", other)),
+    };
+    if let Some(missing) = missing {
+        return Err(format!("Can't switch to {}: no {} is configured", name, missing));
+    }
+
+    if name == "local" {
+        config.use_local = true;
+    } else {
+        // Local takes precedence in routing, so it has to be turned off to switch away
+        config.use_local = false;
+        config.provider = name.to_string();
+    }
+    Ok(())
+}
+
+/// Report what the next transcription will actually use after fallbacks
+pub fn get_effective_config() -> EffectiveConfig {
+    let config = WHISPER_CONFIG.lock();
+
+    let (provider, model, key_source) = match resolve_route(&config) {
+        #[cfg(feature = "whisper-local")]
+        Route::Local { model_path } => ("local", Some(model_path), None),
+        Route::OpenAi { model, .. } => ("openai", Some(model), Some("api_key")),
+        Route::Groq { model, .. } => (
+            "groq",
+            Some(groq_model_name(&model).to_string()),
+            Some("groq_api_key"),
+        ),
+        Route::AssemblyAi { .. } => ("assemblyai", None, Some("assemblyai_api_key")),
+        #[cfg(feature = "deepgram")]
+        Route::Deepgram { model, .. } => ("deepgram", Some(model), Some("deepgram_api_key")),
+        Route::Azure { .. } => ("azure", None, Some("azure_api_key")),
+        Route::Unconfigured => ("none", None, None),
+    };
+
+    let requested = if config.use_local { "local" } else { config.provider.as_str() };
+
+    EffectiveConfig {
+        provider: provider.to_string(),
+        model,
+        use_local: provider == "local",
+        key_source: key_source.map(str::to_string),
+        fell_back: requested != provider,
+    }
+}
+
+/// Enable or disable local punctuation restoration of transcripts
+pub fn set_punctuation_restoration(enabled: bool) {
+    WHISPER_CONFIG.lock().restore_punctuation = enabled;
+    persist();
+}
+
+/// Drop transcript segments scoring below `min_confidence` or above `max_no_speech_prob` (both 0–1),
+/// which catches phrases Whisper invents from noise. Setting either asks OpenAI and Groq for
+/// verbose_json, which whisper-1 and Groq's models support; None disables a check
+pub fn set_confidence_filter(min_confidence: Option<f64>, max_no_speech_prob: Option<f64>) -> Result<(), String> {
+    for value in [min_confidence, max_no_speech_prob].into_iter().flatten() {
+        if !(0.0..=1.0).contains(&value) {
+            return Err(format!("Confidence thresholds must be between 0 and 1, got {}", value));
+        }
+    }
+    WHISPER_CONFIG.lock().confidence = ConfidenceLimits { min_confidence, max_no_speech_prob };
+    persist();
+    Ok(())
+}
+
+/// Sampling temperature sent to OpenAI and Groq; lower is more deterministic. Values outside
+/// 0–1 are clamped, and None leaves it to the server
+pub fn set_temperature(temperature: Option<f32>) {
+    WHISPER_CONFIG.lock().temperature = temperature.map(clamp_temperature);
+    persist();
+}
+
+fn clamp_temperature(temperature: f32) -> f32 {
+    let clamped = if temperature.is_nan() { 0.0 } else { temperature.clamp(0.0, 1.0) };
+    if clamped != temperature {
+        eprintln!("Temperature {} is outside 0-1, using {}", temperature, clamped);
+    }
+    clamped
+}
+
+/// Stream live OpenAI transcriptions, emitting the text as it arrives before the final transcript
+pub fn set_streaming_transcription(enabled: bool) {
+    WHISPER_CONFIG.lock().streaming = enabled;
+    persist();
+}
+
+/// List available audio input devices
+pub fn list_input_devices() -> Result<Vec<String>, KoeError> {
+    let host = cpal::default_host();
+    let devices: Vec<String> = host
+        .input_devices()?
+        .filter_map(|d| d.name().ok())
+        .collect();
+    Ok(devices)
+}
+
+/// What an input device can capture, gathered from its supported configs
+#[derive(Clone, Debug, PartialEq, serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DeviceCapabilities {
+    pub name: String,
+    pub sample_rates: Vec<SampleRateRange>,
+    pub channels: Vec<u16>,
+    pub sample_formats: Vec<String>, // e.g. "f32", "i16"
+    pub supports_16k_mono: bool,     // captures Whisper's 16kHz mono without resampling
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, serde::Serialize)]
+pub struct SampleRateRange {
+    pub min: u32,
+    pub max: u32,
+}
+
+/// Sample rates, channel counts and formats the named input device supports
+pub fn get_device_capabilities(name: &str) -> Result<DeviceCapabilities, KoeError> {
+    let device = cpal::default_host()
+        .input_devices()?
+        .find(|d| d.name().ok().as_deref() == Some(name))
+        .ok_or_else(|| KoeError::AudioDevice(format!("Input device '{}' not found", name)))?;
+    let configs = device
+        .supported_input_configs()?
+        .map(|c| (c.channels(), c.min_sample_rate().0, c.max_sample_rate().0, c.sample_format().to_string()));
+    Ok(summarize_capabilities(name, configs))
+}
+
+/// Collapse (channels, min rate, max rate, format) configs into sorted, de-duplicated lists
+fn summarize_capabilities(name: &str, configs: impl Iterator<Item = (u16, u32, u32, String)>) -> DeviceCapabilities {
+    let mut capabilities = DeviceCapabilities {
+        name: name.to_string(),
+        sample_rates: Vec::new(),
+        channels: Vec::new(),
+        sample_formats: Vec::new(),
+        supports_16k_mono: false,
+    };
+    for (channels, min, max, format) in configs {
+        capabilities.sample_rates.push(SampleRateRange { min, max });
+        capabilities.channels.push(channels);
+        capabilities.sample_formats.push(format);
+        capabilities.supports_16k_mono |= channels == 1 && (min..=max).contains(&16000);
+    }
+    capabilities.sample_rates.sort();
+    capabilities.sample_rates.dedup();
+    capabilities.channels.sort();
+    capabilities.channels.dedup();
+    capabilities.sample_formats.sort();
+    capabilities.sample_formats.dedup();
+    capabilities
+}
+
+/// Get the currently selected device name (or default)
+pub fn get_selected_device() -> Option<String> {
+    let config = DEVICE_CONFIG.lock();
+    config.selected_device.clone()
+}
+
+/// Set the audio input device by name
+pub fn set_input_device(device_name: Option<String>) {
+    DEVICE_CONFIG.lock().selected_device = device_name;
+    persist();
+}
+
+/// Set the speech output device by name; None is the system default
+pub fn set_output_device(device_name: Option<String>) {
+    tts::set_output_device(device_name);
+    persist();
+}
+
+/// Capture a single input channel (zero-based); None drops a channel selection, going back
+/// to averaging all channels, and leaves a first-channel or average downmix as it is
+pub fn set_input_channel(channel: Option<u16>) {
+    let mut config = DEVICE_CONFIG.lock();
+    config.downmix = with_input_channel(config.downmix, channel);
+    drop(config);
+    persist();
+}
+
+fn with_input_channel(downmix: Downmix, channel: Option<u16>) -> Downmix {
+    match (channel, downmix) {
+        (Some(channel), _) => Downmix::Channel(channel),
+        (None, Downmix::Channel(_)) => Downmix::default(),
+        (None, downmix) => downmix,
+    }
+}
+
+/// Get the selected input channel, if any
+pub fn get_input_channel() -> Option<u16> {
+    DEVICE_CONFIG.lock().downmix.channel()
+}
+
+/// Choose how multi-channel input is reduced to mono; applies from the next capture
+pub fn set_downmix(downmix: Downmix) {
+    DEVICE_CONFIG.lock().downmix = downmix;
+    persist();
+}
+
+/// Cap the capture rate chosen when 16kHz isn't supported (at least 16kHz)
+pub fn set_capture_rate_ceiling(hz: u32) -> Result<(), String> {
+    if hz < 16000 {
+        return Err(format!("Capture rate ceiling must be at least 16000Hz, got {}", hz));
+    }
+    DEVICE_CONFIG.lock().max_capture_rate = hz;
+    persist();
+    Ok(())
+}
+
+/// Pick a capture rate within the device's supported range
+fn choose_capture_rate(min_rate: u32, max_rate: u32, ceiling: u32) -> u32 {
+    // Prefer 16kHz (Whisper native), then 44.1kHz, then 48kHz
+    for rate in [16000, 44100, 48000] {
+        if min_rate <= rate && rate <= max_rate && rate <= ceiling {
+            return rate;
+        }
+    }
+    if max_rate < 16000 {
+        // Nothing adequate: take the best the device has
+        max_rate
+    } else {
+        // Lowest adequate rate, so 96/192kHz interfaces don't inflate resampling work
+        min_rate.max(16000).min(max_rate)
+    }
+}
+
+/// Whether capture has a device to open: the selected one, or failing that the default
+pub fn has_input_device() -> bool {
+    let host = cpal::default_host();
+    if host.default_input_device().is_some() {
+        return true;
+    }
+    let selected_device = DEVICE_CONFIG.lock().selected_device.clone();
+    selected_device.is_some_and(|name| {
+        host.input_devices()
+            .map(|mut devices| devices.any(|device| device.name().ok().as_ref() == Some(&name)))
+            .unwrap_or(false)
+    })
+}
+
+/// Get a device by name, or the default input device
+pub(crate) fn get_input_device() -> Result<cpal::Device, KoeError> {
+    let host = cpal::default_host();
+    // Copy the name out so the lock isn't held while enumerating devices
+    let selected_device = DEVICE_CONFIG.lock().selected_device.clone();
+
+    if let Some(ref name) = selected_device {
+        // Try to find the device by name
+        for device in host.input_devices()? {
+            if device.name().ok().as_ref() == Some(name) {
+                return Ok(device);
+            }
+        }
+        // Fall through to default if not found
+        eprintln!("Selected device '{}' not found, using default", name);
+        emit_warning(serde_json::json!({ "kind": "deviceMissing", "device": name }));
+    }
+
+    host.default_input_device().ok_or(KoeError::NoInputDevice)
+}
+
+pub fn start_capture(app: AppHandle) -> Result<(), KoeError> {
+    // Claim capture up front, so a second start arriving mid-setup can't open another stream
+    let Some(session) = claim_capture() else {
+        return Ok(());
+    };
+    start_claimed(&app, session)
+}
+
+/// Open and start the stream once capture is claimed, releasing the claim if that fails
+fn start_claimed(app: &AppHandle, session: u64) -> Result<(), KoeError> {
+    let result = start_on_audio_thread(app, session);
+    if result.is_err() {
+        release_capture();
+        realtime::stop();
+    }
+    result
+}
+
+/// Claim `flag` if nobody holds it; exactly one of several racing callers succeeds
+fn claim(flag: &AtomicBool) -> bool {
+    flag.compare_exchange(false, true, Ordering::SeqCst, Ordering::SeqCst).is_ok()
+}
+
+/// Claim capture, returning the new session's number; None if capture is already running
+fn claim_capture() -> Option<u64> {
+    claim(&CAPTURING).then(|| CAPTURE_SESSION.load(Ordering::SeqCst))
+}
+
+/// End the capture session, returning whether capture was running. The session moves on
+/// before the flag clears, so nothing sees the old session as live once a new one is claimed
+fn release_capture() -> bool {
+    CAPTURE_SESSION.fetch_add(1, Ordering::SeqCst);
+    CAPTURING.swap(false, Ordering::SeqCst)
+}
+
+/// End capture session `session`, unless it already ended; true if this call ended it
+fn release_session(session: u64) -> bool {
+    CAPTURE_SESSION.compare_exchange(session, session + 1, Ordering::SeqCst, Ordering::SeqCst).is_ok()
+        && CAPTURING.swap(false, Ordering::SeqCst)
+}
+
+/// Whether capture session `session` is still running
+fn session_active(session: u64) -> bool {
+    CAPTURING.load(Ordering::SeqCst) && CAPTURE_SESSION.load(Ordering::SeqCst) == session
+}
+
+/// Requests to the audio thread. cpal streams aren't Send, so the capture stream lives on
+/// one thread for its whole life, and start, stop and reconnect reach it from whichever
+/// thread they run on. Start and Reopen carry their capture session, and the stream is only
+/// kept while that session is running.
+enum AudioCommand<A = AppHandle> {
+    Start(A, u64, mpsc::Sender<Result<(), KoeError>>),
+    /// Replace a lost device's stream, replying with the new device's name
+    Reopen(A, u64, mpsc::Sender<Result<String, KoeError>>),
+    /// Drop the stream if its session has ended, replying whether one was dropped
+    Stop(mpsc::Sender<bool>),
+}
+
+/// How the audio thread opens streams and reports on them; tests swap in a fake device
+trait CaptureBackend {
+    type App;
+    type Stream;
+
+    /// Open and start a stream for a new session
+    fn open(&mut self, app: &Self::App) -> Result<Self::Stream, KoeError>;
+    /// Open and start a stream in place of a lost device's, returning the device name
+    fn reopen(&mut self, app: &Self::App) -> Result<(Self::Stream, String), KoeError>;
+    /// The stream for a running session is live
+    fn started(&mut self, app: &Self::App);
+    /// A stream was opened for a session that ended meanwhile, and has been dropped
+    fn abandoned(&mut self);
+    /// Called every NOTICE_INTERVAL while a stream is open, off the stream's callback
+    fn poll(&mut self, _app: &Self::App) {}
+}
+
+/// Capture through cpal, alongside any realtime session
+struct CpalBackend;
+
+impl CaptureBackend for CpalBackend {
+    type App = AppHandle;
+    type Stream = cpal::Stream;
+
+    fn open(&mut self, app: &AppHandle) -> Result<cpal::Stream, KoeError> {
+        open_and_play(app)
+    }
+
+    fn reopen(&mut self, app: &AppHandle) -> Result<(cpal::Stream, String), KoeError> {
+        let (stream, name, sample_rate) = open_stream(app)?;
+        // The realtime socket was streaming the lost device's rate
+        realtime::stop();
+        if let Some((provider, api_key)) = streaming_session(&WHISPER_CONFIG.lock()) {
+            realtime::start(app.clone(), &provider, &api_key, sample_rate)?;
+        }
+        stream.play()?;
+        Ok((stream, name))
+    }
+
+    fn started(&mut self, app: &AppHandle) {
+        app.emit_to(EventTarget::Any, "voice:state", "listening").ok();
+        println!("Voice capture started");
+    }
+
+    fn abandoned(&mut self) {
+        realtime::stop();
+    }
+
+    fn poll(&mut self, app: &AppHandle) {
+        emit_capture_notices(app);
+    }
+}
+
+/// Have the audio thread open and start the capture stream, waiting for the outcome
+fn start_on_audio_thread(app: &AppHandle, session: u64) -> Result<(), KoeError> {
+    let (reply, started) = mpsc::channel();
+    send_audio_command(AudioCommand::Start(app.clone(), session, reply))?;
+    started.recv().map_err(|_| KoeError::Other("Audio thread stopped".to_string()))?
+}
+
+/// Have the audio thread swap in a new stream for a lost device, waiting for the outcome
+fn reopen_on_audio_thread(app: &AppHandle, session: u64) -> Result<String, KoeError> {
+    let (reply, reopened) = mpsc::channel();
+    send_audio_command(AudioCommand::Reopen(app.clone(), session, reply))?;
+    reopened.recv().map_err(|_| KoeError::Other("Audio thread stopped".to_string()))?
+}
+
+/// Send to the audio thread, starting it on first use
+fn send_audio_command(command: AudioCommand) -> Result<(), KoeError> {
+    let mut sender = AUDIO_THREAD.lock();
+    let sender = sender.get_or_insert_with(|| {
+        let (sender, commands) = mpsc::channel();
+        std::thread::spawn(move || run_audio_thread(commands, CpalBackend));
+        sender
+    });
+    sender.send(command).map_err(|_| KoeError::Other("Audio thread stopped".to_string()))
+}
+
+/// Own the capture stream and its session, opening and dropping it as commands arrive
+fn run_audio_thread<B: CaptureBackend>(commands: mpsc::Receiver<AudioCommand<B::App>>, mut backend: B) {
+    // The open stream, with its session and the app its notices go to
+    let mut stream: Option<(u64, B::Stream, B::App)> = None;
+    loop {
+        // While a stream is open, wake regularly to send on what its callback raised
+        let command = match &stream {
+            Some((_, _, app)) => match commands.recv_timeout(NOTICE_INTERVAL) {
+                Ok(command) => command,
+                Err(mpsc::RecvTimeoutError::Timeout) => {
+                    backend.poll(app);
+                    continue;
+                }
+                Err(mpsc::RecvTimeoutError::Disconnected) => break,
+            },
+            None => match commands.recv() {
+                Ok(command) => command,
+                Err(_) => break,
+            },
+        };
+        // A stream outliving its session goes before anything new opens. A stop queued
+        // behind a newer session's start leaves that session's stream alone
+        let ended = stream.as_ref().is_some_and(|(session, ..)| !session_active(*session));
+        if ended {
+            stream = None;
+        }
+        match command {
+            AudioCommand::Start(app, session, reply) => {
+                let result = backend.open(&app).map(|opened| {
+                    // A stop that landed while the device was opening found no stream to drop,
+                    // so check again; any later stop queues behind this and drops it
+                    if session_active(session) {
+                        backend.started(&app);
+                        stream = Some((session, opened, app));
+                    } else {
+                        backend.abandoned();
+                    }
+                });
+                reply.send(result).ok();
+            }
+            AudioCommand::Reopen(app, session, reply) => {
+                // Release the dead stream before reopening the device
+                stream = None;
+                let result = if session_active(session) {
+                    backend.reopen(&app)
+                } else {
+                    Err(KoeError::Other("Capture stopped".to_string()))
+                };
+                let result = result.and_then(|(opened, name)| {
+                    if !session_active(session) {
+                        backend.abandoned();
+                        return Err(KoeError::Other("Capture stopped".to_string()));
+                    }
+                    stream = Some((session, opened, app));
+                    Ok(name)
+                });
+                reply.send(result).ok();
+            }
+            AudioCommand::Stop(reply) => {
+                reply.send(ended).ok();
+            }
+        }
+    }
+}
+
+fn open_and_play(app: &AppHandle) -> Result<cpal::Stream, KoeError> {
+    typing::reset();
+    DROPOUT_COUNT.store(0, Ordering::SeqCst);
+    DEVICE_LOST.store(false, Ordering::SeqCst);
+    let (stream, _, sample_rate) = open_stream(app).map_err(|error| {
+        if matches!(error, KoeError::NoInputDevice) {
+            app.emit_to(EventTarget::Any, "voice:error", error.event()).ok();
+        }
+        error
+    })?;
+
+    if let Some((provider, api_key)) = streaming_session(&WHISPER_CONFIG.lock()) {
+        realtime::start(app.clone(), &provider, &api_key, sample_rate)?;
+    }
+
+    stream.play()?;
+    Ok(stream)
+}
+
+/// Build a (paused) input stream on the selected device, or the default if it's gone;
+/// returns the stream with the device name and capture rate
+fn open_stream(app: &AppHandle) -> Result<(cpal::Stream, String, u32), KoeError> {
+    let device = get_input_device()?;
+    let device_name = device.name().unwrap_or_else(|_| "Unknown".to_string());
+    println!("Using audio device: {}", device_name);
+
+    let downmix = DEVICE_CONFIG.lock().downmix;
+    let input_channel = downmix.channel();
+
+    // Get supported config - prefer mono at any sample rate, unless a specific channel is wanted
+    let supported_config = match input_channel {
+        Some(channel) => device
+            .supported_input_configs()?
+            .filter(|c| c.channels() > channel)
+            .max_by_key(|c| c.max_sample_rate().0)
+            .ok_or_else(|| {
+                let available = device
+                    .supported_input_configs()
+                    .ok()
+                    .and_then(|configs| configs.map(|c| c.channels()).max())
+                    .unwrap_or(0);
+                let message = format!(
+                    "Input channel index {} is out of range: '{}' has {} channel(s)",
+                    channel,
+                    device_name,
+                    available
+                );
+                let error = KoeError::AudioDevice(message);
+                app.emit_to(EventTarget::Any, "voice:error", ErrorEvent { recoverable: false, ..error.event() }).ok();
+                error
+            })?,
+        None => device
+            .supported_input_configs()?
+            .filter(|c| c.channels() == 1)
+            .max_by_key(|c| c.max_sample_rate().0)
+            .or_else(|| {
+                // Fall back to any config if no mono available
+                device.supported_input_configs().ok()?.next()
+            })
+            .ok_or("No supported audio configuration found")?,
+    };
+
+    // Use a reasonable sample rate within the supported range
+    let min_rate = supported_config.min_sample_rate().0;
+    let max_rate = supported_config.max_sample_rate().0;
+
+    let target_rate = choose_capture_rate(min_rate, max_rate, DEVICE_CONFIG.lock().max_capture_rate);
+
+    let sample_format = supported_config.sample_format();
+    let config: cpal::StreamConfig = supported_config
+        .with_sample_rate(cpal::SampleRate(target_rate))
+        .into();
+
+    let actual_channels = config.channels;
+    let actual_sample_rate = config.sample_rate.0;
+    println!("Audio config: {}Hz, {} channel(s)", actual_sample_rate, actual_channels);
+
+    // Update buffer sample rate
+    {
+        let mut buffer = AUDIO_BUFFER.lock();
+        buffer.sample_rate = actual_sample_rate;
+        buffer.samples.clear();
+    }
+
+    let streaming = streaming_session(&WHISPER_CONFIG.lock()).is_some();
+    let mut pipeline = CapturePipeline::new(app.clone(), actual_channels, actual_sample_rate, downmix, streaming);
+
+    // Build input stream
+    let stream = if DEFERRED_CONVERSION.load(Ordering::SeqCst) {
+        println!("Deferred conversion: capturing native {} samples", sample_format);
+        match sample_format {
+            cpal::SampleFormat::F32 => build_deferred_stream::<f32>(&device, &config, pipeline, app.clone())?,
+            cpal::SampleFormat::I16 => build_deferred_stream::<i16>(&device, &config, pipeline, app.clone())?,
+            cpal::SampleFormat::I32 => build_deferred_stream::<i32>(&device, &config, pipeline, app.clone())?,
+            cpal::SampleFormat::U16 => build_deferred_stream::<u16>(&device, &config, pipeline, app.clone())?,
+            other => return Err(format!("Unsupported native sample format {}", other).into()),
+        }
+    } else {
+        match sample_format {
+            cpal::SampleFormat::F32 => device.build_input_stream(
+                &config,
+                move |data: &[f32], info: &cpal::InputCallbackInfo| {
+                    if !CAPTURING.load(Ordering::SeqCst) {
+                        return;
+                    }
+                    pipeline.process_guarded(data, Some(info.timestamp().capture));
+                },
+                stream_error_handler(app.clone()),
+                None,
+            )?,
+            cpal::SampleFormat::I16 => build_converting_stream::<i16>(&device, &config, pipeline, app.clone())?,
+            cpal::SampleFormat::I32 => build_converting_stream::<i32>(&device, &config, pipeline, app.clone())?,
+            cpal::SampleFormat::U16 => build_converting_stream::<u16>(&device, &config, pipeline, app.clone())?,
+            other => return Err(format!("Unsupported sample format {}", other).into()),
+        }
+    };
+
+    {
+        let mut status = CAPTURE_STATUS.lock();
+        status.device_name = Some(device_name.clone());
+        status.sample_rate = actual_sample_rate;
+        status.channels = actual_channels;
+    }
+
+    Ok((stream, device_name, actual_sample_rate))
+}
+
+/// What capture negotiated with the device, plus the live chunking settings
+#[derive(Clone, Debug, Default, serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CaptureStatus {
+    pub device_name: Option<String>, // None when not capturing
+    pub sample_rate: u32,
+    pub channels: u16,
+    pub capturing: bool,
+    pub chunk_seconds: f32,
+    pub vad_threshold: f32, // linear RMS
+}
+
+pub fn get_capture_status() -> CaptureStatus {
+    CaptureStatus {
+        capturing: CAPTURING.load(Ordering::SeqCst),
+        chunk_seconds: vad::chunk_duration(),
+        vad_threshold: vad::threshold(),
+        ..CAPTURE_STATUS.lock().clone()
+    }
+}
+
+/// Realtime provider and key when capture should stream rather than chunk: the one set with
+/// `configure_realtime`, else Deepgram when it's the active provider
+fn streaming_session(config: &WhisperConfig) -> Option<(String, String)> {
+    if let Some(session) = realtime::session() {
+        return Some(session);
+    }
+    match resolve_route(config) {
+        #[cfg(feature = "deepgram")]
+        Route::Deepgram { api_key, .. } => Some(("deepgram".to_string(), api_key)),
+        _ => None,
+    }
+}
+
+/// Reopen the input after the device disappeared in capture session `session`, retrying with
+/// backoff. The audio thread owns the replacement stream; this thread just watches for
+/// the device being lost again, and ends with the session.
+fn spawn_reconnect(app: AppHandle, session: u64) {
+    std::thread::spawn(move || {
+        let mut attempt = 0;
+        while session_active(session) {
+            if !DEVICE_LOST.swap(false, Ordering::SeqCst) {
+                std::thread::sleep(Duration::from_millis(50));
+                continue;
+            }
+
+            attempt += 1;
+            if attempt > MAX_RECONNECT_ATTEMPTS {
+                // Stop this session the way stop_capture would, but never a newer one
+                if release_session(session) {
+                    let flush_reason = PUSH_TO_TALK.load(Ordering::SeqCst).then_some("Audio device lost");
+                    finish_capture(true, flush_reason);
+                }
+                let message = format!("Audio device lost; gave up after {} reconnect attempts", MAX_RECONNECT_ATTEMPTS);
+                eprintln!("{}", message);
+                let error = KoeError::AudioDevice(message);
+                app.emit_to(EventTarget::Any, "voice:error", ErrorEvent { recoverable: false, ..error.event() }).ok();
+                app.emit_to(EventTarget::Any, "voice:state", "stopped").ok();
+                break;
+            }
+
+            app.emit_to(EventTarget::Any, "voice:state", "reconnecting").ok();
+            std::thread::sleep(reconnect_delay(attempt));
+            if !session_active(session) {
+                break;
+            }
+
+            match reopen_on_audio_thread(&app, session) {
+                Ok(name) => {
+                    let selected = DEVICE_CONFIG.lock().selected_device.clone();
+                    let fallback = selected.is_some_and(|selected| selected != name);
+                    println!("Audio input reconnected on '{}' (attempt {})", name, attempt);
+                    app.emit_to(
+                        EventTarget::Any,
+                        "voice:warning",
+                        serde_json::json!({
+                            "kind": "reconnected",
+                            "device": name,
+                            "fallback": fallback
+                        }),
+                    )
+                    .ok();
+                    app.emit_to(EventTarget::Any, "voice:state", "listening").ok();
+                    attempt = 0;
+                }
+                Err(e) => {
+                    eprintln!("Reconnect attempt {} failed: {}", attempt, e);
+                    // Try again on the next pass
+                    DEVICE_LOST.store(true, Ordering::SeqCst);
+                }
+            }
+        }
+        // A newer session may already have its own watcher
+        RECONNECTING.compare_exchange(session, 0, Ordering::SeqCst, Ordering::SeqCst).ok();
+    });
+}
+
+/// Wait before reconnect attempt `attempt` (1-based): 0.5s, doubling up to 8s
+fn reconnect_delay(attempt: u32) -> Duration {
+    Duration::from_millis(500 << attempt.saturating_sub(1).min(4))
+}
+
+/// Everything done with captured audio after it leaves the device: dropout detection,
+/// level metering, channel extraction, buffering and chunking
+struct CapturePipeline {
+    app: AppHandle,
+    channels: u16,
+    sample_rate: u32,
+    deinterleaver: Deinterleaver,
+    high_pass: Option<HighPass>, // fresh per stream, so filter state resets with each capture
+    pcm_tap: Option<PcmTap>,     // raw audio subscribers, also per stream
+    streaming: bool,
+    frames: realtime::FrameSink, // realtime session's queue, when streaming
+    last_callback: Option<(cpal::StreamInstant, usize)>,
+    level_interval: usize,
+    level_sum_squares: f32,
+    level_peak: f32,
+    level_count: usize,
+    interim_len: usize, // buffer length when the last interim was sent
+    speech: vad::SpeechDetector,
+    energy: vad::ChunkEnergy, // the buffered chunk's windows, counted as they arrive
+    overflowing: bool,        // dropping audio at the buffer cap, already warned about
+}
+
+impl CapturePipeline {
+    fn new(app: AppHandle, channels: u16, sample_rate: u32, downmix: Downmix, streaming: bool) -> Self {
+        // A fresh speech detector starts outside speech, so the edge count starts over too
+        CAPTURE_NOTICES.reset();
+        CapturePipeline {
+            app,
+            channels,
+            sample_rate,
+            deinterleaver: Deinterleaver::new(channels, downmix),
+            high_pass: HighPass::for_stream(sample_rate),
+            pcm_tap: PcmTap::for_stream(sample_rate),
+            streaming,
+            frames: realtime::FrameSink::new(),
+            last_callback: None,
+            level_interval: (sample_rate / 20) as usize, // ~50ms
+            level_sum_squares: 0.0,
+            level_peak: 0.0,
+            level_count: 0,
+            interim_len: 0,
+            speech: vad::SpeechDetector::default(),
+            energy: vad::ChunkEnergy::new(sample_rate),
+            overflowing: false,
+        }
+    }
+
+    /// Handle one block of interleaved samples captured at `captured_at`; None for audio
+    /// fed from a file, which can't drop out
+    fn process(&mut self, data: &[f32], captured_at: Option<cpal::StreamInstant>) {
+        let channels = self.channels;
+
+        // Compare capture timestamps against the audio we were handed to spot xruns
+        if let (Some((prev_at, prev_frames)), Some(captured_at)) = (self.last_callback, captured_at) {
+            if let Some(elapsed) = captured_at.duration_since(&prev_at) {
+                if let Some(dropped) = detect_dropout(elapsed.as_secs_f64(), prev_frames, self.sample_rate) {
+                    note_dropout(dropped);
+                }
+            }
+        }
+        self.last_callback = captured_at.map(|at| (at, data.len() / channels as usize));
+        let live = captured_at.is_some();
+        let frames = data.len() / channels as usize;
+
+        // Level meter, throttled to ~20 updates per second
+        self.level_sum_squares += data.iter().map(|s| s * s).sum::<f32>();
+        self.level_peak = data.iter().fold(self.level_peak, |peak, s| peak.max(s.abs()));
+        self.level_count += data.len();
+        if self.level_count / channels as usize >= self.level_interval {
+            let rms = (self.level_sum_squares / self.level_count as f32).sqrt();
+            CAPTURE_NOTICES.level.store(pack_level(rms, self.level_peak), Ordering::Relaxed);
+            self.level_sum_squares = 0.0;
+            self.level_peak = 0.0;
+            self.level_count = 0;
+        }
+
+        // Speaking indicator, from this block's energy against the VAD threshold
+        if frames > 0 {
+            let rms = (data.iter().map(|s| s * s).sum::<f32>() / data.len() as f32).sqrt();
+            let block_ms = frames as f32 * 1000.0 / self.sample_rate as f32;
+            if self.speech.update(rms, vad::threshold(), block_ms).is_some() {
+                CAPTURE_NOTICES.speech_edges.fetch_add(1, Ordering::Relaxed);
+            }
+        }
+
+        // Recording to a named buffer replaces live transcription
+        {
+            let Some(mut named) = lock_block::<NamedBuffers>(&NAMED_BUFFERS, live, frames) else {
+                return;
+            };
+            let NamedBuffers { buffers, recording, .. } = &mut *named;
+            if let Some(name) = recording {
+                if let Some(target) = buffers.get_mut(name) {
+                    let mut block = Vec::with_capacity(data.len() / channels as usize);
+                    self.push_mono(data, &mut block);
+                    monitor::push(&block, self.sample_rate);
+                    target.samples.extend(block);
+                }
+                return;
+            }
+        }
+
+        // Realtime providers segment server-side: forward audio as-is.
+        // Raw audio subscribers that replace transcription take the same path, minus the provider
+        let exclusive_pcm = self.pcm_tap.as_ref().is_some_and(|tap| tap.exclusive);
+        if self.streaming || exclusive_pcm {
+            let mut frame = Vec::with_capacity(data.len() / channels as usize);
+            self.push_mono(data, &mut frame);
+            monitor::push(&frame, self.sample_rate);
+            recording::write(&frame);
+            self.tap_pcm(&frame);
+            if !exclusive_pcm {
+                self.frames.push(frame);
+            }
+            return;
+        }
+
+        let mut block = Vec::with_capacity(data.len() / channels as usize);
+        self.push_mono(data, &mut block);
+        monitor::push(&block, self.sample_rate);
+        recording::write(&block);
+        self.tap_pcm(&block);
+
+        let Some(mut buffer) = lock_block::<AudioBuffer>(&AUDIO_BUFFER, live, frames) else {
+            return;
+        };
+        let appended_from = buffer.samples.len();
+        buffer.samples.extend(&block);
+        let sample_rate = buffer.sample_rate;
+        let dropped = cap_buffer(&mut buffer.samples, sample_rate, max_buffer_secs());
+        // Warn once as the buffer starts overflowing, not on every block it keeps doing so
+        if dropped > 0 && !self.overflowing {
+            CAPTURE_NOTICES.overflow_samples.fetch_add(dropped as u64, Ordering::Relaxed);
+        }
+        self.overflowing = dropped > 0;
+
+        // Push-to-talk sends everything as one segment when capture stops
+        if PUSH_TO_TALK.load(Ordering::Relaxed) {
+            return;
+        }
+
+        // Count only the new samples, unless the buffer changed since the last block (the
+        // cap, a flush, push-to-talk), in which case count it afresh
+        let threshold = vad::threshold();
+        let silence_threshold = vad::silence_threshold(threshold);
+        if dropped == 0 && self.energy.counted() == appended_from {
+            self.energy.push(&block, threshold, silence_threshold);
+        } else {
+            count_afresh(&mut self.energy, &buffer.samples, threshold, silence_threshold);
+        }
+
+        // Process every chunk duration (1s by default) of audio
+        // Adaptive chunking stretches this for fast speakers and shrinks it for slow ones
+        let samples_per_chunk = vad::chunk_samples(buffer.sample_rate);
+        if buffer.samples.len() >= samples_per_chunk {
+            let rms = self.energy.rms();
+            match vad::decide(&self.energy, buffer.sample_rate, &vad::effective_timing()) {
+                vad::ChunkAction::Wait => {
+                    if INTERIM_RESULTS.load(Ordering::Relaxed)
+                        && interim_due(buffer.samples.len(), self.interim_len, samples_per_chunk)
+                        && !INTERIM_IN_FLIGHT.swap(true, Ordering::SeqCst)
+                    {
+                        self.interim_len = buffer.samples.len();
+                        let samples: Vec<f32> = buffer.samples.iter().copied().collect();
+                        let sample_rate = buffer.sample_rate;
+                        drop(buffer);
+                        spawn_interim_transcription(self.app.clone(), samples, sample_rate);
+                    }
+                }
+                vad::ChunkAction::Flush => {
+                    UTTERANCE_GENERATION.fetch_add(1, Ordering::SeqCst);
+                    self.interim_len = 0;
+                    self.energy.reset();
+                    let samples = Vec::from(std::mem::take(&mut buffer.samples));
+                    let sample_rate = buffer.sample_rate;
+                    // Release the buffer before handing off the chunk
+                    drop(buffer);
+                    let duration = samples.len() as f32 / sample_rate as f32;
+                    // Measured with any pre-roll included, which is what would be sent. decide
+                    // already found enough voiced windows, so the whole chunk's RMS isn't gated:
+                    // pre-roll and hangover silence would dilute a short or quiet utterance
+                    let min_secs = vad::min_segment_duration();
+                    if !vad::long_enough(samples.len(), sample_rate, min_secs) {
+                        let reason = format!("{:.2}s segment is under the {:.2}s minimum", duration, min_secs);
+                        record_decision(rms, threshold, duration, Decision::TooShort, reason);
+                    } else {
+                        let reason = format!("Sent {:.1}s for transcription", duration);
+                        let decision = record_decision(rms, threshold, duration, Decision::Transcribed, reason);
+                        let offset = recording::chunk_offset(samples.len());
+                        spawn_transcription(self.app.clone(), samples, sample_rate, offset, decision);
+                    }
+                }
+                vad::ChunkAction::Discard { preroll } => {
+                    UTTERANCE_GENERATION.fetch_add(1, Ordering::SeqCst);
+                    self.interim_len = 0;
+                    let duration = buffer.samples.len() as f32 / buffer.sample_rate as f32;
+                    let voiced_ms = self.energy.voiced_ms();
+                    if voiced_ms == 0 {
+                        let reason = format!("RMS {:.4} below threshold {:.4}", rms, threshold);
+                        record_decision(rms, threshold, duration, Decision::BelowThreshold, reason);
+                    } else {
+                        let min_speech_ms = vad::effective_timing().min_speech_ms;
+                        let reason = format!("{}ms of speech is under the {}ms minimum", voiced_ms, min_speech_ms);
+                        record_decision(rms, threshold, duration, Decision::TooShort, reason);
+                    }
+                    // Keep the tail so the next chunk starts slightly before speech
+                    let keep_from = buffer.samples.len().saturating_sub(preroll);
+                    buffer.samples.drain(..keep_from);
+                    count_afresh(&mut self.energy, &buffer.samples, threshold, silence_threshold);
+                }
+            }
+        }
+    }
+
+    /// Append `data` to `out` as mono, high-passed if enabled
+    fn push_mono(&mut self, data: &[f32], out: &mut Vec<f32>) {
+        let appended_from = out.len();
+        self.deinterleaver.push(data, out);
+        if let Some(high_pass) = self.high_pass.as_mut() {
+            high_pass.process(&mut out[appended_from..]);
+        }
+    }
+
+    /// Hand mono samples to raw audio subscribers, if any
+    fn tap_pcm(&mut self, mono: &[f32]) {
+        if let Some(tap) = self.pcm_tap.as_mut() {
+            tap.push(&self.app, mono);
+        }
+    }
+
+    /// `process`, but a panic only loses this block instead of unwinding into the audio backend
+    fn process_guarded(&mut self, data: &[f32], captured_at: Option<cpal::StreamInstant>) {
+        let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| self.process(data, captured_at)));
+        if result.is_err() {
+            eprintln!("Capture pipeline panicked; dropped {} samples", data.len());
+        }
+    }
+}
+
+/// Recount a chunk's energy over everything buffered, after something other than an append
+/// changed it
+fn count_afresh(energy: &mut vad::ChunkEnergy, samples: &VecDeque<f32>, threshold: f32, silence_threshold: f32) {
+    energy.reset();
+    let (front, back) = samples.as_slices();
+    energy.push(front, threshold, silence_threshold);
+    energy.push(back, threshold, silence_threshold);
+}
+
+/// Capture in the device's native sample format, with the callback only copying blocks
+/// into a queue; conversion and the rest of the pipeline run on a worker thread
+fn build_deferred_stream<T>(
+    device: &cpal::Device,
+    config: &cpal::StreamConfig,
+    mut pipeline: CapturePipeline,
+    app: AppHandle,
+) -> Result<cpal::Stream, cpal::BuildStreamError>
+where
+    T: cpal::SizedSample + Send + 'static,
+    f32: cpal::FromSample<T>,
+{
+    let (sender, receiver) = std::sync::mpsc::sync_channel::<(Vec<T>, cpal::StreamInstant)>(DEFERRED_QUEUE_CAPACITY);
+
+    // Exits once the stream, and with it the sender, is dropped
+    let worker = std::thread::spawn(move || {
+        let mut converted = Vec::new();
+        for (block, captured_at) in receiver {
+            to_f32(&block, &mut converted);
+            pipeline.process_guarded(&converted, Some(captured_at));
+        }
+    });
+    // A reopen drops the old stream first, so its worker is already finishing
+    let previous = DEFERRED_WORKER.lock().replace(worker);
+    if let Some(previous) = previous {
+        previous.join().ok();
+    }
+
+    device.build_input_stream(
+        config,
+        move |data: &[T], info: &cpal::InputCallbackInfo| {
+            if !CAPTURING.load(Ordering::SeqCst) {
+                return;
+            }
+            // A full queue drops the block; the timestamp gap shows up as a dropout downstream
+            let _ = sender.try_send((data.to_vec(), info.timestamp().capture));
+        },
+        stream_error_handler(app),
+        None,
+    )
+}
+
+/// Capture integer samples, normalizing them to f32 in the callback so every format
+/// shares the same downmixing and chunking
+fn build_converting_stream<T>(
+    device: &cpal::Device,
+    config: &cpal::StreamConfig,
+    mut pipeline: CapturePipeline,
+    app: AppHandle,
+) -> Result<cpal::Stream, cpal::BuildStreamError>
+where
+    T: cpal::SizedSample + Send + 'static,
+    f32: cpal::FromSample<T>,
+{
+    let mut converted = Vec::new();
+    device.build_input_stream(
+        config,
+        move |data: &[T], info: &cpal::InputCallbackInfo| {
+            if !CAPTURING.load(Ordering::SeqCst) {
+                return;
+            }
+            to_f32(data, &mut converted);
+            pipeline.process_guarded(&converted, Some(info.timestamp().capture));
+        },
+        stream_error_handler(app),
+        None,
+    )
+}
+
+/// Replace `out` with `data` normalized to -1.0..1.0
+fn to_f32<T>(data: &[T], out: &mut Vec<f32>)
+where
+    T: cpal::Sample,
+    f32: cpal::FromSample<T>,
+{
+    out.clear();
+    out.extend(data.iter().map(|s| s.to_sample::<f32>()));
+}
+
+fn stream_error_handler(app: AppHandle) -> impl FnMut(cpal::StreamError) + Send + 'static {
+    move |err| {
+        eprintln!("Audio stream error: {}", err);
+        app.emit_to(EventTarget::Any, "voice:error", KoeError::AudioDevice(err.to_string()).event()).ok();
+        // Backend errors are often transient (e.g. overruns); only a lost device needs a new stream
+        if matches!(err, cpal::StreamError::DeviceNotAvailable) && CAPTURING.load(Ordering::SeqCst) {
+            DEVICE_LOST.store(true, Ordering::SeqCst);
+            let session = CAPTURE_SESSION.load(Ordering::SeqCst);
+            if RECONNECTING.swap(session, Ordering::SeqCst) != session {
+                spawn_reconnect(app.clone(), session);
+            }
+        }
+    }
+}
+
+/// Keep the capture callback to a plain copy, deferring format conversion, downmixing
+/// and chunking to a worker thread; applies from the next capture start
+pub fn set_deferred_conversion(enabled: bool) {
+    DEFERRED_CONVERSION.store(enabled, Ordering::SeqCst);
+}
+
+/// Cap how much unflushed audio the live buffer holds; beyond it the oldest samples are
+/// dropped with a `voice:warning`. Caps shorter than the longest chunk VAD can gather are
+/// raised to it.
+pub fn set_max_buffer_duration(seconds: u32) -> Result<(), String> {
+    if seconds == 0 {
+        return Err("Max buffer duration must be at least 1 second".to_string());
+    }
+    MAX_BUFFER_SECS.store(seconds, Ordering::SeqCst);
+    Ok(())
+}
+
+/// The buffer cap in effect; push-to-talk keeps at least PUSH_TO_TALK_MAX_BUFFER_SECS
+fn max_buffer_secs() -> u32 {
+    let max = MAX_BUFFER_SECS.load(Ordering::Relaxed);
+    let max = if PUSH_TO_TALK.load(Ordering::Relaxed) {
+        max.max(PUSH_TO_TALK_MAX_BUFFER_SECS)
+    } else {
+        max
+    };
+    buffer_floor(max, vad::chunk_duration())
+}
+
+/// Raise a buffer cap to hold the longest chunk VAD waits for and at least one whole chunk,
+/// so neither a long utterance nor a chunk duration raised later loses its start
+fn buffer_floor(max_secs: u32, chunk_secs: f32) -> u32 {
+    max_secs.max(chunk_secs.ceil() as u32).max(vad::MAX_CHUNK_SECS as u32)
+}
+
+/// Drop the oldest samples beyond `max_secs` of audio; returns how many were dropped
+fn cap_buffer(samples: &mut VecDeque<f32>, sample_rate: u32, max_secs: u32) -> usize {
+    let max_len = sample_rate as usize * max_secs as usize;
+    let excess = samples.len().saturating_sub(max_len);
+    samples.drain(..excess);
+    excess
+}
+
+/// Emit a `voice:warning`, if the app has initialized the voice system
+fn emit_warning(payload: serde_json::Value) {
+    if let Some(app) = EVENT_APP.lock().as_ref() {
+        app.emit_to(EventTarget::Any, "voice:warning", payload).ok();
+    }
+}
+
+/// Report `voice:level` as dBFS (true) or linear RMS (false); both fields are always present
+pub fn set_level_units_dbfs(dbfs: bool) {
+    LEVEL_IN_DBFS.store(dbfs, Ordering::SeqCst);
+}
+
+/// Emit the input level in the configured units, alongside both raw forms and the linear peak
+pub(crate) fn emit_level(app: &AppHandle, rms: f32, peak: f32) {
+    let dbfs = vad::to_dbfs(rms);
+    let in_dbfs = LEVEL_IN_DBFS.load(Ordering::SeqCst);
+    app.emit_to(
+        EventTarget::Any,
+        "voice:level",
+        serde_json::json!({
+            "level": if in_dbfs { dbfs } else { rms },
+            "unit": if in_dbfs { "dbfs" } else { "linear" },
+            "rms": rms,
+            "dbfs": dbfs,
+            "peak": peak
+        }),
+    )
+    .ok();
+}
+
+/// Events the capture callback raises but doesn't emit, since emitting from the real-time
+/// thread can stall it; `emit_capture_notices` sends them on from outside the callback
+struct CaptureNotices {
+    dropouts: AtomicU64, // since last emitted
+    dropped_samples: AtomicU64,
+    level: AtomicU64, // latest RMS and peak from `pack_level`, or NO_LEVEL once emitted
+    speech_edges: AtomicU64, // speech start/end edges seen; they alternate, starting with a start
+    speech_edges_emitted: AtomicU64,
+    overflow_samples: AtomicU64, // dropped at the buffer cap as it started overflowing
+}
+
+const NO_LEVEL: u64 = u64::MAX;
+
+static CAPTURE_NOTICES: CaptureNotices = CaptureNotices {
+    dropouts: AtomicU64::new(0),
+    dropped_samples: AtomicU64::new(0),
+    level: AtomicU64::new(NO_LEVEL),
+    speech_edges: AtomicU64::new(0),
+    speech_edges_emitted: AtomicU64::new(0),
+    overflow_samples: AtomicU64::new(0),
+};
+
+/// How often the audio thread sends on capture notices while a stream is open
+const NOTICE_INTERVAL: Duration = Duration::from_millis(50);
+
+impl CaptureNotices {
+    /// Forget anything a previous stream raised
+    fn reset(&self) {
+        let counters = [
+            &self.dropouts,
+            &self.dropped_samples,
+            &self.speech_edges,
+            &self.speech_edges_emitted,
+            &self.overflow_samples,
+        ];
+        for counter in counters {
+            counter.store(0, Ordering::Relaxed);
+        }
+        self.level.store(NO_LEVEL, Ordering::Relaxed);
+    }
+}
+
+fn pack_level(rms: f32, peak: f32) -> u64 {
+    ((rms.to_bits() as u64) << 32) | peak.to_bits() as u64
+}
+
+/// Count audio lost before it reached the pipeline, for `get_dropout_count` and the next warning
+fn note_dropout(samples: u64) {
+    DROPOUT_COUNT.fetch_add(1, Ordering::SeqCst);
+    CAPTURE_NOTICES.dropouts.fetch_add(1, Ordering::Relaxed);
+    CAPTURE_NOTICES.dropped_samples.fetch_add(samples, Ordering::Relaxed);
+}
+
+/// Lock `mutex` for a block of `frames`. A live callback can't wait, so a contended lock loses
+/// the block as a dropout; file feeding isn't real-time and waits
+fn lock_block<T>(mutex: &Mutex<T>, live: bool, frames: usize) -> Option<parking_lot::MutexGuard<'_, T>> {
+    if !live {
+        return Some(mutex.lock());
+    }
+    let guard = mutex.try_lock();
+    if guard.is_none() {
+        note_dropout(frames as u64);
+    }
+    guard
+}
+
+/// Emit what the capture callback raised since the last call: dropouts, the level meter,
+/// speech edges and buffer overflow
+fn emit_capture_notices(app: &AppHandle) {
+    let notices = &CAPTURE_NOTICES;
+    let dropouts = notices.dropouts.swap(0, Ordering::Relaxed);
+    if dropouts > 0 {
+        let dropped = notices.dropped_samples.swap(0, Ordering::Relaxed);
+        eprintln!(
+            "{} audio dropout(s): ~{} samples lost ({} this session)",
+            dropouts,
+            dropped,
+            DROPOUT_COUNT.load(Ordering::SeqCst)
+        );
+        app.emit_to(
+            EventTarget::Any,
+            "voice:warning",
+            serde_json::json!({
+                "kind": "dropout",
+                "droppedSamples": dropped,
+                "count": dropouts
+            }),
+        )
+        .ok();
+    }
+
+    let level = notices.level.swap(NO_LEVEL, Ordering::Relaxed);
+    if level != NO_LEVEL {
+        emit_level(app, f32::from_bits((level >> 32) as u32), f32::from_bits(level as u32));
+    }
+
+    let edges = notices.speech_edges.load(Ordering::Relaxed);
+    let mut emitted = notices.speech_edges_emitted.load(Ordering::Relaxed);
+    while emitted < edges {
+        emitted += 1;
+        let event = if emitted % 2 == 1 { "voice:speech_start" } else { "voice:speech_end" };
+        app.emit_to(EventTarget::Any, event, ()).ok();
+    }
+    notices.speech_edges_emitted.store(emitted, Ordering::Relaxed);
+
+    let overflow = notices.overflow_samples.swap(0, Ordering::Relaxed);
+    if overflow > 0 {
+        app.emit_to(
+            EventTarget::Any,
+            "voice:warning",
+            serde_json::json!({
+                "kind": "bufferOverflow",
+                "droppedSamples": overflow
+            }),
+        )
+        .ok();
+    }
+}
+
+/// Number of audio dropouts detected in the current capture session
+pub fn get_dropout_count() -> u64 {
+    DROPOUT_COUNT.load(Ordering::SeqCst)
+}
+
+/// Estimate samples lost between two callbacks, if the gap exceeds jitter tolerance
+fn detect_dropout(elapsed_secs: f64, prev_frames: usize, sample_rate: u32) -> Option<u64> {
+    let expected_secs = prev_frames as f64 / sample_rate as f64;
+    // Allow half a buffer (at least 5ms) of scheduling jitter before calling it a dropout
+    let tolerance_secs = (expected_secs * 0.5).max(0.005);
+    let gap_secs = elapsed_secs - expected_secs;
+    if gap_secs > tolerance_secs {
+        Some((gap_secs * sample_rate as f64).round() as u64)
+    } else {
+        None
+    }
+}
+
+/// Transcribe whatever is buffered right now, skipping chunk-size and energy gating
+pub fn flush_now(app: AppHandle) {
+    flush_buffer(app, "Flushed on request");
+}
+
+fn flush_buffer(app: AppHandle, reason: &str) {
+    if let Some((samples, sample_rate, decision)) = take_flushed_segment(reason) {
+        let offset = recording::chunk_offset(samples.len());
+        spawn_transcription(app, samples, sample_rate, offset, decision);
+    }
+}
+
+/// Empty the buffer, returning its audio and decision id unless it's under the minimum
+/// segment length
+fn take_flushed_segment(reason: &str) -> Option<(Vec<f32>, u32, u64)> {
+    let (samples, sample_rate) = {
+        let mut buffer = AUDIO_BUFFER.lock();
+        (Vec::from(std::mem::take(&mut buffer.samples)), buffer.sample_rate)
+    };
+
+    if samples.is_empty() {
+        return None;
+    }
+
+    println!("{}: {} buffered samples", reason, samples.len());
+    let rms = (samples.iter().map(|s| s * s).sum::<f32>() / samples.len() as f32).sqrt();
+    let duration = samples.len() as f32 / sample_rate as f32;
+    let min_secs = vad::min_segment_duration();
+    if !vad::long_enough(samples.len(), sample_rate, min_secs) {
+        let reason = format!("{:.2}s segment is under the {:.2}s minimum", duration, min_secs);
+        record_decision(rms, vad::threshold(), duration, Decision::TooShort, reason);
+        return None;
+    }
+    let decision = record_decision(rms, vad::threshold(), duration, Decision::Transcribed, reason.to_string());
+    Some((samples, sample_rate, decision))
+}
+
+/// Why the last chunk was or wasn't transcribed
+#[derive(Clone, Copy, Debug, PartialEq, serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum Decision {
+    Transcribed,
+    BelowThreshold, // chunk energy under the VAD threshold
+    TooShort,       // speech or the whole segment shorter than its minimum
+    Skipped,        // sent, but the provider returned no text
+}
+
+#[derive(Clone, Debug, serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ChunkDecision {
+    #[serde(skip)]
+    id: u64,
+    pub rms: f32,
+    pub threshold: f32,
+    pub duration: f32, // seconds
+    pub decision: Decision,
+    pub reason: String,
+}
+
+/// Record the fate of a chunk; returns its id for later updates
+fn record_decision(rms: f32, threshold: f32, duration: f32, decision: Decision, reason: String) -> u64 {
+    let id = CHUNK_DECISIONS.fetch_add(1, Ordering::SeqCst) + 1;
+    *LAST_DECISION.lock() = Some(ChunkDecision { id, rms, threshold, duration, decision, reason });
+    id
+}
+
+/// Mark a transcribed chunk as skipped, unless a newer chunk has been decided since
+fn mark_skipped(id: u64, reason: &str) {
+    if let Some(last) = LAST_DECISION.lock().as_mut().filter(|last| last.id == id) {
+        last.decision = Decision::Skipped;
+        last.reason = reason.to_string();
+    }
+}
+
+/// The most recent chunk decision, if any chunk has completed this session
+pub fn get_last_chunk_decision() -> Option<ChunkDecision> {
+    LAST_DECISION.lock().clone()
+}
+
+/// Live segments waiting for a transcription worker
+const SEGMENT_QUEUE_CAPACITY: usize = 2;
+/// Live segments transcribed at once
+const TRANSCRIPTION_WORKERS: usize = 2;
+
+/// A captured chunk queued for transcription
+struct Segment {
+    app: AppHandle,
+    samples: Vec<f32>,
+    sample_rate: u32,
+    file_offset: Option<f64>,
+    conversation: Option<conversation::Slot>,
+    decision: u64,
+    seq: u64,
+}
+
+lazy_static::lazy_static! {
+    // Commands for the thread that owns the capture stream, once it's started
+    static ref AUDIO_THREAD: Mutex<Option<mpsc::Sender<AudioCommand>>> = Mutex::new(None);
+    // Live segment timings per provider and model, in first-used order
+    static ref TRANSCRIPTION_STATS: Mutex<Vec<ModelStats>> = Mutex::new(Vec::new());
+    // Pending segments, oldest first, and the signal that one was queued
+    static ref SEGMENT_QUEUE: (Mutex<VecDeque<Segment>>, parking_lot::Condvar) =
+        (Mutex::new(VecDeque::new()), parking_lot::Condvar::new());
+}
+
+/// Transcribe a segment off the audio thread and emit the result.
+/// `file_offset` is where the segment starts in an active file recording, for subtitle cues;
+/// `decision` is the chunk's entry for `get_last_chunk_decision`.
+fn spawn_transcription(app: AppHandle, samples: Vec<f32>, sample_rate: u32, file_offset: Option<f64>, decision: u64) {
+    // Place the chunk in the conversation timeline as of when it was captured
+    let duration = samples.len() as f64 / sample_rate as f64;
+    let segment = Segment {
+        app,
+        samples,
+        sample_rate,
+        file_offset,
+        conversation: conversation::reserve(duration),
+        decision,
+        seq: NEXT_SEQ.fetch_add(1, Ordering::SeqCst),
+    };
+
+    start_transcription_workers();
+    let (queue, ready) = &*SEGMENT_QUEUE;
+    let dropped = push_bounded(&mut queue.lock(), segment, SEGMENT_QUEUE_CAPACITY);
+    ready.notify_one();
+    if let Some(dropped) = dropped {
+        if let Some(slot) = dropped.conversation {
+            conversation::skip(&dropped.app, slot);
+        }
+        mark_skipped(dropped.decision, "Dropped: transcription fell behind");
+        emit_warning(serde_json::json!({ "kind": "segmentDropped", "seq": dropped.seq }));
+    }
+}
+
+/// Push onto a queue holding at most `capacity` items, returning the oldest if it overflowed
+fn push_bounded<T>(queue: &mut VecDeque<T>, item: T, capacity: usize) -> Option<T> {
+    queue.push_back(item);
+    if queue.len() > capacity {
+        queue.pop_front()
+    } else {
+        None
+    }
+}
+
+/// Start the fixed pool of threads that drain `SEGMENT_QUEUE`, once
+fn start_transcription_workers() {
+    if WORKERS_STARTED.swap(true, Ordering::SeqCst) {
+        return;
+    }
+    for _ in 0..TRANSCRIPTION_WORKERS {
+        // Process using Tauri's async runtime (required for events to reach frontend)
+        tauri::async_runtime::spawn_blocking(|| loop {
+            let (queue, ready) = &*SEGMENT_QUEUE;
+            let segment = {
+                let mut queue = queue.lock();
+                loop {
+                    match queue.pop_front() {
+                        Some(segment) => break segment,
+                        None => ready.wait(&mut queue),
+                    }
+                }
+            };
+            transcribe_guarded(segment);
+        });
+    }
+}
+
+/// Transcribe a segment, catching a panic so it costs that segment rather than the worker
+fn transcribe_guarded(segment: Segment) {
+    let (app, seq, slot) = (segment.app.clone(), segment.seq, segment.conversation);
+    let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| transcribe_segment(segment)));
+    if result.is_err() {
+        eprintln!("Transcription panicked; dropped segment {}", seq);
+    }
+    // A segment that added no turns still has to release the ones queued behind it
+    if let Some(slot) = slot {
+        conversation::skip(&app, slot);
+    }
+}
+
+/// Transcribe one live segment and emit, record or skip its transcript
+fn transcribe_segment(segment: Segment) {
+    let Segment { ref app, ref samples, sample_rate, file_offset, conversation, decision, seq } = segment;
+    let duration = samples.len() as f64 / sample_rate as f64;
+    if SAVE_SEGMENTS.load(Ordering::SeqCst) {
+        save_segment(app, samples, sample_rate).unwrap_or_else(|e| eprintln!("Failed to save segment: {}", e));
+    }
+    let streaming = WHISPER_CONFIG.lock().streaming;
+    let started = Instant::now();
+    let result = transcribe_audio(samples, sample_rate, Pass::Final(streaming.then_some((app, seq))));
+    let latency = started.elapsed();
+    if let Ok(transcription) = &result {
+        let metrics = TranscriptionMetrics {
+            provider: transcription.provider.to_string(),
+            model: transcription.model.clone(),
+            audio_ms: (duration * 1000.0).round() as u64,
+            latency_ms: latency.as_millis() as u64,
+            seq,
+        };
+        accumulate_stats(&mut TRANSCRIPTION_STATS.lock(), &metrics);
+        app.emit_to(EventTarget::Any, "voice:metrics", metrics).ok();
+    }
+    match result.map(|t| (t.text, t.utterances, t.detailed)) {
+        Ok((Some(transcript), utterances, detailed)) if !transcript.trim().is_empty() => {
+            record_success(app);
+            if SUPPRESS_REPEATS.load(Ordering::SeqCst)
+                && repeats_last(&mut LAST_TRANSCRIPT.lock(), &transcript, Instant::now())
+            {
+                mark_skipped(decision, "Repeated the previous transcript");
+                return;
+            }
+            let text = postprocess_transcript(&transcript);
+            // A spoken command runs instead of being typed or kept as dictation
+            let outcome = voice_commands::dispatch(app, &text);
+            if outcome == voice_commands::Outcome::Command {
+                return;
+            }
+            remember_transcript(&transcript);
+            if let Some(start) = file_offset {
+                recording::add_cue(start, duration, &text);
+            }
+            if let Some(slot) = conversation {
+                conversation::add_transcript(app, slot, &text, utterances, duration);
+            }
+            vad::record_speech_rate(
+                transcript.split_whitespace().count(),
+                samples.len() as f32 / sample_rate as f32,
+            );
+            if outcome == voice_commands::Outcome::Dictation {
+                deliver_transcript(app, &transcript, text, true, seq);
+            }
+            if let Some(detailed) = detailed {
+                app.emit_to(EventTarget::Any, "voice:transcript_detailed", detailed).ok();
+            }
+        }
+        Ok(_) => {
+            // Empty or no transcript - ignore
+            record_success(app);
+            mark_skipped(decision, "Transcription returned no text");
+        }
+        Err(e) => {
+            eprintln!("Transcription error: {}", e);
+            record_failure(app, &e);
+        }
+    }
+}
+
+/// Transcribe the unflushed buffer so far and emit it as a non-final transcript, unless the
+/// chunk was flushed meanwhile (its final transcript supersedes this one)
+fn spawn_interim_transcription(app: AppHandle, samples: Vec<f32>, sample_rate: u32) {
+    let generation = UTTERANCE_GENERATION.load(Ordering::SeqCst);
+    let seq = transcript_seq(false);
+    tauri::async_runtime::spawn_blocking(move || {
+        let result = transcribe_audio(&samples, sample_rate, Pass::Interim);
+        INTERIM_IN_FLIGHT.store(false, Ordering::SeqCst);
+        match result.map(|t| t.text) {
+            Ok(Some(transcript)) if !transcript.trim().is_empty() => {
+                if UTTERANCE_GENERATION.load(Ordering::SeqCst) == generation {
+                    emit_transcript(&app, &transcript, false, seq);
+                }
+            }
+            Ok(_) => {}
+            // The final transcription reports failures; interims are best-effort
+            Err(e) => eprintln!("Interim transcription error: {}", e),
+        }
+    });
+}
+
+/// Whether another `step` samples have built up since the last interim
+fn interim_due(len: usize, interim_len: usize, step: usize) -> bool {
+    len.saturating_sub(interim_len) >= step
+}
+
+/// Emit `isFinal: false` transcripts of speech still being buffered (one extra API call per
+/// chunk interval on non-streaming providers); streaming providers always send partials
+pub fn set_interim_results(enabled: bool) {
+    INTERIM_RESULTS.store(enabled, Ordering::SeqCst);
+}
+
+/// Debug aid: save each live segment as a WAV under the app's log dir and emit
+/// `voice:segment_saved`, keeping the newest MAX_SAVED_SEGMENTS
+pub fn set_save_segments(enabled: bool) {
+    SAVE_SEGMENTS.store(enabled, Ordering::SeqCst);
+}
+
+fn save_segment(app: &AppHandle, samples: &[f32], sample_rate: u32) -> Result<(), String> {
+    let dir = app.path().app_log_dir().map_err(|e| e.to_string())?.join("segments");
+    std::fs::create_dir_all(&dir).map_err(|e| format!("Failed to create {}: {}", dir.display(), e))?;
+    let path = dir.join(format!("segment-{}.wav", chrono::Local::now().format("%Y%m%d-%H%M%S%.3f")));
+    std::fs::write(&path, samples_to_wav(samples, sample_rate)?)
+        .map_err(|e| format!("Failed to write {}: {}", path.display(), e))?;
+    prune_segments(&dir, MAX_SAVED_SEGMENTS);
+
+    let rms = (samples.iter().map(|s| s * s).sum::<f32>() / samples.len().max(1) as f32).sqrt();
+    app.emit_to(
+        EventTarget::Any,
+        "voice:segment_saved",
+        serde_json::json!({
+            "path": path.display().to_string(),
+            "rms": rms,
+            "duration": samples.len() as f64 / sample_rate as f64
+        }),
+    )
+    .ok();
+    Ok(())
+}
+
+/// Delete the oldest saved segments beyond `keep`; names sort by time
+fn prune_segments(dir: &Path, keep: usize) {
+    let Ok(entries) = std::fs::read_dir(dir) else {
+        return;
+    };
+    let mut segments: Vec<PathBuf> = entries
+        .filter_map(|entry| entry.ok().map(|e| e.path()))
+        .filter(|path| {
+            let name = path.file_name().and_then(|n| n.to_str()).unwrap_or_default();
+            name.starts_with("segment-") && name.ends_with(".wav")
+        })
+        .collect();
+    if segments.len() <= keep {
+        return;
+    }
+    segments.sort();
+    for old in &segments[..segments.len() - keep] {
+        std::fs::remove_file(old).unwrap_or_else(|e| eprintln!("Failed to remove {}: {}", old.display(), e));
+    }
+}
+
+/// Skip a live transcript identical to the previous one within a few seconds, a common Whisper
+/// artifact on silent chunks. Off by default, since real speech repeats too ("no, no")
+pub fn set_suppress_repeats(enabled: bool) {
+    SUPPRESS_REPEATS.store(enabled, Ordering::SeqCst);
+}
+
+/// Whether `text` repeats `last` within REPEAT_WINDOW, ignoring case and punctuation; records
+/// `text` as the new last either way, so a run of repeats stays suppressed
+fn repeats_last(last: &mut Option<(String, Instant)>, text: &str, now: Instant) -> bool {
+    let normalized: String = text
+        .chars()
+        .filter(|c| c.is_alphanumeric() || c.is_whitespace())
+        .collect::<String>()
+        .split_whitespace()
+        .collect::<Vec<_>>()
+        .join(" ")
+        .to_lowercase();
+    let repeat = last
+        .as_ref()
+        .is_some_and(|(previous, at)| *previous == normalized && now.duration_since(*at) <= REPEAT_WINDOW);
+    *last = Some((normalized, now));
+    repeat
+}
+
+/// Chunk live audio as speech pauses, or hold it all until stop_capture (push-to-talk)
+pub fn set_capture_mode(mode: CaptureMode) {
+    PUSH_TO_TALK.store(mode == CaptureMode::PushToTalk, Ordering::SeqCst);
+}
+
+/// Clear the failure streak, announcing recovery if there was one
+fn record_success(app: &AppHandle) {
+    if CONSECUTIVE_FAILURES.swap(0, Ordering::SeqCst) > 0 {
+        announce_recovery(app);
+    }
+}
+
+/// Count a failed transcription; start probing on the first, give up past the threshold
+fn record_failure(app: &AppHandle, error: &KoeError) {
+    let failures = CONSECUTIVE_FAILURES.fetch_add(1, Ordering::SeqCst) + 1;
+    let max_failures = RECOVERY_CONFIG.lock().max_consecutive_failures;
+
+    if failures >= max_failures {
+        eprintln!("Giving up after {} consecutive transcription failures", failures);
+        CONSECUTIVE_FAILURES.store(0, Ordering::SeqCst);
+        // Stop capture outright, releasing the device; what's buffered would only fail too
+        let was_capturing = release_capture();
+        finish_capture(was_capturing, None);
+        let message = format!(
+            "Transcription unavailable after {} consecutive failures: {}",
+            failures, error
+        );
+        let event = ErrorEvent { message, recoverable: false, ..error.event() };
+        let _ = app.emit_to(EventTarget::Any, "voice:error", event);
+        return;
+    }
+
+    let _ = app.emit_to(EventTarget::Any, "voice:error", error.event());
+    if !PROBING.swap(true, Ordering::SeqCst) {
+        spawn_connectivity_probe(app.clone());
+    }
+}
+
+fn announce_recovery(app: &AppHandle) {
+    println!("Transcription connectivity recovered");
+    app.emit_to(EventTarget::Any, "voice:state", "recovered").ok();
+    if CAPTURING.load(Ordering::SeqCst) {
+        app.emit_to(EventTarget::Any, "voice:state", "listening").ok();
+    }
+}
+
+/// Periodically check the provider is reachable until the failure streak ends
+fn spawn_connectivity_probe(app: AppHandle) {
+    std::thread::spawn(move || {
+        loop {
+            let interval = RECOVERY_CONFIG.lock().probe_interval;
+            std::thread::sleep(interval);
+
+            if !CAPTURING.load(Ordering::SeqCst) || CONSECUTIVE_FAILURES.load(Ordering::SeqCst) == 0 {
+                break;
+            }
+
+            let host = provider_host(&resolve_route(&WHISPER_CONFIG.lock()));
+            let Some(host) = host else { break };
+            if is_reachable(&host) {
+                record_success(&app);
+                break;
+            }
+        }
+        PROBING.store(false, Ordering::SeqCst);
+    });
+}
+
+/// API host and port behind a route, if it goes over the network
+fn provider_host(route: &Route) -> Option<String> {
+    match route {
+        Route::OpenAi { base_url, .. } => {
+            let url = reqwest::Url::parse(base_url).ok()?;
+            Some(format!("{}:{}", url.host_str()?, url.port_or_known_default()?))
+        }
+        Route::Groq { .. } => Some("api.groq.com:443".to_string()),
+        Route::AssemblyAi { .. } => Some("api.assemblyai.com:443".to_string()),
+        #[cfg(feature = "deepgram")]
+        Route::Deepgram { .. } => Some("api.deepgram.com:443".to_string()),
+        Route::Azure { region, .. } => Some(format!("{}:443", azure_host(region))),
+        _ => None,
+    }
+}
+
+/// Lightweight reachability check: resolve `host:port` and open a TCP connection
+fn is_reachable(host: &str) -> bool {
+    use std::net::{TcpStream, ToSocketAddrs};
+
+    let Ok(mut addrs) = host.to_socket_addrs() else {
+        return false;
+    };
+    addrs.any(|addr| TcpStream::connect_timeout(&addr, Duration::from_secs(3)).is_ok())
+}
+
+/// Self-check: the input device that capture would use
+pub(crate) fn check_input_device() -> Result<String, String> {
+    let device = get_input_device().map_err(|e| format!("{}; connect a microphone or pick another input device", e))?;
+    Ok(device.name().unwrap_or_else(|_| "Unknown".to_string()))
+}
+
+/// Self-check: open a test stream on the input device and close it straight away
+pub(crate) fn check_input_stream() -> Result<String, String> {
+    if CAPTURING.load(Ordering::SeqCst) {
+        return Ok("Capture is already running".to_string());
+    }
+
+    let device = get_input_device().map_err(|e| e.to_string())?;
+    let device_name = device.name().unwrap_or_else(|_| "Unknown".to_string());
+    let supported = device
+        .default_input_config()
+        .map_err(|e| format!("'{}' reports no usable input format: {}", device_name, e))?;
+    let sample_format = supported.sample_format();
+    let config: cpal::StreamConfig = supported.into();
+    let stream = match sample_format {
+        cpal::SampleFormat::F32 => build_check_stream::<f32>(&device, &config),
+        cpal::SampleFormat::I16 => build_check_stream::<i16>(&device, &config),
+        cpal::SampleFormat::I32 => build_check_stream::<i32>(&device, &config),
+        cpal::SampleFormat::U16 => build_check_stream::<u16>(&device, &config),
+        other => return Err(format!("'{}' uses unsupported sample format {}", device_name, other)),
+    }
+    .map_err(|e| {
+            format!(
+                "Couldn't open '{}': {}; check microphone permissions or whether another app holds the device",
+                device_name, e
+            )
+        })?;
+    // The stream closes when it goes out of scope
+    stream.play().map_err(|e| format!("Couldn't start '{}': {}", device_name, e))?;
+
+    Ok(format!("{}Hz, {} channel(s), {}", config.sample_rate.0, config.channels, sample_format))
+}
+
+/// An input stream in the device's own sample format that discards what it hears
+fn build_check_stream<T>(device: &cpal::Device, config: &cpal::StreamConfig) -> Result<cpal::Stream, cpal::BuildStreamError>
+where
+    T: cpal::SizedSample,
+{
+    device.build_input_stream(
+        config,
+        |_: &[T], _: &cpal::InputCallbackInfo| {},
+        |err| eprintln!("Self-check stream error: {}", err),
+        None,
+    )
+}
+
+/// Self-check: confirm the active provider accepts its API key
+pub(crate) fn check_provider() -> Result<String, String> {
+    let route = resolve_route(&WHISPER_CONFIG.lock());
+    let (name, url, auth) = match route {
+        #[cfg(feature = "whisper-local")]
+        Route::Local { .. } => return Ok("Using local Whisper, no API key needed".to_string()),
+        // A self-hosted server configured without a key gets no Authorization header
+        Route::OpenAi { api_key, base_url, .. } => (
+            "OpenAI",
+            format!("{}/models", base_url),
+            (!api_key.is_empty()).then(|| ("Authorization", format!("Bearer {}", api_key))),
+        ),
+        Route::Groq { api_key, .. } => (
+            "Groq",
+            "https://api.groq.com/openai/v1/models".to_string(),
+            Some(("Authorization", format!("Bearer {}", api_key))),
+        ),
+        Route::AssemblyAi { api_key } => (
+            "AssemblyAI",
+            "https://api.assemblyai.com/v2/transcript?limit=1".to_string(),
+            Some(("Authorization", api_key)),
+        ),
+        #[cfg(feature = "deepgram")]
+        Route::Deepgram { api_key, .. } => (
+            "Deepgram",
+            "https://api.deepgram.com/v1/projects".to_string(),
+            Some(("Authorization", format!("Token {}", api_key))),
+        ),
+        Route::Azure { api_key, region } => (
+            "Azure",
+            format!("https://{}.api.cognitive.microsoft.com/speechtotext/v3.1/models/base?top=1", region),
+            Some(("Ocp-Apim-Subscription-Key", api_key)),
+        ),
+        Route::Unconfigured => {
+            return Err("No transcription provider configured; add an API key in Settings".to_string())
+        }
+    };
+
+    let mut request = api_client()?.get(url);
+    if let Some((header, value)) = auth {
+        request = request.header(header, value);
+    }
+    let response = request
+        .send()
+        .map_err(|e| format!("Couldn't reach {}: {}; check your network connection", name, e))?;
+    let status = response.status();
+    if status.as_u16() == 401 || status.as_u16() == 403 {
+        return Err(format!("{} rejected the API key ({}); check it in Settings", name, status));
+    }
+    if !status.is_success() {
+        return Err(format!("{} returned {}", name, status));
+    }
+    Ok(format!("{} accepted the API key", name))
+}
+
+/// Self-check: load the local Whisper model, if local transcription is enabled
+pub(crate) fn check_local_model() -> Option<Result<String, String>> {
+    let config = WHISPER_CONFIG.lock();
+    if !config.use_local {
+        return None;
+    }
+
+    #[cfg(feature = "whisper-local")]
+    {
+        let Some(model_path) = config.model_path.clone() else {
+            return Some(Err("Local Whisper is enabled but no model path is set".to_string()));
+        };
+        drop(config);
+        Some(
+            local_context(&model_path)
+                .map(|_| format!("Loaded {}", model_path))
+                .map_err(|e| format!("Failed to load {}: {}; check the model path", model_path, e)),
+        )
+    }
+    #[cfg(not(feature = "whisper-local"))]
+    Some(Err(
+        "Local Whisper is enabled but this build lacks the whisper-local feature".to_string(),
+    ))
+}
+
+pub fn stop_capture() -> Result<(), KoeError> {
+    // Release atomically; the cleanup below is safe to repeat, but only the stop that
+    // actually ended capture sends push-to-talk audio
+    let was_capturing = release_capture();
+    let flush_reason = (was_capturing && PUSH_TO_TALK.load(Ordering::SeqCst)).then_some("Push-to-talk released");
+    finish_capture(was_capturing, flush_reason);
+    Ok(())
+}
+
+/// Release the device and everything tied to a capture session that has just been released,
+/// transcribing what's buffered if `flush_reason` is given and discarding it otherwise
+fn finish_capture(was_capturing: bool, flush_reason: Option<&str>) {
+    // End any named recording, keeping what it captured
+    {
+        let mut named = NAMED_BUFFERS.lock();
+        named.recording = None;
+        named.owns_capture = false;
+    }
+
+    // Drop the stream on the audio thread, waiting so the device is released on return
+    let (reply, dropped) = mpsc::channel();
+    if send_audio_command(AudioCommand::Stop(reply)).is_ok() && dropped.recv().unwrap_or(false) {
+        let worker = DEFERRED_WORKER.lock().take();
+        if let Some(worker) = worker {
+            worker.join().ok();
+        }
+    }
+
+    // Close any realtime session (flushes trailing results first)
+    realtime::stop();
+
+    // Finalize any file recording and its subtitles; a failure still stops capture
+    match recording::finish() {
+        Ok(Some(path)) => println!("Session audio saved to {}", path.display()),
+        Ok(None) => {}
+        Err(e) => eprintln!("Failed to finish session recording: {}", e),
+    }
+
+    // Push-to-talk and the end of a fed file send the held audio as one segment
+    let app = EVENT_APP.lock().clone();
+    match (app, flush_reason) {
+        (Some(app), Some(reason)) => flush_buffer(app, reason),
+        _ => AUDIO_BUFFER.lock().samples.clear(),
+    }
+    *CAPTURE_STATUS.lock() = CaptureStatus::default();
+
+    if was_capturing {
+        println!("Voice capture stopped");
+    }
+}
+
+/// Feed a WAV file through the live capture pipeline in place of the microphone, at `speed`
+/// times real time (0 feeds it as fast as possible). Capture stops by itself at the end of
+/// the file, after transcribing what's left in the buffer
+pub fn start_capture_from_file(app: AppHandle, path: &Path, speed: f32) -> Result<(), KoeError> {
+    if !speed.is_finite() || speed < 0.0 {
+        return Err(format!("Playback speed must be 0 or more, got {}", speed).into());
+    }
+    let (samples, sample_rate) = recording::decode_wav(path)?;
+    let Some(capture) = claim_capture() else {
+        return Err("Capture is already running; stop it before feeding a file".into());
+    };
+
+    typing::reset();
+    DROPOUT_COUNT.store(0, Ordering::SeqCst);
+    {
+        let mut buffer = AUDIO_BUFFER.lock();
+        buffer.sample_rate = sample_rate;
+        buffer.samples.clear();
+    }
+    let session = streaming_session(&WHISPER_CONFIG.lock());
+    if let Some((provider, api_key)) = &session {
+        if let Err(e) = realtime::start(app.clone(), provider, api_key, sample_rate) {
+            release_capture();
+            return Err(e.into());
+        }
+    }
+    // decode_wav already downmixed, so the pipeline sees a mono device at the file's rate
+    let mut pipeline = CapturePipeline::new(app.clone(), 1, sample_rate, Downmix::default(), session.is_some());
+    {
+        let mut status = CAPTURE_STATUS.lock();
+        status.device_name = Some(format!("File: {}", path.display()));
+        status.sample_rate = sample_rate;
+        status.channels = 1;
+    }
+
+    app.emit_to(EventTarget::Any, "voice:state", "listening").ok();
+    println!("Feeding {} through capture at {}x", path.display(), speed);
+
+    // 10ms blocks, like a device callback
+    let block = (sample_rate as usize / 100).max(1);
+    std::thread::spawn(move || {
+        let started = Instant::now();
+        for (i, data) in samples.chunks(block).enumerate() {
+            if !session_active(capture) {
+                // stop_capture ended it early, and capture may since belong to someone else
+                return;
+            }
+            match feed_due((i + 1) * block, sample_rate, speed) {
+                Some(due) => {
+                    if let Some(wait) = due.checked_sub(started.elapsed()) {
+                        std::thread::sleep(wait);
+                    }
+                }
+                // Flat out, segments would pile up faster than they're transcribed and be dropped
+                None => wait_for_segment_room(capture),
+            }
+            pipeline.process_guarded(data, None);
+            emit_capture_notices(&app);
+        }
+        if release_session(capture) {
+            finish_capture(true, Some("End of file"));
+            app.emit_to(EventTarget::Any, "voice:state", "stopped").ok();
+        }
+    });
+    Ok(())
+}
+
+/// Block while the segment queue is full, so a file fed flat out doesn't overrun it
+fn wait_for_segment_room(session: u64) {
+    let (queue, _) = &*SEGMENT_QUEUE;
+    while queue.lock().len() >= SEGMENT_QUEUE_CAPACITY && session_active(session) {
+        std::thread::sleep(Duration::from_millis(10));
+    }
+}
+
+/// When the first `fed` samples of a file would have finished arriving from a device
+/// running `speed` times real time; None when feeding as fast as possible
+fn feed_due(fed: usize, sample_rate: u32, speed: f32) -> Option<Duration> {
+    (speed > 0.0).then(|| Duration::from_secs_f64(fed as f64 / sample_rate as f64 / speed as f64))
+}
+
+/// Start capture that also writes the session to a WAV file, with optional paired subtitles
+pub fn start_capture_to_file(
+    app: AppHandle,
+    path: std::path::PathBuf,
+    subtitles: Option<recording::SubtitleFormat>,
+) -> Result<(), String> {
+    let Some(session) = claim_capture() else {
+        return Err("Capture is already running; stop it before recording to a file".to_string());
+    };
+    start_claimed(&app, session).map_err(|e| e.to_string())?;
+
+    let sample_rate = AUDIO_BUFFER.lock().sample_rate;
+    if let Err(e) = recording::start(path, sample_rate, subtitles) {
+        stop_capture().ok();
+        return Err(e);
+    }
+    Ok(())
+}
+
+/// Start capturing into a named buffer (replacing any buffer of that name)
+pub fn record_to_buffer(app: AppHandle, name: String) -> Result<(), String> {
+    // Reserve the recording in the same lock as the check, so only one of two racing calls gets it
+    {
+        let mut named = NAMED_BUFFERS.lock();
+        if let Some(current) = named.recording.as_ref() {
+            return Err(format!("Already recording to buffer '{}'", current));
+        }
+        named.recording = Some(name.clone());
+    }
+
+    let owns_capture = match claim_capture() {
+        Some(session) => {
+            if let Err(e) = start_claimed(&app, session) {
+                NAMED_BUFFERS.lock().recording = None;
+                return Err(e.to_string());
+            }
+            true
+        }
+        None => false,
+    };
+
+    let sample_rate = AUDIO_BUFFER.lock().sample_rate;
+    let mut named = NAMED_BUFFERS.lock();
+    // Stopped while capture was starting: don't leave capture running for nobody
+    if named.recording.as_deref() != Some(name.as_str()) {
+        drop(named);
+        if owns_capture {
+            stop_capture().map_err(|e| e.to_string())?;
+        }
+        return Err(format!("Recording to buffer '{}' was stopped as it started", name));
+    }
+    named.buffers.insert(name.clone(), AudioBuffer { samples: VecDeque::new(), sample_rate });
+    println!("Recording to buffer '{}'", name);
+    named.owns_capture = owns_capture;
+    Ok(())
+}
+
+/// Stop recording into a named buffer, stopping capture if the recording started it
+pub fn stop_recording_to_buffer(name: &str) -> Result<(), String> {
+    let owns_capture = {
+        let mut named = NAMED_BUFFERS.lock();
+        if named.recording.as_deref() != Some(name) {
+            return Err(format!("Not recording to buffer '{}'", name));
+        }
+        named.recording = None;
+        std::mem::take(&mut named.owns_capture)
+    };
+
+    if owns_capture {
+        stop_capture().map_err(|e| e.to_string())?;
+    }
+    println!("Stopped recording to buffer '{}'", name);
+    Ok(())
+}
+
+/// Transcribe a named buffer through the configured provider; the buffer is kept
+pub fn transcribe_buffer(name: &str) -> Result<String, KoeError> {
+    let (samples, sample_rate) = {
+        let named = NAMED_BUFFERS.lock();
+        if named.recording.as_deref() == Some(name) {
+            return Err(format!("Buffer '{}' is still recording", name).into());
+        }
+        let buffer = named
+            .buffers
+            .get(name)
+            .ok_or_else(|| format!("No buffer named '{}'", name))?;
+        (buffer.samples.iter().copied().collect::<Vec<_>>(), buffer.sample_rate)
+    };
+
+    let transcript = transcribe_audio(&samples, sample_rate, Pass::Final(None))?.text;
+    Ok(transcript.map(|t| postprocess_transcript(&t)).unwrap_or_default())
+}
+
+/// Named buffers, sorted by name
+pub fn list_buffers() -> Vec<BufferInfo> {
+    let named = NAMED_BUFFERS.lock();
+    let mut infos: Vec<BufferInfo> = named
+        .buffers
+        .iter()
+        .map(|(name, buffer)| BufferInfo {
+            name: name.clone(),
+            duration_secs: buffer.samples.len() as f32 / buffer.sample_rate as f32,
+            sample_rate: buffer.sample_rate,
+            recording: named.recording.as_ref() == Some(name),
+        })
+        .collect();
+    infos.sort_by(|a, b| a.name.cmp(&b.name));
+    infos
+}
+
+pub fn delete_buffer(name: &str) -> Result<(), String> {
+    let mut named = NAMED_BUFFERS.lock();
+    if named.recording.as_deref() == Some(name) {
+        return Err(format!("Buffer '{}' is still recording", name));
+    }
+    named
+        .buffers
+        .remove(name)
+        .map(|_| ())
+        .ok_or_else(|| format!("No buffer named '{}'", name))
+}
+
+/// Transcribe a WAV file with segment and word timestamps and format it as SRT
+pub fn transcribe_to_srt(path: &str) -> Result<String, KoeError> {
+    let (samples, sample_rate) = recording::decode_wav(std::path::Path::new(path))
+        .map_err(|e| format!("Failed to read {}: {}", path, e))?;
+    let samples = resampler::resample(&samples, sample_rate, 16000);
+
+    let (route, language) = {
+        let config = WHISPER_CONFIG.lock();
+        let route = resolve_route(&config);
+        let language = config
+            .provider_defaults
+            .get(route.provider())
+            .and_then(|d| d.language.clone())
+            .unwrap_or_else(|| config.language.clone());
+        (route, Some(language).filter(|l| l != "auto"))
+    };
+
+    // Keep each upload well under provider file-size limits
+    let mut segments = Vec::new();
+    let mut words = Vec::new();
+    for (i, chunk) in samples.chunks(16000 * TIMESTAMP_CHUNK_SECS).enumerate() {
+        let result = transcribe_timestamped(&route, chunk, language.as_deref())?;
+        let (chunk_segments, chunk_words) = subtitles::parse_verbose(&result, (i * TIMESTAMP_CHUNK_SECS) as f64);
+        segments.extend(chunk_segments);
+        words.extend(chunk_words);
+    }
+    if segments.is_empty() {
+        return Err(KoeError::Transcription(format!("No speech found in {}", path)));
+    }
+    Ok(recording::format_srt(&subtitles::layout(&segments, &words)))
+}
+
+/// Transcribe 16kHz audio into an OpenAI-style verbose_json value with segments (and words, where supported)
+fn transcribe_timestamped(route: &Route, samples: &[f32], language: Option<&str>) -> Result<serde_json::Value, KoeError> {
+    let (name, url, api_key, model) = match route {
+        #[cfg(feature = "whisper-local")]
+        Route::Local { model_path } => {
+            return transcribe_local_segments(samples, model_path, language).map_err(KoeError::Transcription)
+        }
+        // Timestamp granularities are only offered on whisper-1 (self-hosted servers keep their model)
+        Route::OpenAi { api_key, model, base_url } => (
+            "OpenAI",
+            format!("{}/audio/transcriptions", base_url),
+            api_key,
+            if base_url == OPENAI_BASE_URL { "whisper-1" } else { model.as_str() },
+        ),
+        Route::Groq { api_key, model } => (
+            "Groq",
+            "https://api.groq.com/openai/v1/audio/transcriptions".to_string(),
+            api_key,
+            groq_model_name(model),
+        ),
+        Route::AssemblyAi { .. } => return Err("SRT export isn't supported with AssemblyAI yet".into()),
+        #[cfg(feature = "deepgram")]
+        Route::Deepgram { .. } => return Err("SRT export isn't supported with Deepgram yet".into()),
+        Route::Azure { .. } => return Err("SRT export isn't supported with Azure yet".into()),
+        Route::Unconfigured => {
+            return Err("No transcription provider configured; add an API key in Settings".into())
+        }
+    };
+
+    let wav_data = samples_to_wav(samples, 16000)?;
+    let response = send_with_retry(name, |client| {
+        let part = reqwest::blocking::multipart::Part::bytes(wav_data.clone())
+            .file_name("audio.wav")
+            .mime_str("audio/wav")
+            .map_err(|e| e.to_string())?;
+        let mut form = reqwest::blocking::multipart::Form::new()
+            .part("file", part)
+            .text("model", model.to_string());
+        for (name, value) in request_fields(TranscriptionTask::Transcribe, language, true, true) {
+            form = form.text(name, value);
+        }
+        Ok(bearer(client.post(&url), api_key).multipart(form))
+    })
+    .map_err(|e| e.into_koe(name))?;
+    response.json().map_err(|e| KoeError::Network(e.to_string()))
+}
+
+/// Apply configured post-processing to a raw provider transcript
+fn postprocess_transcript(raw: &str) -> String {
+    #[cfg(feature = "punctuation-restore")]
+    if WHISPER_CONFIG.lock().restore_punctuation {
+        return crate::punctuation::restore(raw);
+    }
+    raw.trim().to_string()
+}
+
+/// Receives transcripts produced by the voice module
+pub trait TranscriptSink: Send + Sync {
+    fn on_transcript(&self, event: &TranscriptEvent);
+}
+
+/// Default sink: emits `voice:transcript` to the frontend
+pub struct EventSink {
+    app: AppHandle,
+}
+
+impl EventSink {
+    pub fn new(app: AppHandle) -> Self {
+        EventSink { app }
+    }
+}
+
+impl TranscriptSink for EventSink {
+    fn on_transcript(&self, event: &TranscriptEvent) {
+        if let Err(e) = self.app.emit_to(EventTarget::Any, "voice:transcript", event.clone()) {
+            eprintln!("Failed to emit transcript: {}", e);
+        }
+    }
+}
+
+/// Route transcripts to a custom sink instead of the Tauri event
+pub fn set_transcript_sink(sink: Arc<dyn TranscriptSink>) {
+    *TRANSCRIPT_SINK.lock() = Some(sink);
+}
+
+/// Fold one segment's timings into the running averages for its provider and model
+fn accumulate_stats(stats: &mut Vec<ModelStats>, metrics: &TranscriptionMetrics) {
+    let index = match stats.iter().position(|s| s.provider == metrics.provider && s.model == metrics.model) {
+        Some(index) => index,
+        None => {
+            stats.push(ModelStats {
+                provider: metrics.provider.clone(),
+                model: metrics.model.clone(),
+                segments: 0,
+                average_latency_ms: 0.0,
+                average_audio_ms: 0.0,
+            });
+            stats.len() - 1
+        }
+    };
+    let entry = &mut stats[index];
+    entry.segments += 1;
+    let n = entry.segments as f64;
+    entry.average_latency_ms += (metrics.latency_ms as f64 - entry.average_latency_ms) / n;
+    entry.average_audio_ms += (metrics.audio_ms as f64 - entry.average_audio_ms) / n;
+}
+
+/// Average transcription latency per provider and model, for comparing them
+pub fn get_transcription_stats() -> Vec<ModelStats> {
+    TRANSCRIPTION_STATS.lock().clone()
+}
+
+/// Sequence number for a transcript not tied to a queued chunk: a final takes the next number,
+/// while an interim shares the number of the final that will replace it
+pub(crate) fn transcript_seq(is_final: bool) -> u64 {
+    if is_final {
+        NEXT_SEQ.fetch_add(1, Ordering::SeqCst)
+    } else {
+        NEXT_SEQ.load(Ordering::SeqCst)
+    }
+}
+
+/// Deliver a transcript to the configured sink, keeping the unprocessed text alongside
+pub(crate) fn emit_transcript(app: &AppHandle, raw: &str, is_final: bool, seq: u64) {
+    let text = postprocess_transcript(raw);
+    // A spoken command runs instead of being typed
+    if is_final && voice_commands::dispatch(app, &text) != voice_commands::Outcome::Dictation {
+        return;
+    }
+    deliver_transcript(app, raw, text, is_final, seq);
+}
+
+/// Send a transcript already checked against the voice commands to the sink
+fn deliver_transcript(app: &AppHandle, raw: &str, text: String, is_final: bool, seq: u64) {
+    let event = TranscriptEvent {
+        insert_text: typing::insert_text(&text, is_final),
+        text,
+        raw_text: raw.to_string(),
+        is_final,
+        seq,
+    };
+    if is_final {
+        println!("Transcript: {}", event.text);
+    }
+
+    let sink = TRANSCRIPT_SINK.lock().clone();
+    match sink {
+        Some(sink) => sink.on_transcript(&event),
+        None => EventSink::new(app.clone()).on_transcript(&event),
+    }
+}
+
+/// Append one mono sample per whole interleaved frame in `data`
+fn downmix(data: &[f32], channels: u16, mode: Downmix, out: &mut Vec<f32>) {
+    let channels = channels.max(1) as usize;
+    match mode {
+        // Stride straight to the wanted channel rather than walking every frame's channels
+        Downmix::First => out.extend(data.iter().step_by(channels)),
+        Downmix::Channel(channel) => out.extend(data.iter().skip(channel as usize).step_by(channels)),
+        Downmix::Average if channels == 1 => out.extend_from_slice(data),
+        Downmix::Average => out.extend(
+            data.chunks_exact(channels)
+                .map(|frame| frame.iter().sum::<f32>() / channels as f32),
+        ),
+    }
+}
+
+/// Feeds interleaved blocks through `downmix` a whole frame at a time,
+/// carrying a trailing partial frame over to the next block so channels stay aligned
+struct Deinterleaver {
+    channels: u16,
+    mode: Downmix,
+    partial: Vec<f32>,
+}
+
+impl Deinterleaver {
+    fn new(channels: u16, mode: Downmix) -> Self {
+        Deinterleaver {
+            channels,
+            mode,
+            partial: Vec::with_capacity(channels as usize),
+        }
+    }
+
+    /// Append mono samples for each frame completed by `data`
+    fn push(&mut self, mut data: &[f32], out: &mut Vec<f32>) {
+        let frame_len = self.channels.max(1) as usize;
+
+        // Finish the frame left over from the previous block first
+        if !self.partial.is_empty() {
+            let needed = (frame_len - self.partial.len()).min(data.len());
+            self.partial.extend_from_slice(&data[..needed]);
+            data = &data[needed..];
+            if self.partial.len() < frame_len {
+                return;
+            }
+            downmix(&self.partial, self.channels, self.mode, out);
+            self.partial.clear();
+        }
+
+        let whole = data.len() - data.len() % frame_len;
+        downmix(&data[..whole], self.channels, self.mode, out);
+        self.partial.extend_from_slice(&data[whole..]);
+    }
+}
+
+/// A chunk's transcript, with whatever else the provider reported about it
+#[derive(Debug, Default)]
+struct Transcription {
+    text: Option<String>,
+    /// Speaker turns, timed from the start of the chunk, when the provider diarized it
+    utterances: Vec<conversation::Turn>,
+    /// Word and segment timings, when word timestamps are on
+    detailed: Option<DetailedTranscript>,
+    /// The provider and model that transcribed it
+    provider: &'static str,
+    model: Option<String>,
+}
+
+impl From<Option<String>> for Transcription {
+    fn from(text: Option<String>) -> Self {
+        Transcription { text, ..Default::default() }
+    }
+}
+
+/// What a transcription is for
+#[derive(Clone, Copy)]
+enum Pass<'a> {
+    /// A finished chunk, with where a streaming provider sends the text so far (and the
+    /// chunk's sequence number); None disables streaming
+    Final(Option<(&'a AppHandle, u64)>),
+    /// A best-effort preview of speech still buffering: it doesn't stream, feed the language
+    /// gate or produce the unconfigured placeholder
+    Interim,
+}
+
+impl<'a> Pass<'a> {
+    fn partials(self) -> Option<(&'a AppHandle, u64)> {
+        match self {
+            Pass::Final(partials) => partials,
+            Pass::Interim => None,
+        }
+    }
+
+    /// Whether an auto-detected language goes through the language gate
+    fn gates_language(self) -> bool {
+        matches!(self, Pass::Final(_))
+    }
+}
+
+/// Transcribe audio using available method (API or local)
+fn transcribe_audio(samples: &[f32], sample_rate: u32, pass: Pass) -> Result<Transcription, KoeError> {
+    // Resample to 16kHz if needed (Whisper expects 16kHz)
+    let (mut samples_16k, rate_16k) = if sample_rate != 16000 {
+        println!("Resampling from {}Hz to 16000Hz ({} samples -> ~{} samples)",
+            sample_rate, samples.len(), samples.len() * 16000 / sample_rate as usize);
+        (resampler::resample(samples, sample_rate, 16000), 16000)
+    } else {
+        (samples.to_vec(), sample_rate)
+    };
+
+    // Even out dynamics so trailing-off words survive
+    compressor::apply(&mut samples_16k, rate_16k);
+    // Then lift quiet mics toward full scale
+    normalize::apply(&mut samples_16k);
+
+    // Resolve the route up front so the config lock isn't held during network I/O
+    let (route, defaults, global_language, global_prompt, task) = {
+        let config = WHISPER_CONFIG.lock();
+        let route = resolve_route(&config);
+        let defaults = config.provider_defaults.get(route.provider()).cloned().unwrap_or_default();
+        (route, defaults, config.language.clone(), config.prompt.clone(), config.task)
+    };
+    let (provider, model) = (route.provider(), route.model().map(str::to_string));
+    // As with language, the active provider's own prompt wins
+    let fixed_prompt = defaults.prompt.or(global_prompt);
+    let prompt = combine_prompts(fixed_prompt.as_deref(), context_prompt().as_deref());
+    let prompt = prompt.as_deref();
+    // The active provider's language wins; None means auto-detect
+    let language = Some(defaults.language.unwrap_or(global_language)).filter(|l| l != "auto");
+    let language = language.as_deref();
+
+    let transcription = match route {
+        #[cfg(feature = "whisper-local")]
+        Route::Local { model_path } => {
+            transcribe_local(&samples_16k, rate_16k, &model_path, prompt, language).map(Transcription::from)
+        }
+        Route::OpenAi { api_key, model, base_url } => {
+            let request = ChunkRequest { model: &model, prompt, language, task, pass };
+            transcribe_openai(&samples_16k, rate_16k, &api_key, &base_url, request)
+        }
+        Route::Groq { api_key, model } => {
+            let request = ChunkRequest { model: &model, prompt, language, task, pass };
+            transcribe_groq(&samples_16k, rate_16k, &api_key, request)
+        }
+        Route::AssemblyAi { api_key } => transcribe_assemblyai(&samples_16k, rate_16k, &api_key, language),
+        #[cfg(feature = "deepgram")]
+        Route::Deepgram { api_key, model } => {
+            transcribe_deepgram(&samples_16k, rate_16k, &api_key, &model, language).map(Transcription::from)
+        }
+        Route::Azure { api_key, region } => {
+            transcribe_azure(&samples_16k, rate_16k, &api_key, &region, language).map(Transcription::from)
+        }
+        Route::Unconfigured if matches!(pass, Pass::Interim) => Ok(Transcription::default()),
+        Route::Unconfigured => {
+            // No transcription method available - return placeholder
+            let duration_secs = samples_16k.len() as f32 / rate_16k as f32;
+            Ok(Transcription::from((duration_secs > 0.5).then(|| {
+                format!("[Audio: {:.1}s - configure API key in Settings for transcription]", duration_secs)
+            })))
+        }
+    };
+    transcription.map(|transcription| Transcription { provider, model, ..transcription })
+}
+
+/// Decide which backend handles the next chunk, applying provider fallbacks
+fn resolve_route(config: &WhisperConfig) -> Route {
+    // Try local whisper first if configured
+    #[cfg(feature = "whisper-local")]
+    if config.use_local {
+        if let Some(ref model_path) = config.model_path {
+            return Route::Local { model_path: model_path.clone() };
+        }
+    }
+
+    let default_model = |provider: &str| {
+        config.provider_defaults.get(provider).and_then(|d| d.model.clone())
+    };
+
+    // Route to configured provider, falling through to OpenAI if its key is missing
+    match config.provider.as_str() {
+        "groq" => {
+            if let Some(ref api_key) = config.groq_api_key {
+                let model = default_model("groq").unwrap_or_else(|| config.model.clone());
+                return Route::Groq { api_key: api_key.clone(), model };
+            }
+        }
+        "assemblyai" => {
+            if let Some(ref api_key) = config.assemblyai_api_key {
+                return Route::AssemblyAi { api_key: api_key.clone() };
+            }
+        }
+        #[cfg(feature = "deepgram")]
+        "deepgram" => {
+            if let Some(ref api_key) = config.deepgram_api_key {
+                let model = default_model("deepgram").unwrap_or_else(|| DEEPGRAM_DEFAULT_MODEL.to_string());
+                return Route::Deepgram { api_key: api_key.clone(), model };
+            }
+        }
+        "azure" => {
+            if let (Some(api_key), Some(region)) = (&config.azure_api_key, &config.azure_region) {
+                return Route::Azure { api_key: api_key.clone(), region: region.clone() };
+            }
+        }
+        _ => {}
+    }
+
+    // "openai" or default; a model picked for a provider that fell through isn't OpenAI's
+    let model = Some(config.model.clone()).filter(|m| config.provider == "openai" && !m.is_empty());
+    match openai_key(config) {
+        Some(api_key) => Route::OpenAi {
+            api_key,
+            model: default_model("openai").or(model).unwrap_or_else(|| "whisper-1".to_string()),
+            base_url: config.base_url.clone().unwrap_or_else(|| OPENAI_BASE_URL.to_string()),
+        },
+        None => Route::Unconfigured,
+    }
+}
+
+/// The OpenAI key; self-hosted servers often need none, so a custom base URL makes it optional
+fn openai_key(config: &WhisperConfig) -> Option<String> {
+    config.api_key.clone().or_else(|| config.base_url.as_ref().map(|_| String::new()))
+}
+
+/// Send OpenAI-provider requests to an OpenAI-compatible server (e.g. whisper.cpp's server,
+/// LocalAI) at `url`, such as "http://localhost:8080/v1"; None restores api.openai.com
+pub fn set_openai_base_url(url: Option<String>) -> Result<(), String> {
+    let base_url = url.filter(|u| !u.trim().is_empty()).map(|u| normalize_base_url(&u)).transpose()?;
+    WHISPER_CONFIG.lock().base_url = base_url;
+    MODEL_CACHE.lock().remove("openai");
+    persist();
+    Ok(())
+}
+
+/// Request OpenAI text-to-speech with the transcription API key and base URL. The
+/// response body streams in `format`; "pcm" is raw 24kHz 16-bit mono.
+pub fn request_openai_speech(
+    text: &str,
+    model: &str,
+    voice: &str,
+    speed: Option<f32>,
+    format: &str,
+) -> Result<reqwest::blocking::Response, String> {
+    let (api_key, base_url) = {
+        let config = WHISPER_CONFIG.lock();
+        let api_key = openai_key(&config).ok_or("OpenAI API key not configured")?;
+        (api_key, config.base_url.clone().unwrap_or_else(|| OPENAI_BASE_URL.to_string()))
+    };
+
+    let mut body = serde_json::json!({
+        "model": model,
+        "voice": voice,
+        "input": text,
+        "response_format": format
+    });
+    if let Some(speed) = speed {
+        body["speed"] = serde_json::json!(speed);
+    }
+    send_with_retry("OpenAI TTS", |client| {
+        Ok(bearer(client.post(format!("{}/audio/speech", base_url)), &api_key).json(&body))
+    })
+    .map_err(|e| format!("OpenAI TTS {}", e))
+}
+
+/// Authorize an OpenAI-style request with `api_key`, sending no Authorization header at all
+/// when it's empty (a self-hosted server with no key)
+fn bearer(request: reqwest::blocking::RequestBuilder, api_key: &str) -> reqwest::blocking::RequestBuilder {
+    if api_key.is_empty() {
+        request
+    } else {
+        request.header("Authorization", format!("Bearer {}", api_key))
+    }
+}
+
+/// Validate an http(s) base URL, dropping any trailing slash
+fn normalize_base_url(url: &str) -> Result<String, String> {
+    let url = url.trim().trim_end_matches('/');
+    let parsed = reqwest::Url::parse(url).map_err(|e| format!("Invalid base URL '{}': {}", url, e))?;
+    if !matches!(parsed.scheme(), "http" | "https") || parsed.host_str().is_none() {
+        return Err(format!("Base URL '{}' must be an http:// or https:// address", url));
+    }
+    Ok(url.to_string())
+}
+
+/// Map the configured model onto a Groq model, substituting the OpenAI default
+fn groq_model_name(model: &str) -> &str {
+    if model.is_empty() || model == "whisper-1" {
+        "whisper-large-v3-turbo" // Default Groq model (fastest)
+    } else {
+        model
+    }
+}
+
+/// What to ask an OpenAI-format API for one chunk
+#[derive(Clone, Copy)]
+struct ChunkRequest<'a> {
+    model: &'a str,
+    prompt: Option<&'a str>,
+    language: Option<&'a str>, // None auto-detects
+    task: TranscriptionTask,
+    pass: Pass<'a>,
+}
+
+/// Transcribe using OpenAI Whisper API, or an OpenAI-compatible server at `base_url`
+fn transcribe_openai(
+    samples: &[f32],
+    sample_rate: u32,
+    api_key: &str,
+    base_url: &str,
+    request: ChunkRequest,
+) -> Result<Transcription, KoeError> {
+    let ChunkRequest { model, prompt, language, task, pass } = request;
+    // Write samples to WAV in memory
+    let wav_data = samples_to_wav(samples, sample_rate)?;
+
+    let (temperature, limits) = {
+        let config = WHISPER_CONFIG.lock();
+        (config.temperature, config.confidence)
+    };
+    let verbose = wants_verbose(model, task, language, pass.gates_language(), wants_word_timestamps(task), limits);
+    let word_timestamps = verbose && wants_word_timestamps(task);
+
+    // verbose_json and translations need the whole response, so only plain live transcription
+    // streams; the stream request never asks for verbose_json, which streaming models reject
+    if !verbose && task == TranscriptionTask::Transcribe {
+        if let Some(target) = pass.partials() {
+            if let Some(result) = transcribe_openai_stream(target, &wav_data, api_key, base_url, &request) {
+                return result.map(Transcription::from);
+            }
+        }
+    }
+
+    // Call OpenAI Whisper API, rebuilding the form for each attempt
+    let response = send_with_retry("OpenAI", |client| {
+        let part = reqwest::blocking::multipart::Part::bytes(wav_data.clone())
+            .file_name("audio.wav")
+            .mime_str("audio/wav")
+            .map_err(|e| e.to_string())?;
+
+        let mut form = reqwest::blocking::multipart::Form::new()
+            .part("file", part)
+            .text("model", model.to_string());
+        for (name, value) in request_fields(task, language, verbose, word_timestamps) {
+            form = form.text(name, value);
+        }
+        if let Some(prompt) = prompt {
+            form = form.text("prompt", prompt.to_string());
+        }
+        if let Some(temperature) = temperature {
+            form = form.text("temperature", temperature.to_string());
+        }
+
+        Ok(bearer(client.post(format!("{}/audio/{}", base_url, task.endpoint())), api_key).multipart(form))
+    })
+    .map_err(|e| e.into_koe("OpenAI"))?;
+
+    let body = response.text().map_err(|e| KoeError::Network(e.to_string()))?;
+    if pass.gates_language() && language.is_none() && task == TranscriptionTask::Transcribe {
+        if let Some(pinned) = gated_language(&body) {
+            // Detection wasn't trusted: redo the chunk in the last confident language
+            let request = ChunkRequest { language: Some(&pinned), ..request };
+            return transcribe_openai(samples, sample_rate, api_key, base_url, request);
+        }
+    }
+    openai_transcription(&body, word_timestamps)
+}
+
+/// Read an OpenAI-format response, with its word timings if they were asked for
+fn openai_transcription(body: &str, word_timestamps: bool) -> Result<Transcription, KoeError> {
+    Ok(Transcription {
+        text: parse_openai_response(body).map_err(KoeError::Transcription)?,
+        detailed: word_timestamps.then(|| parse_detailed(body, WHISPER_CONFIG.lock().confidence)).flatten(),
+        ..Default::default()
+    })
+}
+
+/// Transcribe with `stream=true`, emitting the text so far as non-final transcripts.
+/// None if the server rejected streaming, so the caller can fall back to a plain request.
+/// The request's task and pass are the caller's to check
+fn transcribe_openai_stream(
+    (app, seq): (&AppHandle, u64),
+    wav_data: &[u8],
+    api_key: &str,
+    base_url: &str,
+    request: &ChunkRequest,
+) -> Option<Result<Option<String>, KoeError>> {
+    let ChunkRequest { model, prompt, language, .. } = *request;
+    let temperature = WHISPER_CONFIG.lock().temperature;
+    let response = send_with_retry("OpenAI", |client| {
+        let part = reqwest::blocking::multipart::Part::bytes(wav_data.to_vec())
+            .file_name("audio.wav")
+            .mime_str("audio/wav")
+            .map_err(|e| e.to_string())?;
+
+        let mut form = reqwest::blocking::multipart::Form::new()
+            .part("file", part)
+            .text("model", model.to_string())
+            .text("stream", "true");
+        if let Some((name, value)) = language_field(language) {
+            form = form.text(name, value);
+        }
+        if let Some(prompt) = prompt {
+            form = form.text("prompt", prompt.to_string());
+        }
+        if let Some(temperature) = temperature {
+            form = form.text("temperature", temperature.to_string());
+        }
+
+        Ok(bearer(client.post(format!("{}/audio/transcriptions", base_url)), api_key).multipart(form))
+    });
+    let response = match response {
+        Ok(response) => response,
+        Err(ApiError::Status(400, body)) => {
+            eprintln!("Streaming transcription unsupported, falling back: {}", body);
+            return None;
+        }
+        Err(e) => return Some(Err(e.into_koe("OpenAI"))),
+    };
+
+    let is_stream = response
+        .headers()
+        .get("content-type")
+        .and_then(|v| v.to_str().ok())
+        .is_some_and(|t| t.starts_with("text/event-stream"));
+    if !is_stream {
+        // Models without streaming (e.g. whisper-1) ignore the flag and answer as usual
+        let body = match response.text() {
+            Ok(body) => body,
+            Err(e) => return Some(Err(KoeError::Network(e.to_string()))),
+        };
+        return Some(parse_openai_response(&body).map_err(KoeError::Transcription));
+    }
+    Some(read_transcript_stream(std::io::BufReader::new(response), |partial| {
+        emit_transcript(app, partial, false, seq)
+    }))
+}
+
+/// Follow an OpenAI transcription event stream, passing the text so far to `on_delta`
+fn read_transcript_stream(
+    reader: impl std::io::BufRead,
+    mut on_delta: impl FnMut(&str),
+) -> Result<Option<String>, KoeError> {
+    let mut text = String::new();
+    for line in reader.lines() {
+        let line = line.map_err(|e| KoeError::Network(e.to_string()))?;
+        let Some(data) = line.strip_prefix("data:").map(str::trim) else {
+            continue;
+        };
+        if data == "[DONE]" {
+            break;
+        }
+        let event = serde_json::from_str::<serde_json::Value>(data)
+            .map_err(|e| KoeError::Transcription(format!("Unreadable transcription event: {}", e)))?;
+        match event["type"].as_str() {
+            Some("transcript.text.delta") => {
+                text.push_str(event["delta"].as_str().unwrap_or_default());
+                on_delta(&text);
+            }
+            Some("transcript.text.done") => {
+                if let Some(done) = event["text"].as_str() {
+                    text = done.to_string();
+                }
+                break;
+            }
+            _ => {}
+        }
+    }
+    Ok(Some(text).filter(|t| !t.trim().is_empty()))
+}
+
+/// Transcribe using Groq Whisper API (faster inference)
+fn transcribe_groq(
+    samples: &[f32],
+    sample_rate: u32,
+    api_key: &str,
+    request: ChunkRequest,
+) -> Result<Transcription, KoeError> {
+    let ChunkRequest { model, prompt, language, task, pass } = request;
+    let wav_data = samples_to_wav(samples, sample_rate)?;
+
+    // Groq uses the same API format as OpenAI
+    let groq_model = groq_model_name(model);
+    let (temperature, limits) = {
+        let config = WHISPER_CONFIG.lock();
+        (config.temperature, config.confidence)
+    };
+    let verbose = wants_verbose(groq_model, task, language, pass.gates_language(), wants_word_timestamps(task), limits);
+    let word_timestamps = verbose && wants_word_timestamps(task);
+
+    let response = send_with_retry("Groq", |client| {
+        let part = reqwest::blocking::multipart::Part::bytes(wav_data.clone())
+            .file_name("audio.wav")
+            .mime_str("audio/wav")
+            .map_err(|e| e.to_string())?;
+
+        let mut form = reqwest::blocking::multipart::Form::new()
+            .part("file", part)
+            .text("model", groq_model.to_string());
+        for (name, value) in request_fields(task, language, verbose, word_timestamps) {
+            form = form.text(name, value);
+        }
+        if let Some(prompt) = prompt {
+            form = form.text("prompt", prompt.to_string());
+        }
+        if let Some(temperature) = temperature {
+            form = form.text("temperature", temperature.to_string());
+        }
+
+        Ok(client
+            .post(format!("https://api.groq.com/openai/v1/audio/{}", task.endpoint()))
+            .header("Authorization", format!("Bearer {}", api_key))
+            .multipart(form))
+    })
+    .map_err(|e| e.into_koe("Groq"))?;
+
+    let body = response.text().map_err(|e| KoeError::Network(e.to_string()))?;
+    if pass.gates_language() && language.is_none() && task == TranscriptionTask::Transcribe {
+        if let Some(pinned) = gated_language(&body) {
+            let request = ChunkRequest { language: Some(&pinned), ..request };
+            return transcribe_groq(samples, sample_rate, api_key, request);
+        }
+    }
+    openai_transcription(&body, word_timestamps)
+}
+
+/// Why a transcription request ultimately failed
+#[derive(Debug, PartialEq)]
+enum ApiError {
+    Timeout(u64),         // seconds waited
+    RateLimited(String),  // 429, with the response body
+    Auth(u16, String),    // 401/403: retrying won't help
+    Server(u16, String),  // 5xx
+    Status(u16, String),  // any other non-success
+    Request(String),      // couldn't build or send the request
+}
+
+impl ApiError {
+    fn from_status(status: u16, body: String) -> Self {
+        match status {
+            401 | 403 => ApiError::Auth(status, body),
+            429 => ApiError::RateLimited(body),
+            500..=599 => ApiError::Server(status, body),
+            _ => ApiError::Status(status, body),
+        }
+    }
+
+    fn is_retryable(&self) -> bool {
+        matches!(self, ApiError::RateLimited(_) | ApiError::Server(..))
+    }
+
+    /// Classify for callers, naming the provider in the message
+    fn into_koe(self, provider: &str) -> KoeError {
+        let message = format!("{} {}", provider, self);
+        match self {
+            ApiError::Auth(..) => KoeError::ApiAuth(message),
+            ApiError::RateLimited(_) => KoeError::ApiRateLimit(message),
+            ApiError::Timeout(_) | ApiError::Request(_) => KoeError::Network(message),
+            ApiError::Server(..) | ApiError::Status(..) => KoeError::Transcription(message),
+        }
+    }
+}
+
+impl std::fmt::Display for ApiError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            ApiError::Timeout(secs) => write!(f, "request timed out after {}s", secs),
+            ApiError::RateLimited(body) => write!(f, "rate limit exceeded (429): {}", body),
+            ApiError::Auth(status, body) => write!(f, "rejected the API key ({}): {}", status, body),
+            ApiError::Server(status, body) | ApiError::Status(status, body) => {
+                write!(f, "API error {}: {}", status, body)
+            }
+            ApiError::Request(e) => write!(f, "request failed: {}", e),
+        }
+    }
+}
+
+/// Set how long an API transcription request may take before it's abandoned (default 30s)
+pub fn set_request_timeout(seconds: u32) -> Result<(), String> {
+    if seconds == 0 {
+        return Err("Request timeout must be at least 1 second".to_string());
+    }
+    REQUEST_TIMEOUT_SECS.store(seconds, Ordering::SeqCst);
+    Ok(())
+}
+
+/// Send a request, retrying rate limits and server errors with exponential backoff.
+/// `build` is called per attempt since multipart bodies can't be cloned.
+fn send_with_retry(
+    provider: &str,
+    build: impl Fn(&reqwest::blocking::Client) -> Result<reqwest::blocking::RequestBuilder, String>,
+) -> Result<reqwest::blocking::Response, ApiError> {
+    let timeout_secs = REQUEST_TIMEOUT_SECS.load(Ordering::SeqCst) as u64;
+    let client = api_client().map_err(ApiError::Request)?;
+
+    let mut attempt = 1;
+    loop {
+        let error = match build(&client).map_err(ApiError::Request)?.send() {
+            Ok(response) if response.status().is_success() => return Ok(response),
+            Ok(response) => {
+                let status = response.status().as_u16();
+                ApiError::from_status(status, response.text().unwrap_or_default())
+            }
+            Err(e) if e.is_timeout() => ApiError::Timeout(timeout_secs),
+            Err(e) => ApiError::Request(e.to_string()),
+        };
+        if !error.is_retryable() || attempt >= MAX_REQUEST_ATTEMPTS {
+            return Err(error);
+        }
+
+        let delay = retry_delay(attempt);
+        eprintln!("{} {}; retrying in {:?}", provider, error, delay);
+        emit_warning(serde_json::json!({
+            "kind": "retry",
+            "provider": provider,
+            "attempt": attempt,
+            "delayMs": delay.as_millis() as u64,
+            "reason": error.to_string()
+        }));
+        std::thread::sleep(delay);
+        attempt += 1;
+    }
+}
+
+/// HTTP client with the configured request timeout
+fn api_client() -> Result<reqwest::blocking::Client, String> {
+    let timeout_secs = REQUEST_TIMEOUT_SECS.load(Ordering::SeqCst) as u64;
+    reqwest::blocking::Client::builder()
+        .timeout(Duration::from_secs(timeout_secs))
+        .build()
+        .map_err(|e| e.to_string())
+}
+
+/// Wait before retrying after failed attempt `attempt` (1-based): 1s, then 2s
+fn retry_delay(attempt: u32) -> Duration {
+    Duration::from_millis(1000 << attempt.saturating_sub(1).min(4))
+}
+
+/// The `language` field pinning a language; auto-detect must not send it at all
+fn language_field(language: Option<&str>) -> Option<(&'static str, String)> {
+    language.map(|code| ("language", code.to_string()))
+}
+
+/// Whether `model` can answer in verbose_json; OpenAI's gpt-4o transcribe models only give
+/// json or text
+fn supports_verbose_json(model: &str) -> bool {
+    !model.starts_with("gpt-4o")
+}
+
+/// Also emit word timings as `voice:transcript_detailed` (OpenAI's whisper-1 and Groq; other
+/// OpenAI models reject verbose_json). Translations never carry timings.
+pub fn set_word_timestamps(enabled: bool) {
+    WORD_TIMESTAMPS.store(enabled, Ordering::SeqCst);
+}
+
+fn wants_word_timestamps(task: TranscriptionTask) -> bool {
+    task == TranscriptionTask::Transcribe && WORD_TIMESTAMPS.load(Ordering::SeqCst)
+}
+
+/// Whether a request should ask for verbose_json: for the language gate on auto-detected
+/// transcriptions, for segment scores when `limits` filters on them, or for word timings.
+/// Never for models that reject it; they go without those extras.
+fn wants_verbose(
+    model: &str,
+    task: TranscriptionTask,
+    language: Option<&str>,
+    gates_language: bool,
+    word_timestamps: bool,
+    limits: ConfidenceLimits,
+) -> bool {
+    let gated = gates_language && task == TranscriptionTask::Transcribe && language.is_none();
+    supports_verbose_json(model) && (gated || word_timestamps || limits != ConfidenceLimits::default())
+}
+
+/// The language and response format fields of an OpenAI-style transcription form. This is
+/// the one place `response_format` is set, so it can't be sent twice.
+fn request_fields(
+    task: TranscriptionTask,
+    language: Option<&str>,
+    verbose: bool,
+    word_timestamps: bool,
+) -> Vec<(&'static str, String)> {
+    // Translations always come out in English and take no language
+    let language = language.filter(|_| task == TranscriptionTask::Transcribe);
+    let mut fields: Vec<_> = language_field(language).into_iter().collect();
+    if verbose {
+        fields.push(("response_format", "verbose_json".to_string()));
+        if word_timestamps {
+            fields.push(("timestamp_granularities[]", "word".to_string()));
+            fields.push(("timestamp_granularities[]", "segment".to_string()));
+        }
+    }
+    fields
+}
+
+/// Transcript text, word timings (seconds from the chunk start) and segment scores from a
+/// verbose_json body, leaving out segments `limits` rejects
+fn parse_detailed(body: &str, limits: ConfidenceLimits) -> Option<DetailedTranscript> {
+    let result: serde_json::Value = serde_json::from_str(body).ok()?;
+    let (_, mut words) = subtitles::parse_verbose(&result, 0.0);
+    if words.is_empty() {
+        return None;
+    }
+    let segments = segment_scores(&result, limits);
+    let text = match kept_text(&segments) {
+        Some(text) => {
+            words.retain(|w| {
+                let middle = (w.start + w.end) / 2.0;
+                segments.iter().any(|s| s.kept && s.start <= middle && middle <= s.end)
+            });
+            text
+        }
+        None => result["text"].as_str()?.trim().to_string(),
+    };
+    Some(DetailedTranscript { text, words, segments })
+}
+
+/// Each verbose_json segment's scores and whether `limits` keeps it
+fn segment_scores(result: &serde_json::Value, limits: ConfidenceLimits) -> Vec<SegmentScore> {
+    let Some(segments) = result["segments"].as_array() else {
+        return Vec::new();
+    };
+    segments
+        .iter()
+        .map(|segment| {
+            let avg_logprob = segment["avg_logprob"].as_f64().unwrap_or(0.0);
+            let no_speech_prob = segment["no_speech_prob"].as_f64().unwrap_or(0.0);
+            let confidence = avg_logprob.exp();
+            SegmentScore {
+                start: segment["start"].as_f64().unwrap_or(0.0),
+                end: segment["end"].as_f64().unwrap_or(0.0),
+                text: segment["text"].as_str().unwrap_or_default().trim().to_string(),
+                avg_logprob,
+                no_speech_prob,
+                confidence,
+                kept: limits.keeps(confidence, no_speech_prob),
+            }
+        })
+        .collect()
+}
+
+/// The transcript rebuilt from kept segments; None when nothing was dropped
+fn kept_text(segments: &[SegmentScore]) -> Option<String> {
+    if segments.iter().all(|s| s.kept) {
+        return None;
+    }
+    let kept: Vec<&str> = segments.iter().filter(|s| s.kept && !s.text.is_empty()).map(|s| s.text.as_str()).collect();
+    Some(kept.join(" "))
+}
+
+/// Language to re-transcribe in if the gate rejects this response's detection
+fn gated_language(body: &str) -> Option<String> {
+    let result: serde_json::Value = serde_json::from_str(body).ok()?;
+    language::gate(&result)
+}
+
+/// Extract the transcript from an OpenAI-style response using the configured parsing
+fn parse_openai_response(body: &str) -> Result<Option<String>, String> {
+    let (mode, text_path, limits) = {
+        let config = WHISPER_CONFIG.lock();
+        (config.parse_mode, config.text_path.clone(), config.confidence)
+    };
+    if let Some(text) = confident_text(body, limits) {
+        return Ok(Some(text).filter(|t| !t.is_empty()));
+    }
+    parse_transcription_response(body, mode, text_path.as_deref())
+}
+
+/// The transcript without segments `limits` rejects; None if the body has no segments or all passed
+fn confident_text(body: &str, limits: ConfidenceLimits) -> Option<String> {
+    if limits == ConfidenceLimits::default() {
+        return None;
+    }
+    let result: serde_json::Value = serde_json::from_str(body).ok()?;
+    let segments = segment_scores(&result, limits);
+    kept_text(&segments)
+}
+
+fn parse_transcription_response(body: &str, mode: ParseMode, text_path: Option<&str>) -> Result<Option<String>, String> {
+    let result: serde_json::Value = match serde_json::from_str(body) {
+        Ok(value) => value,
+        // Some servers reply with the bare transcript
+        Err(_) if mode == ParseMode::Lenient && !body.trim().is_empty() => {
+            return Ok(Some(body.trim().to_string()))
+        }
+        Err(e) => return Err(format!("Invalid transcription response: {}", e)),
+    };
+
+    if let Some(path) = text_path {
+        if let Some(text) = value_at_path(&result, path).and_then(|v| v.as_str()) {
+            return Ok(Some(text.to_string()));
+        }
+        if mode == ParseMode::Strict {
+            return Err(format!("No text at '{}' in transcription response", path));
+        }
+    }
+
+    if let Some(text) = result["text"].as_str() {
+        return Ok(Some(text.to_string()));
+    }
+    match mode {
+        ParseMode::Strict => Ok(None),
+        ParseMode::Lenient => Ok(find_text_like(&result)),
+    }
+}
+
+/// Follow a dotted path; numeric segments index into arrays
+fn value_at_path<'a>(value: &'a serde_json::Value, path: &str) -> Option<&'a serde_json::Value> {
+    path.split('.').try_fold(value, |current, segment| match segment.parse::<usize>() {
+        Ok(index) if current.is_array() => current.get(index),
+        _ => current.get(segment),
+    })
+}
+
+/// Best-effort search for a transcript: a top-level string, or the shallowest text-like field
+fn find_text_like(value: &serde_json::Value) -> Option<String> {
+    let mut queue = std::collections::VecDeque::from([value]);
+    while let Some(current) = queue.pop_front() {
+        if let Some(text) = current.as_str() {
+            return Some(text.to_string());
+        }
+        if let Some(object) = current.as_object() {
+            if let Some(text) = TEXT_LIKE_KEYS.iter().find_map(|key| object.get(*key)?.as_str()) {
+                return Some(text.to_string());
+            }
+            queue.extend(object.values().filter(|v| v.is_object() || v.is_array()));
+        } else if let Some(array) = current.as_array() {
+            queue.extend(array.iter().filter(|v| v.is_object() || v.is_array()));
+        }
+    }
+    None
+}
+
+/// Transcription models a provider offers, queried from its models endpoint when it has one
+pub fn fetch_provider_models(provider: &str) -> Result<Vec<String>, String> {
+    if let Some((fetched_at, models)) = MODEL_CACHE.lock().get(provider) {
+        if fetched_at.elapsed() < MODEL_CACHE_TTL {
+            return Ok(models.clone());
+        }
+    }
+
+    let (url, api_key) = {
+        let config = WHISPER_CONFIG.lock();
+        match provider {
+            "openai" => (
+                format!("{}/models", config.base_url.as_deref().unwrap_or(OPENAI_BASE_URL)),
+                openai_key(&config),
+            ),
+            "groq" => ("https://api.groq.com/openai/v1/models".to_string(), config.groq_api_key.clone()),
+            _ => return static_models(provider),
+        }
+    };
+    let api_key = api_key.ok_or_else(|| format!("No API key configured for {}", provider))?;
+
+    let response = match bearer(api_client()?.get(url), &api_key).send() {
+        Ok(response) => response,
+        // Offline or unreachable: the built-in list is still useful
+        Err(e) => {
+            eprintln!("Model list request failed, using built-in models: {}", e);
+            return static_models(provider);
+        }
+    };
+    if !response.status().is_success() {
+        let status = response.status();
+        let text = response.text().unwrap_or_default();
+        return model_list_fallback(provider, status, &text);
+    }
+
+    let result: serde_json::Value = response.json().map_err(|e| e.to_string())?;
+    let models = transcription_models(&result);
+    if models.is_empty() {
+        return static_models(provider);
+    }
+    MODEL_CACHE.lock().insert(provider.to_string(), (Instant::now(), models.clone()));
+    Ok(models)
+}
+
+/// Models to show after the models endpoint answered `status`. Only a rejected key is an
+/// error; anything else, like the 404 self-hosted servers give, means there's no listing
+fn model_list_fallback(provider: &str, status: reqwest::StatusCode, body: &str) -> Result<Vec<String>, String> {
+    if matches!(status, reqwest::StatusCode::UNAUTHORIZED | reqwest::StatusCode::FORBIDDEN) {
+        return Err(format!("Model list error {}: {}", status, body));
+    }
+    eprintln!("Model list unavailable ({}), using built-in models", status);
+    static_models(provider)
+}
+
+/// Whether `model` is one `provider` is known to serve, from the built-in list or a fetched one.
+/// Unknown providers pass, leaving the error to routing
+fn is_known_model(provider: &str, model: &str) -> bool {
+    let model = if provider == "groq" { groq_model_name(model) } else { model };
+    let Ok(known) = static_models(provider) else {
+        return true;
+    };
+    known.is_empty()
+        || known.iter().any(|m| m == model)
+        || MODEL_CACHE.lock().get(provider).is_some_and(|(_, fetched)| fetched.iter().any(|m| m == model))
+}
+
+/// Built-in model list, for providers without a models endpoint
+pub fn static_models(provider: &str) -> Result<Vec<String>, String> {
+    let models: &[&str] = match provider {
+        "openai" => &["whisper-1", "gpt-4o-transcribe", "gpt-4o-mini-transcribe"],
+        "groq" => &["whisper-large-v3-turbo", "whisper-large-v3", "distil-whisper-large-v3-en"],
+        "assemblyai" => &["best", "nano"],
+        "deepgram" => &["nova-2", "nova-3", "enhanced", "base"],
+        "azure" => &[], // Azure picks its model from the language
+        other => return Err(format!("Unknown provider '{}'", other)),
+    };
+    Ok(models.iter().map(|m| m.to_string()).collect())
+}
+
+/// Audio transcription models from an OpenAI-style `/v1/models` listing, sorted
+fn transcription_models(listing: &serde_json::Value) -> Vec<String> {
+    let mut models: Vec<String> = listing["data"]
+        .as_array()
+        .map(|data| {
+            data.iter()
+                .filter_map(|m| m["id"].as_str())
+                .filter(|id| id.contains("whisper") || id.contains("transcribe"))
+                .map(|id| id.to_string())
+                .collect()
+        })
+        .unwrap_or_default();
+    models.sort();
+    models
+}
+
+/// Transcribe using AssemblyAI: upload the audio, submit a job, then poll
+fn transcribe_assemblyai(
+    samples: &[f32],
+    sample_rate: u32,
+    api_key: &str,
+    language: Option<&str>,
+) -> Result<Transcription, KoeError> {
+    const POLL_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(60);
+    const MAX_POLL_INTERVAL: std::time::Duration = std::time::Duration::from_secs(2);
+
+    let wav_data = samples_to_wav(samples, sample_rate)?;
+
+    // 1. Upload the raw audio
+    let response = send_with_retry("AssemblyAI", |client| {
+        Ok(client
+            .post("https://api.assemblyai.com/v2/upload")
+            .header("Authorization", api_key)
+            .header("Content-Type", "application/octet-stream")
+            .body(wav_data.clone()))
+    })
+    .map_err(|e| e.into_koe("AssemblyAI upload"))?;
+
+    let upload: serde_json::Value = response.json().map_err(|e| KoeError::Network(e.to_string()))?;
+    let upload_url = upload["upload_url"]
+        .as_str()
+        .ok_or("AssemblyAI upload returned no URL")?;
+
+    // 2. Submit the transcription job
+    let mut request = match language {
+        Some(code) => serde_json::json!({ "audio_url": upload_url, "language_code": code }),
+        None => serde_json::json!({ "audio_url": upload_url, "language_detection": true }),
+    };
+    if conversation::is_enabled() {
+        request["speaker_labels"] = serde_json::Value::Bool(true);
+    }
+    let response = send_with_retry("AssemblyAI", |client| {
+        Ok(client
+            .post("https://api.assemblyai.com/v2/transcript")
+            .header("Authorization", api_key)
+            .json(&request))
+    })
+    .map_err(|e| e.into_koe("AssemblyAI"))?;
+
+    let job: serde_json::Value = response.json().map_err(|e| KoeError::Network(e.to_string()))?;
+    let job_id = job["id"].as_str().ok_or("AssemblyAI returned no job ID")?;
+    let poll_url = format!("https://api.assemblyai.com/v2/transcript/{}", job_id);
+
+    // 3. Poll with backoff until the job finishes, each poll bounded by the request timeout
+    let started = std::time::Instant::now();
+    let mut interval = std::time::Duration::from_millis(250);
+    loop {
+        match send_with_retry("AssemblyAI", |client| Ok(client.get(&poll_url).header("Authorization", api_key))) {
+            Ok(response) => {
+                let result: serde_json::Value =
+                    response.json().map_err(|e| KoeError::Network(format!("AssemblyAI poll failed: {}", e)))?;
+                if let Some(transcription) = assemblyai_poll_result(&result)? {
+                    return Ok(transcription);
+                }
+            }
+            // The uploaded job keeps running, so a dropped poll just waits for the next one
+            Err(e @ (ApiError::Timeout(_) | ApiError::Request(_))) if started.elapsed() < POLL_TIMEOUT => {
+                eprintln!("AssemblyAI poll {}; polling again", e);
+            }
+            Err(e) => return Err(e.into_koe("AssemblyAI poll")),
+        }
+
+        if started.elapsed() >= POLL_TIMEOUT {
+            return Err(KoeError::Network(format!("AssemblyAI job {} timed out", job_id)));
+        }
+        std::thread::sleep(interval);
+        interval = (interval * 2).min(MAX_POLL_INTERVAL);
+    }
+}
+
+/// The transcription once a polled AssemblyAI job has completed, or None while it's queued or
+/// processing. A failed job, or a reply with no known status, is an error rather than another poll
+fn assemblyai_poll_result(result: &serde_json::Value) -> Result<Option<Transcription>, KoeError> {
+    match result["status"].as_str() {
+        Some("completed") => Ok(Some(Transcription {
+            text: assemblyai_text(result),
+            utterances: conversation::utterances(result),
+            ..Default::default()
+        })),
+        Some("queued") | Some("processing") => Ok(None),
+        Some("error") => Err(KoeError::Transcription(format!(
+            "AssemblyAI transcription failed: {}",
+            result["error"].as_str().unwrap_or("unknown error")
+        ))),
+        Some(status) => Err(KoeError::Transcription(format!("AssemblyAI job has unknown status '{}'", status))),
+        None => Err(KoeError::Transcription(format!("AssemblyAI poll returned no job status: {}", result))),
+    }
+}
+
+/// Transcribe a chunk with Deepgram's pre-recorded API (live capture streams instead)
+#[cfg(feature = "deepgram")]
+fn transcribe_deepgram(
+    samples: &[f32],
+    sample_rate: u32,
+    api_key: &str,
+    model: &str,
+    language: Option<&str>,
+) -> Result<Option<String>, KoeError> {
+    let wav_data = samples_to_wav(samples, sample_rate)?;
+    let language_param = match language {
+        Some(code) => ("language", code.to_string()),
+        None => ("detect_language", "true".to_string()),
+    };
+
+    let response = send_with_retry("Deepgram", |client| {
+        Ok(client
+            .post("https://api.deepgram.com/v1/listen")
+            .query(&[("model", model.to_string()), ("smart_format", "true".to_string()), language_param.clone()])
+            .header("Authorization", format!("Token {}", api_key))
+            .header("Content-Type", "audio/wav")
+            .body(wav_data.clone()))
+    })
+    .map_err(|e| e.into_koe("Deepgram"))?;
+
+    let result: serde_json::Value = response.json().map_err(|e| KoeError::Network(e.to_string()))?;
+    Ok(deepgram_text(&result))
+}
+
+/// The first channel's best transcript from a Deepgram response
+#[cfg(feature = "deepgram")]
+fn deepgram_text(result: &serde_json::Value) -> Option<String> {
+    let text = result["results"]["channels"][0]["alternatives"][0]["transcript"].as_str()?;
+    Some(text.to_string()).filter(|t| !t.trim().is_empty())
+}
+
+/// Azure Speech host for a region's short-audio endpoint
+fn azure_host(region: &str) -> String {
+    format!("{}.stt.speech.microsoft.com", region)
+}
+
+/// Transcribe a chunk with Azure Speech's short-audio REST API, which takes at most 60s per
+/// request; longer audio (e.g. a push-to-talk hold) goes up in pieces
+fn transcribe_azure(
+    samples: &[f32],
+    sample_rate: u32,
+    api_key: &str,
+    region: &str,
+    language: Option<&str>,
+) -> Result<Option<String>, KoeError> {
+    let locale = azure_locale(language)?;
+    let url = format!("https://{}/speech/recognition/conversation/cognitiveservices/v1", azure_host(region));
+
+    let mut texts = Vec::new();
+    for piece in samples.chunks(azure_piece_len(sample_rate)) {
+        let wav_data = samples_to_wav(piece, sample_rate)?;
+        let response = send_with_retry("Azure", |client| {
+            Ok(client
+                .post(&url)
+                .query(&[("language", locale), ("format", "simple")])
+                .header("Ocp-Apim-Subscription-Key", api_key)
+                .header("Content-Type", format!("audio/wav; codecs=audio/pcm; samplerate={}", sample_rate))
+                .body(wav_data.clone()))
+        })
+        .map_err(|e| e.into_koe("Azure"))?;
+
+        let result: serde_json::Value = response.json().map_err(|e| KoeError::Network(e.to_string()))?;
+        texts.extend(azure_text(&result).map_err(KoeError::Transcription)?);
+    }
+    Ok(Some(texts.join(" ")).filter(|text| !text.is_empty()))
+}
+
+/// Samples per Azure request, leaving a margin under the 60s limit
+fn azure_piece_len(sample_rate: u32) -> usize {
+    AZURE_MAX_REQUEST_SECS * sample_rate as usize
+}
+
+/// The transcript from an Azure short-audio response. Azure answers 200 even when nothing was
+/// recognized, so the outcome is in `RecognitionStatus` rather than the HTTP status
+fn azure_text(result: &serde_json::Value) -> Result<Option<String>, String> {
+    match result["RecognitionStatus"].as_str() {
+        Some("Success") => Ok(result["DisplayText"]
+            .as_str()
+            .map(str::to_string)
+            .filter(|t| !t.trim().is_empty())),
+        // Silence or unintelligible audio, not a failure
+        Some("NoMatch" | "InitialSilenceTimeout" | "BabbleTimeout") => Ok(None),
+        Some(status) => Err(format!("Azure recognition failed: {}", status)),
+        None => Err(format!("Azure returned an unexpected response: {}", result)),
+    }
+}
+
+/// Azure locale for each language code the config accepts
+const AZURE_LOCALES: &[(&str, &str)] = &[
+    ("en", "en-US"),
+    ("zh", "zh-CN"),
+    ("de", "de-DE"),
+    ("es", "es-ES"),
+    ("ru", "ru-RU"),
+    ("ko", "ko-KR"),
+    ("fr", "fr-FR"),
+    ("ja", "ja-JP"),
+    ("pt", "pt-BR"),
+    ("tr", "tr-TR"),
+    ("pl", "pl-PL"),
+    ("ca", "ca-ES"),
+    ("nl", "nl-NL"),
+    ("ar", "ar-SA"),
+    ("sv", "sv-SE"),
+    ("it", "it-IT"),
+    ("id", "id-ID"),
+    ("hi", "hi-IN"),
+    ("fi", "fi-FI"),
+    ("vi", "vi-VN"),
+    ("he", "he-IL"),
+    ("uk", "uk-UA"),
+    ("el", "el-GR"),
+    ("cs", "cs-CZ"),
+    ("ro", "ro-RO"),
+    ("da", "da-DK"),
+    ("hu", "hu-HU"),
+    ("no", "nb-NO"),
+    ("th", "th-TH"),
+];
+
+/// Azure wants a locale ("en-US") where the config holds an ISO-639-1 code. The short-audio
+/// endpoint can't detect the language, so None (auto) is an error rather than a guess
+fn azure_locale(language: Option<&str>) -> Result<&'static str, KoeError> {
+    let Some(code) = language else {
+        return Err(KoeError::Transcription(
+            "Azure can't detect the language; set a transcription language".to_string(),
+        ));
+    };
+    AZURE_LOCALES
+        .iter()
+        .find(|(known, _)| *known == code)
+        .map(|(_, locale)| *locale)
+        .ok_or_else(|| KoeError::Transcription(format!("Azure has no locale for language '{}'", code)))
+}
+
+/// Extract the transcript from a completed AssemblyAI job
+fn assemblyai_text(result: &serde_json::Value) -> Option<String> {
+    match result["text"].as_str() {
+        Some(text) => Some(text.to_string()),
+        // Fall back to reassembling word-level results
+        None => {
+            let words: Vec<&str> = result["words"]
+                .as_array()?
+                .iter()
+                .filter_map(|w| w["text"].as_str())
+                .collect();
+            if words.is_empty() {
+                None
+            } else {
+                Some(words.join(" "))
+            }
+        }
+    }
+}
+
+#[cfg(feature = "whisper-local")]
+lazy_static::lazy_static! {
+    // The loaded model and the path it came from; reloading costs seconds per chunk
+    static ref LOCAL_CONTEXT: Mutex<Option<(String, Arc<whisper_rs::WhisperContext>)>> = Mutex::new(None);
+}
+
+/// The whisper context for `model_path`, loaded once and reused until the path changes.
+/// Callers create their own state from it, so transcriptions don't share decoder state
+#[cfg(feature = "whisper-local")]
+fn local_context(model_path: &str) -> Result<Arc<whisper_rs::WhisperContext>, whisper_rs::WhisperError> {
+    use whisper_rs::{WhisperContext, WhisperContextParameters};
+
+    // Held across the load so two chunks don't both read the model
+    let mut cached = LOCAL_CONTEXT.lock();
+    if let Some((path, ctx)) = cached.as_ref() {
+        if path == model_path {
+            return Ok(ctx.clone());
+        }
+    }
+    // Free the old model before loading the new one
+    *cached = None;
+    let ctx = Arc::new(WhisperContext::new_with_params(model_path, WhisperContextParameters::default())?);
+    *cached = Some((model_path.to_string(), ctx.clone()));
+    Ok(ctx)
+}
+
+/// Transcribe using local whisper.cpp (when feature enabled)
+#[cfg(feature = "whisper-local")]
+fn transcribe_local(
+    samples: &[f32],
+    sample_rate: u32,
+    model_path: &str,
+    prompt: Option<&str>,
+    language: Option<&str>,
+) -> Result<Option<String>, KoeError> {
+    use whisper_rs::{FullParams, SamplingStrategy};
+
+    let ctx = local_context(model_path)
+        .map_err(|e| KoeError::Transcription(format!("Failed to load whisper model: {}", e)))?;
+
+    // Create whisper state
+    let mut state = ctx.create_state().map_err(|e| e.to_string())?;
+
+    // Configure parameters
+    let mut params = FullParams::new(SamplingStrategy::Greedy { best_of: 1 });
+    params.set_language(Some(language.unwrap_or("auto")));
+    params.set_print_special(false);
+    params.set_print_progress(false);
+    params.set_print_realtime(false);
+    params.set_print_timestamps(false);
+    if let Some(prompt) = prompt {
+        params.set_initial_prompt(prompt);
+    }
+
+    // Run transcription
+    state
+        .full(params, samples)
+        .map_err(|e| KoeError::Transcription(format!("Transcription failed: {}", e)))?;
+
+    // Get results
+    let num_segments = state.full_n_segments().map_err(|e| e.to_string())?;
+    if num_segments == 0 {
+        return Ok(None);
+    }
+
+    let mut text = String::new();
+    for i in 0..num_segments {
+        if let Ok(segment) = state.full_get_segment_text(i) {
+            text.push_str(&segment);
+            text.push(' ');
+        }
+    }
+
+    Ok(Some(text.trim().to_string()))
+}
+
+/// Segment-timed local transcription, shaped like a verbose_json response
+#[cfg(feature = "whisper-local")]
+fn transcribe_local_segments(samples: &[f32], model_path: &str, language: Option<&str>) -> Result<serde_json::Value, String> {
+    use whisper_rs::{FullParams, SamplingStrategy};
+
+    let ctx = local_context(model_path).map_err(|e| format!("Failed to load whisper model: {}", e))?;
+    let mut state = ctx.create_state().map_err(|e| e.to_string())?;
+
+    let mut params = FullParams::new(SamplingStrategy::Greedy { best_of: 1 });
+    params.set_language(Some(language.unwrap_or("auto")));
+    params.set_print_special(false);
+    params.set_print_progress(false);
+    params.set_print_realtime(false);
+    params.set_print_timestamps(false);
+    state
+        .full(params, samples)
+        .map_err(|e| format!("Transcription failed: {}", e))?;
+
+    // whisper.cpp timestamps are in centiseconds
+    let num_segments = state.full_n_segments().map_err(|e| e.to_string())?;
+    let mut segments = Vec::new();
+    for i in 0..num_segments {
+        let text = state.full_get_segment_text(i).map_err(|e| e.to_string())?;
+        let start = state.full_get_segment_t0(i).map_err(|e| e.to_string())? as f64 / 100.0;
+        let end = state.full_get_segment_t1(i).map_err(|e| e.to_string())? as f64 / 100.0;
+        segments.push(serde_json::json!({ "start": start, "end": end, "text": text }));
+    }
+    Ok(serde_json::json!({ "segments": segments }))
+}
+
+/// Convert f32 samples to WAV bytes
+fn samples_to_wav(samples: &[f32], sample_rate: u32) -> Result<Vec<u8>, String> {
+    let spec = hound::WavSpec {
+        channels: 1,
+        sample_rate,
+        bits_per_sample: 16,
+        sample_format: hound::SampleFormat::Int,
+    };
+
+    let mut cursor = std::io::Cursor::new(Vec::new());
+    {
+        let mut writer = hound::WavWriter::new(&mut cursor, spec).map_err(|e| e.to_string())?;
+
+        for sample in samples {
+            // Convert f32 [-1.0, 1.0] to i16
+            let sample_i16 = (sample * 32767.0).clamp(-32768.0, 32767.0) as i16;
+            writer.write_sample(sample_i16).map_err(|e| e.to_string())?;
+        }
+
+        writer.finalize().map_err(|e| e.to_string())?;
+    }
+
+    Ok(cursor.into_inner())
+}
+
+/// Generate a sine wave at half amplitude
+fn sine_wave(freq: f32, secs: f32, sample_rate: u32) -> Vec<f32> {
+    let count = (secs * sample_rate as f32) as usize;
+    (0..count)
+        .map(|i| (2.0 * std::f32::consts::PI * freq * i as f32 / sample_rate as f32).sin() * 0.5)
+        .collect()
+}
+
+/// WAV bytes of a test tone, for exercising the upload path without a mic
+pub fn generate_test_tone(freq: f32, secs: f32, sample_rate: u32) -> Result<Vec<u8>, String> {
+    if sample_rate == 0 {
+        return Err("Sample rate must be positive".to_string());
+    }
+    if !secs.is_finite() || !(0.0..=60.0).contains(&secs) {
+        return Err(format!("Tone duration must be between 0 and 60 seconds, got {}", secs));
+    }
+    if !freq.is_finite() || freq <= 0.0 {
+        return Err(format!("Tone frequency must be positive, got {}", freq));
+    }
+    samples_to_wav(&sine_wave(freq, secs, sample_rate), sample_rate)
+}
+
+#[derive(Clone, serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TranscriptEvent {
+    pub text: String,
+    pub raw_text: String,
+    pub insert_text: String, // spaced and capitalized to follow the previous transcript
+    pub is_final: bool,
+    pub seq: u64, // the chunk this came from, in capture order; interims share their final's
+}
+
+/// Payload of `voice:metrics`: how long one live segment took to transcribe
+#[derive(Clone, Debug, PartialEq, serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TranscriptionMetrics {
+    pub provider: String,
+    pub model: Option<String>,
+    pub audio_ms: u64,
+    pub latency_ms: u64, // including resampling and preprocessing
+    pub seq: u64,
+}
+
+/// Running averages for one provider and model since the app started
+#[derive(Clone, Debug, PartialEq, serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ModelStats {
+    pub provider: String,
+    pub model: Option<String>,
+    pub segments: u64,
+    pub average_latency_ms: f64,
+    pub average_audio_ms: f64,
+}
+
+/// Payload of `voice:transcript_detailed`
+#[derive(Clone, Debug, PartialEq, serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DetailedTranscript {
+    pub text: String,
+    pub words: Vec<subtitles::Word>,
+    pub segments: Vec<SegmentScore>, // empty if the response had no segments
+}
+
+/// Whisper's scores for one verbose_json segment
+#[derive(Clone, Debug, PartialEq, serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SegmentScore {
+    pub start: f64,
+    pub end: f64,
+    pub text: String,
+    pub avg_logprob: f64,
+    pub no_speech_prob: f64,
+    pub confidence: f64, // exp(avg_logprob), the mean token probability
+    pub kept: bool,      // false if the confidence filter dropped it
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Held by tests that claim and release the global capture session
+    static CAPTURE_TESTS: Mutex<()> = parking_lot::const_mutex(());
+    // Held by tests that change the global transcription config and assert on what they set
+    static CONFIG_TESTS: Mutex<()> = parking_lot::const_mutex(());
+
+    /// Opens streams that only count how many are live, taking a moment so stops land mid-setup
+    struct FakeBackend {
+        live: Arc<AtomicU32>,
+        peak: Arc<AtomicU32>,
+    }
+
+    struct FakeStream(Arc<AtomicU32>);
+
+    impl Drop for FakeStream {
+        fn drop(&mut self) {
+            self.0.fetch_sub(1, Ordering::SeqCst);
+        }
+    }
+
+    impl CaptureBackend for FakeBackend {
+        type App = ();
+        type Stream = FakeStream;
+
+        fn open(&mut self, _: &()) -> Result<FakeStream, KoeError> {
+            let live = self.live.fetch_add(1, Ordering::SeqCst) + 1;
+            self.peak.fetch_max(live, Ordering::SeqCst);
+            std::thread::sleep(Duration::from_micros(200));
+            Ok(FakeStream(self.live.clone()))
+        }
+
+        fn reopen(&mut self, app: &()) -> Result<(FakeStream, String), KoeError> {
+            self.open(app).map(|stream| (stream, "Fake".to_string()))
+        }
+
+        fn started(&mut self, _: &()) {}
+
+        fn abandoned(&mut self) {}
+    }
+
+    fn send_stop(sender: &mpsc::Sender<AudioCommand<()>>) -> bool {
+        let (reply, stopped) = mpsc::channel();
+        sender.send(AudioCommand::Stop(reply)).unwrap();
+        stopped.recv().unwrap()
+    }
+
+    #[test]
+    fn test_samples_to_wav() {
+        let samples = vec![0.0, 0.5, -0.5, 1.0, -1.0];
+        let wav = samples_to_wav(&samples, 16000);
+        assert!(wav.is_ok());
+        let wav_data = wav.unwrap();
+        // WAV header is 44 bytes, then 2 bytes per sample (16-bit)
+        assert_eq!(wav_data.len(), 44 + samples.len() * 2);
+    }
+
+    #[test]
+    fn test_samples_to_wav_empty() {
+        let samples: Vec<f32> = vec![];
+        let wav = samples_to_wav(&samples, 16000);
+        assert!(wav.is_ok());
+    }
+
+    #[test]
+    fn test_generate_test_tone() {
+        let wav = generate_test_tone(440.0, 0.5, 16000).unwrap();
+        assert_eq!(wav.len(), 44 + 8000 * 2);
+        assert!(generate_test_tone(440.0, 1.0, 0).is_err());
+        assert!(generate_test_tone(440.0, -1.0, 16000).is_err());
+    }
+
+    #[test]
+    fn test_configure_whisper() {
+        let _serial = CONFIG_TESTS.lock();
+        configure_whisper(WhisperSettings {
+            api_key: Some("test-key".to_string()),
+            use_local: Some(false),
+            ..Default::default()
+        })
+        .unwrap();
+        let config = WHISPER_CONFIG.lock();
+        assert_eq!(config.api_key, Some("test-key".to_string()));
+        assert!(!config.use_local);
+    }
+
+    #[test]
+    fn test_configure_whisper_groq() {
+        let _serial = CONFIG_TESTS.lock();
+        configure_whisper(WhisperSettings {
+            use_local: Some(false),
+            provider: Some("groq".to_string()),
+            model: Some("whisper-large-v3-turbo".to_string()),
+            groq_api_key: Some("groq-key".to_string()),
+            ..Default::default()
+        })
+        .unwrap();
+        let config = WHISPER_CONFIG.lock();
+        assert_eq!(config.provider, "groq");
+        assert_eq!(config.model, "whisper-large-v3-turbo");
+        assert_eq!(config.groq_api_key, Some("groq-key".to_string()));
+    }
+
+    #[test]
+    fn test_configure_whisper_openai_model() {
+        let _serial = CONFIG_TESTS.lock();
+        configure_whisper(WhisperSettings {
+            use_local: Some(false),
+            provider: Some("openai".to_string()),
+            model: Some("gpt-4o-mini-transcribe".to_string()),
+            api_key: Some("openai-config-key".to_string()),
+            ..Default::default()
+        })
+        .unwrap();
+        // The model checked against the OpenAI list is the one requests carry
+        assert!(is_known_model("openai", "gpt-4o-mini-transcribe"));
+        let config = WHISPER_CONFIG.lock();
+        assert!(matches!(resolve_route(&config), Route::OpenAi { model, .. } if model == "gpt-4o-mini-transcribe"));
+    }
+
+    #[test]
+    fn test_keys_use_test_keychain() {
+        let _serial = CONFIG_TESTS.lock();
+        *TEST_KEYCHAIN.lock() = Some(HashMap::new());
+        configure_whisper(WhisperSettings { deepgram_api_key: Some("dg-test-key-1234".to_string()), ..Default::default() })
+            .unwrap();
+        assert_eq!(keychain_get("deepgram").unwrap().as_deref(), Some("dg-test-key-1234"));
+
+        let mut config = WhisperConfig::default();
+        load_keys(&mut config);
+        assert_eq!(config.deepgram_api_key.as_deref(), Some("dg-test-key-1234"));
+
+        configure_whisper(WhisperSettings { deepgram_api_key: Some(String::new()), ..Default::default() }).unwrap();
+        assert_eq!(keychain_get("deepgram").unwrap(), None);
+        *TEST_KEYCHAIN.lock() = None;
+    }
+
+    #[test]
+    fn test_partial_configure_keeps_keys() {
+        let _serial = CONFIG_TESTS.lock();
+        *TEST_KEYCHAIN.lock() = Some(HashMap::new());
+        configure_whisper(WhisperSettings {
+            assemblyai_api_key: Some("aai-regression-key".to_string()),
+            ..Default::default()
+        })
+        .unwrap();
+        // A model-only patch, so the keys are the only thing it could clobber
+        configure_whisper(WhisperSettings { model: Some("whisper-large-v3-turbo".to_string()), ..Default::default() })
+            .unwrap();
+        assert_eq!(WHISPER_CONFIG.lock().assemblyai_api_key.as_deref(), Some("aai-regression-key"));
+
+        assert_eq!(keychain_get("assemblyai").unwrap().as_deref(), Some("aai-regression-key"));
+        clear_api_key("AssemblyAI").unwrap();
+        assert_eq!(WHISPER_CONFIG.lock().assemblyai_api_key, None);
+        assert_eq!(keychain_get("assemblyai").unwrap(), None);
+        assert!(clear_api_key("whisperhub").is_err());
+
+        // Without a keychain the key still goes from memory
+        *TEST_KEYCHAIN.lock() = None;
+        configure_whisper(WhisperSettings { assemblyai_api_key: Some("aai-regression-key".to_string()), ..Default::default() })
+            .unwrap();
+        clear_api_key("assemblyai").unwrap();
+        assert_eq!(WHISPER_CONFIG.lock().assemblyai_api_key, None);
+    }
+
+    #[test]
+    fn test_whisper_settings_from_frontend_json() {
+        // The shape settingsStore's saveApiKey sends: one field, the rest left out
+        let settings: WhisperSettings = serde_json::from_str(r#"{"groqApiKey": "gsk-from-settings"}"#).unwrap();
+        assert_eq!(settings.groq_api_key.as_deref(), Some("gsk-from-settings"));
+        assert_eq!((settings.api_key, settings.provider), (None, None));
+
+        let settings: WhisperSettings = serde_json::from_str(r#"{"provider": "groq", "model": null}"#).unwrap();
+        assert_eq!((settings.provider.as_deref(), settings.model), (Some("groq"), None));
+    }
+
+    #[test]
+    fn test_configure_patch_semantics() {
+        let _serial = CONFIG_TESTS.lock();
+        let mut field = Some("old".to_string());
+        patch(&mut field, None);
+        assert_eq!(field.as_deref(), Some("old"));
+        patch(&mut field, Some(" new ".to_string()));
+        assert_eq!(field.as_deref(), Some("new"));
+        patch(&mut field, Some(String::new()));
+        assert_eq!(field, None);
+
+        assert_eq!(validate_key("Groq", Some(String::new())), Ok(Some(String::new())));
+        assert!(validate_key("Groq", Some("gsk_".to_string())).is_err());
+        assert!(validate_key("Groq", Some("gsk_abc def123".to_string())).is_err());
+        assert!(configure_whisper(WhisperSettings { groq_api_key: Some("gs".to_string()), ..Default::default() }).is_err());
+    }
+
+    #[test]
+    #[cfg(feature = "deepgram")]
+    fn test_deepgram_route_and_text() {
+        let config = WhisperConfig {
+            provider: "deepgram".to_string(),
+            deepgram_api_key: Some("dg-test-key-1234".to_string()),
+            ..Default::default()
+        };
+        match resolve_route(&config) {
+            Route::Deepgram { model, .. } => assert_eq!(model, DEEPGRAM_DEFAULT_MODEL),
+            _ => panic!("expected Deepgram route"),
+        }
+        assert!(streaming_session(&config).is_some());
+
+        let result = serde_json::json!({
+            "results": {"channels": [{"alternatives": [{"transcript": "Hello there.", "confidence": 0.99}]}]}
+        });
+        assert_eq!(deepgram_text(&result), Some("Hello there.".to_string()));
+        let silent = serde_json::json!({"results": {"channels": [{"alternatives": [{"transcript": ""}]}]}});
+        assert_eq!(deepgram_text(&silent), None);
+    }
+
+    #[test]
+    fn test_azure_config_and_text() {
+        let _serial = CONFIG_TESTS.lock();
+        configure_whisper(WhisperSettings {
+            azure_api_key: Some("az-test-key".to_string()),
+            azure_region: Some(" WestUS ".to_string()),
+            ..Default::default()
+        })
+        .unwrap();
+        {
+            let mut config = WHISPER_CONFIG.lock();
+            assert_eq!(config.azure_region.as_deref(), Some("westus"));
+            assert_eq!(key_slot(&mut config, "azure").as_deref(), Some("az-test-key"));
+        }
+        assert!(validate_azure_region("west.us/evil").is_err());
+
+        // A key without a region can't be routed
+        let mut config = WhisperConfig {
+            provider: "azure".to_string(),
+            azure_api_key: Some("az-test-key".to_string()),
+            ..Default::default()
+        };
+        assert!(apply_provider_switch(&mut config, "azure").unwrap_err().contains("azure_region"));
+        config.azure_region = Some("westus".to_string());
+        match resolve_route(&config) {
+            Route::Azure { region, .. } => assert_eq!(azure_host(&region), "westus.stt.speech.microsoft.com"),
+            _ => panic!("expected Azure route"),
+        }
+        assert_eq!(azure_locale(Some("de")).unwrap(), "de-DE");
+        assert_eq!(azure_locale(Some("no")).unwrap(), "nb-NO");
+        assert!(azure_locale(Some("nb")).is_err());
+        assert!(azure_locale(None).is_err());
+        // Languages given by name normalize to a code with a locale
+        for name in ["english", "catalan", "thai", "norwegian"] {
+            assert!(azure_locale(normalize_language(name).ok().as_deref()).is_ok());
+        }
+        // A 150s push-to-talk hold goes up as three requests
+        let hold = vec![0.0f32; 150 * 16000];
+        let pieces: Vec<_> = hold.chunks(azure_piece_len(16000)).map(<[f32]>::len).collect();
+        assert_eq!(pieces.len(), 3);
+        assert!(pieces.iter().all(|&len| len <= 60 * 16000));
+
+        let result = serde_json::json!({"RecognitionStatus": "Success", "DisplayText": "Hello there.", "Offset": 0, "Duration": 12000000});
+        assert_eq!(azure_text(&result), Ok(Some("Hello there.".to_string())));
+        assert_eq!(azure_text(&serde_json::json!({"RecognitionStatus": "NoMatch"})), Ok(None));
+        assert!(azure_text(&serde_json::json!({"RecognitionStatus": "Error"})).is_err());
+    }
+
+    #[test]
+    fn test_parse_detailed() {
+        // Trimmed from a whisper-1 verbose_json response
+        let body = r#"{
+            "task": "transcribe",
+            "language": "english",
+            "duration": 1.6,
+            "text": " Ship it today.",
+            "segments": [{"id": 0, "start": 0.0, "end": 1.6, "text": " Ship it today.", "no_speech_prob": 0.01}],
+            "words": [
+                {"word": "Ship", "start": 0.0, "end": 0.38},
+                {"word": "it", "start": 0.38, "end": 0.56},
+                {"word": "today", "start": 0.56, "end": 1.12}
+            ]
+        }"#;
+        let detailed = parse_detailed(body, ConfidenceLimits::default()).unwrap();
+        assert_eq!(detailed.text, "Ship it today.");
+        assert_eq!(detailed.words.len(), 3);
+        assert_eq!(detailed.words[2], subtitles::Word { start: 0.56, end: 1.12, text: "today".to_string() });
+
+        // Plain json responses carry no timings
+        assert_eq!(parse_detailed(r#"{"text": "hi"}"#, ConfidenceLimits::default()), None);
+
+        // The timings travel with the transcript, and only when they were asked for
+        let transcription = openai_transcription(body, true).unwrap();
+        assert_eq!(transcription.detailed.map(|d| d.words.len()), Some(3));
+        assert!(openai_transcription(body, false).unwrap().detailed.is_none());
+    }
+
+    #[test]
+    fn test_confidence_filter_drops_hallucinated_segment() {
+        let body = r#"{
+            "text": " Ship it today. Thank you for watching.",
+            "segments": [
+                {"id": 0, "start": 0.0, "end": 1.6, "text": " Ship it today.", "avg_logprob": -0.2, "no_speech_prob": 0.01},
+                {"id": 1, "start": 1.6, "end": 3.0, "text": " Thank you for watching.", "avg_logprob": -0.9, "no_speech_prob": 0.92}
+            ],
+            "words": [
+                {"word": "Ship", "start": 0.0, "end": 0.38},
+                {"word": "Thank", "start": 1.7, "end": 1.9}
+            ]
+        }"#;
+        let limits = ConfidenceLimits { min_confidence: None, max_no_speech_prob: Some(0.6) };
+        assert_eq!(confident_text(body, limits).as_deref(), Some("Ship it today."));
+        assert_eq!(confident_text(body, ConfidenceLimits::default()), None);
+
+        let detailed = parse_detailed(body, limits).unwrap();
+        assert_eq!(detailed.text, "Ship it today.");
+        assert_eq!(detailed.words.len(), 1);
+        assert_eq!(detailed.segments.iter().map(|s| s.kept).collect::<Vec<_>>(), vec![true, false]);
+
+        // exp(-0.9) ≈ 0.41, so a 0.5 floor drops the second segment too
+        let by_logprob = ConfidenceLimits { min_confidence: Some(0.5), max_no_speech_prob: None };
+        assert_eq!(confident_text(body, by_logprob).as_deref(), Some("Ship it today."));
+    }
+
+    #[test]
+    fn test_confidence_limits_request_scores() {
+        let limits = ConfidenceLimits { min_confidence: None, max_no_speech_prob: Some(0.6) };
+        let transcribe = TranscriptionTask::Transcribe;
+        // A pinned language would otherwise get plain json, with nothing to filter on
+        assert!(wants_verbose("whisper-1", transcribe, Some("en"), true, false, limits));
+        assert!(wants_verbose("whisper-1", TranscriptionTask::Translate, None, true, false, limits));
+        assert!(!wants_verbose("whisper-1", transcribe, Some("en"), true, false, ConfidenceLimits::default()));
+    }
+
+    #[test]
+    fn test_verbose_json_only_where_supported() {
+        let none = ConfidenceLimits::default();
+        let transcribe = TranscriptionTask::Transcribe;
+        // Auto-detect asks for verbose_json so the language gate has scores, but only when gated
+        assert!(wants_verbose("whisper-1", transcribe, None, true, false, none));
+        assert!(!wants_verbose("whisper-1", transcribe, None, false, false, none));
+        assert!(wants_verbose("whisper-large-v3-turbo", transcribe, Some("en"), true, true, none));
+        // The gpt-4o models only answer json or text, so they never get it
+        let limits = ConfidenceLimits { min_confidence: Some(0.5), max_no_speech_prob: None };
+        for model in ["gpt-4o-transcribe", "gpt-4o-mini-transcribe"] {
+            assert!(!wants_verbose(model, transcribe, None, true, true, limits));
+            assert!(is_known_model("openai", model));
+        }
+    }
+
+    #[test]
+    fn test_api_error_classification() {
+        assert!(matches!(ApiError::from_status(401, String::new()), ApiError::Auth(401, _)));
+        assert!(ApiError::from_status(429, String::new()).is_retryable());
+        assert!(ApiError::from_status(503, String::new()).is_retryable());
+        assert!(!ApiError::from_status(400, String::new()).is_retryable());
+        assert!(!ApiError::Timeout(30).is_retryable());
+        assert_eq!(ApiError::Timeout(30).to_string(), "request timed out after 30s");
+        assert_eq!(retry_delay(1), Duration::from_secs(1));
+        assert_eq!(retry_delay(2), Duration::from_secs(2));
+    }
+
+    #[test]
+    fn test_base_url_validation() {
+        assert_eq!(normalize_base_url(" http://localhost:8080/v1/ ").unwrap(), "http://localhost:8080/v1");
+        assert!(normalize_base_url("localhost:8080").is_err());
+        assert!(normalize_base_url("ftp://example.com/v1").is_err());
+
+        // A custom server routes to the OpenAI provider without a key
+        let config = WhisperConfig { base_url: Some("http://localhost:8080/v1".to_string()), ..Default::default() };
+        match resolve_route(&config) {
+            Route::OpenAi { api_key, base_url, .. } => {
+                assert!(api_key.is_empty());
+                assert_eq!(base_url, "http://localhost:8080/v1");
+            }
+            _ => panic!("expected OpenAI route"),
+        }
+    }
+
+    #[test]
+    fn test_transcription_task() {
+        assert_eq!(TranscriptionTask::default(), TranscriptionTask::Transcribe);
+        assert_eq!(TranscriptionTask::parse("Translate").unwrap().endpoint(), "translations");
+        assert!(TranscriptionTask::parse("summarize").is_err());
+    }
+
+    #[test]
+    fn test_response_format_sent_once() {
+        let transcribe = TranscriptionTask::Transcribe;
+        for language in [None, Some("en")] {
+            for (verbose, word_timestamps) in [(false, false), (true, false), (true, true)] {
+                let fields = request_fields(transcribe, language, verbose, word_timestamps);
+                let formats = fields.iter().filter(|(name, _)| *name == "response_format").count();
+                assert_eq!(formats, verbose as usize);
+                assert_eq!(fields.iter().any(|(name, _)| *name == "language"), language.is_some());
+            }
+        }
+        let fields = request_fields(TranscriptionTask::Translate, Some("en"), true, false);
+        assert_eq!(fields, vec![("response_format", "verbose_json".to_string())]);
+    }
+
+    #[test]
+    fn test_language_field_omitted_for_auto_detect() {
+        assert_eq!(language_field(Some("de")), Some(("language", "de".to_string())));
+        assert_eq!(language_field(None), None);
+    }
+
+    #[test]
+    fn test_assemblyai_route() {
+        let config = WhisperConfig {
+            provider: "assemblyai".to_string(),
+            assemblyai_api_key: Some("aai-key".to_string()),
+            ..Default::default()
+        };
+        assert!(matches!(resolve_route(&config), Route::AssemblyAi { ref api_key } if api_key == "aai-key"));
+
+        // Without its key, AssemblyAI falls back like Groq does
+        let config = WhisperConfig { provider: "assemblyai".to_string(), ..Default::default() };
+        assert!(matches!(resolve_route(&config), Route::Unconfigured));
+    }
+
+    #[test]
+    fn test_configure_whisper_assemblyai() {
+        let _serial = CONFIG_TESTS.lock();
+        configure_whisper(WhisperSettings {
+            use_local: Some(false),
+            provider: Some("assemblyai".to_string()),
+            assemblyai_api_key: Some("aai-config-key".to_string()),
+            ..Default::default()
+        })
+        .unwrap();
+        let config = WHISPER_CONFIG.lock();
+        assert_eq!(config.provider, "assemblyai");
+        assert_eq!(config.assemblyai_api_key.as_deref(), Some("aai-config-key"));
+        assert!(matches!(resolve_route(&config), Route::AssemblyAi { ref api_key } if api_key == "aai-config-key"));
+    }
+
+    #[test]
+    fn test_effective_config_groq_fallback() {
+        let config = WhisperConfig {
+            provider: "groq".to_string(),
+            api_key: Some("openai-key".to_string()),
+            ..Default::default()
+        };
+        assert!(matches!(resolve_route(&config), Route::OpenAi { .. }));
 
-                // Clear buffer after processing
-                buffer.samples.clear();
-            }
-        },
-        move |err| {
-            eprintln!("Audio stream error: {}", err);
-            err_app.emit_to(EventTarget::Any, "voice:error", err.to_string()).ok();
-        },
-        None,
-    )?;
+        let config = WhisperConfig {
+            provider: "groq".to_string(),
+            groq_api_key: Some("groq-key".to_string()),
+            ..Default::default()
+        };
+        match resolve_route(&config) {
+            Route::Groq { model, .. } => assert_eq!(groq_model_name(&model), "whisper-large-v3-turbo"),
+            _ => panic!("expected Groq route"),
+        }
+    }
 
-    stream.play()?;
+    #[test]
+    fn test_switch_provider_requires_key() {
+        let mut config = WhisperConfig { api_key: Some("sk-test".to_string()), ..Default::default() };
+        let err = apply_provider_switch(&mut config, "groq").unwrap_err();
+        assert!(err.contains("groq_api_key"), "{}", err);
+        assert_eq!(config.provider, "openai");
 
-    // Store stream handle in thread local
-    STREAM_HANDLE.with(|handle| {
-        *handle.borrow_mut() = Some(stream);
-    });
+        config.groq_api_key = Some("gsk-test".to_string());
+        apply_provider_switch(&mut config, "groq").unwrap();
+        assert!(matches!(resolve_route(&config), Route::Groq { .. }));
+        assert!(apply_provider_switch(&mut config, "whisperx").is_err());
+    }
 
-    CAPTURING.store(true, Ordering::SeqCst);
-    app.emit_to(EventTarget::Any, "voice:state", "listening").ok();
+    #[test]
+    fn test_provider_default_model() {
+        let mut config = WhisperConfig {
+            provider: "groq".to_string(),
+            api_key: Some("openai-key".to_string()),
+            groq_api_key: Some("groq-key".to_string()),
+            ..Default::default()
+        };
+        config.provider_defaults.insert(
+            "groq".to_string(),
+            ProviderDefaults { model: Some("whisper-large-v3".to_string()), ..Default::default() },
+        );
+        match resolve_route(&config) {
+            Route::Groq { model, .. } => assert_eq!(model, "whisper-large-v3"),
+            _ => panic!("expected Groq route"),
+        }
 
-    println!("Voice capture started");
-    Ok(())
-}
+        // Switching provider picks up that provider's defaults instead
+        config.provider = "openai".to_string();
+        match resolve_route(&config) {
+            Route::OpenAi { model, .. } => assert_eq!(model, "whisper-1"),
+            _ => panic!("expected OpenAI route"),
+        }
+        assert_eq!(combine_prompts(Some("Koe, Tauri."), Some("last words")).as_deref(), Some("Koe, Tauri. last words"));
+    }
 
-pub fn stop_capture() -> Result<(), Box<dyn std::error::Error>> {
-    CAPTURING.store(false, Ordering::SeqCst);
+    #[test]
+    fn test_openai_route_uses_configured_model() {
+        let mut config = WhisperConfig {
+            model: "gpt-4o-transcribe".to_string(),
+            api_key: Some("openai-key".to_string()),
+            ..Default::default()
+        };
+        match resolve_route(&config) {
+            Route::OpenAi { model, .. } => assert_eq!(model, "gpt-4o-transcribe"),
+            _ => panic!("expected OpenAI route"),
+        }
 
-    // Drop the stream (from thread local)
-    STREAM_HANDLE.with(|handle| {
-        *handle.borrow_mut() = None;
-    });
+        config.model = String::new();
+        assert!(matches!(resolve_route(&config), Route::OpenAi { model, .. } if model == "whisper-1"));
 
-    // Clear buffer
-    {
-        let mut buffer = AUDIO_BUFFER.lock();
-        buffer.samples.clear();
+        // Falling back from Groq doesn't send Groq's model to OpenAI
+        config.provider = "groq".to_string();
+        config.model = "whisper-large-v3".to_string();
+        assert!(matches!(resolve_route(&config), Route::OpenAi { model, .. } if model == "whisper-1"));
     }
 
-    println!("Voice capture stopped");
-    Ok(())
-}
+    #[test]
+    fn test_configure_prompt_patch() {
+        let _serial = CONFIG_TESTS.lock();
+        configure_whisper(WhisperSettings { prompt: Some(" Koe, Tauri. ".to_string()), ..Default::default() }).unwrap();
+        assert_eq!(WHISPER_CONFIG.lock().prompt.as_deref(), Some("Koe, Tauri."));
+        // Patching another setting leaves the prompt; an empty one clears it
+        configure_whisper(WhisperSettings { use_local: Some(false), ..Default::default() }).unwrap();
+        assert_eq!(WHISPER_CONFIG.lock().prompt.as_deref(), Some("Koe, Tauri."));
+        configure_whisper(WhisperSettings { prompt: Some(String::new()), ..Default::default() }).unwrap();
+        assert_eq!(WHISPER_CONFIG.lock().prompt, None);
+    }
 
-/// Resample audio to target sample rate using linear interpolation
-fn resample(samples: &[f32], from_rate: u32, to_rate: u32) -> Vec<f32> {
-    if from_rate == to_rate {
-        return samples.to_vec();
+    #[test]
+    fn test_long_prompt_keeps_the_end() {
+        let glossary = "kubectl ".repeat(200);
+        let prompt = combine_prompts(Some(&glossary), Some("last words")).unwrap();
+        assert!(prompt.len() <= MAX_PROMPT_CHARS);
+        assert!(prompt.starts_with("kubectl"));
+        assert!(prompt.ends_with("last words"));
     }
 
-    let ratio = from_rate as f64 / to_rate as f64;
-    let new_len = (samples.len() as f64 / ratio) as usize;
-    let mut resampled = Vec::with_capacity(new_len);
+    #[test]
+    fn test_assemblyai_text() {
+        let completed = serde_json::json!({
+            "status": "completed",
+            "text": "Hello world.",
+            "confidence": 0.93
+        });
+        assert_eq!(assemblyai_text(&completed), Some("Hello world.".to_string()));
 
-    for i in 0..new_len {
-        let src_idx = i as f64 * ratio;
-        let idx_floor = src_idx.floor() as usize;
-        let idx_ceil = (idx_floor + 1).min(samples.len() - 1);
-        let frac = src_idx - idx_floor as f64;
+        let words_only: serde_json::Value = serde_json::from_str(
+            r#"{"status":"completed","text":null,"words":[{"text":"hello","confidence":0.9},{"text":"there","confidence":0.8}]}"#,
+        )
+        .unwrap();
+        assert_eq!(assemblyai_text(&words_only), Some("hello there".to_string()));
+    }
 
-        let sample = samples[idx_floor] as f64 * (1.0 - frac) + samples[idx_ceil] as f64 * frac;
-        resampled.push(sample as f32);
+    #[test]
+    fn test_assemblyai_poll_result() {
+        let completed = serde_json::json!({ "status": "completed", "text": "Hello world." });
+        let transcription = assemblyai_poll_result(&completed).unwrap().unwrap();
+        assert_eq!(transcription.text.as_deref(), Some("Hello world."));
+        assert!(assemblyai_poll_result(&serde_json::json!({ "status": "processing" })).unwrap().is_none());
+        assert!(assemblyai_poll_result(&serde_json::json!({ "status": "error", "error": "bad audio" })).is_err());
+        // An error body isn't a job, so it mustn't be polled until the timeout
+        assert!(assemblyai_poll_result(&serde_json::json!({ "error": "Authentication error" })).is_err());
+        assert!(assemblyai_poll_result(&serde_json::json!({ "status": "archived" })).is_err());
     }
 
-    resampled
-}
+    #[test]
+    fn test_provider_host() {
+        let route = Route::Groq { api_key: "gsk_test".to_string(), model: "whisper-large-v3-turbo".to_string() };
+        assert_eq!(provider_host(&route).as_deref(), Some("api.groq.com:443"));
+        assert_eq!(provider_host(&Route::Unconfigured), None);
 
-/// Transcribe audio using available method (API or local)
-fn transcribe_audio(samples: &[f32], sample_rate: u32) -> Result<Option<String>, String> {
-    // Resample to 16kHz if needed (Whisper expects 16kHz)
-    let (samples_16k, rate_16k) = if sample_rate != 16000 {
-        println!("Resampling from {}Hz to 16000Hz ({} samples -> ~{} samples)",
-            sample_rate, samples.len(), samples.len() * 16000 / sample_rate as usize);
-        (resample(samples, sample_rate, 16000), 16000)
-    } else {
-        (samples.to_vec(), sample_rate)
-    };
+        let route = Route::OpenAi {
+            api_key: String::new(),
+            model: "whisper-1".to_string(),
+            base_url: "http://localhost:8080/v1".to_string(),
+        };
+        assert_eq!(provider_host(&route).as_deref(), Some("localhost:8080"));
+    }
 
-    let config = WHISPER_CONFIG.lock();
+    #[test]
+    fn test_clearing_input_channel_keeps_downmix() {
+        assert_eq!(with_input_channel(Downmix::First, None), Downmix::First);
+        assert_eq!(with_input_channel(Downmix::Channel(3), None), Downmix::Average);
+        assert_eq!(with_input_channel(Downmix::First, Some(1)), Downmix::Channel(1));
+    }
 
-    // Try local whisper first if configured
-    #[cfg(feature = "whisper-local")]
-    if config.use_local {
-        if let Some(ref model_path) = config.model_path {
-            return transcribe_local(&samples_16k, rate_16k, model_path);
-        }
+    #[test]
+    fn test_downmix_modes() {
+        // Two 16-channel frames where each sample encodes frame * 100 + channel
+        let data: Vec<f32> = (0..2).flat_map(|f| (0..16).map(move |c| (f * 100 + c) as f32)).collect();
+        let mut out = Vec::new();
+        downmix(&data, 16, Downmix::Channel(11), &mut out);
+        assert_eq!(out, vec![11.0, 111.0]);
+
+        out.clear();
+        downmix(&data, 16, Downmix::First, &mut out);
+        assert_eq!(out, vec![0.0, 100.0]);
+
+        // Every channel counts, not just the first
+        out.clear();
+        downmix(&data, 16, Downmix::Average, &mut out);
+        assert_eq!(out, vec![7.5, 107.5]);
+
+        assert_eq!(Downmix::parse("Channel", Some(2)), Ok(Downmix::Channel(2)));
+        assert!(Downmix::parse("channel", None).is_err());
     }
 
-    // Route to configured provider
-    match config.provider.as_str() {
-        "groq" => {
-            if let Some(ref api_key) = config.groq_api_key {
-                return transcribe_groq(&samples_16k, rate_16k, api_key, &config.model);
-            }
-            // Fall through to OpenAI if no Groq key
-            if let Some(ref api_key) = config.api_key {
-                return transcribe_openai(&samples_16k, rate_16k, api_key);
-            }
-        }
-        _ => {
-            // "openai" or default
-            if let Some(ref api_key) = config.api_key {
-                return transcribe_openai(&samples_16k, rate_16k, api_key);
-            }
-        }
+    #[test]
+    fn test_integer_samples_normalize() {
+        let mut out = Vec::new();
+        to_f32(&[0i16, 16384, -16384, i16::MIN, i16::MAX], &mut out);
+        assert_eq!(&out[..4], &[0.0, 0.5, -0.5, -1.0]);
+        assert!((out[4] - 1.0).abs() < 1e-4);
+
+        // Unsigned samples are centered on 32768; `out` is replaced, not appended to
+        to_f32(&[32768u16, 0, 49152], &mut out);
+        assert_eq!(out, vec![0.0, -1.0, 0.5]);
     }
 
-    // No transcription method available - return placeholder
-    let duration_secs = samples_16k.len() as f32 / rate_16k as f32;
-    if duration_secs > 0.5 {
-        Ok(Some(format!(
-            "[Audio: {:.1}s - configure API key in Settings for transcription]",
-            duration_secs
-        )))
-    } else {
-        Ok(None)
+    #[test]
+    fn test_deinterleaver_carries_partial_frames() {
+        // Stereo frames (L, R) = (f, -f), split mid-frame across blocks
+        let data: Vec<f32> = (1..=4).flat_map(|f| [f as f32, -(f as f32) * 10.0]).collect();
+        let mut right = Deinterleaver::new(2, Downmix::Channel(1));
+        let mut out = Vec::new();
+        for block in [&data[..3], &data[3..4], &data[4..]] {
+            right.push(block, &mut out);
+        }
+        assert_eq!(out, vec![-10.0, -20.0, -30.0, -40.0]);
+
+        // Downmixing keeps pairing samples from the same frame
+        let data = [1.0, 3.0, 1.0, 3.0, 1.0, 3.0];
+        let mut mono = Deinterleaver::new(2, Downmix::Average);
+        let mut out = Vec::new();
+        mono.push(&data[..1], &mut out);
+        mono.push(&data[1..5], &mut out);
+        assert_eq!(out, vec![2.0, 2.0]);
+        mono.push(&data[5..], &mut out);
+        assert_eq!(out, vec![2.0, 2.0, 2.0]);
     }
-}
 
-/// Transcribe using OpenAI Whisper API
-fn transcribe_openai(samples: &[f32], sample_rate: u32, api_key: &str) -> Result<Option<String>, String> {
-    // Write samples to WAV in memory
-    let wav_data = samples_to_wav(samples, sample_rate)?;
+    #[test]
+    fn test_transcript_tail() {
+        assert_eq!(transcript_tail("  short text ", 200), "short text");
+        // Cut mid-word, so the partial word is dropped
+        assert_eq!(transcript_tail("the quick brown fox", 8), "fox");
+        // Cut on a word boundary keeps the whole word
+        assert_eq!(transcript_tail("the quick brown fox", 9), "brown fox");
+    }
 
-    // Call OpenAI Whisper API
-    let client = reqwest::blocking::Client::new();
+    #[test]
+    fn test_named_buffers() {
+        NAMED_BUFFERS.lock().buffers.insert(
+            "test-buffer".to_string(),
+            AudioBuffer { samples: vec![0.0; 8000].into(), sample_rate: 16000 },
+        );
+        let info = list_buffers().into_iter().find(|b| b.name == "test-buffer").unwrap();
+        assert_eq!(info.duration_secs, 0.5);
+        assert!(!info.recording);
 
-    let part = reqwest::blocking::multipart::Part::bytes(wav_data)
-        .file_name("audio.wav")
-        .mime_str("audio/wav")
-        .map_err(|e| e.to_string())?;
+        assert!(delete_buffer("test-buffer").is_ok());
+        assert!(delete_buffer("test-buffer").is_err());
+        assert!(transcribe_buffer("test-buffer").is_err());
+    }
 
-    let form = reqwest::blocking::multipart::Form::new()
-        .part("file", part)
-        .text("model", "whisper-1")
-        .text("language", "en");
+    #[test]
+    fn test_parse_transcription_response() {
+        let wrapped = r#"{"data": {"results": [{"transcript": "hello there"}]}}"#;
+        assert_eq!(parse_transcription_response(wrapped, ParseMode::Strict, None), Ok(None));
+        assert_eq!(
+            parse_transcription_response(wrapped, ParseMode::Lenient, None),
+            Ok(Some("hello there".to_string()))
+        );
+        assert_eq!(
+            parse_transcription_response(wrapped, ParseMode::Strict, Some("data.results.0.transcript")),
+            Ok(Some("hello there".to_string()))
+        );
+        assert!(parse_transcription_response(wrapped, ParseMode::Strict, Some("data.text")).is_err());
 
-    let response = client
-        .post("https://api.openai.com/v1/audio/transcriptions")
-        .header("Authorization", format!("Bearer {}", api_key))
-        .multipart(form)
-        .send()
-        .map_err(|e| format!("Request failed: {}", e))?;
+        // Plain-text bodies are only accepted leniently
+        assert!(parse_transcription_response("hi", ParseMode::Strict, None).is_err());
+        assert_eq!(parse_transcription_response("hi", ParseMode::Lenient, None), Ok(Some("hi".to_string())));
+    }
 
-    if !response.status().is_success() {
-        let status = response.status();
-        let text = response.text().unwrap_or_default();
-        return Err(format!("API error {}: {}", status, text));
+    #[test]
+    fn test_choose_capture_rate() {
+        assert_eq!(choose_capture_rate(8000, 192000, 48000), 16000);
+        assert_eq!(choose_capture_rate(44100, 192000, 48000), 44100);
+        // High-end interfaces: lowest adequate rate rather than the maximum
+        assert_eq!(choose_capture_rate(88200, 192000, 48000), 88200);
+        assert_eq!(choose_capture_rate(32000, 192000, 30000), 32000);
+        assert_eq!(choose_capture_rate(8000, 11025, 48000), 11025);
     }
 
-    let result: serde_json::Value = response.json().map_err(|e| e.to_string())?;
+    #[test]
+    fn test_transcription_models() {
+        let listing = serde_json::json!({
+            "data": [
+                {"id": "whisper-large-v3"},
+                {"id": "llama-3.1-8b-instant"},
+                {"id": "gpt-4o-mini-transcribe"},
+                {"id": "distil-whisper-large-v3-en"}
+            ]
+        });
+        assert_eq!(
+            transcription_models(&listing),
+            vec!["distil-whisper-large-v3-en", "gpt-4o-mini-transcribe", "whisper-large-v3"]
+        );
+        assert_eq!(static_models("assemblyai").unwrap(), vec!["best", "nano"]);
+        assert!(static_models("unknown").is_err());
+    }
 
-    Ok(result["text"].as_str().map(|s| s.to_string()))
-}
+    #[test]
+    fn test_model_list_fallback() {
+        let models = model_list_fallback("openai", reqwest::StatusCode::NOT_FOUND, "404 page not found").unwrap();
+        assert_eq!(models, static_models("openai").unwrap());
+        assert!(model_list_fallback("openai", reqwest::StatusCode::METHOD_NOT_ALLOWED, "").is_ok());
+        let err = model_list_fallback("groq", reqwest::StatusCode::UNAUTHORIZED, "invalid key").unwrap_err();
+        assert!(err.contains("401"), "{}", err);
+        assert!(model_list_fallback("groq", reqwest::StatusCode::FORBIDDEN, "").is_err());
+    }
 
-/// Transcribe using Groq Whisper API (faster inference)
-fn transcribe_groq(samples: &[f32], sample_rate: u32, api_key: &str, model: &str) -> Result<Option<String>, String> {
-    let wav_data = samples_to_wav(samples, sample_rate)?;
+    #[test]
+    fn test_is_known_model() {
+        assert!(is_known_model("groq", "whisper-large-v3"));
+        // Groq substitutes its default for the OpenAI one
+        assert!(is_known_model("groq", "whisper-1"));
+        assert!(!is_known_model("openai", "whisper-large-v3"));
+        assert!(is_known_model("someday", "anything"));
+    }
 
-    let client = reqwest::blocking::Client::new();
+    #[test]
+    fn test_lock_is_stuck() {
+        let lock = Mutex::new(0u32);
+        let guard = lock.lock();
+        assert!(lock_is_stuck(&lock, Duration::from_millis(10)));
+        drop(guard);
+        assert!(!lock_is_stuck(&lock, Duration::from_millis(10)));
+    }
 
-    let part = reqwest::blocking::multipart::Part::bytes(wav_data)
-        .file_name("audio.wav")
-        .mime_str("audio/wav")
-        .map_err(|e| e.to_string())?;
+    #[test]
+    fn test_chunk_decision_skip_only_applies_to_latest() {
+        let _serial = CAPTURE_TESTS.lock();
+        let first = record_decision(0.02, 0.01, 1.0, Decision::Transcribed, "Sent".to_string());
+        let second = record_decision(0.004, 0.01, 1.0, Decision::BelowThreshold, "Quiet".to_string());
+        mark_skipped(first, "No text");
+        assert_eq!(get_last_chunk_decision().unwrap().decision, Decision::BelowThreshold);
 
-    // Groq uses the same API format as OpenAI
-    let groq_model = if model.is_empty() || model == "whisper-1" {
-        "whisper-large-v3-turbo" // Default Groq model (fastest)
-    } else {
-        model
-    };
+        mark_skipped(second, "No text");
+        let last = get_last_chunk_decision().unwrap();
+        assert_eq!((last.decision, last.reason.as_str()), (Decision::Skipped, "No text"));
+    }
 
-    let form = reqwest::blocking::multipart::Form::new()
-        .part("file", part)
-        .text("model", groq_model.to_string())
-        .text("language", "en");
+    #[test]
+    fn test_flush_skips_short_segments() {
+        let _serial = CAPTURE_TESTS.lock();
+        let sample_rate = AUDIO_BUFFER.lock().sample_rate as usize;
+        AUDIO_BUFFER.lock().samples = vec![0.1; sample_rate / 10].into();
+        assert!(take_flushed_segment("Push-to-talk released").is_none());
+        assert!(AUDIO_BUFFER.lock().samples.is_empty());
+        assert_eq!(get_last_chunk_decision().unwrap().decision, Decision::TooShort);
 
-    let response = client
-        .post("https://api.groq.com/openai/v1/audio/transcriptions")
-        .header("Authorization", format!("Bearer {}", api_key))
-        .multipart(form)
-        .send()
-        .map_err(|e| format!("Groq request failed: {}", e))?;
+        AUDIO_BUFFER.lock().samples = vec![0.1; sample_rate].into();
+        let (samples, _, _) = take_flushed_segment("Push-to-talk released").unwrap();
+        assert_eq!(samples.len(), sample_rate);
+        assert_eq!(get_last_chunk_decision().unwrap().decision, Decision::Transcribed);
+    }
 
-    if !response.status().is_success() {
-        let status = response.status();
-        let text = response.text().unwrap_or_default();
-        return Err(format!("Groq API error {}: {}", status, text));
+    #[test]
+    fn test_cap_buffer_bounds_silence() {
+        // Two minutes of silence in 10ms blocks, never flushed
+        let mut samples = VecDeque::new();
+        let mut dropped = 0;
+        for _ in 0..12_000 {
+            samples.extend([0.0f32; 160]);
+            dropped += cap_buffer(&mut samples, 16000, DEFAULT_MAX_BUFFER_SECS);
+            assert!(samples.len() <= 16000 * 60);
+        }
+        assert_eq!(samples.len(), 16000 * 60);
+        assert_eq!(dropped, 16000 * 60);
     }
 
-    let result: serde_json::Value = response.json().map_err(|e| e.to_string())?;
+    #[test]
+    fn test_push_to_talk_keeps_long_holds() {
+        let _serial = CAPTURE_TESTS.lock();
+        assert_eq!(max_buffer_secs(), DEFAULT_MAX_BUFFER_SECS);
+        PUSH_TO_TALK.store(true, Ordering::SeqCst);
+        let secs = max_buffer_secs();
+        PUSH_TO_TALK.store(false, Ordering::SeqCst);
+        assert_eq!(secs, PUSH_TO_TALK_MAX_BUFFER_SECS);
+    }
 
-    Ok(result["text"].as_str().map(|s| s.to_string()))
-}
+    #[test]
+    fn test_buffer_cap_holds_a_chunk() {
+        assert_eq!(buffer_floor(60, 10.0), 60);
+        // Silence-gated chunks can run to 30s, so a shorter cap would cut off their start
+        assert_eq!(buffer_floor(2, 10.0), vad::MAX_CHUNK_SECS as u32);
+        assert_eq!(buffer_floor(2, 2.5), vad::MAX_CHUNK_SECS as u32);
+        assert!(set_max_buffer_duration(0).is_err());
+    }
 
-/// Transcribe using local whisper.cpp (when feature enabled)
-#[cfg(feature = "whisper-local")]
-fn transcribe_local(samples: &[f32], sample_rate: u32, model_path: &str) -> Result<Option<String>, String> {
-    use whisper_rs::{FullParams, SamplingStrategy, WhisperContext, WhisperContextParameters};
+    #[test]
+    fn test_accumulate_stats_per_model() {
+        let metrics = |model: &str, latency_ms| TranscriptionMetrics {
+            provider: "groq".to_string(),
+            model: Some(model.to_string()),
+            audio_ms: 1000,
+            latency_ms,
+            seq: 0,
+        };
+        let mut stats = Vec::new();
+        accumulate_stats(&mut stats, &metrics("whisper-large-v3", 300));
+        accumulate_stats(&mut stats, &metrics("whisper-large-v3", 500));
+        accumulate_stats(&mut stats, &metrics("distil-whisper-large-v3-en", 200));
+        assert_eq!(stats.len(), 2);
+        assert_eq!((stats[0].segments, stats[0].average_latency_ms), (2, 400.0));
+        assert_eq!(stats[1].average_audio_ms, 1000.0);
+    }
 
-    // Load whisper context
-    let ctx = WhisperContext::new_with_params(model_path, WhisperContextParameters::default())
-        .map_err(|e| format!("Failed to load whisper model: {}", e))?;
+    #[test]
+    fn test_claim_admits_one_racer() {
+        let flag = Arc::new(AtomicBool::new(false));
+        let holders = Arc::new(AtomicU32::new(0));
+        let threads: Vec<_> = (0..8)
+            .map(|_| {
+                let (flag, holders) = (flag.clone(), holders.clone());
+                std::thread::spawn(move || {
+                    let mut claimed = 0;
+                    for _ in 0..2000 {
+                        if claim(&flag) {
+                            // A second holder here would be a second capture stream
+                            assert_eq!(holders.fetch_add(1, Ordering::SeqCst), 0);
+                            holders.fetch_sub(1, Ordering::SeqCst);
+                            flag.store(false, Ordering::SeqCst);
+                            claimed += 1;
+                        }
+                    }
+                    claimed
+                })
+            })
+            .collect();
+        let claimed: u32 = threads.into_iter().map(|t| t.join().unwrap()).sum();
+        assert!(claimed > 0);
+        assert!(!flag.load(Ordering::SeqCst));
+    }
 
-    // Create whisper state
-    let mut state = ctx.create_state().map_err(|e| e.to_string())?;
+    #[test]
+    fn test_start_stop_hammering_keeps_one_stream() {
+        let _serial = CAPTURE_TESTS.lock();
+        let (live, peak) = (Arc::new(AtomicU32::new(0)), Arc::new(AtomicU32::new(0)));
+        let backend = FakeBackend { live: live.clone(), peak: peak.clone() };
+        let (sender, commands) = mpsc::channel();
+        let audio = std::thread::spawn(move || run_audio_thread(commands, backend));
 
-    // Configure parameters
-    let mut params = FullParams::new(SamplingStrategy::Greedy { best_of: 1 });
-    params.set_language(Some("en"));
-    params.set_print_special(false);
-    params.set_print_progress(false);
-    params.set_print_realtime(false);
-    params.set_print_timestamps(false);
+        // Starts and stops race the way start_capture and stop_capture do
+        let threads: Vec<_> = (0..6)
+            .map(|i| {
+                let sender = sender.clone();
+                std::thread::spawn(move || {
+                    for _ in 0..100 {
+                        if i % 2 == 0 {
+                            if let Some(session) = claim_capture() {
+                                let (reply, started) = mpsc::channel();
+                                sender.send(AudioCommand::Start((), session, reply)).unwrap();
+                                started.recv().unwrap().unwrap();
+                            }
+                        } else {
+                            release_capture();
+                            send_stop(&sender);
+                        }
+                    }
+                })
+            })
+            .collect();
+        for thread in threads {
+            thread.join().unwrap();
+        }
 
-    // Run transcription
-    state
-        .full(params, samples)
-        .map_err(|e| format!("Transcription failed: {}", e))?;
+        // However the race ended, a running session has its stream and nothing else is open
+        assert_eq!(live.load(Ordering::SeqCst), CAPTURING.load(Ordering::SeqCst) as u32);
+        assert_eq!(peak.load(Ordering::SeqCst), 1);
+        let was_capturing = release_capture();
+        assert_eq!(send_stop(&sender), was_capturing);
+        assert_eq!(live.load(Ordering::SeqCst), 0);
 
-    // Get results
-    let num_segments = state.full_n_segments().map_err(|e| e.to_string())?;
-    if num_segments == 0 {
-        return Ok(None);
+        // A stop queued behind the next session's start leaves the new stream running
+        let start = |session| {
+            let (reply, started) = mpsc::channel();
+            sender.send(AudioCommand::Start((), session, reply)).unwrap();
+            started.recv().unwrap().unwrap();
+        };
+        start(claim_capture().unwrap());
+        release_capture();
+        start(claim_capture().unwrap());
+        assert!(!send_stop(&sender));
+        assert_eq!(live.load(Ordering::SeqCst), 1);
+        release_capture();
+        assert!(send_stop(&sender));
+        assert_eq!(live.load(Ordering::SeqCst), 0);
+        drop(sender);
+        audio.join().unwrap();
     }
 
-    let mut text = String::new();
-    for i in 0..num_segments {
-        if let Ok(segment) = state.full_get_segment_text(i) {
-            text.push_str(&segment);
-            text.push(' ');
-        }
-    }
+    #[test]
+    fn test_released_session_stays_inactive_after_restart() {
+        let _serial = CAPTURE_TESTS.lock();
+        let first = claim_capture().unwrap();
+        assert!(session_active(first));
+        assert!(claim_capture().is_none());
 
-    Ok(Some(text.trim().to_string()))
-}
+        // A reconnect or a late stream open from the first session must not count as live
+        // once capture restarts
+        assert!(release_capture());
+        let second = claim_capture().unwrap();
+        assert_ne!(first, second);
+        assert!(!session_active(first));
+        assert!(session_active(second));
 
-/// Convert f32 samples to WAV bytes
-fn samples_to_wav(samples: &[f32], sample_rate: u32) -> Result<Vec<u8>, String> {
-    let spec = hound::WavSpec {
-        channels: 1,
-        sample_rate,
-        bits_per_sample: 16,
-        sample_format: hound::SampleFormat::Int,
-    };
+        assert!(release_capture());
+        assert!(!release_capture());
+        assert!(!session_active(second));
+    }
 
-    let mut cursor = std::io::Cursor::new(Vec::new());
-    {
-        let mut writer = hound::WavWriter::new(&mut cursor, spec).map_err(|e| e.to_string())?;
+    #[test]
+    fn test_stale_feeder_cannot_end_a_newer_session() {
+        let _serial = CAPTURE_TESTS.lock();
+        let fed = claim_capture().unwrap();
+        assert!(release_capture());
+        let current = claim_capture().unwrap();
 
-        for sample in samples {
-            // Convert f32 [-1.0, 1.0] to i16
-            let sample_i16 = (sample * 32767.0).clamp(-32768.0, 32767.0) as i16;
-            writer.write_sample(sample_i16).map_err(|e| e.to_string())?;
-        }
+        // The first file's feeder reaching its end must leave the new capture running
+        assert!(!release_session(fed));
+        assert!(session_active(current));
+        assert!(release_session(current));
+        assert!(!release_session(current));
+    }
 
-        writer.finalize().map_err(|e| e.to_string())?;
+    #[test]
+    fn test_feed_due_scales_with_speed() {
+        assert_eq!(feed_due(16000, 16000, 1.0), Some(Duration::from_secs(1)));
+        assert_eq!(feed_due(16000, 16000, 4.0), Some(Duration::from_millis(250)));
+        assert_eq!(feed_due(16000, 16000, 0.0), None);
     }
 
-    Ok(cursor.into_inner())
-}
+    #[test]
+    fn test_interim_shares_final_seq() {
+        let interim = transcript_seq(false);
+        assert_eq!(transcript_seq(true), interim);
+        assert_eq!(transcript_seq(false), interim + 1);
+    }
 
-#[derive(Clone, serde::Serialize)]
-pub struct TranscriptEvent {
-    pub text: String,
-    pub is_final: bool,
-}
+    #[test]
+    fn test_push_bounded_drops_oldest() {
+        let mut queue = VecDeque::new();
+        assert_eq!(push_bounded(&mut queue, 1, 2), None);
+        assert_eq!(push_bounded(&mut queue, 2, 2), None);
+        assert_eq!(push_bounded(&mut queue, 3, 2), Some(1));
+        assert_eq!(queue, VecDeque::from([2, 3]));
+    }
 
-#[cfg(test)]
-mod tests {
-    use super::*;
+    #[test]
+    fn test_interim_due() {
+        assert!(!interim_due(8000, 0, 16000));
+        assert!(interim_due(16000, 0, 16000));
+        assert!(!interim_due(24000, 16000, 16000));
+        assert!(interim_due(32000, 16000, 16000));
+        // A capped buffer can shrink below the last interim
+        assert!(!interim_due(100, 16000, 16000));
+    }
 
     #[test]
-    fn test_samples_to_wav() {
-        let samples = vec![0.0, 0.5, -0.5, 1.0, -1.0];
-        let wav = samples_to_wav(&samples, 16000);
-        assert!(wav.is_ok());
-        let wav_data = wav.unwrap();
-        // WAV header is 44 bytes, then 2 bytes per sample (16-bit)
-        assert_eq!(wav_data.len(), 44 + samples.len() * 2);
+    fn test_reconnect_delay_backs_off() {
+        let delays: Vec<u64> = (1..=6).map(|a| reconnect_delay(a).as_millis() as u64).collect();
+        assert_eq!(delays, vec![500, 1000, 2000, 4000, 8000, 8000]);
     }
 
     #[test]
-    fn test_samples_to_wav_empty() {
-        let samples: Vec<f32> = vec![];
-        let wav = samples_to_wav(&samples, 16000);
-        assert!(wav.is_ok());
+    fn test_lock_block_drops_when_contended() {
+        let _serial = CAPTURE_TESTS.lock();
+        let mutex = Mutex::new(0);
+        let held = mutex.lock();
+        let before = DROPOUT_COUNT.load(Ordering::SeqCst);
+        assert!(lock_block(&mutex, true, 480).is_none());
+        assert_eq!(DROPOUT_COUNT.load(Ordering::SeqCst), before + 1);
+        drop(held);
+        assert!(lock_block(&mutex, true, 480).is_some());
+
+        let level = pack_level(0.25, 0.5);
+        assert_eq!((f32::from_bits((level >> 32) as u32), f32::from_bits(level as u32)), (0.25, 0.5));
+        assert_ne!(level, NO_LEVEL);
     }
 
     #[test]
-    fn test_configure_whisper() {
-        configure_whisper(Some("test-key".to_string()), false, None, None, None, None);
-        let config = WHISPER_CONFIG.lock();
-        assert_eq!(config.api_key, Some("test-key".to_string()));
-        assert!(!config.use_local);
+    fn test_detect_dropout() {
+        // 480 frames at 48kHz is 10ms; on-time and jittery callbacks aren't dropouts
+        assert_eq!(detect_dropout(0.010, 480, 48000), None);
+        assert_eq!(detect_dropout(0.014, 480, 48000), None);
+        // A 30ms gap after a 10ms buffer lost ~20ms of audio
+        assert_eq!(detect_dropout(0.030, 480, 48000), Some(960));
     }
 
+    /// Benchmark: time spent in the capture callback per 10ms block of 48kHz stereo i16,
+    /// inline (conversion, metering, downmix and the VAD pass over a full 1s chunk) against
+    /// deferred (a copy into the worker's queue).
+    /// Run with: cargo test --release bench_capture_callback -- --ignored --nocapture
     #[test]
-    fn test_configure_whisper_groq() {
-        configure_whisper(None, false, None, Some("groq".to_string()), Some("whisper-large-v3-turbo".to_string()), Some("groq-key".to_string()));
-        let config = WHISPER_CONFIG.lock();
-        assert_eq!(config.provider, "groq");
-        assert_eq!(config.model, "whisper-large-v3-turbo");
-        assert_eq!(config.groq_api_key, Some("groq-key".to_string()));
+    #[ignore]
+    fn bench_capture_callback() {
+        const BLOCKS: usize = 10_000;
+        let sample_rate = 48000u32;
+        let block: Vec<i16> = (0..960).map(|i| ((i as f32 * 0.05).sin() * 8000.0) as i16).collect();
+        let timing = vad::effective_timing();
+        let mut energy = vad::ChunkEnergy::new(sample_rate);
+
+        let mut converted = Vec::new();
+        let mut mono = Vec::new();
+        let mut deinterleaver = Deinterleaver::new(2, Downmix::Average);
+        let started = Instant::now();
+        for _ in 0..BLOCKS {
+            to_f32(&block, &mut converted);
+            let level = converted.iter().map(|s| s * s).sum::<f32>();
+            mono.clear();
+            deinterleaver.push(&converted, &mut mono);
+            energy.push(&mono, 0.0001, 0.001);
+            std::hint::black_box((level, vad::decide(&energy, sample_rate, &timing)));
+        }
+        let inline = started.elapsed() / BLOCKS as u32;
+
+        let (sender, receiver) = mpsc::sync_channel::<Vec<i16>>(BLOCKS);
+        let started = Instant::now();
+        for _ in 0..BLOCKS {
+            let _ = sender.try_send(block.to_vec());
+        }
+        let deferred = started.elapsed() / BLOCKS as u32;
+        drop(receiver);
+
+        println!("Per 10ms callback: inline {:?}, deferred {:?}", inline, deferred);
+        assert!(deferred < inline);
     }
 
     /// Integration test: sends a short audio clip to Groq Whisper API.
@@ -572,12 +5823,16 @@ mod tests {
 
         // Generate 1 second of 440Hz sine wave at 16kHz
         let sample_rate = 16000u32;
-        let duration_samples = sample_rate as usize;
-        let samples: Vec<f32> = (0..duration_samples)
-            .map(|i| (2.0 * std::f32::consts::PI * 440.0 * i as f32 / sample_rate as f32).sin() * 0.5)
-            .collect();
+        let samples = sine_wave(440.0, 1.0, sample_rate);
 
-        let result = transcribe_groq(&samples, sample_rate, &api_key, "whisper-large-v3-turbo");
+        let request = ChunkRequest {
+            model: "whisper-large-v3-turbo",
+            prompt: None,
+            language: Some("en"),
+            task: TranscriptionTask::Transcribe,
+            pass: Pass::Final(None),
+        };
+        let result = transcribe_groq(&samples, sample_rate, &api_key, request);
 
         match result {
             Ok(transcript) => {
@@ -589,4 +5844,182 @@ mod tests {
             }
         }
     }
+
+    #[test]
+    fn test_saved_config_keeps_keys_apart() {
+        let mut config = WhisperConfig {
+            api_key: Some("sk-test".to_string()),
+            provider: "groq".to_string(),
+            ..WhisperConfig::default()
+        };
+        let mut device = DeviceConfig {
+            selected_device: None,
+            downmix: Downmix::default(),
+            max_capture_rate: 48000,
+        };
+        let saved = SavedConfig::from_config(&config, &device);
+        assert_eq!(saved.provider.as_deref(), Some("groq"));
+        assert_eq!(key_slot(&mut config, "openai").as_deref(), Some("sk-test"));
+        assert_eq!(*key_slot(&mut config, "groq"), None);
+
+        // Missing fields keep the current values; stale ones are skipped
+        SavedConfig {
+            model: Some("whisper-large-v3".to_string()),
+            language: Some("klingon".to_string()),
+            ..SavedConfig::default()
+        }
+        .apply(&mut config, &mut device);
+        assert_eq!((config.provider.as_str(), config.model.as_str()), ("groq", "whisper-large-v3"));
+        assert_eq!(config.language, "en");
+        assert_eq!(config.api_key.as_deref(), Some("sk-test"));
+    }
+
+    #[test]
+    fn test_saved_config_file_round_trip() {
+        let mut config = WhisperConfig {
+            provider: "groq".to_string(),
+            parse_mode: ParseMode::Lenient,
+            text_path: Some("result.text".to_string()),
+            restore_punctuation: true,
+            temperature: Some(0.2),
+            streaming: true,
+            confidence: ConfidenceLimits { min_confidence: Some(0.4), max_no_speech_prob: Some(1.5) },
+            ..WhisperConfig::default()
+        };
+        let defaults = ProviderDefaults { language: Some("fr".to_string()), ..Default::default() };
+        config.provider_defaults.insert("groq".to_string(), defaults.clone());
+        let device = DeviceConfig {
+            selected_device: Some("USB Microphone".to_string()),
+            downmix: Downmix::Channel(2),
+            max_capture_rate: 96000,
+        };
+
+        let dir = std::env::temp_dir().join(format!("koe-config-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join(CONFIG_FILE);
+        write_json(&path, &SavedConfig::from_config(&config, &device)).unwrap();
+        let saved: SavedConfig = serde_json::from_str(&read_saved(&path).unwrap()).unwrap();
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        let mut loaded = WhisperConfig::default();
+        let mut loaded_device = DeviceConfig {
+            selected_device: None,
+            downmix: Downmix::default(),
+            max_capture_rate: 48000,
+        };
+        saved.apply(&mut loaded, &mut loaded_device);
+        assert_eq!(loaded.provider, "groq");
+        assert_eq!(loaded.parse_mode, ParseMode::Lenient);
+        assert_eq!(loaded.text_path.as_deref(), Some("result.text"));
+        assert_eq!(loaded.provider_defaults.get("groq"), Some(&defaults));
+        assert!(loaded.restore_punctuation);
+        assert_eq!(loaded.temperature, Some(0.2));
+        assert!(loaded.streaming);
+        // An out-of-range threshold in a hand-edited file is skipped
+        assert_eq!(loaded.confidence, ConfidenceLimits { min_confidence: Some(0.4), max_no_speech_prob: None });
+        assert_eq!(loaded_device.selected_device.as_deref(), Some("USB Microphone"));
+        assert_eq!((loaded_device.downmix, loaded_device.max_capture_rate), (Downmix::Channel(2), 96000));
+    }
+
+    #[test]
+    fn test_selected_device_round_trip() {
+        let mut config = WhisperConfig::default();
+        let selected = DeviceConfig {
+            selected_device: Some("USB Microphone".to_string()),
+            downmix: Downmix::Channel(1),
+            max_capture_rate: 48000,
+        };
+        let json = serde_json::to_string(&SavedConfig::from_config(&config, &selected)).unwrap();
+        let saved: SavedConfig = serde_json::from_str(&json).unwrap();
+
+        let mut device = DeviceConfig {
+            selected_device: None,
+            downmix: Downmix::default(),
+            max_capture_rate: 48000,
+        };
+        saved.apply(&mut config, &mut device);
+        assert_eq!(device.selected_device.as_deref(), Some("USB Microphone"));
+    }
+
+    #[test]
+    fn test_read_transcript_stream() {
+        let stream = concat!(
+            "data: {\"type\":\"transcript.text.delta\",\"delta\":\"Hello\"}\n\n",
+            "data: {\"type\":\"transcript.text.delta\",\"delta\":\" wor\"}\n\n",
+            "data: {\"type\":\"transcript.text.done\",\"text\":\"Hello world.\"}\n\n",
+        );
+        let mut partials = Vec::new();
+        let text = read_transcript_stream(stream.as_bytes(), |p| partials.push(p.to_string())).unwrap();
+        assert_eq!(partials, vec!["Hello", "Hello wor"]);
+        assert_eq!(text.as_deref(), Some("Hello world."));
+    }
+
+    #[test]
+    fn test_repeats_last() {
+        let start = Instant::now();
+        let mut last = None;
+        assert!(!repeats_last(&mut last, "Thanks for watching!", start));
+        assert!(repeats_last(&mut last, " thanks for watching.", start + Duration::from_secs(2)));
+        assert!(!repeats_last(&mut last, "Ship it.", start + Duration::from_secs(3)));
+        assert!(!repeats_last(&mut last, "Ship it.", start + Duration::from_secs(10)));
+    }
+
+    #[test]
+    fn test_summarize_capabilities() {
+        let configs = vec![
+            (2, 44100, 48000, "f32".to_string()),
+            (1, 8000, 48000, "i16".to_string()),
+            (2, 44100, 48000, "i16".to_string()),
+        ];
+        let capabilities = summarize_capabilities("USB Microphone", configs.into_iter());
+        assert_eq!(capabilities.channels, vec![1, 2]);
+        assert_eq!(capabilities.sample_formats, vec!["f32", "i16"]);
+        assert_eq!(
+            capabilities.sample_rates,
+            vec![SampleRateRange { min: 8000, max: 48000 }, SampleRateRange { min: 44100, max: 48000 }]
+        );
+        assert!(capabilities.supports_16k_mono);
+
+        let stereo_only = summarize_capabilities("Interface", vec![(2, 48000, 48000, "f32".to_string())].into_iter());
+        assert!(!stereo_only.supports_16k_mono);
+    }
+
+    #[test]
+    fn test_temperature_is_clamped_and_stored() {
+        let _serial = CONFIG_TESTS.lock();
+        assert_eq!(clamp_temperature(0.2), 0.2);
+        assert_eq!(clamp_temperature(1.5), 1.0);
+        assert_eq!(clamp_temperature(-0.1), 0.0);
+        assert_eq!(clamp_temperature(f32::NAN), 0.0);
+
+        set_temperature(Some(3.0));
+        assert_eq!(WHISPER_CONFIG.lock().temperature, Some(1.0));
+        set_temperature(None);
+        assert_eq!(WHISPER_CONFIG.lock().temperature, None);
+    }
+
+    #[test]
+    fn test_prune_segments_keeps_newest() {
+        let dir = std::env::temp_dir().join(format!("koe-segments-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        for name in ["segment-20260101-000001.000.wav", "segment-20260101-000002.000.wav", "segment-20260101-000003.000.wav", "notes.txt"] {
+            std::fs::write(dir.join(name), b"").unwrap();
+        }
+        prune_segments(&dir, 2);
+        let mut left: Vec<String> = std::fs::read_dir(&dir)
+            .unwrap()
+            .map(|e| e.unwrap().file_name().to_string_lossy().into_owned())
+            .collect();
+        left.sort();
+        assert_eq!(left, vec!["notes.txt", "segment-20260101-000002.000.wav", "segment-20260101-000003.000.wav"]);
+        std::fs::remove_dir_all(&dir).ok();
+    }
+
+    #[test]
+    fn test_capture_mode_parse() {
+        assert_eq!(CaptureMode::parse("pushToTalk"), Ok(CaptureMode::PushToTalk));
+        assert_eq!(CaptureMode::parse("push-to-talk"), Ok(CaptureMode::PushToTalk));
+        assert_eq!(CaptureMode::parse("Continuous"), Ok(CaptureMode::Continuous));
+        assert!(CaptureMode::parse("toggle").is_err());
+    }
 }
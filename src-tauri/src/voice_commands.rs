@@ -0,0 +1,205 @@
+use parking_lot::Mutex;
+use regex::Regex;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{self, Sender};
+use tauri::{AppHandle, Emitter, EventTarget};
+
+use crate::talon;
+
+// Final transcripts that match no rule are still emitted as dictation
+static DICTATION_PASSTHROUGH: AtomicBool = AtomicBool::new(true);
+
+/// A spoken phrase (or regex) and the Talon code it runs
+#[derive(Clone, Debug, PartialEq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct VoiceCommandRule {
+    pub pattern: String,
+    pub talon_code: String,
+    #[serde(default)]
+    pub regex: bool, // otherwise the whole transcript must be the phrase
+}
+
+enum Matcher {
+    Phrase(String), // normalized
+    Regex(Regex),
+}
+
+struct Command {
+    rule: VoiceCommandRule,
+    matcher: Matcher,
+}
+
+/// What became of a final transcript
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Outcome {
+    Command,   // a rule matched and its code is queued
+    Dictation, // no rule matched; emit it as dictation
+    Dropped,   // no rule matched and dictation passthrough is off
+}
+
+lazy_static::lazy_static! {
+    static ref COMMANDS: Mutex<Vec<Command>> = Mutex::new(Vec::new());
+    // Feeds the command worker, started with the first match
+    static ref QUEUE: Mutex<Option<Sender<(AppHandle, VoiceCommandRule)>>> = Mutex::new(None);
+}
+
+/// Replace the command rules; nothing changes if any pattern is invalid
+pub fn register(rules: Vec<VoiceCommandRule>) -> Result<(), String> {
+    let commands = rules
+        .into_iter()
+        .map(|rule| {
+            let matcher = if rule.regex {
+                Regex::new(&format!("(?i){}", rule.pattern))
+                    .map(Matcher::Regex)
+                    .map_err(|e| format!("Invalid command pattern '{}': {}", rule.pattern, e))?
+            } else {
+                let phrase = normalize(&rule.pattern);
+                if phrase.is_empty() {
+                    return Err(format!("Command phrase '{}' has no words", rule.pattern));
+                }
+                Matcher::Phrase(phrase)
+            };
+            Ok(Command { rule, matcher })
+        })
+        .collect::<Result<Vec<_>, String>>()?;
+    *COMMANDS.lock() = commands;
+    Ok(())
+}
+
+pub fn rules() -> Vec<VoiceCommandRule> {
+    COMMANDS.lock().iter().map(|c| c.rule.clone()).collect()
+}
+
+/// Emit unmatched transcripts as dictation (true) or drop them, for command-only use
+pub fn set_dictation_passthrough(enabled: bool) {
+    DICTATION_PASSTHROUGH.store(enabled, Ordering::SeqCst);
+}
+
+/// Queue the first rule matching a final transcript, emitting `voice:command_matched`
+pub fn dispatch(app: &AppHandle, text: &str) -> Outcome {
+    let Some(rule) = find_match(&COMMANDS.lock(), text) else {
+        return if DICTATION_PASSTHROUGH.load(Ordering::SeqCst) {
+            Outcome::Dictation
+        } else {
+            Outcome::Dropped
+        };
+    };
+    println!("Voice command: '{}' -> {}", text, rule.pattern);
+    app.emit_to(
+        EventTarget::Any,
+        "voice:command_matched",
+        serde_json::json!({
+            "pattern": rule.pattern,
+            "action": rule.talon_code,
+            "text": text
+        }),
+    )
+    .ok();
+
+    // Keep the transcription worker free while Talon runs
+    let mut queue = QUEUE.lock();
+    let sender = queue.get_or_insert_with(spawn_command_worker);
+    if sender.send((app.clone(), rule)).is_err() {
+        eprintln!("Voice command worker exited");
+        *queue = None;
+    }
+    Outcome::Command
+}
+
+/// Run queued commands one at a time, in the order they were spoken
+fn spawn_command_worker() -> Sender<(AppHandle, VoiceCommandRule)> {
+    let (sender, receiver) = mpsc::channel::<(AppHandle, VoiceCommandRule)>();
+    std::thread::spawn(move || {
+        for (app, rule) in receiver {
+            run(&app, &rule);
+        }
+    });
+    sender
+}
+
+fn run(app: &AppHandle, rule: &VoiceCommandRule) {
+    if let Err(e) = talon::execute_talon(&rule.talon_code, talon::DEFAULT_TIMEOUT) {
+        eprintln!("Voice command '{}' failed: {}", rule.pattern, e);
+        app.emit_to(
+            EventTarget::Any,
+            "voice:warning",
+            serde_json::json!({
+                "kind": "commandFailed",
+                "pattern": rule.pattern,
+                "message": e.to_string()
+            }),
+        )
+        .ok();
+    }
+}
+
+fn find_match(commands: &[Command], text: &str) -> Option<VoiceCommandRule> {
+    let phrase = normalize(text);
+    commands
+        .iter()
+        .find(|command| match &command.matcher {
+            Matcher::Phrase(p) => *p == phrase,
+            Matcher::Regex(r) => r.is_match(text),
+        })
+        .map(|command| command.rule.clone())
+}
+
+/// Lowercase words without punctuation, so "New line." matches "new line"
+fn normalize(text: &str) -> String {
+    text.split_whitespace()
+        .map(|word| {
+            word.chars()
+                .filter(|c| c.is_alphanumeric() || *c == '\'')
+                .flat_map(char::to_lowercase)
+                .collect::<String>()
+        })
+        .filter(|word| !word.is_empty())
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn phrase(pattern: &str, talon_code: &str) -> Command {
+        Command {
+            rule: VoiceCommandRule {
+                pattern: pattern.to_string(),
+                talon_code: talon_code.to_string(),
+                regex: false,
+            },
+            matcher: Matcher::Phrase(normalize(pattern)),
+        }
+    }
+
+    #[test]
+    fn test_phrase_matches_whole_transcript() {
+        let commands = vec![phrase("new line", "key(enter)"), phrase("Save file", "key(cmd-s)")];
+        assert_eq!(find_match(&commands, " New line.").unwrap().talon_code, "key(enter)");
+        assert_eq!(find_match(&commands, "SAVE FILE!").unwrap().talon_code, "key(cmd-s)");
+        assert!(find_match(&commands, "start a new line here").is_none());
+    }
+
+    #[test]
+    fn test_regex_matches_anywhere_ignoring_case() {
+        let rule = VoiceCommandRule {
+            pattern: r"^go to line (\d+)".to_string(),
+            talon_code: "edit.jump_line(1)".to_string(),
+            regex: true,
+        };
+        let commands = vec![
+            phrase("go to line", "pass"),
+            Command { matcher: Matcher::Regex(Regex::new(&format!("(?i){}", rule.pattern)).unwrap()), rule },
+        ];
+        assert_eq!(find_match(&commands, "Go to line 42.").unwrap().talon_code, "edit.jump_line(1)");
+        assert_eq!(find_match(&commands, "go to line").unwrap().talon_code, "pass");
+        assert!(find_match(&commands, "please go to line 3").is_none());
+    }
+
+    #[test]
+    fn test_register_rejects_empty_phrase() {
+        let rule = VoiceCommandRule { pattern: "...".to_string(), talon_code: "pass".to_string(), regex: false };
+        assert!(register(vec![rule]).is_err());
+    }
+}
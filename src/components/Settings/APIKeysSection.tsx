@@ -1,6 +1,6 @@
-import { useState } from 'react';
+import { useEffect, useState } from 'react';
 import { invoke } from '@tauri-apps/api/core';
-import { useSettingsStore, getApiKey } from '../../store/settingsStore';
+import { useSettingsStore, getApiKey, saveApiKey, removeApiKey, getBackendApiKeys } from '../../store/settingsStore';
 
 interface APIKeyConfig {
   id: keyof ReturnType<typeof useSettingsStore.getState>['apiKeys'];
@@ -71,13 +71,19 @@ const IMAGE_PROVIDERS = [
 
 const TRANSCRIPTION_PROVIDERS = [
   { id: 'groq' as const, label: 'Groq (Fastest)', keyRequired: 'groq' as const, models: ['whisper-large-v3-turbo', 'whisper-large-v3', 'distil-whisper-large-v3-en'] },
-  { id: 'openai' as const, label: 'OpenAI Whisper', keyRequired: 'openai' as const, models: ['whisper-1'] },
+  { id: 'openai' as const, label: 'OpenAI Whisper', keyRequired: 'openai' as const, models: ['whisper-1', 'gpt-4o-transcribe', 'gpt-4o-mini-transcribe'] },
 ];
 
 export default function APIKeysSection() {
-  const { apiKeys, setApiKey, clearApiKey, imageProvider, setImageProvider, transcriptionProvider, setTranscriptionProvider, transcriptionModel, setTranscriptionModel } = useSettingsStore();
+  const { apiKeys, imageProvider, setImageProvider, transcriptionProvider, setTranscriptionProvider, transcriptionModel, setTranscriptionModel } = useSettingsStore();
   const [editingKey, setEditingKey] = useState<string | null>(null);
   const [keyInputs, setKeyInputs] = useState<Record<string, string>>({});
+  const [backendKeys, setBackendKeys] = useState<string[]>([]);
+
+  const refreshBackendKeys = () => {
+    getBackendApiKeys().then(setBackendKeys).catch(() => setBackendKeys([]));
+  };
+  useEffect(refreshBackendKeys, []);
 
   const handleEditKey = (keyId: string) => {
     setEditingKey(keyId);
@@ -87,19 +93,23 @@ export default function APIKeysSection() {
   const handleSaveKey = (keyId: keyof typeof apiKeys) => {
     const value = keyInputs[keyId]?.trim();
     if (value) {
-      setApiKey(keyId, value);
+      saveApiKey(keyId, value)
+        .then(refreshBackendKeys)
+        .catch((err) => console.error('Failed to save API key:', err));
     }
     setEditingKey(null);
     setKeyInputs({ ...keyInputs, [keyId]: '' });
   };
 
   const handleClearKey = (keyId: keyof typeof apiKeys) => {
-    clearApiKey(keyId);
+    removeApiKey(keyId)
+      .then(refreshBackendKeys)
+      .catch((err) => console.error('Failed to clear API key:', err));
     setEditingKey(null);
   };
 
   const isKeyConfigured = (keyId: keyof typeof apiKeys) => {
-    return Boolean(getApiKey(keyId));
+    return Boolean(getApiKey(keyId)) || backendKeys.includes(keyId);
   };
 
   const isKeyFromEnv = (keyId: keyof typeof apiKeys) => {
@@ -12,6 +12,14 @@ export default function AudioDeviceSelector() {
     loadDevices();
   }, []);
 
+  // The backend only watches for devices coming and going while capturing or while this is shown
+  useEffect(() => {
+    invoke('set_device_picker_open', { open: true }).catch(() => {});
+    return () => {
+      invoke('set_device_picker_open', { open: false }).catch(() => {});
+    };
+  }, []);
+
   const loadDevices = async () => {
     try {
       setLoading(true);
@@ -1,6 +1,6 @@
 import { useState, useCallback } from 'react';
 import { invoke } from '@tauri-apps/api/core';
-import { useSettingsStore, getApiKey } from '../../store/settingsStore';
+import { useSettingsStore, getApiKey, saveApiKey } from '../../store/settingsStore';
 import './SetupWizard.css';
 
 interface SetupStep {
@@ -72,7 +72,6 @@ interface SetupWizardProps {
 }
 
 export default function SetupWizard({ onComplete }: SetupWizardProps) {
-  const { setApiKey } = useSettingsStore();
   const [currentStep, setCurrentStep] = useState(0);
   const [keyInput, setKeyInput] = useState('');
   const [showKey, setShowKey] = useState(false);
@@ -83,7 +82,7 @@ export default function SetupWizard({ onComplete }: SetupWizardProps) {
 
   const handleSaveKey = useCallback(() => {
     if (keyInput.trim()) {
-      setApiKey(step.id, keyInput.trim());
+      saveApiKey(step.id, keyInput.trim()).catch((err) => console.error('Failed to save API key:', err));
     }
     setKeyInput('');
     setShowKey(false);
@@ -93,7 +92,7 @@ export default function SetupWizard({ onComplete }: SetupWizardProps) {
     } else {
       setCurrentStep((s) => s + 1);
     }
-  }, [keyInput, step.id, setApiKey, isLastStep, onComplete]);
+  }, [keyInput, step.id, isLastStep, onComplete]);
 
   const handleSkip = useCallback(() => {
     setKeyInput('');
@@ -238,7 +238,7 @@ describe('useVoice', () => {
       // Simulate error event
       act(() => {
         mockListenCallbacks['voice:error']?.({
-          payload: 'Microphone error',
+          payload: { code: 'audioDevice', message: 'Microphone error', recoverable: false },
         });
       });
 
@@ -257,7 +257,7 @@ describe('useVoice', () => {
       // Simulate error event
       act(() => {
         mockListenCallbacks['voice:error']?.({
-          payload: 'Microphone access denied',
+          payload: { code: 'audioDevice', message: 'Microphone access denied', recoverable: false },
         });
       });
 
@@ -276,7 +276,7 @@ describe('useVoice', () => {
       // Simulate error event
       act(() => {
         mockListenCallbacks['voice:error']?.({
-          payload: 'Some error',
+          payload: { code: 'audioDevice', message: 'Some error', recoverable: false },
         });
       });
 
@@ -304,7 +304,7 @@ describe('useVoice', () => {
       // Simulate error with "stopped" in message (should be ignored)
       act(() => {
         mockListenCallbacks['voice:error']?.({
-          payload: 'Voice capture stopped',
+          payload: { code: 'audioDevice', message: 'Voice capture stopped', recoverable: false },
         });
       });
 
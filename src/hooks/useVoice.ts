@@ -2,7 +2,7 @@ import { useEffect, useCallback, useState, useRef } from 'react';
 import { listen } from '@tauri-apps/api/event';
 import { invoke } from '@tauri-apps/api/core';
 import { useWindowStore } from '../store/windowStore';
-import { useSettingsStore, getApiKey } from '../store/settingsStore';
+import { useSettingsStore, migrateTranscriptionKeys } from '../store/settingsStore';
 import { getThought, updateThought } from '../sync';
 import { sounds } from '../services/sounds';
 import { logger } from '../services/logger';
@@ -43,13 +43,19 @@ interface DebugState {
 interface TranscriptEvent {
   text: string;
   isFinal: boolean;
+  seq?: number;
+}
+
+interface VoiceErrorEvent {
+  code: string;
+  message: string;
+  recoverable: boolean;
 }
 
 // Voice hook - always listens for events regardless of voiceEnabled setting
 export function useVoice() {
   const voiceEnabled = useSettingsStore((s) => s.voiceEnabled);
   const audioInputDevice = useSettingsStore((s) => s.audioInputDevice);
-  const apiKeys = useSettingsStore((s) => s.apiKeys);
   const transcriptionProvider = useSettingsStore((s) => s.transcriptionProvider);
   const transcriptionModel = useSettingsStore((s) => s.transcriptionModel);
   const {
@@ -77,27 +83,33 @@ export function useVoice() {
   });
   const eventsReceivedRef = useRef(0);
 
-  // Configure Whisper with API keys and provider settings
-  // Re-runs when API keys or transcription settings change
+  // Move keys from localStorage or .env into the backend's keychain if it doesn't have them yet
+  useEffect(() => {
+    migrateTranscriptionKeys().catch((err) => {
+      console.error('Failed to hand API keys to the backend:', err);
+    });
+  }, []);
+
+  // Push transcription settings when the user changes them. Nothing is sent on mount, so the
+  // backend's saved config (keys, local mode) stands; keys are sent by the Settings save action
+  const lastTranscription = useRef({ provider: transcriptionProvider, model: transcriptionModel });
   useEffect(() => {
-    const openaiKey = getApiKey('openai');
-    const groqKey = getApiKey('groq');
-    console.log('Configuring Whisper: provider=%s, model=%s, openai=%s, groq=%s',
-      transcriptionProvider, transcriptionModel || '(default)',
-      openaiKey ? 'present' : 'not set',
-      groqKey ? 'present' : 'not set');
+    const last = lastTranscription.current;
+    if (last.provider === transcriptionProvider && last.model === transcriptionModel) return;
+    lastTranscription.current = { provider: transcriptionProvider, model: transcriptionModel };
+    console.log('Configuring Whisper: provider=%s, model=%s',
+      transcriptionProvider, transcriptionModel || '(default)');
 
     invoke('configure_whisper', {
-      apiKey: openaiKey || null,
-      useLocal: false,
-      modelPath: null,
-      provider: transcriptionProvider,
-      model: transcriptionModel || null,
-      groqApiKey: groqKey || null,
+      settings: {
+        // null leaves a setting unchanged
+        provider: last.provider !== transcriptionProvider ? transcriptionProvider : null,
+        model: last.model !== transcriptionModel ? transcriptionModel || null : null,
+      },
     }).catch((err) => {
       console.error('Failed to configure Whisper:', err);
     });
-  }, [apiKeys, transcriptionProvider, transcriptionModel]);
+  }, [transcriptionProvider, transcriptionModel]);
 
   // Sync audio device setting to backend when it changes
   useEffect(() => {
@@ -245,14 +257,15 @@ export function useVoice() {
           }
         });
 
-        const unlistenError = await listen<string>('voice:error', (event) => {
+        const unlistenError = await listen<VoiceErrorEvent>('voice:error', (event) => {
           logger.error('[Voice] Error event:', event.payload);
-          if (mounted && event.payload && !event.payload.includes('stopped')) {
+          const message = event.payload?.message;
+          if (mounted && message && !message.includes('stopped')) {
             setDebugState(prev => ({
               ...prev,
-              errors: [...prev.errors.slice(-4), event.payload],
+              errors: [...prev.errors.slice(-4), message],
             }));
-            setLastError(event.payload);
+            setLastError(message);
             setVoiceState('error');
           }
         });
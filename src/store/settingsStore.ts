@@ -1,4 +1,5 @@
 import { create } from 'zustand';
+import { invoke } from '@tauri-apps/api/core';
 import { persist, createJSONStorage } from 'zustand/middleware';
 import type { CustomPosition } from '../models/types';
 import type { ThemeName } from '../styles/themes';
@@ -149,6 +150,56 @@ export const useSettingsStore = create<SettingsState>()(
   )
 );
 
+type ApiKeyId = keyof SettingsState['apiKeys'];
+
+// configure_whisper field for each key the backend transcribes with
+const TRANSCRIPTION_KEY_FIELDS: Partial<Record<ApiKeyId, string>> = {
+  openai: 'apiKey',
+  groq: 'groqApiKey',
+};
+
+// Keys only the backend uses; the frontend never keeps them (OpenAI's is also used for images)
+const BACKEND_ONLY_KEYS: ApiKeyId[] = ['groq'];
+
+// Save a key the user entered, handing transcription keys to the backend. Only called
+// from an explicit save, so half-typed keys never reach it.
+export async function saveApiKey(provider: ApiKeyId, key: string): Promise<void> {
+  const field = TRANSCRIPTION_KEY_FIELDS[provider];
+  if (field) {
+    await invoke('configure_whisper', { settings: { [field]: key } });
+  }
+  if (!BACKEND_ONLY_KEYS.includes(provider)) {
+    useSettingsStore.getState().setApiKey(provider, key);
+  }
+}
+
+// Hand transcription keys saved locally or set in .env to a backend that lacks them, as
+// earlier versions only sent them on mount. Backend-only keys are blanked locally once sent.
+export async function migrateTranscriptionKeys(): Promise<void> {
+  const backendKeys = await getBackendApiKeys();
+  for (const provider of Object.keys(TRANSCRIPTION_KEY_FIELDS) as ApiKeyId[]) {
+    const key = getApiKey(provider);
+    if (!key || backendKeys.includes(provider)) continue;
+    await invoke('configure_whisper', { settings: { [TRANSCRIPTION_KEY_FIELDS[provider]!]: key } });
+    if (BACKEND_ONLY_KEYS.includes(provider)) {
+      useSettingsStore.getState().clearApiKey(provider);
+    }
+  }
+}
+
+// Providers whose key the backend holds in the OS keychain
+export function getBackendApiKeys(): Promise<string[]> {
+  return invoke<string[]>('get_configured_api_keys');
+}
+
+// Remove a key, including the backend's copy of a transcription key
+export async function removeApiKey(provider: ApiKeyId): Promise<void> {
+  if (TRANSCRIPTION_KEY_FIELDS[provider]) {
+    await invoke('clear_api_key', { provider });
+  }
+  useSettingsStore.getState().clearApiKey(provider);
+}
+
 // Helper to get API key (checks store first, then env vars)
 export function getApiKey(provider: keyof SettingsState['apiKeys']): string {
   const storeKey = useSettingsStore.getState().apiKeys[provider];